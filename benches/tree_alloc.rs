@@ -0,0 +1,82 @@
+//! Where does the time (and allocation) go when speaking a large expression?
+//! This is the benchmark used to decide that an arena for the MathML tree wouldn't help:
+//! building and dropping the tree is a tiny fraction of canonicalization + speech.
+//!
+//! Run with `cargo bench --bench tree_alloc [-- n1 n2 ...]` (default n is 500).
+//! The expression is an mrow of 'n' terms of the form x^i/(y+i+1).
+#![allow(clippy::needless_return)]
+use libmathcat::interface::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Counts allocations so they can be reported per phase
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.realloc(ptr, layout, new_size);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn make_expr(n: usize) -> String {
+    let mut terms = String::new();
+    for i in 0..n {
+        if i > 0 {
+            terms += "<mo>+</mo>";
+        }
+        terms += &format!(
+            "<mfrac><msup><mi>x</mi><mn>{}</mn></msup><mrow><mi>y</mi><mo>+</mo><mn>{}</mn><mo>+</mo><mn>1</mn></mrow></mfrac>",
+            i, i);
+    }
+    return format!("<math><mrow>{}</mrow></math>", terms);
+}
+
+/// Run 'f', printing how long it took and how many allocations it made
+fn measure<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    println!("  {:<16} {:>10.2?} {:>12} allocs", label, elapsed, ALLOCATIONS.load(Ordering::Relaxed) - allocations);
+    return result;
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1)
+                .filter_map(|arg| arg.parse().ok())     // 'cargo bench' passes "--bench"
+                .collect();
+    let sizes = if sizes.is_empty() {vec![500]} else {sizes};
+
+    let rules_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("Rules");
+    if let Err(e) = set_rules_dir(rules_dir.to_str().unwrap().to_string()) {
+        panic!("{}", errors_to_string(&e));
+    }
+    // load the rules outside of the measurements
+    set_mathml("<math><mi>x</mi></math>".to_string()).unwrap();
+    get_spoken_text().unwrap();
+
+    for n in sizes {
+        let expr = make_expr(n);
+        println!("n={}:", n);
+        let package = measure("parse tree", || sxd_document::parser::parse(&expr).unwrap());
+        measure("drop tree", || drop(package));
+        measure("set_mathml", || set_mathml(expr.clone()).unwrap());
+        measure("get_spoken_text", || get_spoken_text().unwrap());
+    }
+}
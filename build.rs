@@ -63,12 +63,12 @@ fn main() {
     // let archive = PathBuf::from(concat!(env!("OUT_DIR"),"/rules.zip"));
 
     let out_dir = std::env::var_os("OUT_DIR").unwrap();
-    let archive: PathBuf = [out_dir, std::ffi::OsString::from("rules.zip")].iter().collect();
-    eprintln!("zip file location: '{:?}'", archive.to_str());
+    let archive_path: PathBuf = [out_dir, std::ffi::OsString::from("rules.zip")].iter().collect();
+    eprintln!("zip file location: '{:?}'", archive_path.to_str());
 
-    let archive = match File::create(&archive) {
+    let archive = match File::create(&archive_path) {
         Ok(file) => file,
-        Err(e) => panic!("build.rs couldn't create {:?}: {}", archive.to_str(), e),
+        Err(e) => panic!("build.rs couldn't create {:?}: {}", archive_path.to_str(), e),
     };
     // let root_dir = std::env::var_os("CARGO_MANIFEST_DIR ").unwrap(); 
     // let zip_directory: PathBuf = [root_dir.clone(), std::ffi::OsString::from("Rules")].iter().collect();
@@ -81,5 +81,13 @@ fn main() {
     if let Err(e) = zip_dir(zip_directory, archive, zip_options) {
         panic!("Error: {}", e);
     }
+
+    // Installers can ship this single file in place of the Rules dir (see find_rules_dir in prefs.rs)
+    if let Some(copy_to) = std::env::var_os("MATHCAT_RULES_ZIP") {
+        if let Err(e) = std::fs::copy(&archive_path, &copy_to) {
+            panic!("build.rs couldn't copy {:?} to {:?}: {}", archive_path.to_str(), copy_to, e);
+        }
+    }
     println!("cargo:rerun-if-changed=Rules");
+    println!("cargo:rerun-if-env-changed=MATHCAT_RULES_ZIP");
 }
\ No newline at end of file
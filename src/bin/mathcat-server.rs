@@ -0,0 +1,78 @@
+//! A long-running MathCAT server for applications that can't use the library directly (e.g., Java, .NET, Node).
+//! The protocol is JSON-RPC 2.0 with one request/response per line -- see `libmathcat::server` for the methods.
+//!
+//! Usage: `mathcat-server [--rules <Rules dir>] [--port <port>]`
+//! * Without `--port`, requests are read from stdin and the responses are written to stdout.
+//! * With `--port`, the server listens on that port on the local machine (127.0.0.1).
+//!   Each connection is handled on its own thread with its own sessions; the preferences are shared by all of them.
+//!
+//! If `--rules` isn't given, the `MathCATRulesDir` environment variable or the Rules next to the executable are used.
+use libmathcat::interface::*;
+use libmathcat::server::Server;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+
+fn main() {
+    env_logger::builder()
+        .format_timestamp(None)
+        .target(env_logger::Target::Stderr)
+        .init();
+
+    // the Rules next to the executable (MathCATRulesDir, if set, is used instead -- see `set_rules_dir`)
+    let mut rules_dir = match std::env::current_exe() {
+        Ok(exe) => exe.parent().unwrap().join("Rules").to_string_lossy().to_string(),
+        Err(_) => String::new(),
+    };
+    let mut port: Option<u16> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--rules", Some(dir)) => rules_dir = dir,
+            ("--port", Some(value)) => match value.parse() {
+                Ok(value) => port = Some(value),
+                Err(_) => exit_with_error(&format!("'{}' is not a valid port number", value)),
+            },
+            _ => exit_with_error(&format!("unknown argument '{}'\nUsage: mathcat-server [--rules <Rules dir>] [--port <port>]", arg)),
+        }
+    }
+
+    if let Err(e) = set_rules_dir(rules_dir) {
+        exit_with_error(&errors_to_string(&e));
+    }
+
+    let result = match port {
+        None => Server::new().serve(std::io::stdin().lock(), std::io::stdout().lock()),
+        Some(port) => listen(port),
+    };
+    if let Err(e) = result {
+        exit_with_error(&e.to_string());
+    }
+}
+
+fn listen(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("mathcat-server listening on {}", listener.local_addr()?);
+    // the preference files are read once for all the connections -- the sessions keep their own overrides
+    let prefs = get_shared_preferences();
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let prefs = prefs.clone();
+        std::thread::spawn(move || {
+            use_shared_preferences(prefs);
+            if let Err(e) = serve_connection(stream) {
+                eprintln!("mathcat-server: connection error: {}", e);
+            }
+        });
+    }
+    Ok( () )
+}
+
+fn serve_connection(stream: TcpStream) -> std::io::Result<()> {
+    let input = BufReader::new(stream.try_clone()?);
+    Server::new().serve(input, stream)
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("mathcat-server: {}", message);
+    std::process::exit(1);
+}
@@ -5,6 +5,7 @@ use crate::errors::*;
 use regex::{Captures, Regex, RegexSet};
 use phf::{phf_map, phf_set};
 use crate::speech::{BRAILLE_RULES, SpeechRulesWithContext};
+use crate::prefs::SharedPreferences;
 use std::ops::Range;
 
 static UEB_PREFIXES: phf::Set<char> = phf_set! {
@@ -15,16 +16,21 @@ static UEB_PREFIXES: phf::Set<char> = phf_set! {
 /// braille the MathML
 /// If 'nav_node_id' is not an empty string, then the element with that id will have dots 7 & 8 turned on as per the pref
 pub fn braille_mathml(mathml: Element, nav_node_id: String) -> Result<String> {
-    crate::speech::SpeechRules::update();
+    return braille_mathml_with_prefs(mathml, nav_node_id, &crate::prefs::PreferenceManager::get());
+}
+
+/// [`braille_mathml`] using the preferences in `pref_manager` (e.g., from [`crate::prefs::PreferenceManager::with_overrides`])
+pub fn braille_mathml_with_prefs(mathml: Element, nav_node_id: String, pref_manager: &SharedPreferences) -> Result<String> {
+    crate::speech::SpeechRules::update()?;
     return BRAILLE_RULES.with(|rules| {
         rules.borrow_mut().read_files()?;
         let rules = rules.borrow();
         let new_package = Package::new();
-        let mut rules_with_context = SpeechRulesWithContext::new(&rules, new_package.as_document(), nav_node_id);
+        let mut rules_with_context = SpeechRulesWithContext::new_with_prefs(&rules, new_package.as_document(), nav_node_id, pref_manager.clone());
         let braille_string = rules_with_context.match_pattern::<String>(mathml)
                         .chain_err(|| "Pattern match/replacement failure!")?;
         let braille_string = braille_string.replace(' ', "");
-        let pref_manager = rules_with_context.get_rules().pref_manager.borrow();
+        let pref_manager = crate::prefs::read_prefs(rules_with_context.get_pref_manager())?;
         let highlight_style = pref_manager.get_user_prefs().to_string("BrailleNavHighlight");
         let braille_code = pref_manager.get_user_prefs().to_string("BrailleCode");
         let braille = match braille_code.as_str() {
@@ -212,7 +218,7 @@ fn nemeth_cleanup(raw_braille: String) -> String {
     // SRE doesn't have H: Hebrew or U: Russian, so not encoded (yet)
     // Note: some "positive" patterns find cases to keep the char and transform them to the lower case version
     static NEMETH_INDICATOR_REPLACEMENTS: phf::Map<&str, &str> = phf_map! {
-        "S" => "⠈⠰",    // sans-serif
+        "S" => "⠠⠨",    // sans-serif
         "B" => "⠸",     // bold
         "𝔹" => "⠈",     // blackboard
         "T" => "⠈",     // script (mapped to be the same a blackboard)
@@ -478,7 +484,7 @@ fn ueb_cleanup(raw_braille: String) -> String {
 
     // these typeforms need to get pulled from user-prefs as they are transcriber-defined
     let pref_manager = crate::prefs::PreferenceManager::get();
-    let pref_manager = pref_manager.borrow();
+    let pref_manager = crate::prefs::read_prefs_unchecked(&pref_manager);
     let prefs = pref_manager.get_user_prefs();
     let double_struck = prefs.to_string("UEB_DoubleStruck");
     let sans_serif = prefs.to_string("UEB_SansSerif");
@@ -1378,12 +1384,18 @@ impl BrailleChars {
         let mut typeface = "R".to_string();     // assumption is "R" and if attr or letter is different, something happens
         let mut is_all_caps = true;
         let mut is_all_caps_valid = false;      // all_caps only valid if we did a replacement
+        let mut has_typeform_letter = false;    // each letter has its own indicators, so no double cap
         let result = PICK_APART_CHAR.replace_all(&braille_chars, |caps: &Captures| {
             // debug!("  face: {:?}, lang: {:?}, num {:?}, cap: {:?}, char: {:?}",
             //        &caps["face"], &caps["lang"], &caps["num"], &caps["cap"], &caps["char"]);
             let mut nemeth_chars = "".to_string();
             let char_face = if caps["face"].is_empty() {attr_typeface} else {&caps["face"]};
-            let typeface_changed =  typeface != char_face;
+            // Nemeth typeform indicators only apply to the next letter, so they are repeated for each letter
+            let is_typeform_letter = char_face != "R" && !caps["letter"].is_empty();
+            if is_typeform_letter {
+                has_typeform_letter = true;
+            }
+            let typeface_changed =  typeface != char_face || is_typeform_letter;
             if typeface_changed {
                 typeface = char_face.to_string();   // needs to outlast this instance of the loop
                 nemeth_chars += &typeface;
@@ -1404,7 +1416,7 @@ impl BrailleChars {
         });
         // debug!("  result: {}", &result);
         let mut text_chars = text.chars();     // see if more than one char
-        if is_all_caps_valid && is_all_caps && text_chars.next().is_some() &&  text_chars.next().is_some() {
+        if is_all_caps_valid && is_all_caps && !has_typeform_letter && text_chars.next().is_some() &&  text_chars.next().is_some() {
             return Ok( "CC".to_string() + &result.replace('C', ""));
         } else {
             return Ok( result.to_string() );
@@ -1556,6 +1568,179 @@ impl Function for BrailleChars {
         }
     }
     
+
+/// North American Braille ASCII, indexed by the dot pattern (the low 6 bits of the Unicode braille char)
+static BRF_ASCII: &[u8; 64] = b" A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=";
+
+/// Convert a (Unicode) braille string to Braille ASCII as used in BRF files.
+/// Dots 7 & 8 (e.g., nav highlighting) are dropped; non-braille chars are passed through unchanged.
+pub fn braille_to_brf(braille: &str) -> String {
+    return braille.chars()
+        .map(|ch| {
+            let ch_as_u32 = ch as u32;
+            if (0x2800..=0x28FF).contains(&ch_as_u32) {
+                BRF_ASCII[(ch_as_u32 & 0x3F) as usize] as char
+            } else {
+                ch
+            }
+        })
+        .collect();
+}
+
+// Words for braille cells used by `braille_echo`. Longer sequences are listed before their prefixes.
+static NEMETH_ECHO: &[(&str, &str)] = &[
+    ("⠸⠲", "punctuation indicator, period"), ("⠠⠀", "comma"),
+    ("⠨⠅", "equals"), ("⠐⠅", "less than"), ("⠨⠂", "greater than"),
+    ("⠈⠡", "times"), ("⠨⠌", "divided by"), ("⠸⠌", "slash"),
+    ("⠈⠷", "open bracket"), ("⠈⠾", "close bracket"), ("⠨⠷", "open brace"), ("⠨⠾", "close brace"),
+    ("⠬", "plus"), ("⠤", "minus"), ("⠡", "dot"), ("⠷", "open parenthesis"), ("⠾", "close parenthesis"),
+    ("⠹", "open fraction"), ("⠌", "fraction line"), ("⠼", "close fraction"),
+    ("⠜", "radical"), ("⠻", "termination indicator"),
+    ("⠘", "superscript indicator"), ("⠰", "subscript indicator"), ("⠐", "baseline indicator"),
+    ("⠠", "capital indicator"), ("⠨", "Greek letter indicator"), ("⠸", "bold indicator"),
+];
+static NEMETH_DIGITS: [char; 10] = ['⠴', '⠂', '⠆', '⠒', '⠲', '⠢', '⠖', '⠶', '⠦', '⠔'];
+
+static UEB_ECHO: &[(&str, &str)] = &[
+    ("⠰⠰⠰", "grade 1 passage indicator"), ("⠰⠰", "grade 1 word indicator"), ("⠰", "grade 1 indicator"),
+    ("⠠⠠", "capitals word indicator"), ("⠠", "capital indicator"),
+    ("⠐⠖", "plus"), ("⠐⠤", "minus"), ("⠐⠶", "equals"), ("⠐⠦", "times"), ("⠐⠌", "divided by"),
+    ("⠈⠣", "less than"), ("⠈⠜", "greater than"), ("⠸⠌", "slash"),
+    ("⠐⠣", "open parenthesis"), ("⠐⠜", "close parenthesis"), ("⠨⠣", "open bracket"), ("⠨⠜", "close bracket"),
+    ("⠸⠣", "open brace"), ("⠸⠜", "close brace"),
+    ("⠣", "open grouping"), ("⠜", "close grouping"),
+    ("⠷", "open fraction"), ("⠨⠌", "fraction line"), ("⠌", "fraction line"), ("⠾", "close fraction"),
+    ("⠩", "open radical"), ("⠬", "close radical"),
+    ("⠔", "superscript indicator"), ("⠢", "subscript indicator"),
+    ("⠨", "Greek letter indicator"), ("⠂", "comma"), ("⠲", "period"),
+];
+static UEB_DIGITS: [char; 10] = ['⠚', '⠁', '⠃', '⠉', '⠙', '⠑', '⠋', '⠛', '⠓', '⠊'];
+
+static LETTER_CELLS: [char; 26] = [
+    '⠁', '⠃', '⠉', '⠙', '⠑', '⠋', '⠛', '⠓', '⠊', '⠚', '⠅', '⠇', '⠍',
+    '⠝', '⠕', '⠏', '⠟', '⠗', '⠎', '⠞', '⠥', '⠧', '⠺', '⠭', '⠽', '⠵',
+];
+
+/// Return words for the cells in `braille` (e.g., "numeric indicator, 3, 4") for people learning `braille_code`.
+/// The indicators, digits, letters, and common symbols of Nemeth and UEB are named; other cells are given as dot numbers ("dots 1 2 4").
+/// Blank cells are not spoken. Dots 7 & 8 (e.g., nav highlighting) are ignored.
+pub fn braille_echo(braille: &str, braille_code: &str) -> String {
+    let (symbols, digits) = match braille_code {
+        "Nemeth" => (NEMETH_ECHO, &NEMETH_DIGITS),
+        "UEB" => (UEB_ECHO, &UEB_DIGITS),
+        _ => (&[] as &[(&str, &str)], &[' '; 10]),
+    };
+    let is_nemeth = braille_code == "Nemeth";
+    let cells: Vec<char> = braille.chars().map(unhighlight).collect();
+    let is_digit = |i: usize| cells.get(i).is_some_and(|ch| digits.contains(ch));
+    let mut words: Vec<String> = Vec::new();
+    let mut in_number = false;      // UEB digits are letters after the numeric indicator
+    let mut i = 0;
+    while i < cells.len() {
+        let ch = cells[i];
+        if ch == '⠀' {
+            in_number = false;
+            i += 1;
+            continue;
+        }
+        // the numeric indicator and decimal point share cells with other symbols
+        if ch == '⠼' && (braille_code == "UEB" || is_nemeth && is_digit(i+1) || (cells.get(i+1) == Some(&'⠨') && is_digit(i+2))) {
+            words.push("numeric indicator".to_string());
+            in_number = true;
+            i += 1;
+            continue;
+        }
+        if is_nemeth && ch == '⠨' && is_digit(i+1) && i > 0 && (is_digit(i-1) || cells[i-1] == '⠼') ||
+           in_number && !is_nemeth && ch == '⠲' && is_digit(i+1) {
+            words.push("decimal point".to_string());
+            i += 1;
+            continue;
+        }
+        if let Some(digit) = digits.iter().position(|&digit| digit == ch).filter(|_| is_nemeth || in_number) {
+            words.push(digit.to_string());
+            i += 1;
+            continue;
+        }
+        in_number = false;
+        let rest: String = cells[i..].iter().collect();
+        if let Some((cells_matched, word)) = symbols.iter().find(|(symbol, _)| rest.starts_with(symbol)) {
+            words.push(word.to_string());
+            i += cells_matched.chars().count();
+            continue;
+        }
+        match LETTER_CELLS.iter().position(|&letter| letter == ch).filter(|_| !symbols.is_empty()) {
+            Some(letter) => words.push(((b'a' + letter as u8) as char).to_string()),
+            None => words.push(dot_numbers(ch)),
+        }
+        i += 1;
+    }
+    return words.join(", ");
+
+    fn dot_numbers(ch: char) -> String {
+        let ch_as_u32 = ch as u32;
+        if !(0x2800..=0x28FF).contains(&ch_as_u32) {
+            return ch.to_string();
+        }
+        let dots = (0..8).filter(|dot| ch_as_u32 & (1 << dot) != 0)
+                    .map(|dot| (dot + 1).to_string())
+                    .collect::<Vec<String>>();
+        return format!("{} {}", if dots.len() == 1 {"dot"} else {"dots"}, dots.join(" "));
+    }
+}
+
+/// Format a list of (Unicode) braille expressions as a BRF file.
+/// Each expression starts on a new line and is separated from the previous one by a blank line.
+/// Expressions longer than `line_length` are broken at a space if possible, otherwise at the line length;
+///   runover lines are indented two cells (Nemeth 2022, §1.6).
+/// This is a hard wrap: the braille code's rules for where an expression may be divided
+///   (e.g., before a comparison sign) are not used and no continuation indicator is added.
+/// A form feed is emitted every `page_length` lines; a blank line is never put at the top of a page.
+/// Lines end with "\r\n" as is conventional for BRF files.
+pub fn format_brf(braille_list: &[String], line_length: usize, page_length: usize) -> String {
+    const RUNOVER_INDENT: usize = 2;
+    let line_length = std::cmp::max(line_length, RUNOVER_INDENT + 1);
+    let mut lines: Vec<String> = Vec::new();
+    for braille in braille_list {
+        let braille = braille_to_brf(braille);
+        if !lines.is_empty() {
+            lines.push("".to_string());
+        }
+        let mut chars: &[char] = &braille.chars().collect::<Vec<char>>();
+        let mut indent = 0;
+        while !chars.is_empty() {
+            let room = line_length - indent;
+            let (line, rest) = if chars.len() <= room {
+                (chars, &chars[chars.len()..])
+            } else {
+                // break at the last space that fits (the space itself is dropped)
+                match chars[..=room].iter().rposition(|&ch| ch == ' ') {
+                    Some(i) if i > 0 => (&chars[..i], &chars[i+1..]),
+                    _ => (&chars[..room], &chars[room..]),
+                }
+            };
+            lines.push(" ".repeat(indent) + &line.iter().collect::<String>());
+            chars = rest;
+            indent = RUNOVER_INDENT;
+        }
+    }
+
+    let mut result = String::with_capacity(lines.iter().map(|line| line.len() + 2).sum());
+    let mut n_lines_on_page = 0;
+    for line in lines {
+        if page_length > 0 && n_lines_on_page == page_length {
+            result.push('\u{000C}');
+            n_lines_on_page = 0;
+        }
+        if n_lines_on_page == 0 && line.is_empty() {
+            continue;
+        }
+        result.push_str(&line);
+        result.push_str("\r\n");
+        n_lines_on_page += 1;
+    }
+    return result;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1584,4 +1769,78 @@ mod tests {
         assert_eq!("⠼⠙⣰⣁⠉", braille);
         return Ok( () );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn brf_ascii() {
+        assert_eq!(braille_to_brf("⠀⠁⠃⠉⠿⠼⠆⠬⠒"), " ABC=#2+3");
+        assert_eq!(braille_to_brf("⣼⣙⠰⠁⠉"), "#D;AC");     // dots 7 & 8 are dropped
+    }
+
+    #[test]
+    fn brf_line_breaks() {
+        // breaks at a space if possible and indents runover lines
+        let brf = format_brf(&["⠁⠃⠀⠉⠙⠀⠑⠋⠛".to_string()], 6, 25);
+        assert_eq!(brf, "AB CD\r\n  EFG\r\n");
+        // no space -- break at the line length
+        let brf = format_brf(&["⠁⠃⠉⠙⠑⠋⠛⠓".to_string()], 5, 25);
+        assert_eq!(brf, "ABCDE\r\n  FGH\r\n");
+    }
+
+    #[test]
+    fn brf_long_expression() {
+        // Nemeth for x+y+z = a+b+c-d-e-f -- a hard wrap, so the first break is after the comparison sign
+        let brf = format_brf(&["⠭⠬⠽⠬⠵⠀⠨⠅⠀⠁⠬⠃⠬⠉⠤⠙⠤⠑⠤⠋".to_string()], 10, 25);
+        assert_eq!(brf, "X+Y+Z .K\r\n  A+B+C-D-\r\n  E-F\r\n");
+    }
+
+    #[test]
+    fn brf_pages() {
+        let braille_list = ["⠁".to_string(), "⠃".to_string(), "⠉".to_string()];
+        assert_eq!(format_brf(&braille_list, 40, 0), "A\r\n\r\nB\r\n\r\nC\r\n");
+        // the blank line that would start the second page is dropped
+        assert_eq!(format_brf(&braille_list, 40, 2), "A\r\n\r\n\u{000C}B\r\n\r\n\u{000C}C\r\n");
+    }
+
+    #[test]
+    fn get_brf_restores_mathml() -> Result<()> {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_preference("BrailleNavHighlight".to_string(), "Off".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi></math>".to_string()).unwrap();
+        let brf = get_brf(vec!["<math><mn>3</mn><mo>+</mo><mi>y</mi></math>".to_string(),
+                               "<math><msup><mi>x</mi><mn>2</mn></msup></math>".to_string()])?;
+        assert_eq!(brf, "#3+Y\r\n\r\nX^2\r\n");
+        assert_eq!(get_braille("".to_string())?, "⠭");
+        return Ok( () );
+    }
+
+    #[test]
+    fn braille_echo_cells() {
+        assert_eq!(braille_echo("⠼⠒⠲", "Nemeth"), "numeric indicator, 3, 4");
+        assert_eq!(braille_echo("⠹⠁⠌⠃⠼", "Nemeth"), "open fraction, a, fraction line, b, close fraction");
+        assert_eq!(braille_echo("⠭⠘⠆⠐⠬⠼⠂⠨⠢", "Nemeth"),
+                   "x, superscript indicator, 2, baseline indicator, plus, numeric indicator, 1, decimal point, 5");
+        assert_eq!(braille_echo("⠠⠭⠀⠨⠅⠀⠨⠁", "Nemeth"), "capital indicator, x, equals, Greek letter indicator, a");
+        assert_eq!(braille_echo("⠼⠉⠙⠐⠖⠭", "UEB"), "numeric indicator, 3, 4, plus, x");
+        assert_eq!(braille_echo("⠼⠁⠲⠑⠰⠁", "UEB"), "numeric indicator, 1, decimal point, 5, grade 1 indicator, a");
+        assert_eq!(braille_echo("⠿\u{28C1}", "UEB"), "dots 1 2 3 4 5 6, a");      // highlighting is ignored
+        assert_eq!(braille_echo("⠼⠁", "Vietnam"), "dots 3 4 5 6, dot 1");
+    }
+
+    #[test]
+    fn braille_echo_pref() -> Result<()> {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_preference("BrailleNavHighlight".to_string(), "Off".to_string()).unwrap();
+        set_mathml("<math><mn>3</mn><mo>+</mo><mi>y</mi></math>".to_string()).unwrap();
+        assert_eq!(get_braille_echo()?, "numeric indicator, 3, plus, y");
+        set_preference("BrailleEcho".to_string(), "true".to_string()).unwrap();
+        assert_eq!(get_spoken_text()?, "numeric indicator, 3, plus, y");
+        set_preference("BrailleEcho".to_string(), "false".to_string()).unwrap();
+        assert_ne!(get_spoken_text()?, "numeric indicator, 3, plus, y");
+        let echo = get_spoken_text_with_prefs("<math><mn>3</mn><mo>+</mo><mi>y</mi></math>".to_string(),
+                                              &[("BrailleEcho".to_string(), "true".to_string())])?;
+        assert_eq!(echo, "numeric indicator, 3, plus, y");
+        return Ok( () );
+    }
+}
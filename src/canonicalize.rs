@@ -26,8 +26,18 @@ const EMPTY_IN_2D: &str = "data-empty-in-2D";
 // character to use instead of the text content for priority, etc.
 pub const CHEMICAL_BOND: &str ="data-chemical-bond";
 
-/// Used when mhchem is detected and we should favor postscripts rather than prescripts in constructing an mmultiscripts
-const MHCHEM_MMULTISCRIPTS_HACK: &str = "MHCHEM_SCRIPT_HACK";
+/// Set to the (1-based) index of an answer blank in an interactive widget (see [`mark_answer_blanks`])
+pub const ANSWER_BLANK_ATTR: &str = "data-answer-blank";
+
+/// The attributes online homework systems use to mark an editable answer slot and the values that mean it is one
+static ANSWER_ROLE_ATTRS: &[&str] = &["data-role", "role", "aria-role"];
+static ANSWER_ROLES: phf::Set<&str> = phf_set! {
+	"answer", "answer-blank", "blank", "input", "textbox",
+};
+
+/// Set on the empty base of a script element when zero-width 'mpadded's (e.g., from mhchem) say whether the scripts
+/// belong to the preceding element ("postscript") or the following element ("prescript") when constructing an mmultiscripts
+const SCRIPT_ATTACHMENT_ATTR: &str = "data-script-attachment";
 
 // (perfect) hash of operators built from MathML's operator dictionary
 static OPERATORS: phf::Map<&str, OperatorInfo> = include!("operator-info.in");
@@ -81,6 +91,14 @@ lazy_static!{
 	static ref DEFAULT_OPERATOR_INFO_POSTFIX: &'static OperatorInfo = &OperatorInfo{
 		op_type: OperatorTypes::POSTFIX, priority: 260, next: &None
 	};
+	// used when the markup says an operator that isn't in the operator dictionary is a fence or separator (e.g., fence="true")
+	static ref DEFAULT_OPERATOR_INFO_LEFT_FENCE: &'static OperatorInfo = &OperatorInfo{
+		op_type: OperatorTypes::LEFT_FENCE, priority: 20, next: &None
+	};
+	static ref DEFAULT_OPERATOR_INFO_RIGHT_FENCE: &'static OperatorInfo = &OperatorInfo{
+		op_type: OperatorTypes::RIGHT_FENCE, priority: 20, next: &None
+	};
+	static ref DEFAULT_OPERATOR_INFO_SEPARATOR: &'static OperatorInfo = OPERATORS.get(",").unwrap();
 
 	// avoids having to use Option<OperatorInfo> in some cases
 	static ref ILLEGAL_OPERATOR_INFO: &'static OperatorInfo = &OperatorInfo{
@@ -204,6 +222,18 @@ impl OperatorInfo {
 		return self.op_type.bits & op_type.bits != 0;
 	}
 
+	// true if any of the versions of the operator (e.g., prefix and infix) is a fence
+	fn has_fence_version(&self) -> bool {
+		let mut op_info = Some(self);
+		while let Some(op) = op_info {
+			if op.is_left_fence() || op.is_right_fence() {
+				return true;
+			}
+			op_info = op.next.as_ref();
+		}
+		return false;
+	}
+
 	fn is_plus_or_minus(&self) -> bool {
 		return ptr_eq(self, *PLUS) || ptr_eq(self, *MINUS);
 	}
@@ -316,6 +346,11 @@ pub fn is_fence(mo: Element) -> bool {
 			.find_operator(mo, None, None, None).is_fence();
 }
 
+// true if the markup (e.g., from a converter) says the 'mo' is a fence: either a form is given or fence="true"
+fn is_marked_as_fence(mo: Element) -> bool {
+	return mo.attribute_value("form").is_some() || mo.attribute_value("fence") == Some("true");
+}
+
 pub fn is_relational_op(mo: Element) -> bool {
 	return CanonicalizeContext::new()
 			.find_operator(mo, None, None, None).priority == *EQUAL_PRIORITY;
@@ -397,6 +432,188 @@ pub fn canonicalize(mathml: Element) -> Result<Element> {
 struct CanonicalizeContext {
 }
 
+/// Returns true if `mathml` is an editable answer slot (e.g., `data-role="answer"` or `role="textbox"`)
+pub fn is_answer_blank(mathml: Element) -> bool {
+	return ANSWER_ROLE_ATTRS.iter().any(|&attr|
+		mathml.attribute_value(attr).is_some_and(|role| ANSWER_ROLES.contains(role.trim().to_ascii_lowercase().as_str()))
+	);
+}
+
+/// Number the answer blanks in document order by setting [`ANSWER_BLANK_ATTR`] on them.
+/// Answer blanks inside of an answer blank aren't numbered. `count` is the number of answer blanks found so far.
+fn mark_answer_blanks(mathml: Element, count: &mut usize) {
+	if is_answer_blank(mathml) {
+		*count += 1;
+		mathml.set_attribute_value(ANSWER_BLANK_ATTR, &count.to_string());
+		return;
+	}
+	for child in mathml.children() {
+		if let Some(child) = child.element() {
+			mark_answer_blanks(child, count);
+		}
+	}
+}
+
+/// Returns true if `name` is the name of a MathML element
+pub fn is_mathml_element_name(name: &str) -> bool {
+	return ALL_MATHML_ELEMENTS.contains(name) || name == "semantics";
+}
+
+/// The kind of problem found by [`validate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+	/// The element is not a MathML element
+	UnknownElement,
+	/// The element has the wrong number of children (e.g., an `mfrac` with three children)
+	WrongNumberOfChildren,
+	/// A child (or text) is not allowed where it is (e.g., a child of `mtable` that is not an `mtr`)
+	InvalidChild,
+	/// A token element (e.g., `mi`) contains elements rather than just text
+	InvalidTokenContent,
+	/// The element is deprecated (e.g., `mfenced`)
+	DeprecatedElement,
+	/// The attribute is deprecated (e.g., `fontweight`)
+	DeprecatedAttribute,
+}
+
+/// A problem found by [`validate`] along with a suggested fix
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathMLDiagnostic {
+	/// The kind of problem
+	pub kind: DiagnosticKind,
+	/// An XPath-like path to the element with the problem (e.g., "/math/mrow[1]/mfrac[2]")
+	pub location: String,
+	/// A description of the problem
+	pub message: String,
+	/// How to fix the problem
+	pub suggestion: String,
+}
+
+static DEPRECATED_ATTRIBUTES: phf::Map<&str, &str> = phf_map! {
+	"fontfamily" => "Use 'mathvariant' instead",
+	"fontweight" => "Use 'mathvariant' (e.g., 'bold') instead",
+	"fontstyle" => "Use 'mathvariant' (e.g., 'italic' or 'normal') instead",
+	"fontsize" => "Use 'mathsize' instead",
+	"color" => "Use 'mathcolor' instead",
+	"background" => "Use 'mathbackground' instead",
+	"mode" => "Use 'display' ('block' or 'inline') instead",
+	"macros" => "Remove the attribute",
+	"other" => "Remove the attribute",
+};
+
+/// Return the problems found in `mathml`, which should not have been trimmed or canonicalized.
+/// Canonicalization repairs many of these problems, but authoring tools should fix them at the source.
+pub fn validate(mathml: Element) -> Vec<MathMLDiagnostic> {
+	let mut diagnostics = vec![];
+	let location = format!("/{}", name(&mathml));
+	if name(&mathml) != "math" {
+		add_diagnostic(&mut diagnostics, DiagnosticKind::InvalidChild, &location,
+					   format!("The root element is '{}' and not 'math'", name(&mathml)), "Wrap the MathML in a 'math' element");
+	}
+	validate_element(mathml, &location, &mut diagnostics);
+	return diagnostics;
+
+	fn add_diagnostic(diagnostics: &mut Vec<MathMLDiagnostic>, kind: DiagnosticKind, location: &str, message: String, suggestion: &str) {
+		diagnostics.push( MathMLDiagnostic{ kind, location: location.to_string(), message, suggestion: suggestion.to_string() } );
+	}
+
+	fn validate_element(mathml: Element, location: &str, diagnostics: &mut Vec<MathMLDiagnostic>) {
+		let element_name = name(&mathml);
+		for attr in mathml.attributes() {
+			let attr_name = attr.name().local_part();
+			if let Some(suggestion) = DEPRECATED_ATTRIBUTES.get(attr_name) {
+				if attr_name != "mode" || element_name == "math" {
+					add_diagnostic(diagnostics, DiagnosticKind::DeprecatedAttribute, location,
+								   format!("The '{}' attribute on '{}' is deprecated", attr_name, element_name), suggestion);
+				}
+			}
+		}
+
+		let children: Vec<Element> = mathml.children().iter().filter_map(|child| child.element()).collect();
+		if element_name == "semantics" {
+			// only the presentation child is spoken
+			if let Some(&child) = children.first() {
+				validate_element(child, &format!("{}/{}[1]", location, name(&child)), diagnostics);
+			}
+			return;
+		}
+		if !ALL_MATHML_ELEMENTS.contains(element_name) {
+			add_diagnostic(diagnostics, DiagnosticKind::UnknownElement, location,
+						   format!("'{}' is not a MathML element", element_name),
+						   "Replace it with a MathML element (e.g., an 'mrow' to group its children)");
+			return;
+		}
+		if element_name == "mfenced" {
+			add_diagnostic(diagnostics, DiagnosticKind::DeprecatedElement, location,
+						   "'mfenced' is deprecated".to_string(),
+						   "Replace it with an 'mrow' that contains 'mo' elements for the fences and separators");
+		}
+
+		if is_leaf(mathml) {
+			if EMPTY_ELEMENTS.contains(element_name) {
+				if mathml.children().iter().any(|child| child.text().is_none_or(|text| !text.text().trim().is_empty())) {
+					add_diagnostic(diagnostics, DiagnosticKind::WrongNumberOfChildren, location,
+								   format!("'{}' should be empty", element_name), "Remove its contents");
+				}
+			} else if children.iter().any(|child| !matches!(name(child), "mglyph" | "malignmark")) {
+				add_diagnostic(diagnostics, DiagnosticKind::InvalidTokenContent, location,
+							   format!("'{}' should only contain text", element_name),
+							   "Move the child elements out of the token element (e.g., into an 'mrow')");
+			}
+			return;
+		}
+
+		if mathml.children().iter().any(|child| child.text().is_some_and(|text| !text.text().trim().is_empty())) {
+			add_diagnostic(diagnostics, DiagnosticKind::InvalidChild, location,
+						   format!("'{}' contains text that is not in a token element", element_name),
+						   "Wrap the text in an 'mi', 'mn', 'mo', or 'mtext' element");
+		}
+
+		let n_children = children.len();
+		if ELEMENTS_WITH_FIXED_NUMBER_OF_CHILDREN.contains(element_name) {
+			let expected = match element_name {
+				"munderover" | "msubsup" => if n_children == 3 {None} else {Some("3 children")},
+				"mmultiscripts" => {
+					let has_prescripts = children.iter().any(|child| name(child) == "mprescripts");
+					if has_prescripts ^ n_children.is_multiple_of(2) {Some("a base followed by pairs of scripts")} else {None}
+				},
+				"mlongdiv" => if n_children >= 3 {None} else {Some("at least 3 children")},
+				_ => if n_children == 2 {None} else {Some("2 children")},
+			};
+			if let Some(expected) = expected {
+				add_diagnostic(diagnostics, DiagnosticKind::WrongNumberOfChildren, location,
+							   format!("'{}' has {} children but should have {}", element_name, n_children, expected),
+							   if n_children > 2 {
+									"Group the parts of each argument with an 'mrow'"
+							   } else {
+									"Add the missing children (an empty 'mrow' can be used for an empty argument)"
+							   });
+			}
+		}
+
+		for (i, &child) in children.iter().enumerate() {
+			let child_name = name(&child);
+			let index = children[..i].iter().filter(|&&sibling| name(&sibling) == child_name).count() + 1;
+			let child_location = format!("{}/{}[{}]", location, child_name, index);
+			let is_row = child_name == "mtr" || child_name == "mlabeledtr";
+			if element_name == "mtable" && !is_row {
+				add_diagnostic(diagnostics, DiagnosticKind::InvalidChild, &child_location,
+							   format!("'{}' is not allowed as a child of 'mtable'", child_name), "Wrap it in 'mtr' and 'mtd' elements");
+			} else if element_name != "mtable" && is_row {
+				add_diagnostic(diagnostics, DiagnosticKind::InvalidChild, &child_location,
+							   format!("'{}' should be a child of 'mtable'", child_name), "Put it inside an 'mtable'");
+			} else if (element_name == "mtr" || element_name == "mlabeledtr") && child_name != "mtd" {
+				add_diagnostic(diagnostics, DiagnosticKind::InvalidChild, &child_location,
+							   format!("'{}' is not allowed as a child of '{}'", child_name, element_name), "Wrap it in an 'mtd' element");
+			} else if child_name == "mtd" && !(element_name == "mtr" || element_name == "mlabeledtr") {
+				add_diagnostic(diagnostics, DiagnosticKind::InvalidChild, &child_location,
+							   "'mtd' should be a child of 'mtr'".to_string(), "Put it inside an 'mtr' in an 'mtable'");
+			}
+			validate_element(child, &child_location, diagnostics);
+		}
+	}
+}
+
 #[derive(PartialEq)]
 #[allow(non_camel_case_types)] 
 enum DigitBlockType {
@@ -420,6 +637,17 @@ static ELEMENTS_WITH_ONE_CHILD: phf::Set<&str> = phf_set! {
 	"math", "msqrt", "merror", "mpadded", "mphantom", "menclose", "mtd", "mscarry"
 };
 
+// Parents whose children are cleaned one after another (mrow and elements with an inferred mrow)
+static MROW_LIKE_PARENTS: phf::Set<&str> = phf_set! {
+	"mrow", "math", "msqrt", "merror", "menclose", "mtd", "mscarry"
+};
+
+// Attributes on mstyle and mpadded that only change the size or spacing of the display
+static LAYOUT_ONLY_ATTRS: phf::Set<&str> = phf_set! {
+	"displaystyle", "scriptlevel", "scriptsizemultiplier", "scriptminsize", "mathsize",
+	"width", "height", "depth", "lspace", "voffset",
+};
+
 static ELEMENTS_WITH_FIXED_NUMBER_OF_CHILDREN: phf::Set<&str> = phf_set! {
 	"mfrac", "mroot", "msub", "msup", "msubsup","munder", "mover", "munderover", "mmultiscripts", "mlongdiv"
 };
@@ -428,6 +656,82 @@ static EMPTY_ELEMENTS: phf::Set<&str> = phf_set! {
 	"mspace", "none", "mprescripts", "mglyph", "malignmark", "maligngroup", "msline",
 };
 
+// Operators and their negated (slashed) form.
+// Some converters build the negated form by overlaying a slash on the operator (a combining overlay char or a strike-through menclose).
+static NEGATED_OPERATORS: phf::Map<&str, &str> = phf_map! {
+	"=" => "≠", "<" => "≮", ">" => "≯", "≤" => "≰", "≥" => "≱",
+	"∈" => "∉", "∋" => "∌", "∃" => "∄", "|" => "∤", "∣" => "∤", "∥" => "∦", "‖" => "∦",
+	"⊂" => "⊄", "⊃" => "⊅", "⊆" => "⊈", "⊇" => "⊉", "⊑" => "⋢", "⊒" => "⋣",
+	"∼" => "≁", "~" => "≁", "≃" => "≄", "≅" => "≇", "≈" => "≉", "≡" => "≢", "≍" => "≭",
+	"≲" => "≴", "≳" => "≵", "≶" => "≸", "≷" => "≹",
+	"≺" => "⊀", "≻" => "⊁", "≼" => "⋠", "≽" => "⋡",
+	"⊢" => "⊬", "⊨" => "⊭", "⊩" => "⊮", "⊫" => "⊯",
+	"⊲" => "⋪", "⊳" => "⋫", "⊴" => "⋬", "⊵" => "⋭",
+};
+
+/// Returns the negated operator if 'text' is an operator followed by a combining slash overlay (e.g., "=" followed by U+0338)
+fn negated_overlay_text(text: &str) -> Option<&'static str> {
+	let base = text.strip_suffix('\u{0338}').or_else(|| text.strip_suffix('\u{0337}'))?;
+	return NEGATED_OPERATORS.get(base).copied();
+}
+
+/// Returns the negated operator if 'menclose' strikes through a single operator (e.g., an "=" with an 'updiagonalstrike')
+fn negated_menclose_text(menclose: Element) -> Option<&'static str> {
+	let notation = menclose.attribute_value("notation")?.trim();
+	if notation != "updiagonalstrike" && notation != "downdiagonalstrike" {
+		return None;
+	}
+	let children = menclose.children();
+	if children.len() != 1 {
+		return None;
+	}
+	let child = as_element(children[0]);
+	if name(&child) != "mo" {
+		return None;
+	}
+	return NEGATED_OPERATORS.get(as_text(child)).copied();
+}
+
+/// If 'mi' is a single character followed by a combining accent (e.g., "x" followed by U+0302), it is turned into
+///   the equivalent 'mover' (or 'munder') with the accent as an 'mo' and the element is returned.
+/// The accent's 'mo' is canonicalized later along with other accents.
+fn fold_combining_accent(mi: Element) -> Option<Element> {
+	let mut chars = as_text(mi).chars();
+	let (base, accent) = (chars.next()?, chars.next()?);
+	if chars.next().is_some() {
+		return None;
+	}
+	let script_name = match accent {
+		'\u{0300}'..='\u{0308}' | '\u{030A}' | '\u{030C}' | '\u{20D6}' | '\u{20D7}' | '\u{20E1}' => "mover",
+		'\u{0330}' | '\u{0331}' | '\u{0332}' => "munder",
+		_ => return None,
+	};
+	let doc = mi.document();
+	let new_mi = create_mathml_element(&doc, "mi");
+	new_mi.set_text(&base.to_string());
+	for attr in mi.attributes() {
+		if attr.name().local_part() != "id" {
+			new_mi.set_attribute_value(attr.name(), attr.value());
+			mi.remove_attribute(attr.name());
+		}
+	}
+	let mo = create_mathml_element(&doc, "mo");
+	mo.set_text(&accent.to_string());
+	set_mathml_name(mi, script_name);
+	mi.set_attribute_value(if script_name == "mover" {"accent"} else {"accentunder"}, "true");
+	mi.replace_children([new_mi, mo]);
+	return Some(mi);
+}
+
+static ALL_MATHML_ELEMENTS: phf::Set<&str> = phf_set!{
+	"mi", "mo", "mn", "mtext", "ms", "mspace", "mglyph",
+	"mfrac", "mroot", "msub", "msup", "msubsup","munder", "mover", "munderover", "mmultiscripts",
+	"mstack", "mlongdiv", "msgroup", "msrow", "mscarries", "mscarry", "msline",
+	"none", "mprescripts", "malignmark", "maligngroup",
+	"math", "msqrt", "merror", "mpadded", "mphantom", "menclose", "mtd", "mstyle",
+	"mrow", "mfenced", "mtable", "mtr", "mlabeledtr", "maction",
+};
+
 lazy_static! {
 	// turns out Roman Numerals tests aren't needed, but we do want to block VII from being a chemical match
 	// two cases because we don't want to have a match for 'Cl', etc.
@@ -466,6 +770,7 @@ impl CanonicalizeContext {
 			converted_mathml = self.canonicalize_mrows(mathml)
 				.chain_err(|| format!("while processing\n{}", mml_to_string(&mathml)))?;
 		}
+		mark_answer_blanks(converted_mathml, &mut 0);
 		debug!("\nMathML after canonicalize:\n{}", mml_to_string(&converted_mathml));
 		return Ok(converted_mathml);
 	}
@@ -501,15 +806,6 @@ impl CanonicalizeContext {
 
 	/// Return an error is some element is not MathML (only look at first child of <semantics>) or if it has the wrong number of children
 	fn assure_mathml(mathml: Element) -> Result<()> {
-		static ALL_MATHML_ELEMENTS: phf::Set<&str> = phf_set!{
-			"mi", "mo", "mn", "mtext", "ms", "mspace", "mglyph",
-			"mfrac", "mroot", "msub", "msup", "msubsup","munder", "mover", "munderover", "mmultiscripts",
-			"mstack", "mlongdiv", "msgroup", "msrow", "mscarries", "mscarry", "msline",
-			"none", "mprescripts", "malignmark", "maligngroup",
-			"math", "msqrt", "merror", "mpadded", "mphantom", "menclose", "mtd", "mstyle",
-			"mrow", "mfenced", "mtable", "mtr", "mlabeledtr",
-		};
-
 		let n_children = mathml.children().len();
 		let element_name = name(&mathml);
 		if is_leaf(mathml) {
@@ -610,6 +906,8 @@ impl CanonicalizeContext {
 	/// 
 	/// mstyle, mpadded, and mphantom, malignmark, maligngroup are removed (but children might be kept)
 	/// 
+	/// maction is replaced by the child that is displayed (MathML Core only displays one child)
+	/// 
 	/// Significant changes are made cleaning up empty bases of scripts, looking for chemistry, merging numbers with commas,
 	///   "arg trig" functions, pseudo scripts, and others
 	/// 
@@ -620,6 +918,9 @@ impl CanonicalizeContext {
 			static ref IS_PRIME: Regex = Regex::new(r"['′″‴⁗]").unwrap(); 
         }
 
+		static ELLIPSES: phf::Set<&str> = phf_set! {
+			"…", "⋯", "⋮", "⋰", "⋱",
+		};
 		static CURRENCY_SYMBOLS: phf::Set<&str> = phf_set! {
 			"$", "¢", "€", "£", "₡", "₤", "₨", "₩", "₪", "₱", "₹", "₺", "₿" // could add more currencies...
 		};
@@ -637,7 +938,7 @@ impl CanonicalizeContext {
 
 		// handle empty leaves -- leaving it empty causes problems with the speech rules
 		if is_leaf(mathml) && !EMPTY_ELEMENTS.contains(element_name) && as_text(mathml).is_empty() {
-			if !parent_requires_child {
+			if !parent_requires_child && !is_answer_blank(mathml) {
 				return None;
 			}
 			CanonicalizeContext::make_empty_element(mathml);
@@ -646,7 +947,7 @@ impl CanonicalizeContext {
 		if mathml.children().is_empty() && !EMPTY_ELEMENTS.contains(element_name) {
 			if element_name == "mrow" && mathml.attribute("intent").is_none() {
 				// if it is an empty mrow that doesn't need to be there, get rid of it. Otherwise, replace it with an mtext
-				if parent_requires_child {
+				if parent_requires_child || is_answer_blank(mathml) {
 					if parent_name == "mmultiscripts" {	// MathML Core dropped "none" in favor of <mrow/>, but MathCAT is written with <none/>
 						set_mathml_name(mathml, "none");
 						return Some(mathml);
@@ -696,6 +997,9 @@ impl CanonicalizeContext {
 				// 	mathml.set_attribute_value("data-roman-numeral", "true");	// mark for easy detection
 				// 	return Some(mathml);
 			 	// }
+				if let Some(accented) = fold_combining_accent(mathml) {
+					return Some(accented);
+				}
 				if let Some(dash) = canonicalize_dash(text) {		// needs to be before OPERATORS.get due to "--"
 					mathml.set_text(dash);
 					return Some(mathml);
@@ -744,20 +1048,32 @@ impl CanonicalizeContext {
 				return if parent_requires_child || !text.is_empty() {Some(mathml)} else {None};
 			},
 			"mo" => {
+				// some converters build "≠", "∉", etc., by following the operator with a combining slash
+				if let Some(negated) = negated_overlay_text(as_text(mathml)) {
+					mathml.set_text(negated);
+					return Some(mathml);
+				}
 				// WIRIS editor puts non-breaking whitespace as standalone in 'mo'
 				let text = as_text(mathml);
+				// converters sometimes use an invisible operator for an invisible fence (e.g., "\left.") -- it shouldn't be spoken
+				if mathml.attribute_value("fence") == Some("true") && text.chars().all(|ch| ('\u{2061}'..='\u{2064}').contains(&ch)) {
+					if !parent_requires_child {
+						return None;
+					}
+					return Some( CanonicalizeContext::make_empty_element(mathml) );
+				}
 				if !text.is_empty() && IS_WHITESPACE.is_match(text) {
 					// can't throw it out because it is needed by braille -- change to what it really is
 					set_mathml_name(mathml, "mtext");
 				}
 				// common bug: trig functions, lim, etc., should be mi
-				// same for ellipsis ("…")
+				// same for ellipses ("…", "⋯", etc) -- they stand in for elided operands
 				if let Some(result) = merge_arc_trig(mathml) {
 					return Some(result);
 				};
 
 				return crate::definitions::DEFINITIONS.with(|definitions| {
-					if text == "…" || 
+					if ELLIPSES.contains(text) || 
 					   definitions.borrow().get_hashset("FunctionNames").unwrap().contains(text) ||
 					   definitions.borrow().get_hashset("GeometryShapes").unwrap().contains(text) {
 						set_mathml_name(mathml, "mi");
@@ -780,7 +1096,31 @@ impl CanonicalizeContext {
 			"mstyle" | "mpadded" => {
 				// Throw out mstyle and mpadded -- to do this, we need to avoid mstyle being the arg of clean_mathml
 				// FIX: should probably push the attrs down to the children (set in 'self')
-				let children = mathml.children();
+				// Sizing attrs only affect the display, so they are dropped rather than pushed down to the child
+				remove_layout_only_attrs(mathml);
+				let mut children = mathml.children();
+				while children.len() == 1 && is_layout_only(mathml) {
+					// nested layout-only wrappers (e.g., from TeX's {\displaystyle ...}) -- unwrap them
+					let child = as_element(children[0]);
+					if !(name(&child) == "mstyle" || name(&child) == "mpadded") || !is_layout_only(remove_layout_only_attrs(child)) {
+						break;
+					}
+					mathml.replace_children(child.children());
+					children = mathml.children();
+				}
+				if children.len() > 1 && MROW_LIKE_PARENTS.contains(&parent_name) && is_layout_only(mathml) &&
+				   mathml.parent().unwrap().element().unwrap().children().len() > 1 {
+					// The parent is (or has an inferred) mrow, so splice the children into it instead of adding an mrow.
+					// That way the grouping doesn't become a navigation stop or change how the parent is parsed.
+					// The first child gets lifted below; the others are cleaned as siblings when the parent's loop gets to them.
+					// (If the mstyle is the only child, the mrow made below replaces the parent's inferred mrow, so there is nothing to do.)
+					let parent = mathml.parent().unwrap().element().unwrap();
+					let mut siblings = parent.children();
+					let i = siblings.iter().position(|&sibling| sibling == ChildOfElement::Element(mathml)).unwrap();
+					siblings.splice(i+1..i+1, children[1..].iter().copied());
+					parent.replace_children(siblings);
+					children = mathml.children();
+				}
 				if children.is_empty() {
 					if parent_requires_child {
 						// need a placeholder -- make it empty mtext
@@ -809,6 +1149,32 @@ impl CanonicalizeContext {
 					return self.clean_mathml(mathml);	// now it's an mrow so a different path next time
 				}
 			},
+			"maction" => {
+				// MathML Core (and hence browsers) only display one child of maction -- keep that one and throw out the rest
+				let children = mathml.children();
+				let selection = mathml.attribute_value("selection")
+						.and_then(|selection| selection.trim().parse::<usize>().ok())
+						.filter(|&selection| selection >= 1 && selection <= children.len())
+						.unwrap_or(1);
+				if children.is_empty() {
+					return if parent_requires_child {Some( CanonicalizeContext::make_empty_element(mathml) )} else {None};
+				}
+				let selected_child = as_element(children[selection-1]);
+				mathml.replace_children(vec![selected_child]);
+				if let Some(new_mathml) = self.clean_mathml(selected_child) {
+					// "lift" the child up so all the links (e.g., siblings) are correct
+					mathml.replace_children(new_mathml.children());
+					set_mathml_name(mathml, name(&new_mathml));
+					mathml.remove_attribute("actiontype");
+					mathml.remove_attribute("selection");
+					add_attrs(mathml, new_mathml.attributes());
+					return Some(mathml);
+				} else if parent_requires_child {
+					return Some( CanonicalizeContext::make_empty_element(mathml) );
+				} else {
+					return None;
+				}
+			},
 			"mphantom" | "malignmark" | "maligngroup"=> {
 				if parent_requires_child {
 					return Some( CanonicalizeContext::make_empty_element(mathml));
@@ -854,7 +1220,6 @@ impl CanonicalizeContext {
 					if children.is_empty() && mathml.attribute("intent").is_none() {
 						return if parent_requires_child {Some(mathml)} else {None};
 					} else if children.len() == 1 && CanonicalizeContext::is_ok_to_merge_mrow_child(mathml) {
-						let is_from_mhchem = is_from_mhchem_hack(mathml);
 						if let Some(new_mathml) = self.clean_mathml(as_element(children[0])) {
 							// "lift" the child up so all the links (e.g., siblings) are correct
 							mathml.replace_children(new_mathml.children());
@@ -862,11 +1227,7 @@ impl CanonicalizeContext {
 							add_attrs(mathml, new_mathml.attributes());
 							return Some(mathml);
 						} else if parent_requires_child {
-							let empty = CanonicalizeContext::make_empty_element(mathml);
-							if is_from_mhchem {
-								empty.set_attribute_value(MHCHEM_MMULTISCRIPTS_HACK, "true");
-							}
-							return Some(empty);
+							return Some( CanonicalizeContext::make_empty_element(mathml) );
 						} else {
 							return None;
 						}
@@ -882,6 +1243,12 @@ impl CanonicalizeContext {
 					mathml
 				};
 
+				let script_attachment = if element_name == "msub" || element_name == "msup" || element_name == "msubsup" {
+					overlapping_script_attachment(mathml)
+				} else {
+					None
+				};
+
 				// cleaning children can add or delete subsequent children, so we need to constantly update the children (and mathml)
 				let mut children = mathml.children();
 				let mut i = 0;
@@ -945,6 +1312,12 @@ impl CanonicalizeContext {
 								return None;
 							}
 						}
+						if let Some(attachment) = script_attachment {
+							let base = as_element(children[0]);
+							if CanonicalizeContext::is_empty_element(base) {
+								base.set_attribute_value(SCRIPT_ATTACHMENT_ATTR, attachment);
+							}
+						}
 					}
 					let mathml = if element_name == "mmultiscripts" {clean_mmultiscripts(mathml).unwrap()} else {mathml};
 					// debug!("some scripted element...\n{}", mml_to_string(&mathml));	
@@ -965,6 +1338,17 @@ impl CanonicalizeContext {
 
 				mathml.replace_children(children);
 				// debug!("clean_mathml: after loop\n{}", mml_to_string(&mathml));
+				if element_name == "menclose" {
+					if let Some(negated) = negated_menclose_text(mathml) {
+						// the strike-through makes the operator a negated operator -- lift the 'mo' up (keeping its attrs)
+						let mo = as_element(mathml.children()[0]);
+						set_mathml_name(mathml, "mo");
+						mathml.remove_attribute("notation");
+						add_attrs(mathml, mo.attributes());
+						mathml.set_text(negated);
+						return Some(mathml);
+					}
+				}
 
 				if element_name == "mrow" || ELEMENTS_WITH_ONE_CHILD.contains(element_name) {
 					clean_chemistry_mrow(mathml);
@@ -990,36 +1374,140 @@ impl CanonicalizeContext {
 		}
 
 
-		/// Returns true if it detects that this is likely coming from mhchem (msub/msup with mrow/mrow/mpadded width=0/mphantom/mi=A)
-		/// This should be called with 'mrow' being the outer mrow
-		fn is_from_mhchem_hack(mrow: Element) -> bool {
-			assert_eq!(name(&mrow), "mrow");
-			assert_eq!(mrow.children().len(), 1);
-			let parent = mrow.parent().unwrap().element().unwrap();
-			let parent_name = name(&parent);
-			if !(parent_name == "msub" || parent_name == "msup") {
-				return false;
+		/// TeX converters (e.g., MathJax's mhchem) overlap scripts with their neighbors by using zero-width 'mpadded's
+		///   rather than generating 'mmultiscripts'. For example, the base might be '<mpadded width="0"><mphantom><mi>A</mi></mphantom></mpadded>'
+		///   and the scripts might be right-aligned with '<mpadded width="0" lspace="-1width">' (i.e., '\\llap').
+		/// If the base of 'script' (msub/msup/msubsup) takes no space, this returns whether the scripts belong to the
+		///   preceding element ("postscript") or the following element ("prescript" -- the scripts are shifted left so they end where the base starts).
+		/// Returns None if the base has a width.
+		/// Note: this needs to be called before the children are cleaned because cleaning removes the 'mpadded's.
+		fn overlapping_script_attachment(script: Element) -> Option<&'static str> {
+			let children = script.children();
+			if children.len() < 2 || !is_zero_width(as_element(children[0])) {
+				return None;
+			}
+			let scripts = children[1..].iter().map(|&child| as_element(child)).filter(|&child| !is_invisible(child)).collect::<Vec<Element>>();
+			if !scripts.is_empty() && scripts.iter().all(|&child| is_shifted_left(child)) {
+				return Some("prescript");
+			} else {
+				return Some("postscript");
 			}
 
-			let mrow = as_element(mrow.children()[0]);
-			if !(name(&mrow) == "mrow" && mrow.children().len() == 1) {
-				return false;
+			/// Returns true if 'mathml' doesn't take up any horizontal space
+			fn is_zero_width(mathml: Element) -> bool {
+				match name(&mathml) {
+					"mpadded" => {
+						if let Some(width) = mathml.attribute_value("width") {
+							return mpadded_length(width, (1.0, 0.0)).is_some_and(is_not_positive);
+						}
+						return mathml.children().iter().all(|&child| is_zero_width(as_element(child)));
+					},
+					"mrow" | "mstyle" => return mathml.children().iter().all(|&child| is_zero_width(as_element(child))),
+					"mspace" => return mathml.attribute_value("width").is_none_or(|width| width.trim().starts_with('-') ||
+													mpadded_length(width, (0.0, 0.0)).is_some_and(is_not_positive)),
+					"none" => return true,
+					"mi" | "mn" | "mo" | "mtext" => return as_text(mathml).trim().is_empty(),
+					_ => return false,
+				}
 			}
-			let child = as_element(mrow.children()[0]);
-			if !(name(&child) == "mpadded" && child.attribute("width").is_some()) {
-				return false;
+
+			/// Returns true if nothing in 'mathml' is displayed
+			fn is_invisible(mathml: Element) -> bool {
+				match name(&mathml) {
+					"mphantom" | "mspace" | "none" => return true,
+					"mpadded" | "mrow" | "mstyle" => return mathml.children().iter().all(|&child| is_invisible(as_element(child))),
+					"mi" | "mn" | "mo" | "mtext" => return as_text(mathml).trim().is_empty(),
+					_ => return false,
+				}
 			}
-			if child.attribute_value("width").unwrap() != "0" {
-				return false;
+
+			/// Returns true if all the visible parts of 'mathml' are drawn to the left of where 'mathml' is placed
+			fn is_shifted_left(mathml: Element) -> bool {
+				match name(&mathml) {
+					"mpadded" => {
+						if let (Some(width), Some(lspace)) = (mathml.attribute_value("width"), mathml.attribute_value("lspace")) {
+							// the content starts at 'lspace' and is 'w' wide, so it ends at 'lspace + w' which needs to be <= 0
+							return mpadded_length(width, (1.0, 0.0)).is_some_and(is_not_positive) &&
+								   mpadded_length(lspace, (0.0, 0.0)).is_some_and(|(w, em)| is_not_positive((w + 1.0, em)));
+						}
+						return is_shifted_left_children(mathml);
+					},
+					"mrow" | "mstyle" => return is_shifted_left_children(mathml),
+					_ => return false,
+				}
 			}
 
-			let child = as_element(child.children()[0]);
-			if !(name(&child) == "mphantom" && child.children().len() == 1) {
-				return false;
+			fn is_shifted_left_children(mathml: Element) -> bool {
+				let visible_children = mathml.children().iter()
+						.map(|&child| as_element(child))
+						.filter(|&child| !is_invisible(child))
+						.collect::<Vec<Element>>();
+				return !visible_children.is_empty() && visible_children.iter().all(|&child| is_shifted_left(child));
+			}
+
+			fn is_not_positive((width_multiple, ems): (f64, f64)) -> bool {
+				return width_multiple <= 0.0 && ems <= 0.0;
 			}
+		}
 
-			let child = as_element(child.children()[0]);
-			return name(&child) == "mi" && as_text(child) == "A";
+		/// Interpret an 'mpadded' 'width' or 'lspace' value (e.g., "0", "-0.2em", "+1width", "-100%", "negativethinmathspace").
+		/// The value is returned as '(w, em)', meaning 'w' times the width of the content plus 'em' ems.
+		/// 'default' is the value when the attribute isn't given (width: '(1.0, 0.0)', lspace: '(0.0, 0.0)');
+		///   it is what signed (relative) values are added to and what percentages and unitless numbers are multiples of.
+		/// Returns None if the value can't be interpreted, including the 'height' and 'depth' pseudo-units which say nothing about the width.
+		fn mpadded_length(value: &str, default: (f64, f64)) -> Option<(f64, f64)> {
+			let value = value.trim();
+			let (sign, value) = match value.chars().next() {
+				Some('+') => (Some(1.0), value[1..].trim_start()),
+				Some('-') => (Some(-1.0), value[1..].trim_start()),
+				_ => (None, value),
+			};
+			let i_unit = value.find(|ch: char| !(ch.is_ascii_digit() || ch == '.')).unwrap_or(value.len());
+			let (amount, unit) = value.split_at(i_unit);
+			let unit = unit.trim();
+			let length = if amount.is_empty() {
+				(0.0, named_space_in_ems(unit)?)
+			} else {
+				let amount = amount.parse::<f64>().ok()?;
+				match unit {
+					"" => (amount * default.0, amount * default.1),
+					"%" => (amount * default.0 / 100.0, amount * default.1 / 100.0),
+					"width" => (amount, 0.0),
+					"height" | "depth" if amount == 0.0 => (0.0, 0.0),
+					"em" | "rem" => (0.0, amount),
+					"ex" => (0.0, amount / 2.0),
+					"mu" => (0.0, amount / 18.0),
+					"px" => (0.0, amount / 16.0),	// assume a 12pt (16px) font
+					"pt" => (0.0, amount / 12.0),
+					"pc" => (0.0, amount),
+					"in" => (0.0, 6.0 * amount),
+					"cm" => (0.0, amount * 6.0 / 2.54),
+					"mm" => (0.0, amount * 6.0 / 25.4),
+					_ => return None,
+				}
+			};
+			return Some( match sign {
+				None => length,
+				Some(sign) => (default.0 + sign * length.0, default.1 + sign * length.1),
+			} );
+
+			fn named_space_in_ems(name: &str) -> Option<f64> {
+				let (sign, name) = match name.strip_prefix("negative") {
+					Some(name) => (-1.0, name),
+					None => (1.0, name),
+				};
+				let eighteenths = match name {
+					"veryverythinmathspace" => 1.0,
+					"verythinmathspace" => 2.0,
+					"thinmathspace" => 3.0,
+					"mediummathspace" => 4.0,
+					"thickmathspace" => 5.0,
+					"verythickmathspace" => 6.0,
+					"veryverythickmathspace" => 7.0,
+					_ => return None,
+				};
+				return Some(sign * eighteenths / 18.0);
+			}
 		}
 
 		/// Returns true if it appears the width is just a spacing tweak rather than really a space.
@@ -1761,8 +2249,8 @@ impl CanonicalizeContext {
 		/// Converts the script element with an empty base to mmultiscripts by sucking the base from the following or preceding element.
 		/// The following element is preferred so that these become prescripts (common usage is from TeX), but if the preceding element
 		///   has a closer mi/mtext, it is used.
-		/// mhchem has some ugly output (at least in MathJax) and that's where using the following element makes sense
-		///   because an empty based (mpadded width=0) is used for the scripts. A hacky attribute indicates this case.
+		/// mhchem has some ugly output (at least in MathJax) where the scripts have an empty base (mpadded width=0) and
+		///   the layout determines which element the scripts belong to. SCRIPT_ATTACHMENT_ATTR on the base indicates this case.
 		fn convert_to_mmultiscripts(mrow_children: &mut Vec<ChildOfElement>, i: usize) -> usize {
 			// this is a bit messy/confusing because we might scan forwards or backwards and this affects whether
 			// we are scanning for prescripts or postscripts
@@ -1897,11 +2385,11 @@ impl CanonicalizeContext {
 			// We already know there are no empty scripts to the left (because we find first empty base from left to right).
 			// However, there may be some empty bases before we get to real base on the right.
 			let script_element_base = as_element(as_element(mrow_children[i]).children()[0]);
-			let from_mchem = script_element_base.attribute(MHCHEM_MMULTISCRIPTS_HACK).is_some();
-			if mrow_children.len() > i+1 && !(from_mchem && i > 0) && is_child_simple_base(mrow_children[i+1]) {
+			let attachment = script_element_base.attribute_value(SCRIPT_ATTACHMENT_ATTR);
+			if mrow_children.len() > i+1 && !(attachment == Some("postscript") && i > 0) && is_child_simple_base(mrow_children[i+1]) {
 				return i+1;
 			}
-			if i > 0 {
+			if i > 0 && attachment != Some("prescript") {
 				if let Some(i_start) = is_grouped_base(&mrow_children[..i]) {
 					assert!(i_start < i-1);	// should be at least two children (open and close)
 					// create a new mrow, add the grouped children to it, then drain all but the first of them from the original mrow vec.
@@ -2282,13 +2770,17 @@ impl CanonicalizeContext {
 		if !is_base && (parent_name == "mover" || parent_name == "munder" || parent_name == "munderover") {
 			// canonicalize various diacritics for munder, mover, munderover
 			mo_text = match mo_text {
-				"_" | "\u{02C9}"| "\u{0304}"| "\u{0305}"| "\u{2212}" |
+				"_" | "\u{02C9}"| "\u{0304}"| "\u{0305}"| "\u{2212}" | "\u{0331}"| "\u{0332}" |
 				"\u{2010}" | "\u{2011}" | "\u{2012}" | "\u{2013}" | "\u{2014}" | "\u{2015}" => "\u{00AF}",
-				"\u{02BC}" => "`",
-				"\u{02DC}" => "~",
+				"\u{02BC}" | "\u{0300}" => "`",
+				"\u{0301}" => "\u{00B4}",
+				"\u{02DC}" | "\u{0303}" | "\u{0330}" => "~",
 				"\u{02C6}"| "\u{0302}" => "^",
 				"\u{0307}" => "\u{02D9}",	// Nemeth distinguishes this from "." -- \u{02D9} is generated for over dots by most generators
 				"\u{0308}" => "¨",
+				"\u{20D6}" => "←",
+				"\u{20D7}" => "→",
+				"\u{20E1}" => "↔",
 				_ => mo_text,
 			}
 			// FIX: MathType generates the wrong version of union and intersection ops (binary instead of unary)
@@ -2347,8 +2839,21 @@ impl CanonicalizeContext {
 		// get the unicode value and return the OpKeyword associated with it
 		assert!( name(&mo_node) == "mo");
 	
+		let found_op_info = if mo_node.attribute_value(CHEMICAL_BOND).is_some() {
+			Some(&*IMPLIED_CHEMICAL_BOND)
+		} else {
+			OPERATORS.get(as_text(mo_node))
+		};
+
 		// if a form has been given, that takes precedence
-		let form = mo_node.attribute_value("form");
+		// the exception is converters that mark an operator as a fence when the operator dictionary says it can't be one (e.g., "+"):
+		//   that markup is wrong, so it is ignored (along with the form) and the form is inferred from the position
+		// note: fence="false" is ignored -- converters put it on fences that are written without \left/\right in TeX (e.g., "|x|")
+		let is_marked_as_fence = mo_node.attribute_value("fence") == Some("true");
+		let form = match found_op_info {
+			Some(op_info) if is_marked_as_fence && !op_info.has_fence_version() => None,
+			_ => mo_node.attribute_value("form"),
+		};
 		let op_type =  match form {
 			None => compute_type_from_position(self, previous_operator, previous_node, next_node),
 			Some(form) => match form.to_lowercase().as_str() {
@@ -2358,13 +2863,13 @@ impl CanonicalizeContext {
 			}
 		};	
 	
-		let found_op_info = if mo_node.attribute_value(CHEMICAL_BOND).is_some() {
-			Some(&*IMPLIED_CHEMICAL_BOND)
-		} else {
-			OPERATORS.get(as_text(mo_node))
-		};
 		if found_op_info.is_none() {
-			// no known operator -- return the unknown operator with the correct "fix" type
+			// no known operator -- use what the markup says it is or else return the unknown operator with the correct "fix" type
+			if is_marked_as_fence {
+				return if op_type == OperatorTypes::POSTFIX {&DEFAULT_OPERATOR_INFO_RIGHT_FENCE} else {&DEFAULT_OPERATOR_INFO_LEFT_FENCE};
+			} else if mo_node.attribute_value("separator") == Some("true") {
+				return &DEFAULT_OPERATOR_INFO_SEPARATOR;
+			}
 			return op_not_in_operator_dictionary(op_type);
 		}
 	
@@ -2456,7 +2961,8 @@ impl CanonicalizeContext {
 	fn determine_vertical_bar_op<'a>(&self, original_op: &'static OperatorInfo, mo_node: Element<'a>, 
 				next_child: Option<Element<'a>>,
 				parse_stack: &'a mut Vec<StackInfo>,
-				n_vertical_bars_on_right: usize) -> &'static OperatorInfo {
+				n_vertical_bars_on_right: usize,
+				has_marked_vertical_bar_on_right: bool) -> &'static OperatorInfo {
 		// if in a prefix location, it is a left fence
 		// note:  if there is an operator on the top of the stack, it wants an operand (otherwise it would have been reduced)
 		let operator_str = as_text(mo_node);
@@ -2487,21 +2993,37 @@ impl CanonicalizeContext {
 	
 		// If there is a matching open vertical bar, it is either at the top of the stack or the entry just below the top
 
-		let has_left_match = if let Some(op_prefix) = operator_versions.prefix {
+		// The matching bar is the first child of the mrow for that stack entry
+		let left_match = if let Some(op_prefix) = operator_versions.prefix {
 			if ptr_eq(top(parse_stack).op_pair.op, op_prefix) { 	// match at top of stack? (empty matching bars)
-				true
+				top(parse_stack).mrow.children().first().map(|child| as_element(*child))
 			} else if parse_stack.len() > 2 {
 				// matching op is below top (operand between matching bars) -- pop, peek, push
 				let old_top = parse_stack.pop().unwrap();		
-				let top_op = top(parse_stack).op_pair.op;																	// can only access top, so we need to pop off top and push back later
+				let top_info = top(parse_stack);																	// can only access top, so we need to pop off top and push back later
+				let left_match = if ptr_eq(top_info.op_pair.op, op_prefix) {
+					top_info.mrow.children().first().map(|child| as_element(*child))
+				} else {
+					None
+				};
 				parse_stack.push(old_top);
-				ptr_eq(top_op, op_prefix)
+				left_match
 			} else {
-				false
+				None
 			}
 		} else {
-			false
+			None
 		};
+		let has_left_match = left_match.is_some();
+
+		// If the markup says the matching bar is a fence and there is a bar marked as a fence to the right, that is its match
+		//   (e.g., the outer bars in "|x|y|" have form="prefix"/"postfix"), so a bar that isn't marked is infix
+		if let Some(left_match) = left_match {
+			if has_marked_vertical_bar_on_right &&
+			   is_marked_as_fence(get_possible_embellished_node(left_match)) && !is_marked_as_fence(mo_node) {
+				return operator_versions.infix.unwrap_or(op);
+			}
+		}
 		if operator_versions.postfix.is_some() && (next_child.is_none() || has_left_match) {
 			// last child in row (must be a close) or we have a left match
 			// debug!("   is postfix");
@@ -3124,15 +3646,20 @@ impl CanonicalizeContext {
 							top(&parse_stack).last_child_in_mrow(), next_node)
 				};
 	
-				// deal with vertical bars which might be infix, open, or close fences
+				// deal with vertical bars which might be infix, open, or close fences (unless the form was given)
 				// note: mrow shrinks as we iterate through it (removing children from it)
-				current_op.op = self.determine_vertical_bar_op(
-					current_op.op,
-					base_of_child,
-					next_node,
-					&mut parse_stack,
-					self.n_vertical_bars_on_right(&children[i_child+1..], current_op.ch)
-				);
+				if base_of_child.attribute_value("form").is_none() {
+					current_op.op = self.determine_vertical_bar_op(
+						current_op.op,
+						base_of_child,
+						next_node,
+						&mut parse_stack,
+						self.n_vertical_bars_on_right(&children[i_child+1..], current_op.ch),
+						children[i_child+1..].iter()
+							.map(|child| get_possible_embellished_node(as_element(*child)))
+							.any(|child| name(&child) == "mo" && as_text(child) == current_op.ch && is_marked_as_fence(child))
+					);
+				}
 			} else if top(&parse_stack).last_child_in_mrow().is_some() {
 				let previous_child = top(&parse_stack).last_child_in_mrow().unwrap();
 				let base_of_previous_child = get_possible_embellished_node(previous_child);
@@ -3249,19 +3776,37 @@ impl CanonicalizeContext {
 fn top<'s, 'a:'s, 'op:'a>(vec: &'s[StackInfo<'a, 'op>]) -> &'s StackInfo<'a, 'op> {
 	return &vec[vec.len()-1];
 }
+/// Removes the attrs that only change the size or spacing of the display (mstyle and mpadded)
+fn remove_layout_only_attrs(mathml: Element) -> Element {
+	for attr in mathml.attributes() {
+		if LAYOUT_ONLY_ATTRS.contains(attr.name().local_part()) {
+			mathml.remove_attribute(attr.name());
+		}
+	}
+	return mathml;
+}
+
+/// Returns true if 'mathml' (an mstyle or mpadded whose sizing attrs have been removed) has no attrs that could matter for speech
+fn is_layout_only(mathml: Element) -> bool {
+	return mathml.attributes().iter().all(|attr| {
+		let attr_name = attr.name().local_part();
+		attr_name == "id" || attr_name.starts_with("data-")
+	});
+}
+
 // Replace the attrs of 'mathml' with 'attrs' and keep the global attrs of 'mathml' (i.e, lift 'attrs' to 'mathml' for replacing children)
 fn add_attrs<'a>(mathml: Element<'a>, attrs: Vec<Attribute>) -> Element<'a> {
 	static GLOBAL_ATTRS: phf::Set<&str> = phf_set! {
 		"class", "dir", "displaystyle", "id", "mathbackground", "mathcolor", "mathsize",
 		"mathvariant", "nonce", "scriptlevel", "style", "tabindex",
-		"intent", "arg",
+		"intent", "arg", "role",
 	};
 	
 	// debug!(   "Adding back {} attr(s) to {}", attrs.len(), name(&mathml));
 	// remove non-global attrs
 	for attr in mathml.attributes() {
 		let attr_name = attr.name().local_part();
-		if !( attr_name.starts_with("data-") || GLOBAL_ATTRS.contains(attr_name) ||
+		if !( attr_name.starts_with("data-") || attr_name.starts_with("aria-") || GLOBAL_ATTRS.contains(attr_name) ||
 		      attr_name.starts_with("on") ) {			// allows too much - cheapo way to allow event handlers like "onchange"
 			mathml.remove_attribute(attr.name());
 		}
@@ -4054,6 +4599,78 @@ mod canonicalize_tests {
         assert!(are_strs_canonically_equal(test_str, target_str));
     }
 
+    #[test]
+    fn vertical_bars_marked_as_fences() {
+		// the middle bar isn't paired with the first one because the outer bars are marked as fences
+        let test_str = "<math>
+				<mo fence='true' form='prefix'>|</mo><mi>x</mi><mo>|</mo><mi>y</mi><mo fence='true' form='postfix'>|</mo>
+            </math>";
+        let target_str = "<math>
+		<mrow data-changed='added'>
+		  <mo fence='true' form='prefix'>|</mo>
+		  <mrow data-changed='added'>
+			<mi>x</mi>
+			<mo>|</mo>
+			<mi>y</mi>
+		  </mrow>
+		  <mo fence='true' form='postfix'>|</mo>
+		</mrow>
+	   </math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+    }
+
+    #[test]
+    fn invisible_fences() {
+		// some converters use invisible operators as invisible fences -- they shouldn't be spoken (e.g., as "and")
+        let test_str = "<math>
+				<mo fence='true' form='prefix'>&#x2064;</mo><mi>x</mi><mo>+</mo><mi>y</mi><mo fence='true' form='postfix'>&#x2064;</mo>
+            </math>";
+        let target_str = "<math>
+		<mrow data-changed='added'>
+		  <mi>x</mi>
+		  <mo>+</mo>
+		  <mi>y</mi>
+		</mrow>
+	   </math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+    }
+
+    #[test]
+    fn misclassified_fence() {
+		// "+" can't be a fence, so the markup is ignored
+        let test_str = "<math>
+				<mi>x</mi><mo fence='true' form='prefix'>+</mo><mi>y</mi>
+            </math>";
+        let target_str = "<math>
+		<mrow data-changed='added'>
+		  <mi>x</mi>
+		  <mo fence='true' form='prefix'>+</mo>
+		  <mi>y</mi>
+		</mrow>
+	   </math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+    }
+
+    #[test]
+    fn fence_and_separator_not_in_dictionary() {
+		// characters not in the operator dictionary get their meaning from the fence/separator attributes
+        let test_str = "<math>
+				<mo fence='true'>&#x0F3A;</mo><mi>x</mi><mo separator='true'>&#x0F3B;</mo><mi>y</mi><mo fence='true'>&#x0F3D;</mo>
+            </math>";
+        let target_str = "<math>
+		<mrow data-changed='added'>
+		  <mo fence='true'>&#x0F3A;</mo>
+		  <mrow data-changed='added'>
+			<mi>x</mi>
+			<mo separator='true'>&#x0F3B;</mo>
+			<mi>y</mi>
+		  </mrow>
+		  <mo fence='true'>&#x0F3D;</mo>
+		</mrow>
+	   </math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+    }
+
     #[test]
 	#[ignore]  // need to figure out a test for this ("|" should have a precedence around ":" since that is an alternative notation for "such that", but "∣" is higher precedence)
     fn vertical_bar_divides() {
@@ -4423,6 +5040,140 @@ mod canonicalize_tests {
         assert!(are_strs_canonically_equal(test_str, target_str));
 	}
 
+	#[test]
+    fn mathml_core_maction() {
+        let test_str = "<math><mrow><maction actiontype='statusline'><mi>x</mi><mtext>the variable x</mtext></maction>
+				<mo>+</mo><mn>1</mn></mrow></math>";
+        let target_str = "<math><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mathml_core_maction_selection() {
+        let test_str = "<math><maction actiontype='toggle' selection='2'>
+				<mi>a</mi>
+				<mrow><mi>b</mi><mo>+</mo><mi>c</mi></mrow>
+			</maction></math>";
+        let target_str = "<math><mrow><mi>b</mi><mo>+</mo><mi>c</mi></mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mathml_core_maction_bad_selection() {
+        let test_str = "<math><mfrac><maction actiontype='toggle' selection='7'><mn>1</mn><mn>2</mn></maction><mn>3</mn></mfrac></math>";
+        let target_str = "<math><mfrac><mn>1</mn><mn>3</mn></mfrac></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn negated_operator_overlay() {
+        let test_str = "<math><mrow><mi>x</mi><mo>=&#x338;</mo><mn>2</mn><mo>,</mo><mi>y</mi><mo>&#x2208;&#x0338;</mo><mi>S</mi></mrow></math>";
+        let target_str = "<math><mrow>
+				<mrow data-changed='added'><mi>x</mi><mo>≠</mo><mn>2</mn></mrow>
+				<mo>,</mo>
+				<mrow data-changed='added'><mi>y</mi><mo>∉</mo><mi>S</mi></mrow>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn negated_operator_menclose() {
+        let test_str = "<math><mrow><mi>x</mi><menclose notation='updiagonalstrike'><mo>=</mo></menclose><mn>2</mn></mrow></math>";
+        let target_str = "<math><mrow><mi>x</mi><mo>≠</mo><mn>2</mn></mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn menclose_strike_not_operator() {
+        // a crossed out number (e.g., cancellation) stays as it is
+        let test_str = "<math><mrow><mn>2</mn><mo>+</mo><menclose notation='updiagonalstrike'><mn>3</mn></menclose></mrow></math>";
+        let target_str = "<math><mrow><mn>2</mn><mo>+</mo><menclose notation='updiagonalstrike'><mn>3</mn></menclose></mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mstyle_layout_attrs_dropped() {
+        // sizing attrs aren't pushed down to the lifted child
+        let test_str = "<math><mstyle displaystyle='true' scriptlevel='0'><mfrac><mn>1</mn><mn>2</mn></mfrac></mstyle></math>";
+        let target_str = "<math><mfrac><mn>1</mn><mn>2</mn></mfrac></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mstyle_layout_only_spliced() {
+        // the mstyle's children are parsed as part of the parent mrow -- no extra grouping
+        let test_str = "<math><mrow>
+				<mi>x</mi><mo>+</mo>
+				<mstyle scriptlevel='1'><mstyle displaystyle='false'><mi>y</mi><mo>+</mo><mi>z</mi></mstyle></mstyle>
+				<mo>=</mo><mn>2</mn>
+			</mrow></math>";
+        let target_str = "<math><mrow>
+				<mrow data-changed='added'><mi>x</mi><mo>+</mo><mi>y</mi><mo>+</mo><mi>z</mi></mrow>
+				<mo>=</mo><mn>2</mn>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mstyle_not_layout_only() {
+        // color might be meaningful, so the grouping is kept
+        let test_str = "<math><mrow><mi>x</mi><mo>+</mo><mstyle mathcolor='red'><mi>y</mi><mo>+</mo><mi>z</mi></mstyle></mrow></math>";
+        let target_str = "<math><mrow>
+				<mi>x</mi><mo>+</mo>
+				<mrow mathcolor='red' data-changed='added'><mi>y</mi><mo>+</mo><mi>z</mi></mrow>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn combining_accent_on_mi() {
+        let test_str = "<math><mrow><mi mathvariant='bold'>x&#x302;</mi><mo>+</mo><mi>v&#x20D7;</mi><mo>+</mo><mi>y&#x332;</mi></mrow></math>";
+        let target_str = "<math><mrow>
+				<mover accent='true'><mi mathvariant='bold'>𝐱</mi><mo>^</mo></mover>
+				<mo>+</mo>
+				<mover accent='true'><mi>v</mi><mo>→</mo></mover>
+				<mo>+</mo>
+				<munder accentunder='true'><mi>y</mi><mo>¯</mo></munder>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn combining_accent_not_single_char() {
+        // only a single character identifier is turned into an accented identifier
+        let test_str = "<math><mi>ab&#x302;</mi></math>";
+        let target_str = "<math><mi>ab&#x302;</mi></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mathml_core_chromium_serialization() {
+        // Chromium's MathML Core output: no mfenced, lots of extra mrows, mrow as an empty script
+        let test_str = "<math display='block'><mrow><mrow>
+				<mrow><mo form='prefix' stretchy='false'>(</mo><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mo form='postfix' stretchy='false'>)</mo></mrow>
+				<mrow><msup><mrow><mi>x</mi></mrow><mrow><mn>2</mn></mrow></msup></mrow>
+			</mrow></mrow></math>";
+        let target_str = "<math display='block'>
+				<mrow>
+					<mrow>
+						<mo form='prefix' stretchy='false'>(</mo>
+						<mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow>
+						<mo form='postfix' stretchy='false'>)</mo>
+					</mrow>
+					<mo data-changed='added'>&#x2062;</mo>
+					<msup><mi>x</mi><mn>2</mn></msup>
+				</mrow>
+			</math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn mathml_core_empty_mrow_script() {
+        let test_str = "<math><mmultiscripts><mi>T</mi><mi>i</mi><mrow></mrow><mrow></mrow><mi>j</mi></mmultiscripts></math>";
+        let target_str = "<math><mmultiscripts><mi>T</mi><mi>i</mi><none/><none/><mi>j</mi></mmultiscripts></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
 	#[test]
     fn clean_up_mi_operator() {
         let test_str = "<math><mrow><mi>∠</mi><mi>A</mi><mi>B</mi><mi>C</mi></mrow></math>";
@@ -4713,9 +5464,9 @@ mod canonicalize_tests {
         let target_str = "<math>
 		<msqrt>
 		  <mrow>
-			<mn mathsize='normal' mathvariant='bold'>𝟎</mn>
+			<mn mathvariant='bold'>𝟎</mn>
 			<mo data-changed='added'>&#x2062;</mo>
-			<mn mathsize='normal' mathvariant='bold' data-changed='added'>.02</mn>
+			<mn mathvariant='bold' data-changed='added'>.02</mn>
 		  </mrow>
 		</msqrt>
 	   </math>";
@@ -4762,7 +5513,7 @@ mod canonicalize_tests {
 		</mrow>
 	  </math>";
         let target_str = "<math>
-		<msqrt scriptlevel='0' displaystyle='true'>
+		<msqrt>
 		  <mrow data-changed='added'>
 			<munder>
 			  <mo>∑</mo>
@@ -4822,6 +5573,42 @@ mod canonicalize_tests {
         assert!(are_strs_canonically_equal(test_str, target_str));
 	}
 
+	#[test]
+    fn zero_width_base_right_aligned_prescript() {
+		// the base takes no space and the script is shifted left by its width (\llap), so it is a prescript of the following element
+        let test_str = "<math><mi>x</mi><mo>+</mo>
+				<msup>
+					<mpadded width='0'><mphantom><mi>A</mi></mphantom></mpadded>
+					<mpadded width='-1width' lspace='-1width'><mn>14</mn></mpadded>
+				</msup>
+				<mi>C</mi>
+			</math>";
+        let target_str = "<math><mrow data-changed='added'>
+				<mi>x</mi><mo>+</mo>
+				<mmultiscripts><mi>C</mi><mprescripts/><none/><mn>14</mn></mmultiscripts>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
+	#[test]
+    fn zero_width_base_postscript() {
+		// the base takes no space, so the scripts are placed right after (and belong to) the preceding element
+        let test_str = "<math><mi>X</mi>
+				<msubsup>
+					<mpadded width='0em'><mphantom><mo>(</mo></mphantom></mpadded>
+					<mn>2</mn>
+					<mo>-</mo>
+				</msubsup>
+				<mi>Y</mi>
+			</math>";
+        let target_str = "<math><mrow data-changed='added'>
+				<mmultiscripts><mi>X</mi><mn>2</mn><mo>-</mo></mmultiscripts>
+				<mo data-changed='added'>&#x2062;</mo>
+				<mi>Y</mi>
+			</mrow></math>";
+        assert!(are_strs_canonically_equal(test_str, target_str));
+	}
+
 	#[test]
     fn pre_and_postscript_only() {
         let test_str = "<math>
@@ -83,7 +83,7 @@ static CHEM_EQUATION_ARROWS: phf::Set<char> = phf_set! {
 
 pub fn is_chemistry_off() -> bool {
     let pref_manager = crate::prefs::PreferenceManager::get();
-    return pref_manager.borrow().get_user_prefs().to_string("Chemistry") == "Off";
+    return crate::prefs::read_prefs_unchecked(&pref_manager).get_user_prefs().to_string("Chemistry") == "Off";
 }
 
 pub fn clean_chemistry_mrow(mathml: Element) {
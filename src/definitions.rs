@@ -118,12 +118,40 @@ impl Definitions {
             }
         }
     }
+
+    /// Returns the version of the "NumbersOrdinal..." vector `name` for the grammatical `gender` if there is one
+    /// (e.g., "NumbersOrdinalFeminineOnes" for "NumbersOrdinalOnes" and "Feminine"), otherwise returns the vector `name`.
+    /// An empty `gender` is the same as calling [`Definitions::get_vec`].
+    pub fn get_gendered_vec(&self, name: &str, gender: &str) -> Option<Ref<'_, Vec<String>>> {
+        if !gender.is_empty() {
+            let gendered_name = name.replacen("NumbersOrdinal", &("NumbersOrdinal".to_string() + gender), 1);
+            if let Some(words) = self.get_vec(&gendered_name) {
+                return Some(words);
+            }
+        }
+        return self.get_vec(name);
+    }
 }
 
 thread_local!{
     /// Global variable containing all of the definitions.
     /// See [`Definitions`] for more details.
     pub static DEFINITIONS: RefCell<Definitions> = RefCell::new( Definitions::new() );
+
+    // we cache the last location (saves 3-4ms on startup/switching): creating the SpeechRules calls `read_definitions_file` for each rule
+    static LOCATION_CACHE: RefCell<Locations> = RefCell::new( Locations::default() );
+}
+
+/// Exchange this thread's definitions and the files they were read from with `definitions` and `locations`
+/// (calling it again undoes the exchange). This is used to give a document its own rules (see `crate::speech::ThreadRules`).
+pub(crate) fn swap_definitions(definitions: &mut Definitions, locations: &mut Locations) {
+    DEFINITIONS.with(|defs| std::mem::swap(&mut *defs.borrow_mut(), definitions));
+    LOCATION_CACHE.with(|cache| std::mem::swap(&mut *cache.borrow_mut(), locations));
+}
+
+/// Forget which files were last read so that the next call to [`read_definitions_file`] reads them again (e.g., they were edited).
+pub fn invalidate_definitions_file() {
+    LOCATION_CACHE.with(|cache| *cache.borrow_mut() = Locations::default());
 }
 
 /// Reads the `definitions.yaml` files specified by `locations`.
@@ -131,12 +159,6 @@ thread_local!{
 /// If there is a failure during read, the error is propagated to the caller
 pub fn read_definitions_file(locations: &Locations) -> Result<()> {
     // for each file in `locations`, read the contents and process them
-    // we cache the last location (saves 3-4ms on startup/switching): creating the SpeechRules calls this for each rule
-    thread_local!{
-        static LOCATION_CACHE: RefCell<Locations> =
-                RefCell::new( Locations::default() );
-    }
-    
     if LOCATION_CACHE.with(|cache| are_locations_same(&cache.borrow(), locations)) {
         return Ok( () );
     } else {
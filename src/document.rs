@@ -0,0 +1,129 @@
+//! Helpers for working with whole documents (e.g., an EPUB chapter or other XHTML file) rather than a single expression.
+//!
+//! A common publisher workflow is to add alt text to all the math in a book so that it is read reasonably
+//! by screen readers and other AT that don't understand MathML.
+//! [`add_math_alt_text`] finds each `math` element, generates speech for it using the current preferences,
+//! and sets its `alttext` and `aria-label` attributes to that speech.
+#![allow(clippy::needless_return)]
+
+use regex::{Captures, Regex};
+use crate::errors::*;
+use crate::interface::{set_mathml, spoken_text, prefs_with_overrides, with_saved_mathml};
+use crate::prefs::SharedPreferences;
+
+/// Return `document` with `alttext` and `aria-label` attributes added to each `math` element.
+/// Any existing `alttext` and `aria-label` attributes on the `math` elements are replaced.
+/// The rest of the document is returned unchanged (it is not parsed, so it does not need to be well-formed).
+/// The speech is generated with `TTS` set to `None` (for this call only) because it is used as an attribute value.
+/// The MathML set by [`set_mathml`] (if any) is restored afterwards, but the navigation state is reset.
+pub fn add_math_alt_text(document: &str) -> Result<String> {
+    lazy_static! {
+        // the math element (possibly with a namespace prefix) -- nested math elements are not legal, so a non-greedy match is fine
+        // the name must be followed by whitespace, '/', or '>' so that elements such as 'math-field' don't match
+        static ref MATH_ELEMENT: Regex = Regex::new(
+            r#"(?s)<((?:[[:alpha:]]+:)?math)((?:\s[^>]*?)?)(/?)>(.*?</(?:[[:alpha:]]+:)?math\s*>)?"#).unwrap();
+        static ref ALT_TEXT_ATTRS: Regex = Regex::new(r#"\s(alttext|aria-label)\s*=\s*("[^"]*"|'[^']*')"#).unwrap();
+    }
+
+    let pref_manager = prefs_with_overrides(&[("TTS", "None")])?;
+    return with_saved_mathml(|| {
+        let mut error = None;
+        let result = MATH_ELEMENT.replace_all(document, |cap: &Captures| {
+            if error.is_some() || !cap[3].is_empty() || cap.get(4).is_none() {
+                return cap[0].to_string();   // already failed, or an empty (or unclosed) math element -- nothing to speak
+            }
+            match alt_text(&cap[0], &pref_manager) {
+                Ok(speech) => {
+                    let attrs = ALT_TEXT_ATTRS.replace_all(&cap[2], "");
+                    let speech = escape_attr_value(&speech);
+                    return format!("<{}{} alttext=\"{}\" aria-label=\"{}\">{}", &cap[1], attrs, speech, speech, &cap[4]);
+                },
+                Err(e) => {
+                    error = Some(e);
+                    return cap[0].to_string();
+                },
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+        return Ok( result.to_string() );
+    });
+
+    fn alt_text(math: &str, pref_manager: &SharedPreferences) -> Result<String> {
+        set_mathml(math.to_string())
+            .chain_err(|| format!("while generating alt text for\n{}", math))?;
+        return spoken_text(pref_manager);
+    }
+}
+
+/// Escape the characters that can't appear in a (double-quoted) attribute value
+fn escape_attr_value(value: &str) -> String {
+    return value.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('"', "&quot;");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::*;
+
+    fn init_prefs() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+    }
+
+    #[test]
+    fn xhtml_alt_text() -> Result<()> {
+        init_prefs();
+        let document = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+            <p>Consider <math alttext="old"><msup><mi>x</mi><mn>2</mn></msup></math> and
+            <m:math xmlns:m="http://www.w3.org/1998/Math/MathML" display="block"><m:mi>y</m:mi></m:math>.</p>
+            <p><math/></p>
+        </body></html>"#;
+        let target = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+            <p>Consider <math alttext="x squared" aria-label="x squared"><msup><mi>x</mi><mn>2</mn></msup></math> and
+            <m:math xmlns:m="http://www.w3.org/1998/Math/MathML" display="block" alttext="y" aria-label="y"><m:mi>y</m:mi></m:math>.</p>
+            <p><math/></p>
+        </body></html>"#;
+        assert_eq!(add_math_alt_text(document)?, target);
+        return Ok( () );
+    }
+
+    #[test]
+    fn alt_text_restores_mathml() -> Result<()> {
+        init_prefs();
+        set_mathml("<math><mi>z</mi></math>".to_string()).unwrap();
+        add_math_alt_text("<p><math><mn>1</mn><mo>&lt;</mo><mn>2</mn></math></p>")?;
+        assert_eq!(get_spoken_text()?, "z");
+        return Ok( () );
+    }
+
+    #[test]
+    fn math_prefixed_element_names() -> Result<()> {
+        init_prefs();
+        let document = r#"<p><math-field id="answer"><math><mi>x</mi></math></math-field> <mathx>y</mathx></p>"#;
+        let target = r#"<p><math-field id="answer"><math alttext="x" aria-label="x"><mi>x</mi></math></math-field> <mathx>y</mathx></p>"#;
+        assert_eq!(add_math_alt_text(document)?, target);
+        return Ok( () );
+    }
+
+    #[test]
+    fn alt_text_is_not_ssml() -> Result<()> {
+        init_prefs();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        let result = add_math_alt_text("<p><math><mfrac><mi>x</mi><mn>2</mn></mfrac></math></p>");
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        assert_eq!(result?, r#"<p><math alttext="x over 2" aria-label="x over 2"><mfrac><mi>x</mi><mn>2</mn></mfrac></math></p>"#);
+        return Ok( () );
+    }
+
+    #[test]
+    fn bad_math_is_error() {
+        init_prefs();
+        assert!(add_math_alt_text("<p><math><mi>x</mo></math></p>").is_err());
+    }
+}
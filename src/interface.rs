@@ -2,7 +2,9 @@
 //!
 #![allow(non_snake_case)]
 #![allow(clippy::needless_return)]
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
 
 use sxd_document::parser;
 use sxd_document::Package;
@@ -12,6 +14,10 @@ use regex::{Regex, Captures};
 use phf::phf_map;
 
 use crate::canonicalize::{name, as_element};
+use crate::prefs::SharedPreferences;
+pub use crate::canonicalize::{MathMLDiagnostic, DiagnosticKind};
+pub use crate::pretty_print::FormatOptions;
+pub use crate::speech::PhraseSource;
 
 
 use crate::navigate::*;
@@ -30,6 +36,11 @@ fn cleanup_mathml(mathml: Element) -> Result<Element> {
 thread_local!{
     /// The current node being navigated (also spoken and brailled) is stored in `MATHML_INSTANCE`.
     pub static MATHML_INSTANCE: RefCell<Package> = init_mathml_instance();
+
+    /// If [`set_mathml`] was given several `math` elements, this holds all of them (otherwise it is empty).
+    /// The entry for the selected expression (`MATHML_LIST_INDEX`) is a placeholder because that expression is in `MATHML_INSTANCE`.
+    static MATHML_LIST: RefCell<Vec<Package>> = const { RefCell::new(Vec::new()) };
+    static MATHML_LIST_INDEX: Cell<usize> = const { Cell::new(0) };
 }
 
 fn init_mathml_instance() -> RefCell<Package> {
@@ -40,10 +51,42 @@ fn init_mathml_instance() -> RefCell<Package> {
 
 /// Set the Rules directory
 /// IMPORTANT: this should be the very first call to MathCAT unless the environment var MathCATRulesDir is set
+/// `dir` (or MathCATRulesDir) can also be a zip file of the Rules dir (paths in it start with "Rules/").
+/// If `dir` doesn't exist, a "Rules.zip" file in its place is used (e.g., ".../Rules.zip" for ".../Rules").
+/// If MathCAT is built with the `built-in-rules` feature and the directory can't be found, the Rules built into the library are used.
+/// If the Rules can't be found or read, the error is an `ErrorKind::NotInitialized` error. Until a later call succeeds,
+/// the other calls (speech, braille, navigation, preferences) return that error rather than failing in some other way.
 pub fn set_rules_dir(dir: String) -> Result<()> {
     use std::path::PathBuf;
-    let pref_manager = crate::prefs::PreferenceManager::get();
-    return pref_manager.borrow_mut().initialize(PathBuf::from(dir));
+    crate::shim_filesystem::clear_in_memory_rules();
+    crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.initialize(PathBuf::from(dir))?;
+    crate::speech::SpeechRules::reset_if_invalid();
+    return Ok( () );
+}
+
+/// Use `zipped_rules` (a zip of the Rules dir such as `ZIPPED_RULE_FILES`) for the Rules instead of the file system.
+/// After this call, MathCAT does not access the file system, environment variables (e.g., MathCATRulesDir),
+/// the user's config dir (so there are no user prefs), or the executable's location.
+/// This is needed for sandboxed AT where those calls fail or violate a policy.
+/// Calling `set_rules_dir` goes back to using the file system.
+/// IMPORTANT: this should be the very first call to MathCAT (in place of `set_rules_dir`)
+pub fn set_rules_zip(zipped_rules: Vec<u8>) -> Result<()> {
+    use std::path::PathBuf;
+    crate::shim_filesystem::set_in_memory_rules(&zipped_rules)?;
+    crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.initialize(PathBuf::from("Rules"))?;
+    crate::speech::SpeechRules::reset_if_invalid();
+    return Ok( () );
+}
+
+/// Use `file_system` to find and read the Rules (e.g., Rules that are fetched from a server) -- `rules_dir` is the Rules dir in it.
+/// The files are found the same way as with [`set_rules_dir`]. See [`crate::FileSystem`] for what needs to be implemented.
+/// IMPORTANT: this should be the very first call to MathCAT (in place of `set_rules_dir`)
+pub fn set_rules_file_system(file_system: std::rc::Rc<dyn crate::FileSystem>, rules_dir: String) -> Result<()> {
+    use std::path::PathBuf;
+    crate::shim_filesystem::set_file_system(file_system)?;
+    crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.initialize(PathBuf::from(rules_dir))?;
+    crate::speech::SpeechRules::reset_if_invalid();
+    return Ok( () );
 }
 
 /// Returns the version number (from Cargo.toml) of the build
@@ -53,105 +96,695 @@ pub fn get_version() -> String {
 }
 
 
-/// This will override any previous MathML that was set.
-/// This returns canonical MathML with 'id's set on any node that doesn't have an id.
-/// The ids can be used for sync highlighting if the `Bookmark` API preference is true.
-pub fn set_mathml(mathml_str: String) -> Result<String> {
+/// Parse `mathml_str` after converting HTML entities and removing namespace prefixes and MathJax attributes.
+fn parse_mathml(mathml_str: &str) -> Result<Package> {
     lazy_static! {
         // if these are present when resent to MathJaX, MathJaX crashes (https://github.com/mathjax/MathJax/issues/2822)
         static ref MATHJAX_V2: Regex = Regex::new(r#"class *= *['"]MJX-.*?['"]"#).unwrap();
-        static ref MATHJAX_V3: Regex = Regex::new(r#"class *= *['"]data-mjx-.*?['"]"#).unwrap();
+        static ref MATHJAX_V3: Regex = Regex::new(r#"(class *= *['"]mjx-.*?['"])|(data-mjx-[a-z-]+ *= *['"].*?['"])"#).unwrap();
+        static ref MATHJAX_V3_MO: Regex = Regex::new(r#"(<mo)(\s[^>]*>)"#).unwrap();
         static ref NAMESPACE_DECL: Regex = Regex::new(r#"xmlns:[[:alpha:]]+"#).unwrap();     // very limited namespace prefix match
         static ref PREFIX: Regex = Regex::new(r#"(</?)[[:alpha:]]+:"#).unwrap();     // very limited namespace prefix match
         static ref HTML_ENTITIES: Regex = Regex::new(r#"&([a-zA-Z]+?);"#).unwrap();
     }
+    // FIX: convert this to an included file once I get the full entity list
+    static HTML_ENTITIES_MAPPING: phf::Map<&str, &str> = include!("entities.in");
+
+    let mut error_message = "".to_string();     // can't return a result inside the replace_all, so we do this hack of setting the message and then returning the error
+    // need to deal with character data and convert to something the parser knows
+    let mathml_str = HTML_ENTITIES.replace_all(mathml_str, |cap: &Captures| {
+        match HTML_ENTITIES_MAPPING.get(&cap[1]) {
+            None => {
+                error_message = format!("No entity named '{}'", &cap[0]);
+                cap[0].to_string()
+            },
+            Some(&ch) => ch.to_string(),
+        }
+    });
 
+    if !error_message.is_empty() {
+        bail!(error_message);
+    }
+    let mathml_str = MATHJAX_V2.replace_all(&mathml_str, "");
+    // the MathJax attributes on an 'mo' are needed to recognize a split stretchy op (see merge_mathjax_split_stretchy_ops)
+    let mathml_str = MATHJAX_V3_MO.replace_all(&mathml_str, |cap: &Captures| {
+        if cap[2].contains("data-mjx-") {
+            format!("{} {}='true'{}", &cap[1], MATHJAX_MO_ATTR, &cap[2])
+        } else {
+            cap[0].to_string()
+        }
+    });
+    let mathml_str = MATHJAX_V3.replace_all(&mathml_str, "");
+
+    // the speech rules use the xpath "name" function and that includes the prefix
+    // getting rid of the prefix properly probably involves a recursive replacement in the tree
+    // if the prefix is used, it is almost certainly something like "m" or "mml", so this cheat will work.
+    let mathml_str = NAMESPACE_DECL.replace(&mathml_str, "xmlns");  // do this before the PREFIX replace!
+    let mathml_str = PREFIX.replace_all(&mathml_str, "$1");
+
+    return match parser::parse(&mathml_str) {
+        Ok(package) => {
+            merge_mathjax_split_stretchy_ops(get_element(&package));
+            Ok(package)
+        },
+        Err(e) => bail!("Invalid MathML input:\n{}\nError is: {}", &mathml_str, &e.to_string()),
+    };
+}
+
+/// Marks an 'mo' that had MathJax v3 ('data-mjx-*') attributes before they were removed
+const MATHJAX_MO_ATTR: &str = "data-mathjax-mo";
+
+/// MathJax v3 sometimes splits a stretched operator into the operator followed by a bare copy of it (an extender piece).
+/// The operator has MathJax's attributes ('data-mjx-*' or 'data-semantic-*') but the copy doesn't, so that is what is looked for:
+///   stretchy ops that are the same next to each other in correct MathML (e.g., nested fences or "||") are left alone.
+/// The marker added for the 'data-mjx-*' attributes is removed.
+fn merge_mathjax_split_stretchy_ops(element: Element) {
+    let mut previous: Option<Element> = None;
+    for child in element.children() {
+        let child = match child.element() {
+            None => continue,
+            Some(child) => child,
+        };
+        if name(&child) != "mo" {
+            merge_mathjax_split_stretchy_ops(child);
+            previous = None;
+            continue;
+        }
+        let has_mathjax_attrs = child.attribute(MATHJAX_MO_ATTR).is_some() ||
+                child.attributes().iter().any(|attr| attr.name().local_part().starts_with("data-semantic-"));
+        child.remove_attribute(MATHJAX_MO_ATTR);
+        let is_stretchy = child.attribute_value("stretchy") == Some("true");
+        if let Some(op) = previous {
+            if is_stretchy && !has_mathjax_attrs && crate::canonicalize::as_text(op) == crate::canonicalize::as_text(child) {
+                child.remove_from_parent();
+                continue;       // 'previous' stays the same
+            }
+        }
+        previous = if is_stretchy && has_mathjax_attrs {Some(child)} else {None};
+    }
+}
+
+/// Check `mathml_str` for problems such as unknown elements, wrong numbers of children, and deprecated elements and attributes.
+/// Each problem is returned with a suggested fix so that authoring tools can use MathCAT as a correctness checker.
+/// An empty list means no problems were found; an error is returned only if `mathml_str` can't be parsed.
+/// The MathML set by [`set_mathml`] is not changed.
+pub fn validate_mathml(mathml_str: String) -> Result<Vec<MathMLDiagnostic>> {
+    let package = parse_mathml(&mathml_str)?;
+    return Ok( crate::canonicalize::validate(get_element(&package)) );
+}
+
+/// This will override any previous MathML that was set.
+/// This returns canonical MathML with 'id's set on any node that doesn't have an id.
+/// The ids can be used for sync highlighting if the `Bookmark` API preference is true.
+///
+/// `mathml_str` can also be a fragment with several `math` elements (e.g., a whole exercise), either one after another
+/// or inside some other element such as a `div` or list. In that case the canonical MathML for each of them is returned
+/// (one after another) and the first one is selected for speech, braille, and navigation. See [`select_mathml`].
+pub fn set_mathml(mathml_str: String) -> Result<String> {
     NAVIGATION_STATE.with(|nav_stack| {
         nav_stack.borrow_mut().reset();
     });
-    return MATHML_INSTANCE.with(|old_package| {
-        // FIX: convert this to an included file once I get the full entity list
-        static HTML_ENTITIES_MAPPING: phf::Map<&str, &str> = include!("entities.in");
-
-        let mut error_message = "".to_string();     // can't return a result inside the replace_all, so we do this hack of setting the message and then returning the error
-        // need to deal with character data and convert to something the parser knows
-        let mathml_str = HTML_ENTITIES.replace_all(&mathml_str, |cap: &Captures| {
-            match HTML_ENTITIES_MAPPING.get(&cap[1]) {
-                None => {
-                    error_message = format!("No entity named '{}'", &cap[0]);
-                    cap[0].to_string()
-                },
-                Some(&ch) => ch.to_string(),
+    let mut packages = parse_mathml_list(&mathml_str)?;
+    crate::speech::SpeechRules::initialize_all_rules()?;
+
+    let mut mathml_string = String::new();
+    for package in &packages {
+        let mathml = get_element(package);
+        let mathml = cleanup_mathml(mathml)?;
+        mathml_string += &mml_to_string(&mathml);
+    }
+
+    let first_package = std::mem::replace(&mut packages[0], init_mathml_instance().into_inner());
+    MATHML_INSTANCE.with(|old_package| old_package.replace(first_package));
+    MATHML_LIST.with(|list| *list.borrow_mut() = if packages.len() == 1 {vec![]} else {packages});
+    MATHML_LIST_INDEX.with(|index| index.set(0));
+    return Ok( mathml_string );
+}
+
+/// Set the MathML to `mathml_str`, which is an edited version of the expression that was set (e.g., the user is typing).
+/// Unlike [`set_mathml`], the navigation focus is kept: it moves to the corresponding node in the new expression.
+/// That is the node with the same `id` if the `id`s come from the host, otherwise the node at the same place in the tree
+/// (see [`get_navigation_node_path`]). If the node no longer exists, its closest surviving ancestor is used.
+/// The return value is the same as for [`set_mathml`].
+pub fn update_mathml(mathml_str: String) -> Result<String> {
+    // find where we are in the old expression (None if we aren't navigating)
+    let old_position = MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        if mathml.children().is_empty() {
+            return None;
+        }
+        let node = NAVIGATION_STATE.with(|nav_stack| nav_stack.borrow().get_navigation_mathml(mathml).ok())?.0;
+        if node == mathml {
+            return None;
+        }
+        let id = if node.attribute("data-id-added").is_none() {node.attribute_value("id").map(|id| id.to_string())} else {None};
+        return Some( (id, crate::navigate::path_to_node(mathml, node).ok()?) );
+    });
+
+    let result = set_mathml(mathml_str)?;
+    if let Some( (id, path) ) = old_position {
+        MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            let node = match id.and_then(|id| crate::navigate::get_node_by_id(mathml, &id)) {
+                Some(node) => node,
+                None => crate::navigate::get_closest_node_by_path(mathml, &path),
+            };
+            if node != mathml {
+                NAVIGATION_STATE.with(|nav_stack| nav_stack.borrow_mut().set_navigation_node(node.attribute_value("id").unwrap()));
             }
         });
+    }
+    return Ok(result);
+}
 
-        if !error_message.is_empty() {
-            bail!(error_message);
-        }
-        let mathml_str = MATHJAX_V2.replace_all(&mathml_str, "");
-        let mathml_str = MATHJAX_V3.replace_all(&mathml_str, "");
+/// The number of expressions set by the last call to [`set_mathml`] (more than one if it was given several `math` elements).
+pub fn get_mathml_count() -> usize {
+    return MATHML_LIST.with(|list| list.borrow().len().max(1));
+}
 
-        // the speech rules use the xpath "name" function and that includes the prefix
-        // getting rid of the prefix properly probably involves a recursive replacement in the tree
-        // if the prefix is used, it is almost certainly something like "m" or "mml", so this cheat will work.
-        let mathml_str = NAMESPACE_DECL.replace(&mathml_str, "xmlns");  // do this before the PREFIX replace!
-        let mathml_str = PREFIX.replace_all(&mathml_str, "$1");
+/// Select expression `index` (0-based) of the ones set by the last call to [`set_mathml`] so that
+/// speech, braille, and navigation use it. The navigation state is reset.
+pub fn select_mathml(index: usize) -> Result<()> {
+    let count = get_mathml_count();
+    if index >= count {
+        bail!("MathML index {} is not in the range 0-{}", index, count-1);
+    }
+    let current = MATHML_LIST_INDEX.with(|current| current.get());
+    if index == current {
+        return Ok( () );
+    }
+    NAVIGATION_STATE.with(|nav_stack| {
+        nav_stack.borrow_mut().reset();
+    });
+    MATHML_INSTANCE.with(|package_instance| {
+        MATHML_LIST.with(|list| {
+            let mut package_instance = package_instance.borrow_mut();
+            let mut list = list.borrow_mut();
+            // put the current expression back in its slot and move the selected one out of its slot
+            std::mem::swap(&mut *package_instance, &mut list[current]);
+            std::mem::swap(&mut *package_instance, &mut list[index]);
+        })
+    });
+    MATHML_LIST_INDEX.with(|current| current.set(index));
+    return Ok( () );
+}
 
-        let new_package = parser::parse(&mathml_str);    
-        if let Err(e) = new_package {
-            bail!("Invalid MathML input:\n{}\nError is: {}", &mathml_str, &e.to_string());
+/// Parse `mathml_str` into one package for each `math` element in it.
+/// If `mathml_str` doesn't contain several `math` elements, the result is the single parsed package (which might not be `math`).
+fn parse_mathml_list(mathml_str: &str) -> Result<Vec<Package>> {
+    let package = match parse_mathml(mathml_str) {
+        Ok(package) if name(&get_element(&package)) == "math" => return Ok( vec![package] ),
+        Ok(package) => package,
+        Err(e) => {
+            // several 'math' elements in a row are not well-formed XML -- try them inside of an element
+            match parse_mathml(&format!("<mathcat-list>{}</mathcat-list>", mathml_str)) {
+                Ok(package) => package,
+                Err(_) => return Err(e),
+            }
+        },
+    };
+
+    let mut math_elements = vec![];
+    find_math_elements(get_element(&package), &mut math_elements);
+    if math_elements.is_empty() {
+        if name(&get_element(&package)) == "mathcat-list" {
+            bail!("Invalid MathML input: no 'math' element found in\n{}", mathml_str);
         }
-        crate::speech::SpeechRules::initialize_all_rules()?;
+        return Ok( vec![package] );
+    }
+    return Ok( math_elements.iter()
+        .map(|&math| {
+            let new_package = Package::new();
+            let doc = new_package.as_document();
+            doc.root().append_child(copy_element(doc, math));
+            new_package
+        })
+        .collect() );
+
+    fn find_math_elements<'a>(element: Element<'a>, math_elements: &mut Vec<Element<'a>>) {
+        for child in element.children() {
+            if let ChildOfElement::Element(child) = child {
+                if name(&child) == "math" {
+                    math_elements.push(child);
+                } else {
+                    find_math_elements(child, math_elements);
+                }
+            }
+        }
+    }
+}
 
-        let new_package = new_package.unwrap();
-        let mathml = get_element(&new_package);
-        let mathml = cleanup_mathml(mathml)?;
-        let mathml_string = mml_to_string(&mathml);
-        old_package.replace(new_package);
+/// Copy `element` (and all its descendants) into `doc`
+fn copy_element<'d>(doc: Document<'d>, element: Element) -> Element<'d> {
+    let new_element = doc.create_element(element.name());
+    for attr in element.attributes() {
+        new_element.set_attribute_value(attr.name(), attr.value());
+    }
+    for child in element.children() {
+        match child {
+            ChildOfElement::Element(child) => new_element.append_child(copy_element(doc, child)),
+            ChildOfElement::Text(text) => new_element.append_child(doc.create_text(text.text())),
+            _ => (),
+        }
+    }
+    return new_element;
+}
 
-        return Ok( mathml_string );
-    })
+/// Return a readable dump of the (canonicalized) MathML that was set or, if `intent` is true, of the intent tree inferred from it.
+/// This is meant for debugging rules: with `options.color` set, ANSI escape codes color the element names, attributes,
+/// and intents so that the dump is easy to read in a terminal.
+pub fn get_debug_tree(intent: bool, options: FormatOptions) -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        if !intent {
+            return Ok( crate::pretty_print::format_element_with_options(&mathml, 0, &options) );
+        }
+        let new_package = Package::new();
+        let intent = crate::speech::intent_from_mathml(mathml, new_package.as_document())?;
+        return Ok( crate::pretty_print::format_element_with_options(&intent, 0, &options) );
+    });
 }
 
 /// Get the spoken text of the MathML that was set.
 /// The speech takes into account any AT or user preferences.
+/// If the `Overview` preference is true, large expressions (see `OverviewSize`) are described by [`get_overview_text`] instead.
 pub fn get_spoken_text() -> Result<String> {
+    return spoken_text(&crate::prefs::PreferenceManager::get());
+}
+
+/// [`get_spoken_text`] using the preferences in `pref_manager` (e.g., from [`prefs_with_overrides`])
+pub(crate) fn spoken_text(pref_manager: &SharedPreferences) -> Result<String> {
     // use std::time::{Instant};
     // let instant = Instant::now();
+    let (is_braille_echo, overview_size) = {
+        let pref_manager = crate::prefs::read_prefs(pref_manager)?;
+        let prefs = pref_manager.get_user_prefs();
+        (prefs.to_bool("BrailleEcho") == Some(true),
+         if prefs.to_bool("Overview") == Some(true) {prefs.to_int("OverviewSize")} else {None})
+    };
+    if is_braille_echo {
+        return braille_echo(pref_manager);
+    }
     return MATHML_INSTANCE.with(|package_instance| {
         let package_instance = package_instance.borrow();
         let mathml = get_element(&package_instance);
+        if let Some(overview_size) = overview_size {
+            if crate::xpath_functions::NodeSize::size(mathml) as i64 >= overview_size {
+                return overview_text(pref_manager);
+            }
+        }
         let new_package = Package::new();
-        let intent = crate::speech::intent_from_mathml(mathml, new_package.as_document())?;
+        let intent = crate::speech::intent_from_mathml_with_prefs(mathml, new_package.as_document(), pref_manager)?;
         debug!("Intent tree:\n{}", mml_to_string(&intent));
-        let speech = crate::speech::speak_intent(intent)?;
+        let speech = crate::speech::speak_intent_with_prefs(intent, pref_manager)?;
         // info!("Time taken: {}ms", instant.elapsed().as_millis());
         return Ok( speech );
     });
 }
 
+/// A copy of this thread's preferences with the (name, value) pairs in `overrides` used instead of the current values.
+/// The calls that need different preferences for a single call (e.g., `TTS` set to "None") generate speech with the copy,
+/// so the preferences (which might be shared with other threads) are never changed and the preference listeners aren't called.
+/// The overrides can't change which rule files are used (see [`DOCUMENT_PREFS`]).
+pub(crate) fn prefs_with_overrides(overrides: &[(&str, &str)]) -> Result<SharedPreferences> {
+    let overrides = overrides.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<(String, String)>>();
+    let pref_manager = crate::prefs::read_prefs(&crate::prefs::PreferenceManager::get())?.with_overrides(&overrides)?;
+    return Ok( Arc::new(RwLock::new(pref_manager)) );
+}
+
+/// Prefs that are used when the MathML is set or that change which rule files are used.
+/// An override of one of these needs a [`DocumentContext`] (which has its own rules) rather than [`prefs_with_overrides`].
+static DOCUMENT_PREFS: &[&str] = &["Language", "LanguageFallback", "BrailleCode", "Chemistry"];
+
+/// A range of the speech returned by [`get_spoken_text_with_mapping`] and the element it is the speech for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpokenRange {
+    /// The character (not byte) offset of the start of the range in the speech
+    pub start: usize,
+    /// The character offset just past the end of the range
+    pub end: usize,
+    /// The `id` of the element (these are the `id`s in the MathML returned by [`set_mathml`])
+    pub id: String,
+}
+
+/// Get the spoken text (without any TTS markup) of the MathML that was set along with the element each word is the speech for.
+/// This lets an e-reader highlight the sub-expression that is being spoken ("karaoke" reading) using its TTS's word callbacks.
+/// Consecutive words that are from the same element are in a single range; pauses (',' and ';') are not part of a range.
+pub fn get_spoken_text_with_mapping() -> Result<(String, Vec<SpokenRange>)> {
+    // the speech has to be the speech of the intent tree, so an overview or braille echo isn't used
+    let pref_manager = prefs_with_overrides(&[("TTS", "None"), ("Overview", "false"), ("BrailleEcho", "false")])?;
+    let result: Result<(String, Vec<(String, String)>)> = MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        let new_package = Package::new();
+        let intent = crate::speech::intent_from_mathml_with_prefs(mathml, new_package.as_document(), &pref_manager)?;
+        let sources = crate::speech::speak_intent_with_sources(intent, &pref_manager)?;
+        return Ok( (crate::speech::speak_intent_with_prefs(intent, &pref_manager)?, sources) );
+    });
+    let (speech, sources) = result?;
+
+    // match up the words in the speech with the words that have sources (pauses might be merged differently)
+    let is_pause = |ch: &char| *ch == ',' || *ch == ';';
+    let chars = speech.chars().collect::<Vec<char>>();
+    let mut mapping: Vec<SpokenRange> = vec![];
+    let mut i_source = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() || is_pause(&chars[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && !chars[i].is_whitespace() {
+            i += 1;
+        }
+        let mut end = i;
+        while is_pause(&chars[end-1]) {
+            end -= 1;
+        }
+        let word = chars[start..end].iter().collect::<String>();
+        let found = sources.iter().skip(i_source).take(5)
+                        .position(|(source_word, _)| source_word.trim_matches(|ch| is_pause(&ch)) == word);
+        let id = match found {
+            None => continue,
+            Some(offset) => {
+                i_source += offset + 1;
+                &sources[i_source - 1].1
+            },
+        };
+        if id.is_empty() {
+            continue;
+        }
+        match mapping.last_mut() {
+            Some(last) if &last.id == id && chars[last.end..start].iter().all(|ch| ch.is_whitespace()) => last.end = end,
+            _ => mapping.push( SpokenRange{ start, end, id: id.clone() } ),
+        }
+    }
+    return Ok( (speech, mapping) );
+}
+
+/// Get the spoken text of the MathML that was set using the speech style `style` (e.g., "SimpleSpeak")
+/// instead of the `SpeechStyle` preference. The preferences are not changed.
+/// The rules for each style are cached, so an AT can cheaply offer a "try the other style" command.
+pub fn get_spoken_text_with_style(style: String) -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        let new_package = Package::new();
+        let intent = crate::speech::intent_from_mathml(mathml, new_package.as_document())?;
+        return crate::speech::speak_intent_with_style(intent, &style)
+                .chain_err(|| format!("while speaking with the speech style '{}'", style));
+    });
+}
+
+/// Start a new document session for the (experimental) "Explanation" speech style.
+/// In that style, the meaning of a notation (e.g., a fraction) is explained the first time it is spoken in a session.
+/// An AT should call this when the user moves to a new document.
+pub fn reset_explanation_session() {
+    crate::xpath_functions::reset_explanation_session();
+}
+
+/// For developers: find which rules produced `phrase` in the last speech that was generated.
+/// Each piece of text in the speech that contains `phrase` (ignoring case) is returned along with the rule, tag, and file it came from.
+/// Text that comes from several rules (e.g., "a over b") is not found -- look for the individual words instead.
+pub fn explain_phrase(phrase: String) -> Result<Vec<PhraseSource>> {
+    let phrase = phrase.trim().to_lowercase();
+    if phrase.is_empty() {
+        bail!("explain_phrase: the phrase to look for is empty");
+    }
+    return Ok(
+        crate::speech::get_last_phrase_sources().into_iter()
+            .filter(|source| source.text.to_lowercase().contains(&phrase))
+            .collect()
+    );
+}
+
+/// The speech for the MathML that was set at each of the `Verbosity` levels. See [`get_spoken_text_all_verbosities`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerbosityReadings {
+    /// The speech with `Verbosity` set to "Terse"
+    pub terse: String,
+    /// The speech with `Verbosity` set to "Medium"
+    pub medium: String,
+    /// The speech with `Verbosity` set to "Verbose"
+    pub verbose: String,
+}
+
+/// Get the spoken text of the MathML that was set at all three `Verbosity` levels (Terse, Medium, Verbose).
+/// The MathML is only canonicalized once (by [`set_mathml`]) and its intent is only inferred once,
+/// so an AT can offer a "more detail" command without regenerating from scratch.
+/// The `Verbosity` preference is not changed (each reading uses its own value), so other threads sharing the preferences aren't affected.
+pub fn get_spoken_text_all_verbosities() -> Result<VerbosityReadings> {
+    let terse = prefs_with_overrides(&[("Verbosity", "Terse")])?;
+    let medium = prefs_with_overrides(&[("Verbosity", "Medium")])?;
+    let verbose = prefs_with_overrides(&[("Verbosity", "Verbose")])?;
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        let new_package = Package::new();
+        let intent = crate::speech::intent_from_mathml(mathml, new_package.as_document())?;
+        return Ok( VerbosityReadings {
+            terse: crate::speech::speak_intent_with_prefs(intent, &terse)?,
+            medium: crate::speech::speak_intent_with_prefs(intent, &medium)?,
+            verbose: crate::speech::speak_intent_with_prefs(intent, &verbose)?,
+        } );
+    });
+}
+
+/// Get the spoken text of `mathml` with the preferences in `prefs` (name/value pairs such as ("Verbosity", "Verbose"))
+/// overriding the current values. This is meant for commands such as "read this verbosely".
+/// The preferences are not changed (so other threads sharing them aren't affected) and the MathML that was set
+/// (along with the navigation state) is restored afterwards.
+pub fn get_spoken_text_with_prefs(mathml: String, prefs: &[(String, String)]) -> Result<String> {
+    if prefs.iter().any(|(name, _)| DOCUMENT_PREFS.contains(&name.as_str())) {
+        // the MathML needs to be set and spoken with other rules (e.g., for another language)
+        let mut document = DocumentContext::new();
+        for (name, value) in prefs {
+            document.set_preference(name.clone(), value.clone())?;
+        }
+        document.set_mathml(mathml)?;
+        return document.get_spoken_text();
+    }
+    let prefs = prefs.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+    let pref_manager = prefs_with_overrides(&prefs)?;
+    return with_saved_mathml(|| {
+        set_mathml(mathml)?;
+        return spoken_text(&pref_manager);
+    });
+}
+
 /// Get the spoken text for an overview of the MathML that was set.
+/// Expressions with at least `OverviewSize` symbols are described by their structure without their content
+/// (e.g., "a fraction with a sum of 5 terms over a product of 3 factors") so the user can decide whether to navigate into them.
+/// Shorter expressions are read in a shortened form.
 /// The speech takes into account any AT or user preferences.
-/// Note: this implementation for is currently minimal and should not be used.
 pub fn get_overview_text() -> Result<String> {
+    return overview_text(&crate::prefs::PreferenceManager::get());
+}
+
+/// [`get_overview_text`] using the preferences in `pref_manager`
+fn overview_text(pref_manager: &SharedPreferences) -> Result<String> {
     // use std::time::{Instant};
     // let instant = Instant::now();
     return MATHML_INSTANCE.with(|package_instance| {
         let package_instance = package_instance.borrow();
         let mathml = get_element(&package_instance);
-        let speech = crate::speech::overview_mathml(mathml)?;
+        // the rules for 'math' say what kind of expression it is (for a table of contents) -- here the structure is wanted
+        let mathml = if mathml.children().len() == 1 {as_element(mathml.children()[0])} else {mathml};
+        let speech = crate::speech::overview_mathml_with_prefs(mathml, pref_manager)?;
         // info!("Time taken: {}ms", instant.elapsed().as_millis());
         return Ok( speech );
     });
 }
 
+thread_local!{
+    /// The MathML for each entry of the last table of contents (see [`get_table_of_contents`]).
+    static TABLE_OF_CONTENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Get a numbered menu of one-line overviews of several expressions (e.g., all the equations in a section)
+/// so that they can be skimmed: "1: definition of f; 2: a limit; 3: an equation".
+/// Use [`select_table_of_contents_entry`] to get the full reading of an entry.
+/// The MathML set by [`set_mathml`] (if any) is restored afterwards, but the navigation state is reset.
+pub fn get_table_of_contents(mathml_list: Vec<String>) -> Result<String> {
+    let overviews = with_saved_mathml(|| {
+        return mathml_list.iter()
+            .map(|mathml| {
+                set_mathml(mathml.clone())?;
+                // the rules for 'math' say what kind of expression it is (e.g., "an equation")
+                return MATHML_INSTANCE.with(|package_instance| crate::speech::overview_mathml(get_element(&package_instance.borrow())));
+            })
+            .collect::<Result<Vec<String>>>();
+    })?;
+    TABLE_OF_CONTENTS.with(|toc| *toc.borrow_mut() = mathml_list);
+    return Ok( overviews.iter().enumerate()
+                .map(|(i, overview)| format!("{}: {}", i+1, overview))
+                .collect::<Vec<String>>()
+                .join("; ") );
+}
+
+/// Set the MathML to entry `index` (1-based) of the last table of contents (see [`get_table_of_contents`])
+/// and return its spoken text. Navigation then works on that expression.
+pub fn select_table_of_contents_entry(index: usize) -> Result<String> {
+    let mathml = TABLE_OF_CONTENTS.with(|toc| -> Result<String> {
+        let toc = toc.borrow();
+        if index == 0 || index > toc.len() {
+            bail!("Table of contents entry {} is not in the range 1-{}", index, toc.len());
+        }
+        return Ok( toc[index-1].clone() );
+    })?;
+    set_mathml(mathml)?;
+    return get_spoken_text();
+}
+
+/// A region of the expression that should be visually highlighted while the associated speech is spoken.
+/// See [`get_highlight_plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighlightRegion {
+    /// The `id` of the element to highlight (these are the `id`s in the MathML returned by [`set_mathml`])
+    pub id: String,
+    /// The nesting level of the element (the `math` element is at level 0)
+    pub level: usize,
+    /// The speech (without any TTS markup) spoken while the region is highlighted
+    pub text: String,
+    /// The estimated time (in milliseconds) to speak `text`, including any pauses, based on the `Rate` and `MathRate` preferences
+    pub duration_ms: f64,
+}
+
+/// Get the speech of the MathML that was set broken into chunks, one for each region to highlight.
+/// This is meant for low-vision reading tools that want to sync visual emphasis with the audio.
+/// The regions are in speech order; the durations are estimates, so a tool should use TTS callbacks if they are available.
+pub fn get_highlight_plan() -> Result<Vec<HighlightRegion>> {
+    lazy_static! {
+        static ref MARK_OR_BREAK: Regex = Regex::new(r#"<mark name='([^']*)'/>|<break time='([0-9.]+)ms'/>"#).unwrap();
+        static ref OTHER_TAGS: Regex = Regex::new(r#"<[^>]*>"#).unwrap();
+    }
+
+    // the bookmarks in the SSML speech tell us what node is being spoken
+    let pref_manager = prefs_with_overrides(&[("TTS", "SSML"), ("Bookmark", "true")])?;
+    let speech = spoken_text(&pref_manager)?;
+    let words_per_minute = crate::prefs::read_prefs(&pref_manager)?.get_effective_rate(0.0);
+
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        let mut regions: Vec<HighlightRegion> = Vec::new();
+        let mut current = new_region(mathml, mathml.attribute_value("id").unwrap_or_default());
+        let mut start = 0;
+        for cap in MARK_OR_BREAK.captures_iter(&speech) {
+            let whole_match = cap.get(0).unwrap();
+            current.text.push_str(&speech[start..whole_match.start()]);
+            start = whole_match.end();
+            if let Some(id) = cap.get(1) {
+                push_region(&mut regions, current, words_per_minute)?;
+                current = new_region(mathml, id.as_str());
+            } else {
+                current.duration_ms += cap[2].parse::<f64>().unwrap_or(0.0);
+            }
+        }
+        current.text.push_str(&speech[start..]);
+        push_region(&mut regions, current, words_per_minute)?;
+        return Ok( regions );
+    });
+
+    fn new_region(mathml: Element, id: &str) -> HighlightRegion {
+        let mut level = 0;
+        if let Some(element) = get_node_by_id(mathml, id) {
+            let mut parent = element.parent();
+            while let Some(ParentOfChild::Element(parent_element)) = parent {
+                level += 1;
+                parent = parent_element.parent();
+            }
+        }
+        return HighlightRegion{ id: id.to_string(), level, text: String::new(), duration_ms: 0.0 };
+    }
+
+    fn push_region(regions: &mut Vec<HighlightRegion>, mut region: HighlightRegion, words_per_minute: f64) -> Result<()> {
+        let text = OTHER_TAGS.replace_all(&region.text, "");
+        region.text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if region.text.is_empty() && region.duration_ms == 0.0 {
+            return Ok( () );
+        }
+        if !(words_per_minute > 0.0 && words_per_minute.is_finite()) {
+            bail!("The speech rate ({} words/minute) must be a positive number to estimate how long the speech takes", words_per_minute);
+        }
+        let n_words = region.text.split_whitespace().count() as f64;
+        region.duration_ms += 60_000.0 * n_words / words_per_minute;
+        regions.push(region);
+        return Ok( () );
+    }
+}
+
+/// Get an estimate of how long (in milliseconds) it will take to speak the MathML that was set.
+/// The estimate is computed from the words and pauses in the speech along with the `Rate` and `MathRate` preferences,
+/// so it can be used to budget time for an expression without synthesizing the audio.
+pub fn estimate_duration_ms() -> Result<f64> {
+    return Ok( get_highlight_plan()?.iter().map(|region| region.duration_ms).sum() );
+}
+
+/// A part of the speech along with the silence that follows it. See [`get_spoken_segments`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechSegment {
+    /// The speech (without any TTS markup)
+    pub text: String,
+    /// The length (in milliseconds) of the silence to insert after `text` (0 if there is none)
+    pub pause_ms: f64,
+}
+
+/// Get the speech of the MathML that was set broken at the pauses.
+/// This is meant for speech engines that don't understand SSML or SAPI5 -- they can insert silences of the given length between the segments.
+/// The pause lengths are those used for SSML's `<break time='...'/>`: they are scaled by the `PauseFactor` preference
+/// and (unless `ScalePausesWithRate` is `false`) by the speech rate.
+/// The `TTS` preference is not used (nor changed).
+pub fn get_spoken_segments() -> Result<Vec<SpeechSegment>> {
+    lazy_static! {
+        static ref BREAK: Regex = Regex::new(r#"<break time='([0-9.]+)ms'/>"#).unwrap();
+        static ref OTHER_TAGS: Regex = Regex::new(r#"<[^>]*>"#).unwrap();
+    }
+
+    let speech = spoken_text(&prefs_with_overrides(&[("TTS", "SSML")])?)?;
+
+    let mut segments: Vec<SpeechSegment> = Vec::new();
+    let mut start = 0;
+    for cap in BREAK.captures_iter(&speech) {
+        let whole_match = cap.get(0).unwrap();
+        push_segment(&mut segments, &speech[start..whole_match.start()], cap[1].parse::<f64>().unwrap_or(0.0));
+        start = whole_match.end();
+    }
+    push_segment(&mut segments, &speech[start..], 0.0);
+    return Ok( segments );
+
+    fn push_segment(segments: &mut Vec<SpeechSegment>, text: &str, pause_ms: f64) {
+        let text = OTHER_TAGS.replace_all(text, "");
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if text.is_empty() {
+            // pauses with nothing between them run together
+            match segments.last_mut() {
+                Some(last) => last.pause_ms += pause_ms,
+                None if pause_ms > 0.0 => segments.push( SpeechSegment{ text, pause_ms } ),
+                None => (),
+            }
+        } else {
+            segments.push( SpeechSegment{ text, pause_ms } );
+        }
+    }
+}
+
 /// Get the value of the named preference.
 /// None is returned if `name` is not a known preference.
 pub fn get_preference(name: String) -> Result<String> {
     use yaml_rust::Yaml;
     return crate::speech::SPEECH_RULES.with(|rules| {
         let rules = rules.borrow();
-        let pref_manager = rules.pref_manager.borrow();
+        let pref_manager = crate::prefs::read_prefs(&rules.pref_manager)?;
+        pref_manager.check_initialized()?;
         let prefs = pref_manager.merge_prefs();
         return match prefs.get(&name) {
             None => bail!("No preference named '{}'", &name),
@@ -173,11 +806,15 @@ pub fn get_preference(name: String) -> Result<String> {
 /// * TTS -- SSML, SAPI5, None
 /// * Pitch -- normalized at '1.0'
 /// * Rate -- words per minute (should match current speech rate).
-///       There is a separate "MathRate" that is user settable that causes a relative percentage change from this rate.
+///       There is a separate "MathRate" that is user settable that causes a relative percentage change from this rate (e.g., SSML's `<prosody rate='80%'>`).
 /// * Volume -- default 100
 /// * Voice -- set a voice to use (not implemented)
 /// * Gender -- set pick any voice of the given gender (not implemented)
 /// * Bookmark -- set to `true` if a `mark`/`bookmark` should be part of the returned speech (used for sync highlighting)
+/// * ATProfile -- the screen reader the speech is given to (e.g., JAWS, NVDA, VoiceOver) so that characters it mangles are avoided.
+///   The replacements for each profile are in Rules/ATProfiles/<ATProfile>.yaml. Default: None
+/// * Text_Pitch, Text_Voice -- a pitch change (in percent) and a voice for text in the math (`mtext` such as "such that")
+///   so that it sounds different from the math. The defaults ('0' and the empty string) don't change the pitch or voice.
 ///
 /// Important: both the preference name and value are case-sensitive
 /// 
@@ -185,90 +822,838 @@ pub fn get_preference(name: String) -> Result<String> {
 /// The values are persistent and extend beyond calls to [`set_mathml`].
 /// A value can be overwritten by calling this function again with a different value.
 /// 
+/// An unknown name is an `ErrorKind::UnknownPreference` error and a value that isn't allowed (e.g., "Loud" for "Verbosity")
+/// is an `ErrorKind::InvalidPreferenceValue` error -- the preference isn't changed in either case.
+/// A preference the site administrator locked in system-prefs.yaml is an `ErrorKind::LockedPreference` error.
+/// 
 /// FIX: Some preferences are both API and user preferences and something such as '!name' should be used for overrides. Not implemented yet.
 pub fn set_preference(name: String, value: String) -> Result<()> {
-    return crate::speech::SPEECH_RULES.with(|rules| {
-        let mut rules = rules.borrow_mut();
+    let pref_manager = crate::speech::SPEECH_RULES.with(|rules| -> Result<crate::prefs::SharedPreferences> {
+        let rules = rules.borrow();
         if let Some(error_string) = rules.get_error() {
             bail!("{}", error_string);
         }
-        // note: Rust complains if I set
-        //    pref_manager = rules.pref_manager.borrow_mut()
-        // here/upfront, so it is borrowed separately below. That way its borrowed lifetime is small
-        let files_changed;
-        {
-            use crate::prefs::NO_PREFERENCE;
-            let mut pref_manager = rules.pref_manager.borrow_mut();
-            if pref_manager.get_api_prefs().to_string(&name) != NO_PREFERENCE {
-                match name.as_str() {
-                    "Pitch" | "Rate" | "Volume" | "CapitalLetters_Pitch"=> {
-                        pref_manager.set_api_float_pref(&name, to_float(&name, &value)?);    
-                    },
-                    "Bookmark" | "CapitalLetters_UseWord" | "CapitalLetters_Beep" => {
-                        pref_manager.set_api_boolean_pref(&name, value.to_lowercase()=="true");    
-                    },
-                    _ => {
-                        pref_manager.set_api_string_pref(&name, &value);
-                    }
-                }
-                files_changed = None;
-            } else if pref_manager.get_user_prefs().to_string(name.as_str()) == NO_PREFERENCE {
-                bail!("set_preference: {} is not a known preference", &name); 
-            } else {
-                files_changed = pref_manager.set_user_prefs(&name, &value);     // assume string valued
+        return Ok( rules.pref_manager.clone() );
+    })?;
+    crate::prefs::read_prefs(&pref_manager)?.check_initialized()?;
+    // the rules aren't borrowed while the prefs are locked so that the listeners (called when the lock is released) can use MathCAT
+    let files_changed;
+    {
+        use crate::prefs::NO_PREFERENCE;
+        let mut pref_manager = crate::prefs::write_prefs(&pref_manager)?;
+        pref_manager.validate_preference(&name, &value)?;
+        if pref_manager.get_api_prefs().to_string(&name) != NO_PREFERENCE {
+            pref_manager.set_api_pref_from_string(&name, &value)?;
+            files_changed = None;
+        } else if pref_manager.get_user_prefs().to_string(name.as_str()) == NO_PREFERENCE {
+            bail!(ErrorKind::UnknownPreference(name));
+        } else {
+            files_changed = pref_manager.set_user_prefs(&name, &value);     // assume string valued
+        }
+        pref_manager.merge_prefs();
+    }
+
+    return crate::speech::SPEECH_RULES.with(|rules| {
+        let mut rules = rules.borrow_mut();
+        match name.as_str() {
+            "Language" | "SpeechStyle" | "LanguageFallback" => {
+                if let Some(files_changed) = files_changed {
+                    rules.invalidate(files_changed);
+                }
+            },
+            "BrailleCode" => {
+                crate::speech::BRAILLE_RULES.with(|braille_rules| {
+                    if let Some(files_changed) = files_changed {
+                        braille_rules.borrow_mut().invalidate(files_changed);
+                    }
+                })
+            },
+            _ => {
+            }
+        }
+        return Ok( () );
+    });
+}
+
+/// Get all of the preferences (user and AT) as a JSON object that maps their names to their values (e.g., `{"Rate": 180.0, "Verbosity": "Medium", ...}`).
+/// Strings, numbers, and booleans are JSON strings, numbers, and booleans.
+/// This lets an AT store MathCAT's preferences in its own settings (e.g., NVDA's .ini file) and restore them with [`set_preferences_json`].
+pub fn get_preferences_json() -> Result<String> {
+    use yaml_rust::Yaml;
+    use serde_json::{Map, Number, Value};
+    use crate::prefs::PreferenceType;
+    return crate::speech::SPEECH_RULES.with(|rules| {
+        let rules = rules.borrow();
+        let pref_manager = crate::prefs::read_prefs(&rules.pref_manager)?;
+        pref_manager.check_initialized()?;
+        let mut json = Map::new();
+        for (name, yaml) in pref_manager.merge_prefs() {
+            // 'set_preference' stores user prefs as strings, so the type comes from the description when there is one
+            let value_type = crate::prefs::PreferenceManager::get_preference_descriptions().iter()
+                    .find(|description| description.name == name)
+                    .map(|description| description.value_type);
+            let value = match (value_type, yaml) {
+                (_, Yaml::Boolean(b)) => Value::Bool(b),
+                (Some(PreferenceType::Float), Yaml::Integer(i)) => to_number(&i.to_string()),
+                (_, Yaml::Integer(i)) => Value::Number(Number::from(i)),
+                (_, Yaml::Real(s)) => to_number(&s),
+                (Some(PreferenceType::Float), Yaml::String(s)) => to_number(&s),
+                (None | Some(PreferenceType::Bool), Yaml::String(s)) if s == "true" || s == "false" => Value::Bool(s == "true"),
+                (_, Yaml::String(s)) => Value::String(s),
+                _ => bail!("Internal error in get_preferences_json -- unknown YAML type for '{}'", name),
+            };
+            json.insert(name, value);
+        }
+        return Ok( Value::Object(json).to_string() );
+    });
+
+    /// All number-valued preferences are floats (see `set_preference`) -- a value that isn't a number is kept as a string
+    fn to_number(value: &str) -> Value {
+        return match value.trim().parse::<f64>().ok().and_then(Number::from_f64) {
+            Some(n) => Value::Number(n),
+            None => Value::String(value.to_string()),
+        };
+    }
+}
+
+/// Set the preferences in `json`, a JSON object that maps preference names to their values (e.g., from [`get_preferences_json`]).
+/// The values can be strings, numbers, or booleans. Preferences that aren't in `json` are not changed.
+/// All of the preferences are checked before any are set, so none of them are changed if there is an error
+/// (see [`set_preference`] for the errors).
+pub fn set_preferences_json(json: String) -> Result<()> {
+    use serde_json::Value;
+    let prefs = match serde_json::from_str::<Value>(&json) {
+        Ok(Value::Object(prefs)) => prefs,
+        Ok(_) => bail!("set_preferences_json: the JSON must be an object that maps preference names to values"),
+        Err(e) => bail!("set_preferences_json: JSON parse error: {}", e),
+    };
+    let mut name_values = Vec::with_capacity(prefs.len());
+    for (name, value) in prefs {
+        let value = match value {
+            Value::String(s) => s,
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => bail!("set_preferences_json: the value of '{}' must be a string, number, or boolean", name),
+        };
+        name_values.push( (name, value) );
+    }
+    {
+        let pref_manager = crate::prefs::PreferenceManager::get();
+        let pref_manager = crate::prefs::read_prefs(&pref_manager)?;
+        pref_manager.check_initialized()?;
+        for (name, value) in &name_values {
+            pref_manager.validate_preference(name, value)?;
+        }
+    }
+    for (name, value) in name_values {
+        set_preference(name, value)?;
+    }
+    return Ok( () );
+}
+
+/// Switch to the preference profile `name` -- "" means no profile.
+/// A profile is a named set of preferences in the "Profiles" section of prefs.yaml (e.g., "Exam mode" or "Calculus class").
+/// All of its preferences are changed at once and the ones changed by the previous profile are restored.
+pub fn set_profile(name: String) -> Result<()> {
+    return crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.set_profile(&name);
+}
+
+/// Get the names of the preference profiles in the prefs.yaml files (see [`set_profile`]).
+pub fn get_profile_names() -> Vec<String> {
+    return crate::prefs::read_prefs_unchecked(&crate::prefs::PreferenceManager::get()).get_profile_names();
+}
+
+/// Get a description of every preference MathCAT understands: its name, type, allowed values, default,
+/// and the speech style or braille code it applies to.
+/// An AT can use this to generate its settings UI rather than hard-coding the list of preferences.
+pub fn get_preference_descriptions() -> Vec<crate::prefs::PreferenceDescription> {
+    return crate::prefs::PreferenceManager::get_preference_descriptions().to_vec();
+}
+
+/// Save the user preferences that differ from the defaults (e.g., ones changed with [`set_preference`]) to the user's prefs.yaml file
+/// so that they are used the next time MathCAT starts. The file is in the `MathCAT` subdirectory of the user's config directory.
+/// If the file already exists, only the lines that change are rewritten, so the user's comments in it are kept.
+/// Returns the name of the file that was written.
+pub fn save_user_prefs() -> Result<String> {
+    let file = crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.save_user_prefs()?;
+    return Ok( file.to_string_lossy().to_string() );
+}
+
+/// Register `listener` to be called with the name, old value, and new value of a preference whenever its value changes.
+/// Changes from [`set_preference`], from rereading the user's prefs.yaml file, and from [`set_rules_dir`] are all reported,
+/// so this can be used to keep an AT's settings UI in sync with edits to the prefs file.
+/// The old value is "\u{FFFF}" if the preference didn't exist.
+///
+/// The listener is called after the preferences are unlocked, so it can use them (e.g., call [`set_preference`]).
+pub fn add_preference_listener(listener: crate::prefs::PreferenceListener) -> Result<()> {
+    crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.add_listener(listener);
+    return Ok( () );
+}
+
+/// Remove all the listeners added by [`add_preference_listener`].
+pub fn clear_preference_listeners() -> Result<()> {
+    crate::prefs::write_prefs(&crate::prefs::PreferenceManager::get())?.clear_listeners();
+    return Ok( () );
+}
+
+/// Register `processor` to change the words of all the speech generated on this thread (including navigation speech)
+/// before it is returned. The processors are called in the order they were added. See [`crate::speech::SpeechPostProcessor`].
+pub fn add_speech_post_processor(processor: Box<dyn crate::speech::SpeechPostProcessor>) {
+    crate::speech::add_speech_post_processor(processor);
+}
+
+/// Remove all the post processors added on this thread by [`add_speech_post_processor`].
+pub fn clear_speech_post_processors() {
+    crate::speech::clear_speech_post_processors();
+}
+
+/// Get the preferences used by this thread so that other threads can use them via [`use_shared_preferences`].
+/// This lets a multi-threaded host (e.g., a web service) set the Rules dir and preferences once for all its threads.
+pub fn get_shared_preferences() -> crate::prefs::SharedPreferences {
+    return crate::prefs::PreferenceManager::get();
+}
+
+/// Use `prefs` (from [`get_shared_preferences`] on another thread) for this thread in place of [`set_rules_dir`].
+/// Changes made by any of the threads (e.g., with [`set_preference`]) are seen by all of them and the preference files are read only once.
+/// Only the preferences are shared: each thread still reads and keeps its own speech, braille, and navigation rules,
+/// and uses its own file access (see [`set_rules_file_system`]).
+/// If a thread panics while it is changing the preferences, the next use of them returns an error because they might be inconsistent;
+/// after that, they can be used again ([`set_rules_dir`] resets them).
+pub fn use_shared_preferences(prefs: crate::prefs::SharedPreferences) {
+    crate::prefs::PreferenceManager::share_with_this_thread(prefs);
+}
+
+/// The number of times MathCAT found that rule, unicode, definition, or preference files changed and reloaded them.
+/// The files are checked (at most once every `FileCheckInterval` seconds) when speech, braille, or navigation is requested,
+/// so editing a file such as ClearSpeak_Rules.yaml or unicode.yaml takes effect on the next request.
+/// An AT can compare the count from before and after a request to see if a reload happened.
+pub fn get_reload_count() -> usize {
+    return crate::prefs::read_prefs_unchecked(&crate::prefs::PreferenceManager::get()).get_reload_count();
+}
+
+/// When MathCAT last found that rule or preference files changed and reloaded them (`None` if that hasn't happened).
+/// See [`get_reload_count`].
+pub fn get_last_reload_time() -> Option<std::time::SystemTime> {
+    return crate::prefs::read_prefs_unchecked(&crate::prefs::PreferenceManager::get()).get_last_reload_time();
+}
+
+/// The number of times the preference files (e.g., the user's prefs.yaml) were edited outside of MathCAT and reread.
+/// Calling this checks for changes (at most once every `FileCheckInterval` seconds), so an AT can poll it
+/// (e.g., on a timer while its settings dialog is open) and re-query the preferences when the count changes.
+/// The listeners added by [`add_preference_listener`] are also called for the changed preferences when the check finds a change.
+pub fn get_prefs_file_change_count() -> usize {
+    if let Err(e) = crate::speech::SpeechRules::update() {
+        error!("{}", errors_to_string(&e));
+    }
+    return crate::prefs::read_prefs_unchecked(&crate::prefs::PreferenceManager::get()).get_prefs_file_change_count();
+}
+
+/// Get the braille associated with the MathML that was set by [`set_mathml`].
+/// The braille returned depends upon the preference for the `code` preference (default `Nemeth`).
+pub fn get_braille(nav_node_id: String) -> Result<String> {
+    // use std::time::{Instant};
+    // let instant = Instant::now();
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        let braille = crate::braille::braille_mathml(mathml, nav_node_id)?;
+        // info!("Time taken: {}ms", instant.elapsed().as_millis());
+        return Ok( braille );
+    });
+}
+
+/// Get words for the braille of the MathML that was set (e.g., "numeric indicator, 3, 4") for people learning the `BrailleCode` braille code.
+/// This is what [`get_spoken_text`] returns when the `BrailleEcho` preference is true.
+pub fn get_braille_echo() -> Result<String> {
+    return braille_echo(&crate::prefs::PreferenceManager::get());
+}
+
+/// [`get_braille_echo`] using the preferences in `pref_manager` (e.g., from [`prefs_with_overrides`])
+fn braille_echo(pref_manager: &SharedPreferences) -> Result<String> {
+    let braille = MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        return crate::braille::braille_mathml_with_prefs(get_element(&package_instance), "".to_string(), pref_manager);
+    })?;
+    let braille_code = crate::prefs::read_prefs(pref_manager)?.get_user_prefs().to_string("BrailleCode");
+    return Ok( crate::braille::braille_echo(&braille, &braille_code) );
+}
+
+/// Get the speech for the single character `ch` (e.g., for a review cursor that is outside of any expression).
+/// The speech is the same as `ch` gets when it is in an expression: it is treated as an `mn` if it is a digit,
+/// an `mi` if it is a letter, and an `mo` otherwise.
+/// The MathML set by [`set_mathml`] and the navigation state are not changed.
+pub fn get_symbol_speech(ch: char) -> Result<String> {
+    return with_symbol_mathml(ch, |mathml| {
+        let new_package = Package::new();
+        let intent = crate::speech::intent_from_mathml(mathml, new_package.as_document())?;
+        return crate::speech::speak_intent(intent);
+    });
+}
+
+/// Get the braille (for the `BrailleCode` preference) for the single character `ch`. See [`get_symbol_speech`].
+pub fn get_symbol_braille(ch: char) -> Result<String> {
+    return with_symbol_mathml(ch, |mathml| crate::braille::braille_mathml(mathml, "".to_string()));
+}
+
+/// Get a full description of the single character `ch` for a user who hears an unfamiliar symbol
+/// (e.g., "script capital l" for 'ℒ' and "greek small letter zai" for 'ξ'). The verbosity is not changed.
+/// A letter is described by its style (e.g., "script"), its alphabet if that isn't Latin, its case, and the letter.
+/// Other characters are spoken verbosely.
+/// The words for the alphabets and cases are the language's `SpellCharacterAlphabets_vec` and `SpellCharacterCase_vec` definitions;
+/// if they aren't defined, the letter is just spoken verbosely.
+/// The MathML set by [`set_mathml`] and the navigation state are not changed.
+pub fn spell_character(ch: char) -> Result<String> {
+    if ch.is_whitespace() || ch.is_control() {
+        bail!("spell_character: '{}' (U+{:04X}) is not a symbol", ch.escape_default(), ch as u32);
+    }
+    let pref_manager = prefs_with_overrides(
+        &[("Verbosity", "Verbose"), ("TTS", "None"), ("CapitalLetters_UseWord", "false"), ("CapitalLetters_Beep", "false")]
+    )?;
+    let speech = |ch: char| -> Result<String> {
+        let speech = with_symbol_mathml(ch, |mathml| {
+            let new_package = Package::new();
+            let intent = crate::speech::intent_from_mathml_with_prefs(mathml, new_package.as_document(), &pref_manager)?;
+            return crate::speech::speak_intent_with_prefs(intent, &pref_manager);
+        })?;
+        return Ok( speech.split_whitespace().collect::<Vec<&str>>().join(" ") );
+    };
+
+    let description = speech(ch)?;
+    let base = base_letter(ch);
+    if !base.is_uppercase() && !base.is_lowercase() {
+        return Ok(description);
+    }
+    let words = crate::definitions::DEFINITIONS.with(|definitions| {
+        let definitions = definitions.borrow();
+        return (definitions.get_vec("SpellCharacterAlphabets_vec").map(|words| words.clone()),
+                definitions.get_vec("SpellCharacterCase_vec").map(|words| words.clone()));
+    });
+    let (alphabets, cases) = match words {
+        (Some(alphabets), Some(cases)) if alphabets.len() == 3 && cases.len() == 2 => (alphabets, cases),
+        _ => return Ok(description),
+    };
+
+    // the style (e.g., "script bold") is what is said before the letter
+    let letter = speech(base)?;
+    let style = description.strip_suffix(&letter).unwrap_or_default().trim();
+    let alphabet = match base as u32 {
+        0x370..=0x3FF | 0x1F00..=0x1FFF => alphabets[0].as_str(),
+        0x400..=0x52F => alphabets[1].as_str(),
+        0x5D0..=0x5EA => alphabets[2].as_str(),
+        _ => "",
+    };
+    let case = if base.is_uppercase() {&cases[0]} else {&cases[1]};
+    return Ok( [style, alphabet, case, &letter].iter()
+                .filter(|word| !word.is_empty())
+                .copied()
+                .collect::<Vec<&str>>()
+                .join(" ") );
+
+    /// The letter that a styled letter (e.g., '𝓛' or 'ℒ') is a version of
+    fn base_letter(ch: char) -> char {
+        const GREEK_VARIANTS: [char; 6] = ['ϵ', 'ϑ', 'ϰ', 'ϕ', 'ϱ', 'ϖ'];
+        let code = ch as u32;
+        let base = match code {
+            // the Mathematical Alphanumeric Symbols block has runs of 52 Latin letters (A-Z, a-z) for each style...
+            0x1D400..=0x1D6A3 => match (code - 0x1D400) % 52 {
+                i @ 0..=25 => 'A' as u32 + i,
+                i => 'a' as u32 + i - 26,
+            },
+            // ...followed by runs of 58 Greek letters (Α-Ω with ϴ and ∇, α-ω, ∂, and the variants)
+            0x1D6A8..=0x1D7C9 => match (code - 0x1D6A8) % 58 {
+                17 => 'ϴ' as u32,
+                25 => '∇' as u32,
+                i @ 0..=24 => 0x391 + i,
+                51 => '∂' as u32,
+                i @ 26..=50 => 0x3B1 + i - 26,
+                i => GREEK_VARIANTS[(i - 52) as usize] as u32,
+            },
+            _ => return match ch {
+                'ℂ' | 'ℭ' => 'C',
+                'ℊ' => 'g',
+                'ℋ' | 'ℌ' | 'ℍ' => 'H',
+                'ℎ' => 'h',
+                'ℐ' | 'ℑ' => 'I',
+                'ℒ' => 'L',
+                'ℕ' => 'N',
+                'ℙ' => 'P',
+                'ℚ' => 'Q',
+                'ℛ' | 'ℜ' | 'ℝ' => 'R',
+                'ℤ' | 'ℨ' => 'Z',
+                'ℬ' => 'B',
+                'ℯ' => 'e',
+                'ℰ' => 'E',
+                'ℱ' => 'F',
+                'ℳ' => 'M',
+                'ℴ' => 'o',
+                _ => ch,
+            },
+        };
+        return char::from_u32(base).unwrap_or(ch);
+    }
+}
+
+/// Get a full description (see [`spell_character`]) of each of the characters of the leaf element with the given `id`
+/// in the MathML that was set (e.g., the current navigation node). The descriptions are separated by commas.
+pub fn get_overview_of_symbol(id: String) -> Result<String> {
+    let text = MATHML_INSTANCE.with(|package_instance| -> Result<String> {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        return match get_node_by_id(mathml, &id) {
+            None => bail!("There is no element with the id '{}'", id),
+            Some(element) if is_leaf(element) => Ok( crate::canonicalize::as_text(element).to_string() ),
+            Some(element) => bail!("The element with the id '{}' is a '{}', not a symbol", id, name(&element)),
+        };
+    })?;
+    return Ok( text.chars()
+                .filter(|ch| !ch.is_whitespace())
+                .map(spell_character)
+                .collect::<Result<Vec<String>>>()?
+                .join(", ") );
+}
+
+/// Call `f` with the canonicalized MathML for a standalone `ch` (see [`get_symbol_speech`])
+fn with_symbol_mathml<T>(ch: char, f: impl FnOnce(Element) -> Result<T>) -> Result<T> {
+    if ch.is_whitespace() || ch.is_control() {
+        bail!("get_symbol_speech/braille: '{}' (U+{:04X}) is not a symbol", ch.escape_default(), ch as u32);
+    }
+    let element = if ch.is_numeric() {"mn"} else if ch.is_alphabetic() {"mi"} else {"mo"};
+    let package = parse_mathml(&format!("<math><{0}>&#x{1:X};</{0}></math>", element, ch as u32))?;
+    crate::speech::SpeechRules::initialize_all_rules()?;
+    let mathml = cleanup_mathml(get_element(&package))?;
+    return f(mathml);
+}
+
+/// Get a BRF (formatted braille file) for a list of MathML expressions (e.g., all the math in a document).
+/// The braille code is set by the `BrailleCode` preference;
+/// the page layout is set by the `BrfLineLength` and `BrfPageLength` preferences.
+/// The MathML set by [`set_mathml`] (if any) is restored afterwards, but the navigation state is reset.
+pub fn get_brf(mathml_list: Vec<String>) -> Result<String> {
+    let pref_manager = crate::prefs::PreferenceManager::get();
+    let (line_length, page_length) = {
+        let pref_manager = crate::prefs::read_prefs(&pref_manager)?;
+        let prefs = pref_manager.get_user_prefs();
+        let to_usize = |name: &str| prefs.to_int(name).filter(|&value| value >= 0).map(|value| value as usize);
+        let line_length = match to_usize("BrfLineLength") {
+            Some(line_length) => line_length,
+            None => bail!("BrfLineLength preference '{}' must be a positive integer", prefs.to_string("BrfLineLength")),
+        };
+        let page_length = match to_usize("BrfPageLength") {
+            Some(page_length) => page_length,
+            None => bail!("BrfPageLength preference '{}' must be a non-negative integer", prefs.to_string("BrfPageLength")),
+        };
+        (line_length, page_length)
+    };
+
+    let braille_list = with_saved_mathml(|| {
+        return mathml_list.into_iter()
+            .map(|mathml| {
+                set_mathml(mathml)?;
+                return get_braille("".to_string());
+            })
+            .collect::<Result<Vec<String>>>();
+    })?;
+    return Ok( crate::braille::format_brf(&braille_list, line_length, page_length) );
+}
+
+/// Statistics about the math in a document (e.g., a book). See [`get_document_statistics`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentStatistics {
+    /// The number of expressions
+    pub expression_count: usize,
+    /// The number of expressions that couldn't be processed (e.g., the MathML is not well-formed) -- they aren't in the other counts
+    pub error_count: usize,
+    /// How many times each element (e.g., "mfrac" or "msqrt") occurs in the canonical MathML of the expressions
+    pub element_counts: BTreeMap<String, usize>,
+    /// The number of expressions in which chemistry was detected
+    pub chemistry_count: usize,
+    /// The characters that don't have a definition in the speech unicode files along with how often they occur
+    /// (they are spoken as themselves, so they likely aren't spoken well)
+    pub unknown_characters: BTreeMap<char, usize>,
+    /// The average number of elements in the canonical MathML of an expression
+    pub average_complexity: f64,
+}
+
+/// Get statistics about a list of MathML expressions (e.g., all the math in a book) so that an accessibility auditor can
+/// characterize a document's math from one run: the kinds of structures used, whether there is chemistry,
+/// and characters that MathCAT doesn't know how to speak. Expressions that have errors are counted but otherwise skipped.
+/// The MathML set by [`set_mathml`] (if any) is restored afterwards, but the navigation state is reset.
+pub fn get_document_statistics(mathml_list: Vec<String>) -> Result<DocumentStatistics> {
+    let mut statistics = DocumentStatistics {
+        expression_count: mathml_list.len(),
+        ..Default::default()
+    };
+    let mut element_total = 0;
+    with_saved_mathml(|| -> Result<()> {
+        for mathml in mathml_list {
+            if set_mathml(mathml).is_err() {
+                statistics.error_count += 1;
+                continue;
+            }
+            MATHML_INSTANCE.with(|package_instance| -> Result<()> {
+                let package_instance = package_instance.borrow();
+                let mathml = get_element(&package_instance);
+                let mut has_chemistry = false;
+                crate::speech::SPEECH_RULES.with(|rules| -> Result<()> {
+                    let mut rules = rules.borrow_mut();
+                    rules.read_files()?;
+                    element_total += add_statistics(mathml, &rules, &mut statistics, &mut has_chemistry)?;
+                    return Ok( () );
+                })?;
+                if has_chemistry {
+                    statistics.chemistry_count += 1;
+                }
+                return Ok( () );
+            })?;
+        }
+        return Ok( () );
+    })?;
+    let processed_count = statistics.expression_count - statistics.error_count;
+    if processed_count > 0 {
+        statistics.average_complexity = element_total as f64 / processed_count as f64;
+    }
+    return Ok(statistics);
+
+    /// Add the counts for `mathml` and its descendants to `statistics` and return the number of elements
+    fn add_statistics(mathml: Element, rules: &crate::speech::SpeechRules, statistics: &mut DocumentStatistics, has_chemistry: &mut bool) -> Result<usize> {
+        *statistics.element_counts.entry(name(&mathml).to_string()).or_insert(0) += 1;
+        if mathml.attribute("data-chem-formula").is_some() || mathml.attribute("data-chem-equation").is_some() {
+            *has_chemistry = true;
+        }
+        if is_leaf(mathml) {
+            if name(&mathml) != "mtext" {
+                for ch in crate::canonicalize::as_text(mathml).chars().filter(|ch| !ch.is_whitespace()) {
+                    if !rules.has_unicode_def(ch)? {
+                        *statistics.unknown_characters.entry(ch).or_insert(0) += 1;
+                    }
+                }
+            }
+            return Ok(1);
+        }
+        let mut count = 1;
+        for child in mathml.children() {
+            count += add_statistics(as_element(child), rules, statistics, has_chemistry)?;
+        }
+        return Ok(count);
+    }
+}
+
+/// The values for the `aria-label` and `aria-braillelabel` attributes of a `math` element.
+/// See [`get_aria_labels`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AriaLabels {
+    /// The speech without any TTS markup and with the pauses collapsed to commas
+    pub aria_label: String,
+    /// The braille for the code given by the `BrailleCode` preference
+    pub aria_braillelabel: String,
+}
+
+/// Get the `aria-label` and `aria-braillelabel` values for `mathml` so that a web toolkit can annotate the MathML with one call.
+/// The MathML set by [`set_mathml`] (if any) is restored afterwards, but the navigation state is reset.
+pub fn get_aria_labels(mathml: String) -> Result<AriaLabels> {
+    lazy_static! {
+        // with TTS=None, pauses are ',' or ';' followed by a space (numbers such as "1,000" don't have a space)
+        static ref PAUSES: Regex = Regex::new(r"\s*[,;]+(\s+[,;]+)*(\s+|$)").unwrap();
+    }
+
+    let pref_manager = prefs_with_overrides(&[("TTS", "None")])?;
+    let labels: Result<(String, String)> = with_saved_mathml(|| {
+        set_mathml(mathml)?;
+        return Ok( (spoken_text(&pref_manager)?, get_braille("".to_string())?) );
+    });
+    let (speech, braille) = labels?;
+    let speech = PAUSES.replace_all(&speech, ", ");
+    return Ok( AriaLabels {
+        aria_label: speech.trim_end_matches(", ").trim().to_string(),
+        aria_braillelabel: braille,
+    } );
+}
+
+/// Return the MathML that was set with `intent` attributes for the intents that MathCAT inferred (e.g., `intent="power($a1,$a2)"`).
+/// Publishers can save this in their source files so that MathCAT's reading of the notation is used by every AT that supports `intent`.
+/// The children used by an intent are given `arg` attributes (an existing `arg` is kept). Intents that are in the MathML are not changed.
+/// The `id`s that MathCAT added are removed, but the other canonicalization changes (e.g., added `mrow`s) are kept.
+pub fn get_mathml_with_intent() -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        if mathml.children().is_empty() {
+            bail!("MathML has not been set -- can't export it with intents");
+        }
+
+        // work on a copy so that the intent inference doesn't touch the MathML used for speech and navigation
+        let new_package = Package::new();
+        let doc = new_package.as_document();
+        let mathml = copy_element(doc, mathml);
+        doc.root().append_child(mathml);
+        let intent_package = Package::new();
+        let intent = crate::speech::intent_from_mathml(mathml, intent_package.as_document())?;
+
+        let mut ids = HashMap::new();
+        collect_ids(mathml, &mut ids);
+        let mut n_args = 0;
+        add_intents(intent, &ids, &mut n_args);
+        remove_added_ids(mathml);
+        return Ok( mml_to_string(&mathml) );
+    });
+
+    fn collect_ids<'a>(mathml: Element<'a>, ids: &mut HashMap<String, Element<'a>>) {
+        if let Some(id) = mathml.attribute_value("id") {
+            ids.insert(id.to_string(), mathml);
+        }
+        for child in mathml.children() {
+            if let Some(child) = child.element() {
+                collect_ids(child, ids);
+            }
+        }
+    }
+
+    /// Add an `intent` to the MathML element that each concept in the `intent` tree came from
+    fn add_intents(intent: Element, ids: &HashMap<String, Element>, n_args: &mut usize) {
+        if !crate::canonicalize::is_mathml_element_name(name(&intent)) {
+            if let Some(&mathml) = intent.attribute_value("id").and_then(|id| ids.get(id)) {
+                let mut args = vec![];
+                if mathml.attribute("intent").is_none() {
+                    if let Some(value) = intent_value(intent, mathml, ids, &mut args, n_args) {
+                        for (arg_element, arg_name) in args {
+                            arg_element.set_attribute_value("arg", &arg_name);
+                        }
+                        mathml.set_attribute_value("intent", &value);
+                    }
+                }
+            }
+        }
+        for child in intent.children() {
+            if let Some(child) = child.element() {
+                add_intents(child, ids, n_args);
+            }
+        }
+    }
+
+    /// The value of the `intent` attribute for the concept `intent` on the MathML element `mathml` (None if it can't be written).
+    /// The children that are referenced (`$a1`) are added to `args`.
+    /// A child that came from `mathml` itself (e.g., the 'msub' part of an 'msubsup') is written as a nested concept.
+    fn intent_value<'a>(intent: Element, mathml: Element<'a>, ids: &HashMap<String, Element<'a>>,
+                        args: &mut Vec<(Element<'a>, String)>, n_args: &mut usize) -> Option<String> {
+        if crate::canonicalize::is_mathml_element_name(name(&intent)) || intent.children().iter().any(|child| child.text().is_some()) {
+            return None;
+        }
+        let mut value = name(&intent).to_string();
+        if let Some(properties) = intent.attribute_value("data-intent-property") {
+            for property in properties.split(':').filter(|property| !property.is_empty()) {
+                value = value + ":" + property;
+            }
+        }
+        let children = intent.children().iter().filter_map(|child| child.element()).collect::<Vec<Element>>();
+        if children.is_empty() {
+            return Some(value);
+        }
+
+        let mut child_values = Vec::with_capacity(children.len());
+        for child in children {
+            let child_value = match child.attribute_value("id").and_then(|id| ids.get(id)) {
+                Some(&child_mathml) if child_mathml == mathml => intent_value(child, mathml, ids, args, n_args)?,
+                Some(&child_mathml) if is_descendant(child_mathml, mathml) => {
+                    let arg_name = match child_mathml.attribute_value("arg") {
+                        Some(arg_name) => arg_name.to_string(),
+                        None => {
+                            *n_args += 1;
+                            format!("a{}", n_args)
+                        },
+                    };
+                    args.push( (child_mathml, arg_name.clone()) );
+                    "$".to_string() + &arg_name
+                },
+                Some(_) => return None,
+                None => {
+                    // a leaf that was added by the intent rules is written as a literal
+                    if !is_leaf(child) {
+                        return None;
+                    }
+                    let text = crate::canonicalize::as_text(child);
+                    if text.is_empty() || !text.chars().all(|ch| ch.is_alphanumeric() || ch == '.' || ch == '-') {
+                        return None;
+                    }
+                    text.to_string()
+                },
+            };
+            child_values.push(child_value);
+        }
+        return Some( format!("{}({})", value, child_values.join(",")) );
+    }
+
+    fn is_descendant(mathml: Element, ancestor: Element) -> bool {
+        let mut parent = mathml.parent().and_then(|parent| parent.element());
+        while let Some(element) = parent {
+            if element == ancestor {
+                return true;
+            }
+            parent = element.parent().and_then(|parent| parent.element());
+        }
+        return false;
+    }
+
+    fn remove_added_ids(mathml: Element) {
+        if mathml.attribute("data-id-added").is_some() {
+            mathml.remove_attribute("id");
+            mathml.remove_attribute("data-id-added");
+        }
+        mathml.remove_attribute("data-changed");
+        for child in mathml.children() {
+            if let Some(child) = child.element() {
+                remove_added_ids(child);
+            }
+        }
+    }
+}
+
+/// Run `f` (which presumably calls [`set_mathml`]) and then restore the MathML that was previously set.
+/// This is used by the calls that work on many expressions at once (e.g., a whole document).
+pub(crate) fn with_saved_mathml<T>(f: impl FnOnce() -> T) -> T {
+    /// The MathML that was set -- it is restored when this is dropped, even if `f` panics
+    struct SavedMathML {
+        package: Option<Package>,
+        list: Vec<Package>,
+        index: usize,
+    }
+    impl Drop for SavedMathML {
+        fn drop(&mut self) {
+            if let Some(package) = self.package.take() {
+                MATHML_INSTANCE.with(|package_instance| package_instance.replace(package));
+            }
+            MATHML_LIST.with(|list| list.replace(std::mem::take(&mut self.list)));
+            MATHML_LIST_INDEX.with(|index| index.set(self.index));
+        }
+    }
+
+    let _saved = SavedMathML {
+        package: Some( MATHML_INSTANCE.with(|package_instance| package_instance.replace(init_mathml_instance().into_inner())) ),
+        list: MATHML_LIST.with(|list| list.take()),
+        index: MATHML_LIST_INDEX.with(|index| index.get()),
+    };
+    return f();
+}
+
+/// The MathML, navigation state, and preference overrides of one document (e.g., a book open in an e-reader).
+/// Several documents can be open at the same time, each with its own language, braille code, etc.
+/// Each document has its own copy of the preferences (the current ones with its overrides) and its own rules.
+/// Each call swaps the document in, does the work, and then swaps the MathML, navigation state, preferences, and rules
+/// that were there before back in, so the global calls such as [`set_mathml`] are not affected.
+/// The preferences themselves (which might be shared with other threads) are never changed.
+pub struct DocumentContext {
+    prefs: Vec<(String, String)>,
+    global_prefs: Option<crate::prefs::PreferenceManager>,  // copy of the prefs `rules` were made from (None if they need to be made)
+    rules: Option<crate::speech::ThreadRules>,
+    mathml: Package,
+    mathml_list: Vec<Package>,
+    mathml_list_index: usize,
+    nav_state: NavigationState,
+}
+
+impl Default for DocumentContext {
+    fn default() -> Self {
+        DocumentContext::new()
+    }
+}
+
+impl DocumentContext {
+    pub fn new() -> DocumentContext {
+        return DocumentContext {
+            prefs: vec![],
+            global_prefs: None,
+            rules: None,
+            mathml: init_mathml_instance().into_inner(),
+            mathml_list: vec![],
+            mathml_list_index: 0,
+            nav_state: NavigationState::new(),
+        };
+    }
+
+    /// Override the preference `name` for this document. The name and value are checked as in [`set_preference`].
+    pub fn set_preference(&mut self, name: String, value: String) -> Result<()> {
+        get_preference(name.clone())?;      // unknown name
+        crate::prefs::read_prefs(&crate::prefs::PreferenceManager::get())?.validate_preference(&name, &value)?;
+        match self.prefs.iter_mut().find(|(pref_name, _)| *pref_name == name) {
+            Some(pref) => pref.1 = value,
+            None => self.prefs.push( (name, value) ),
+        }
+        self.global_prefs = None;
+        return Ok( () );
+    }
+
+    /// The value of the preference `name` for this document (the global value if this document doesn't override it)
+    pub fn get_preference(&self, name: String) -> Result<String> {
+        return match self.prefs.iter().find(|(pref_name, _)| *pref_name == name) {
+            Some( (_, value) ) => Ok( value.clone() ),
+            None => get_preference(name),
+        };
+    }
+
+    /// Same as [`set_mathml`], but for this document
+    pub fn set_mathml(&mut self, mathml_str: String) -> Result<String> {
+        return self.with_document(|| set_mathml(mathml_str));
+    }
+
+    /// Same as [`get_spoken_text`], but for this document
+    pub fn get_spoken_text(&mut self) -> Result<String> {
+        return self.with_document(get_spoken_text);
+    }
+
+    /// Same as [`get_braille`], but for this document
+    pub fn get_braille(&mut self, nav_node_id: String) -> Result<String> {
+        return self.with_document(|| get_braille(nav_node_id));
+    }
+
+    /// Same as [`do_navigate_command`], but for this document
+    pub fn do_navigate_command(&mut self, command: String) -> Result<String> {
+        return self.with_document(|| do_navigate_command(command));
+    }
+
+    /// Run `f` with this document's MathML, navigation state, preferences, and rules in place of the current ones
+    fn with_document<T>(&mut self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        /// Swaps the document back out when it is dropped, so the state is restored even if `f` panics
+        struct SwappedIn<'d>(&'d mut DocumentContext);
+        impl Drop for SwappedIn<'_> {
+            fn drop(&mut self) {
+                self.0.swap_state();
             }
-            pref_manager.merge_prefs();
         }
 
-        match name.as_str() {
-            "SpeechStyle" => {
-                if let Some(files_changed) = files_changed {
-                    rules.invalidate(files_changed);
-                }
-            },
-            "Language" => {
-                // check the format
-                if !( value.len() == 2 ||
-                      (value.len() == 5 && value.as_bytes()[2] == b'-') ) {
-                        bail!("Improper format for 'Language' preference '{}'. Should be of form 'en' or 'en-gb'", value);
-                      }
-                if let Some(files_changed) = files_changed {
-                    rules.invalidate(files_changed);
-                }
-            },
-            "BrailleCode" => {
-                crate::speech::BRAILLE_RULES.with(|braille_rules| {
-                    if let Some(files_changed) = files_changed {
-                        braille_rules.borrow_mut().invalidate(files_changed);
-                    }
-                })
-            },
-            _ => {
+        self.update_prefs()?;
+        self.swap_state();
+        let _swapped_in = SwappedIn(self);
+        return f();
+    }
+
+    /// (Re)make this document's preferences if its overrides or the current preferences changed
+    fn update_prefs(&mut self) -> Result<()> {
+        crate::speech::SpeechRules::update()?;      // find any changes to the files
+        let pref_manager = crate::prefs::PreferenceManager::get();
+        let pref_manager = crate::prefs::read_prefs(&pref_manager)?;
+        if let Some(global_prefs) = &self.global_prefs {
+            if pref_manager.has_same_prefs_and_files(global_prefs) {
+                return Ok( () );
             }
         }
+        let document_prefs = pref_manager.with_overrides(&self.prefs)?;
+        match &mut self.rules {
+            None => self.rules = Some( crate::speech::ThreadRules::new(document_prefs) ),
+            Some(rules) => rules.set_prefs(document_prefs),
+        }
+        self.global_prefs = Some( pref_manager.with_overrides(&[])? );
         return Ok( () );
-    });
-
-    fn to_float(name: &str, value: &str) -> Result<f64> {
-        match value.parse::<f64>() {
-            Ok(val) => return Ok(val),
-            Err(_) => bail!("SetPreference: preference'{}'s value '{}' must be a float", name, value),
-        };
     }
-}
 
-/// Get the braille associated with the MathML that was set by [`set_mathml`].
-/// The braille returned depends upon the preference for the `code` preference (default `Nemeth`).
-pub fn get_braille(nav_node_id: String) -> Result<String> {
-    // use std::time::{Instant};
-    // let instant = Instant::now();
-    return MATHML_INSTANCE.with(|package_instance| {
-        let package_instance = package_instance.borrow();
-        let mathml = get_element(&package_instance);
-        let braille = crate::braille::braille_mathml(mathml, nav_node_id)?;
-        // info!("Time taken: {}ms", instant.elapsed().as_millis());
-        return Ok( braille );
-    });
+    /// Exchange this document's state with the current one (calling it again undoes it)
+    fn swap_state(&mut self) {
+        MATHML_INSTANCE.with(|package_instance| std::mem::swap(&mut *package_instance.borrow_mut(), &mut self.mathml));
+        MATHML_LIST.with(|list| std::mem::swap(&mut *list.borrow_mut(), &mut self.mathml_list));
+        MATHML_LIST_INDEX.with(|index| self.mathml_list_index = index.replace(self.mathml_list_index));
+        NAVIGATION_STATE.with(|nav_state| std::mem::swap(&mut *nav_state.borrow_mut(), &mut self.nav_state));
+        if let Some(rules) = &mut self.rules {
+            rules.swap();
+        }
+    }
 }
 
 /// Given a key code along with the modifier keys, the current node is moved accordingly (or value reported in some cases).
@@ -299,6 +1684,8 @@ pub fn do_navigate_keypress(key: usize, shift_key: bool, control_key: bool, alt_
 /// `DescribePrevious`, `DescribeNext`, `DescribeCurrent`
 /// * Location information:
 /// `WhereAmI`, `WhereAmIAll`
+/// * Position among the siblings (e.g., "term 2 of 5") followed by an overview, without moving:
+///   `DescribeSiblings`
 /// * Change navigation modes (circle up/down):
 ///  `ToggleZoomLockUp`, `ToggleZoomLockDown`
 /// * Speak the current navigation mode
@@ -357,6 +1744,108 @@ pub fn get_navigation_mathml_id() -> Result<(String, usize)> {
     });
 }
 
+/// Return a bookmarkable path to the current (navigation) node (e.g., "/fraction[1]/numerator/identifier[2]").
+/// Unlike `id`s, paths remain valid when the same MathML is set again (e.g., in a later session).
+/// Each step is either the role of a child (e.g., "numerator" or "base") or the kind of child along with its
+/// position among siblings of the same kind (e.g., "identifier[2]").
+pub fn get_navigation_node_path() -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        return NAVIGATION_STATE.with(|nav_stack| {
+            let (node, _) = nav_stack.borrow().get_navigation_mathml(mathml)?;
+            return crate::navigate::path_to_node(mathml, node);
+        })
+    });
+}
+
+/// Return the path (see `get_navigation_node_path`) to the node with the given `id`.
+pub fn get_node_path(id: String) -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        return match crate::navigate::get_node_by_id(mathml, &id) {
+            None => bail!("No node with id '{}' was found", id),
+            Some(node) => crate::navigate::path_to_node(mathml, node),
+        };
+    });
+}
+
+/// Make the node given by `path` (see `get_navigation_node_path`) the current navigation node.
+/// Returns the `id` of that node.
+pub fn set_navigation_node_from_path(path: String) -> Result<String> {
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        if mathml.children().is_empty() {
+            bail!("MathML has not been set -- can't set the navigation node");
+        }
+        let node = crate::navigate::get_node_by_path(mathml, &path)?;
+        let id = node.attribute_value("id").unwrap().to_string();
+        NAVIGATION_STATE.with(|nav_stack| nav_stack.borrow_mut().set_navigation_node(&id));
+        return Ok(id);
+    });
+}
+
+/// A node in the accessibility tree returned by [`get_accessibility_tree`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessibilityNode {
+    /// The `id` of the element (these are the `id`s in the MathML returned by [`set_mathml`])
+    pub id: String,
+    /// The kind of node (e.g., "fraction", "group", "identifier", or "math" for the root)
+    pub role: String,
+    /// The speech (without any TTS markup) for the node -- this is what navigation says when it moves to the node
+    pub name: String,
+    /// The path to the node (see [`get_navigation_node_path`]), which includes the role the node plays in its parent (e.g., "numerator")
+    pub path: String,
+    /// The bounding box (x, y, width, height) of the node. MathCAT doesn't do layout, so this is always `None`;
+    /// it is there for a platform bridge to fill in from the rendered math.
+    pub bounds: Option<[f64; 4]>,
+    pub children: Vec<AccessibilityNode>,
+}
+
+/// Get the MathML that was set as a tree of accessibility nodes so that a platform bridge (e.g., UIA or AT-SPI)
+/// can expose the math as a navigable subtree rather than as a single string.
+/// The tree has the same grouping as navigation in the `Enhanced` `NavMode` except that invisible operators are left out.
+/// The navigation state is not changed.
+pub fn get_accessibility_tree() -> Result<AccessibilityNode> {
+    let pref_manager = prefs_with_overrides(&[("TTS", "None")])?;
+    return MATHML_INSTANCE.with(|package_instance| {
+        let package_instance = package_instance.borrow();
+        let mathml = get_element(&package_instance);
+        if mathml.children().is_empty() {
+            bail!("MathML has not been set -- can't get the accessibility tree");
+        }
+        let new_package = Package::new();
+        return build_node(mathml, mathml, new_package.as_document(), &pref_manager);
+    });
+
+    fn build_node(mathml: Element, node: Element, doc: Document, pref_manager: &SharedPreferences) -> Result<AccessibilityNode> {
+        let children = if is_leaf(node) {
+            vec![]
+        } else {
+            node.children().into_iter()
+                .map(as_element)
+                .filter(|&child| !is_invisible_operator(child))
+                .map(|child| build_node(mathml, child, doc, pref_manager))
+                .collect::<Result<Vec<AccessibilityNode>>>()?
+        };
+        let speech = crate::navigate::speak_in_context(node, doc, pref_manager)?;
+        return Ok( AccessibilityNode {
+            id: node.attribute_value("id").unwrap_or_default().to_string(),
+            role: crate::navigate::node_kind(name(&node)).to_string(),
+            name: speech.split_whitespace().collect::<Vec<&str>>().join(" "),
+            path: crate::navigate::path_to_node(mathml, node)?,
+            bounds: None,
+            children,
+        } );
+    }
+
+    fn is_invisible_operator(element: Element) -> bool {
+        return name(&element) == "mo" &&
+               crate::canonicalize::as_text(element).chars().all(|ch| ('\u{2061}'..='\u{2064}').contains(&ch));
+    }
+}
 
 /// Convert the returned error from set_mathml, etc., to a useful string for display
 pub fn errors_to_string(e:&Error) -> String {
@@ -775,12 +2264,90 @@ mod tests {
         assert_eq!(entity_str, converted_str);
     }
 
+    /// Calls `set_mathml` on both strings and checks that the results are the same (ignoring ids)
+    fn are_set_mathml_results_equal(test: &str, target: &str) -> bool {
+        set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        lazy_static! {
+            static ref ID_MATCH: Regex = Regex::new(r#"id='.+?' "#).unwrap();
+        }
+        let test_str = set_mathml(test.to_string()).unwrap();
+        let target_str = set_mathml(target.to_string()).unwrap();
+        let test_str = ID_MATCH.replace_all(&test_str, "");
+        let target_str = ID_MATCH.replace_all(&target_str, "");
+        assert_eq!(test_str, target_str);
+        return true;
+    }
+
+    #[test]
+    fn mathjax_v3_data_mjx_attrs() {
+        // MathJax v3 assistive MathML for '\\mathrm{d}x + \\left|y\\right|'
+        let test = r#"<math xmlns="http://www.w3.org/1998/Math/MathML" data-mjx-texclass="ORD">
+            <mrow data-mjx-texclass="ORD"><mi mathvariant="normal">d</mi></mrow><mi>x</mi><mo>+</mo>
+            <mrow data-mjx-texclass="INNER"><mo data-mjx-texclass="OPEN">|</mo><mi>y</mi><mo data-mjx-texclass="CLOSE">|</mo></mrow>
+        </math>"#;
+        let target = r#"<math xmlns="http://www.w3.org/1998/Math/MathML">
+            <mrow><mi mathvariant="normal">d</mi></mrow><mi>x</mi><mo>+</mo>
+            <mrow><mo>|</mo><mi>y</mi><mo>|</mo></mrow>
+        </math>"#;
+        assert!(are_set_mathml_results_equal(test, target));
+    }
+
+    #[test]
+    fn mathjax_v3_class_artifacts() {
+        // '\\class{mjx-box}{x}' and TeXAtom classes should not survive
+        let test = r#"<math><mrow class="MJX-TeXAtom-ORD"><mi class="mjx-box">x</mi></mrow><mo>=</mo><mn>1</mn></math>"#;
+        let target = r#"<math><mrow><mi>x</mi></mrow><mo>=</mo><mn>1</mn></math>"#;
+        assert!(are_set_mathml_results_equal(test, target));
+    }
+
+    #[test]
+    fn mathjax_v3_duplicate_stretchy_op() {
+        // MathJax v3 output for a stretched arrow that repeated the operator
+        let test = r#"<math><mi>A</mi><mo data-mjx-texclass="REL" stretchy="true">→</mo><mo stretchy="true">→</mo><mi>B</mi></math>"#;
+        let target = r#"<math><mi>A</mi><mo stretchy="true">→</mo><mi>B</mi></math>"#;
+        assert!(are_set_mathml_results_equal(test, target));
+    }
+
+    #[test]
+    fn nested_stretchy_fences_are_kept() {
+        set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        // '\\left(\\left(x\\right)\\right)' written without the inner mrows
+        let test = r#"<math><mrow><mo stretchy="true">(</mo><mo stretchy="true">(</mo><mi>x</mi><mo stretchy="true">)</mo><mo stretchy="true">)</mo></mrow></math>"#;
+        let result = set_mathml(test.to_string()).unwrap();
+        assert_eq!(result.matches("<mo").count(), 4, "a fence was removed:\n{}", result);
+
+        // MathJax v3 output has its attributes on both fences
+        let test = r#"<math><mrow><mo data-mjx-texclass="OPEN" stretchy="true">(</mo><mo data-mjx-texclass="OPEN" stretchy="true">(</mo><mi>x</mi>
+                <mo data-mjx-texclass="CLOSE" stretchy="true">)</mo><mo data-mjx-texclass="CLOSE" stretchy="true">)</mo></mrow></math>"#;
+        let result = set_mathml(test.to_string()).unwrap();
+        assert_eq!(result.matches("<mo").count(), 4, "a fence was removed:\n{}", result);
+        assert!(!result.contains(MATHJAX_MO_ATTR));
+    }
+
     #[test]
+    fn stretchy_norm_bars_are_kept() {
+        set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        let test = r#"<math><mo stretchy="true">|</mo><mo stretchy="true">|</mo><mi>v</mi><mo stretchy="true">|</mo><mo stretchy="true">|</mo></math>"#;
+        let result = set_mathml(test.to_string()).unwrap();
+        assert_eq!(result.matches("<mo").count() - result.matches("<mo data-changed='added'").count(), 4, "a bar was removed:\n{}", result);
+        let test = r#"<math><mo data-mjx-texclass="ORD" stretchy="true">|</mo><mo data-mjx-texclass="ORD" stretchy="true">|</mo><mi>v</mi>
+                <mo data-mjx-texclass="ORD" stretchy="true">|</mo><mo data-mjx-texclass="ORD" stretchy="true">|</mo></math>"#;
+        let result = set_mathml(test.to_string()).unwrap();
+        assert_eq!(result.matches("<mo").count() - result.matches("<mo data-changed='added'").count(), 4, "a bar was removed:\n{}", result);
+    }
+
+    #[test]
+    #[cfg(not(feature = "built-in-rules"))]     // the built-in Rules are used if the Rules dir isn't valid
     fn can_recover_from_invalid_set_rules_dir() {
         use std::env;
         // MathCAT will check the env var "MathCATRulesDir" as an override, so the following test might succeed if we don't override the env var
         env::set_var("MathCATRulesDir", "MathCATRulesDir");
         assert!(set_rules_dir("someInvalidRulesDir".to_string()).is_err());
+        // the error is returned (the rules are not used) until there is a valid Rules dir
+        match get_spoken_text() {
+            Err(e) => assert!(matches!(e.kind(), ErrorKind::NotInitialized(_)), "error is '{}'", errors_to_string(&e)),
+            Ok(speech) => panic!("expected a 'NotInitialized' error, got the speech '{}'", speech),
+        }
         assert!(set_rules_dir(super::super::abs_rules_dir_path()).is_ok());
         assert!(set_mathml("<math><mn>1</mn></math>".to_string()).is_ok());
     }
@@ -812,4 +2379,975 @@ mod tests {
         let target = "<math><mn>1</mn> <mtext>a aa</mtext> <mi>y</mi></math>";
         assert!(are_parsed_strs_equal(test, target));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn highlight_plan() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        set_preference("Rate".to_string(), "120".to_string()).unwrap();
+        set_mathml("<math id='m'><mrow id='r'><msup id='s'><mi id='x'>x</mi><mn id='two'>2</mn></msup><mo id='plus'>+</mo><mn id='one'>1</mn></mrow></math>".to_string()).unwrap();
+        let changed = listen_for_pref_changes();
+        let plan = get_highlight_plan().unwrap();
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        let ids_and_levels = plan.iter().map(|region| (region.id.as_str(), region.level, region.text.as_str())).collect::<Vec<_>>();
+        assert_eq!(ids_and_levels, vec![("x", 3, "x"), ("two", 3, "squared"), ("plus", 2, "plus"), ("one", 2, "1")]);
+        assert_eq!(plan[1].duration_ms, 500.0);     // one word at 120 words/min
+        assert_eq!(get_preference("TTS".to_string()).unwrap(), "None");
+        assert_eq!(get_preference("Bookmark".to_string()).unwrap(), "false");
+
+        // the durations can't be computed without a speech rate
+        set_preference("Rate".to_string(), "0".to_string()).unwrap();
+        let plan = get_highlight_plan();
+        set_preference("Rate".to_string(), "180".to_string()).unwrap();
+        assert!(plan.is_err());
+    }
+
+    #[test]
+    fn speech_post_processor() {
+        struct SwapWords;
+        impl crate::speech::SpeechPostProcessor for SwapWords {
+            fn process(&self, words: &mut Vec<String>) {
+                for word in words.iter_mut() {
+                    if word == "plus" {
+                        *word = "and".to_string();
+                    }
+                }
+                words.retain(|word| word != "the");
+            }
+        }
+
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mn>2</mn></mfrac></math>".to_string()).unwrap();
+        add_speech_post_processor(Box::new(SwapWords));
+        let speech = get_spoken_text();
+        let nav_speech = do_navigate_command("ZoomIn".to_string());
+        clear_speech_post_processors();
+        let plain_speech = get_spoken_text();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        // the TTS markup is left alone
+        assert_eq!(speech.unwrap(),
+            "fraction with numerator <break time='300ms'/> <say-as interpret-as='characters'>x</say-as> and 1 <break time='300ms'/> and denominator 2 <break time='600ms'/>");
+        assert_eq!(nav_speech.unwrap(), "in numerator <break time='300ms'/> <say-as interpret-as='characters'>x</say-as> and 1");
+        assert!(plain_speech.unwrap().starts_with("the fraction"));
+    }
+
+    #[test]
+    fn spoken_segments() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        set_mathml("<math><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mn>2</mn></mfrac><mo>=</mo><mi>y</mi></math>".to_string()).unwrap();
+        let changed = listen_for_pref_changes();
+        let segments = get_spoken_segments().unwrap();
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        let texts = segments.iter().map(|segment| segment.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["the fraction with numerator", "x plus 1", "and denominator 2", "is equal to y"]);
+        let pauses = segments.iter().map(|segment| segment.pause_ms).collect::<Vec<_>>();
+        assert_eq!(pauses, vec![300.0, 300.0, 600.0, 0.0]);
+
+        // pauses are twice as long with a PauseFactor of 200 and twice as long again when the math is spoken at half the rate
+        set_preference("PauseFactor".to_string(), "200".to_string()).unwrap();
+        set_preference("MathRate".to_string(), "50".to_string()).unwrap();
+        let segments = get_spoken_segments();
+        set_preference("PauseFactor".to_string(), "100".to_string()).unwrap();
+        set_preference("MathRate".to_string(), "100".to_string()).unwrap();
+        let pauses = segments.unwrap().iter().map(|segment| segment.pause_ms).collect::<Vec<_>>();
+        assert_eq!(pauses, vec![1200.0, 1200.0, 2400.0, 0.0]);
+        assert_eq!(get_preference("TTS".to_string()).unwrap(), "None");
+    }
+
+    #[test]
+    fn aria_labels() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math><mn>2</mn></math>".to_string()).unwrap();
+        let changed = listen_for_pref_changes();
+        let labels = get_aria_labels("<math><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1,000</mn></mrow><mi>y</mi></mfrac></math>".to_string()).unwrap();
+        clear_preference_listeners().unwrap();
+        assert_eq!(labels.aria_label, "the fraction with numerator, x plus 1000, and denominator y");
+        assert_eq!(labels.aria_braillelabel, "⠹⠭⠬⠂⠠⠴⠴⠴⠌⠽⠼");
+        // the prefs are never changed (not even temporarily) and the MathML is restored
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(get_preference("TTS".to_string()).unwrap(), "SSML");
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "2");
+    }
+
+    #[test]
+    fn at_profile() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        set_mathml("<math><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mi>y</mi></mfrac></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the fraction with numerator; x plus 1; and denominator y;");
+        set_preference("ATProfile".to_string(), "JAWS".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the fraction with numerator, x plus 1, and denominator y,");
+        assert!(set_preference("ATProfile".to_string(), "NoSuchScreenReader".to_string()).is_err());
+
+        // the replacements are not made in the TTS markup
+        set_preference("ATProfile".to_string(), "VoiceOver".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi><mo>&lt;</mo><mn>1</mn></math>".to_string()).unwrap();
+        let speech = get_spoken_text().unwrap();
+        set_preference("ATProfile".to_string(), "None".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        assert!(speech.contains("<say-as interpret-as="), "speech is {}", speech);
+        assert!(!speech.contains("‹"), "speech is {}", speech);
+    }
+
+    /// Add a preference listener that records the name of each preference that is changed (call `clear_preference_listeners` when done)
+    fn listen_for_pref_changes() -> std::sync::Arc<std::sync::Mutex<Vec<String>>> {
+        let changed = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let listener_changed = changed.clone();
+        add_preference_listener(Box::new(move |name, _, _| listener_changed.lock().unwrap().push(name.to_string()))).unwrap();
+        return changed;
+    }
+
+    #[test]
+    fn all_verbosities() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        // the prefs are never changed (not even temporarily)
+        let changed = listen_for_pref_changes();
+        let readings = get_spoken_text_all_verbosities().unwrap();
+        clear_preference_listeners().unwrap();
+        assert_eq!(readings, VerbosityReadings {
+            terse: "square root, x,".to_string(),
+            medium: "the square root of x,".to_string(),
+            verbose: "the square root of x, end root;".to_string(),
+        });
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Medium");
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+    }
+
+    #[test]
+    fn spoken_text_with_style() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><mfrac><mi>x</mi><mrow><mi>y</mi><mo>+</mo><mn>1</mn></mrow></mfrac></math>".to_string()).unwrap();
+        let clear_speak = get_spoken_text().unwrap();
+        let simple_speak = get_spoken_text_with_style("SimpleSpeak".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "SimpleSpeak".to_string()).unwrap();
+        assert_eq!(simple_speak, get_spoken_text().unwrap());
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        assert_ne!(clear_speak, simple_speak);
+        assert_eq!(get_preference("SpeechStyle".to_string()).unwrap(), "ClearSpeak");
+        assert_eq!(get_spoken_text().unwrap(), clear_speak);
+        assert_eq!(get_spoken_text_with_style("ClearSpeak".to_string()).unwrap(), clear_speak);
+        assert!(get_spoken_text_with_style("NoSuchStyle".to_string()).is_err());
+    }
+
+    #[test]
+    fn explain_phrase() {
+        use super::explain_phrase;
+        set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><mfrac><mi>a</mi><mi>b</mi></mfrac><mo>+</mo><mn>1</mn></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "eigh over b plus 1");
+
+        let over = explain_phrase("Over".to_string()).unwrap();
+        assert_eq!(over.len(), 1);
+        assert_eq!(over[0].text, "over");
+        assert_eq!(over[0].rule, "fraction-over-simple");
+        assert_eq!(over[0].tag, "fraction");
+        assert!(over[0].file.ends_with("ClearSpeak_Rules.yaml"));
+
+        let plus = explain_phrase("plus".to_string()).unwrap();
+        assert_eq!(plus.len(), 1);
+        assert_eq!(plus[0].rule, "+");
+        assert_eq!(plus[0].tag, "unicode");
+        assert!(plus[0].file.ends_with("unicode.yaml"));
+
+        assert!(explain_phrase("a over b".to_string()).unwrap().is_empty());
+        assert!(explain_phrase(" ".to_string()).is_err());
+    }
+
+    #[test]
+    fn preference_validation() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        match set_preference("Verbositty".to_string(), "Verbose".to_string()) {
+            Err(e) => assert!(matches!(e.kind(), ErrorKind::UnknownPreference(name) if name == "Verbositty")),
+            Ok(_) => panic!("'Verbositty' should be an unknown preference"),
+        }
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        match set_preference("Verbosity".to_string(), "Loud".to_string()) {
+            Err(e) => {
+                assert!(matches!(e.kind(), ErrorKind::InvalidPreferenceValue(name, value, _) if name == "Verbosity" && value == "Loud"));
+                assert_eq!(e.to_string(), "'Loud' is not a valid value for the preference 'Verbosity' -- it must be one of Terse, Medium, Verbose");
+            },
+            Ok(_) => panic!("'Loud' should be an invalid value for 'Verbosity'"),
+        }
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Medium");
+        set_preference("Verbosity".to_string(), "verbose".to_string()).unwrap();       // case doesn't matter
+        assert!(set_preference("Rate".to_string(), "fast".to_string()).is_err());
+        assert!(set_preference("Bookmark".to_string(), "yes".to_string()).is_err());
+        for language in ["e", "english", "en_gb", "en-", "en--gb", "12", "en-toolongtag"] {
+            assert!(set_preference("Language".to_string(), language.to_string()).is_err(), "'{}' should be an invalid language", language);
+        }
+        assert!(crate::prefs::is_language_code("tlh") && crate::prefs::is_language_code("zh-hant-tw"));
+        set_preference("SpeechStyle".to_string(), "SimpleSpeak".to_string()).unwrap();    // not an enum
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+    }
+
+    #[test]
+    fn preferences_json() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Verbosity".to_string(), "Terse".to_string()).unwrap();
+        set_preference("Rate".to_string(), "200".to_string()).unwrap();
+        let json = get_preferences_json().unwrap();
+        let prefs: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(prefs["Verbosity"], "Terse");
+        assert_eq!(prefs["Rate"], 200.0);
+        assert_eq!(prefs["Bookmark"], false);
+
+        set_preference("Verbosity".to_string(), "Verbose".to_string()).unwrap();
+        set_preference("Rate".to_string(), "100".to_string()).unwrap();
+        set_preferences_json(json.clone()).unwrap();
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Terse");
+        assert_eq!(get_preference("Rate".to_string()).unwrap(), "200");
+        assert_eq!(get_preferences_json().unwrap(), json);
+
+        // nothing is changed if there is an error
+        assert!(set_preferences_json(r#"{"Verbosity": "Medium", "Rate": "fast"}"#.to_string()).is_err());
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Terse");
+        assert!(set_preferences_json(r#"["Verbosity"]"#.to_string()).is_err());
+        set_preferences_json(r#"{"Verbosity": "Medium", "Rate": 180}"#.to_string()).unwrap();
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Medium");
+    }
+
+    #[test]
+    fn spoken_text_with_prefs() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        set_mathml("<math><mi>y</mi></math>".to_string()).unwrap();
+        let y_speech = get_spoken_text().unwrap();
+
+        // the prefs are never changed (not even temporarily)
+        let changed = listen_for_pref_changes();
+        let sqrt = "<math><msqrt><mi>x</mi></msqrt></math>".to_string();
+        let verbose = [("Verbosity".to_string(), "Verbose".to_string())];
+        assert_eq!(get_spoken_text_with_prefs(sqrt.clone(), &verbose).unwrap(), "the square root of x, end root;");
+        let simple_speak = [("SpeechStyle".to_string(), "SimpleSpeak".to_string()), ("TTS".to_string(), "SSML".to_string())];
+        let fraction = "<math><mfrac><mi>x</mi><mrow><mi>y</mi><mo>+</mo><mn>1</mn></mrow></mfrac></math>".to_string();
+        assert_eq!(get_spoken_text_with_prefs(fraction, &simple_speak).unwrap(),
+                   "fraction <break time='150ms'/> <say-as interpret-as='characters'>x</say-as> over <break time='150ms'/> \
+                    <say-as interpret-as='characters'>y</say-as>  plus  1 <break time='150ms'/> end fraction <break time='300ms'/>");
+        let vietnamese = [("Language".to_string(), "vi".to_string())];
+        assert!(!get_spoken_text_with_prefs(sqrt.clone(), &vietnamese).unwrap().contains("square root"));
+        let bad = [("Verbosity".to_string(), "Terse".to_string()), ("NoSuchPref".to_string(), "x".to_string())];
+        assert!(get_spoken_text_with_prefs(sqrt.clone(), &bad).is_err());
+        let bad = [("Verbosity".to_string(), "Loud".to_string())];
+        assert!(get_spoken_text_with_prefs(sqrt, &bad).is_err());
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Medium");
+        assert_eq!(get_preference("SpeechStyle".to_string()).unwrap(), "ClearSpeak");
+        assert_eq!(get_preference("Language".to_string()).unwrap(), "en");
+        assert_eq!(get_spoken_text().unwrap(), y_speech);
+    }
+
+    #[test]
+    fn symbol_speech_and_braille() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi><mo>+</mo><mn>1</mn></math>".to_string()).unwrap();
+        assert_eq!(get_symbol_speech('≤').unwrap(), "is less than or equal to");
+        assert_eq!(get_symbol_speech('-').unwrap(), "minus");
+        assert_eq!(get_symbol_speech('<').unwrap(), "is less than");
+        assert_eq!(get_symbol_speech('A').unwrap(), "cap eigh");
+        assert_eq!(get_symbol_braille('α').unwrap(), "⠨⠁");
+        assert_eq!(get_symbol_braille('5').unwrap(), "⠼⠢");
+        assert!(get_symbol_speech(' ').is_err());
+        assert_eq!(get_spoken_text().unwrap(), "x plus 1");
+    }
+
+    #[test]
+    fn accessibility_tree() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math><mn>2</mn><mi>x</mi><mo>=</mo><mfrac><mi>a</mi><mi>b</mi></mfrac></math>".to_string()).unwrap();
+        let changed = listen_for_pref_changes();
+        let tree = get_accessibility_tree();
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(get_preference("TTS".to_string()).unwrap(), "SSML");
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        let tree = tree.unwrap();
+        assert_eq!(tree.role, "math");
+        assert_eq!(tree.bounds, None);
+        let mrow = &tree.children[0];
+        assert_eq!(mrow.children.iter().map(|child| child.role.as_str()).collect::<Vec<_>>(), vec!["group", "operator", "fraction"]);
+        // the invisible times isn't part of the tree
+        assert_eq!(mrow.children[0].children.iter().map(|child| child.name.as_str()).collect::<Vec<_>>(), vec!["2", "x"]);
+        assert_eq!(mrow.children[1].name, "is equal to");
+        let fraction = &mrow.children[2];
+        assert_eq!(fraction.name, "a over b");
+        assert_eq!(fraction.children[1].path, "/group[1]/fraction[1]/denominator");
+        assert_eq!(get_node_path(fraction.children[1].id.clone()).unwrap(), fraction.children[1].path);
+    }
+
+    #[test]
+    fn spell_characters() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        set_mathml("<math><mi id='L'>ℒ</mi><mo id='op'>+</mo><mi id='xi'>ξ</mi><mi id='sin'>sin</mi></math>".to_string()).unwrap();
+        let speech = get_spoken_text().unwrap();
+        // the prefs are never changed (not even temporarily)
+        let changed = listen_for_pref_changes();
+        assert_eq!(spell_character('ℒ').unwrap(), "script capital l");
+        assert_eq!(spell_character('𝓛').unwrap(), "script bold capital l");
+        assert_eq!(spell_character('ξ').unwrap(), "greek small letter zai");
+        assert_eq!(spell_character('𝚵').unwrap(), "bold greek capital zai");
+        assert_eq!(spell_character('ж').unwrap(), "cyrillic small letter zhe");
+        assert_eq!(spell_character('x').unwrap(), "small letter x");
+        assert_eq!(spell_character('∫').unwrap(), "integral");
+        assert!(spell_character(' ').is_err());
+
+        assert_eq!(get_overview_of_symbol("L".to_string()).unwrap(), "script capital l");
+        assert_eq!(get_overview_of_symbol("sin".to_string()).unwrap(), "small letter s, small letter i, small letter n");
+        assert!(get_overview_of_symbol("nonexistent".to_string()).is_err());
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(get_spoken_text().unwrap(), speech);
+    }
+
+    #[test]
+    fn duration_estimate() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mi>y</mi></mfrac></math>".to_string()).unwrap();
+        set_preference("Rate".to_string(), "120".to_string()).unwrap();
+        let slow = estimate_duration_ms().unwrap();
+        set_preference("Rate".to_string(), "240".to_string()).unwrap();
+        let fast = estimate_duration_ms().unwrap();
+        set_preference("Rate".to_string(), "180".to_string()).unwrap();
+        // "the fraction with numerator x plus 1 and denominator y" is 9 words (4500ms at 120 words/min) plus the pauses
+        assert!(slow > 4500.0, "duration is {}", slow);
+        // the pauses scale with the rate
+        assert!((slow - 2.0 * fast).abs() < 1.0, "durations are {} and {}", slow, fast);
+    }
+
+    #[test]
+    fn rules_zip() {
+        assert!(set_rules_zip(b"not a zip file".to_vec()).is_err());
+        set_rules_zip(crate::ZIPPED_RULE_FILES.to_vec()).unwrap();
+        assert!(crate::shim_filesystem::is_using_in_memory_rules());
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+        assert_eq!(get_braille("".to_string()).unwrap(), "⠜⠭⠻");
+
+        // back to the file system
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        assert!(!crate::shim_filesystem::is_using_in_memory_rules());
+    }
+
+    #[test]
+    fn rules_zip_file() {
+        let dir = crate::TestDir::new("zip");
+        let zip_file = dir.join("Rules.zip");
+        std::fs::write(&zip_file, crate::ZIPPED_RULE_FILES).unwrap();
+        let not_rules = dir.join("NotRules.zip");
+        std::fs::write(&not_rules, b"not a zip file").unwrap();
+
+        if cfg!(not(feature = "built-in-rules")) {     // otherwise, the built-in Rules are used
+            assert!(set_rules_dir(not_rules.to_str().unwrap().to_string()).is_err());
+        }
+        set_rules_dir(zip_file.to_str().unwrap().to_string()).unwrap();
+        // the rules come from the zip file, but it isn't sandboxed (e.g., the user's prefs are used)
+        assert!(!crate::shim_filesystem::is_using_in_memory_rules());
+        set_preference("Language".to_string(), "en-gb".to_string()).unwrap();     // falls back to "en"
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+
+        // "Rules.zip" is used when there is no "Rules" dir
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_rules_dir(dir.join("Rules").to_str().unwrap().to_string()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+        if cfg!(not(feature = "built-in-rules")) {
+            assert!(set_rules_dir(dir.join("NoRules").to_str().unwrap().to_string()).is_err());
+        }
+
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+    }
+
+    #[test]
+    fn rules_file_system() {
+        use crate::shim_filesystem::{FileSystem, InMemoryFileSystem};
+        use std::rc::Rc;
+        use std::path::{Path, PathBuf};
+        use std::time::SystemTime;
+
+        // counts the files that are read
+        struct CountingFileSystem {
+            files: InMemoryFileSystem,
+            reads: Rc<Cell<usize>>,
+        }
+        impl FileSystem for CountingFileSystem {
+            fn is_file(&self, path: &Path) -> bool { self.files.is_file(path) }
+            fn is_dir(&self, path: &Path) -> bool { self.files.is_dir(path) }
+            fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> { self.files.canonicalize(path) }
+            fn read_to_string(&self, path: &Path) -> Result<String> {
+                self.reads.set(self.reads.get() + 1);
+                return self.files.read_to_string(path);
+            }
+            fn modified(&self, path: &Path) -> Option<SystemTime> { self.files.modified(path) }
+            fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> { self.files.list_dir(path) }
+        }
+
+        let files = InMemoryFileSystem::from_zip(crate::ZIPPED_RULE_FILES).unwrap();
+        assert!(files.list_dir(Path::new("Rules/Languages")).unwrap().contains(&PathBuf::from("Rules/Languages/en")));
+        assert!(files.list_dir(Path::new("Rules/prefs.yaml")).is_err());
+        let reads = Rc::new(Cell::new(0));
+        set_rules_file_system(Rc::new(CountingFileSystem { files, reads: reads.clone() }), "Rules".to_string()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+        assert!(reads.get() > 0);
+
+        // back to the file system
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        assert!(!crate::shim_filesystem::is_using_in_memory_rules());
+    }
+
+    #[test]
+    fn env_preferences() {
+        // prefs that aren't used by other tests (the environment is shared by all the tests)
+        std::env::set_var("MATHCAT_PREF_speechoverrides_leftparen", "open");
+        std::env::set_var("MATHCAT_PREF_Gender", "female");
+        std::env::set_var("MATHCAT_PREF_SpeechSound", "Loud");        // not allowed -- ignored
+        std::env::set_var("MATHCAT_PREF_NoSuchPref", "x");            // unknown -- ignored
+        let result = set_rules_dir(super::super::abs_rules_dir_path());
+        std::env::remove_var("MATHCAT_PREF_speechoverrides_leftparen");
+        std::env::remove_var("MATHCAT_PREF_Gender");
+        std::env::remove_var("MATHCAT_PREF_SpeechSound");
+        std::env::remove_var("MATHCAT_PREF_NoSuchPref");
+        result.unwrap();
+        assert_eq!(get_preference("SpeechOverrides_LeftParen".to_string()).unwrap(), "open");
+        assert_eq!(get_preference("Gender".to_string()).unwrap(), "female");
+        assert_eq!(get_preference("SpeechSound".to_string()).unwrap(), "None");
+
+        // set_preference takes precedence
+        set_preference("SpeechOverrides_LeftParen".to_string(), "".to_string()).unwrap();
+        assert_eq!(get_preference("SpeechOverrides_LeftParen".to_string()).unwrap(), "");
+    }
+
+    #[test]
+    fn preference_profiles() {
+        use crate::shim_filesystem::{FileSystem, InMemoryFileSystem};
+        use std::rc::Rc;
+        use std::path::{Path, PathBuf};
+        use std::time::SystemTime;
+
+        // adds profiles to Rules/prefs.yaml
+        struct ProfilesFileSystem {
+            files: InMemoryFileSystem,
+        }
+        impl FileSystem for ProfilesFileSystem {
+            fn is_file(&self, path: &Path) -> bool { self.files.is_file(path) }
+            fn is_dir(&self, path: &Path) -> bool { self.files.is_dir(path) }
+            fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> { self.files.canonicalize(path) }
+            fn read_to_string(&self, path: &Path) -> Result<String> {
+                let contents = self.files.read_to_string(path)?;
+                if path != Path::new("Rules/prefs.yaml") {
+                    return Ok(contents);
+                }
+                return Ok( contents + "\n  Profiles:\n    Review:\n      Speech: {Verbosity: Terse}\n    Teaching:\n      \
+                        Speech: {Verbosity: Verbose, ClearSpeak: {Roots: RootEnd}}\n      Braille: {BrailleCode: UEB}\n" );
+            }
+            fn modified(&self, path: &Path) -> Option<SystemTime> { self.files.modified(path) }
+            fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> { self.files.list_dir(path) }
+            fn is_sandboxed(&self) -> bool { true }
+        }
+
+        let files = InMemoryFileSystem::from_zip(crate::ZIPPED_RULE_FILES).unwrap();
+        set_rules_file_system(Rc::new(ProfilesFileSystem { files }), "Rules".to_string()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        assert_eq!(get_profile_names(), vec!["Review".to_string(), "Teaching".to_string()]);
+
+        set_profile("Teaching".to_string()).unwrap();
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Verbose");
+        assert_eq!(get_preference("ClearSpeak_Roots".to_string()).unwrap(), "RootEnd");
+        assert_eq!(get_preference("BrailleCode".to_string()).unwrap(), "UEB");
+        set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(get_braille("".to_string()).unwrap(), "⠰⠰⠩⠭⠬");
+
+        // switching restores what "Teaching" changed
+        set_profile("Review".to_string()).unwrap();
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Terse");
+        assert_eq!(get_preference("ClearSpeak_Roots".to_string()).unwrap(), "Auto");
+        assert_eq!(get_preference("BrailleCode".to_string()).unwrap(), "Nemeth");
+        assert_eq!(get_braille("".to_string()).unwrap(), "⠜⠭⠻");
+
+        assert!(set_profile("NoSuchProfile".to_string()).is_err());
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Terse");
+        set_profile("".to_string()).unwrap();
+        assert_eq!(get_preference("Verbosity".to_string()).unwrap(), "Medium");
+
+        // back to the file system
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+    }
+
+    #[test]
+    fn built_in_rules_fallback() {
+        let result = set_rules_dir("no-such-dir/Rules".to_string());
+        if cfg!(feature = "built-in-rules") {
+            result.unwrap();
+            assert!(crate::shim_filesystem::is_using_in_memory_rules());
+            set_preference("Language".to_string(), "en".to_string()).unwrap();
+            set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+            set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+            assert_eq!(get_spoken_text().unwrap(), "the square root of x,");
+        } else {
+            assert!(result.is_err());
+        }
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        assert!(!crate::shim_filesystem::is_using_in_memory_rules());
+    }
+
+    #[test]
+    fn shared_preferences() {
+        let mathml = "<math><mfrac><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mi>c</mi></mfrac></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml(mathml.to_string()).unwrap();
+        let clearspeak = get_spoken_text().unwrap();
+
+        let prefs = get_shared_preferences();
+        let simplespeak = std::thread::spawn(move || {
+            use_shared_preferences(prefs);
+            assert_eq!(get_preference("SpeechStyle".to_string()).unwrap(), "ClearSpeak");
+            set_mathml(mathml.to_string()).unwrap();
+            assert_eq!(get_spoken_text().unwrap(), clearspeak);
+            set_preference("SpeechStyle".to_string(), "SimpleSpeak".to_string()).unwrap();
+            return get_spoken_text().unwrap();
+        }).join().unwrap();
+
+        // the change made by the other thread is used by this thread's rules too
+        assert_eq!(get_preference("SpeechStyle".to_string()).unwrap(), "SimpleSpeak");
+        assert_eq!(get_spoken_text().unwrap(), simplespeak);
+        assert_ne!(simplespeak, get_spoken_text_with_style("ClearSpeak".to_string()).unwrap());
+    }
+
+    #[test]
+    fn poisoned_shared_preferences() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        let prefs = get_shared_preferences();
+        let panicked = std::thread::spawn(move || {
+            let _pref_manager = prefs.write().unwrap();
+            panic!("panic while the prefs are locked");
+        }).join();
+        assert!(panicked.is_err());
+
+        // the panic is reported (instead of panicking here) and then the prefs can be used again
+        assert!(get_preference("Verbosity".to_string()).is_err());
+        assert!(get_preference("Verbosity".to_string()).is_ok());
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+    }
+
+    #[test]
+    fn document_context() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_mathml("<math><mi>y</mi></math>".to_string()).unwrap();
+
+        // the prefs are never changed (not even temporarily)
+        let changed = listen_for_pref_changes();
+        let mut ueb_doc = DocumentContext::new();
+        ueb_doc.set_preference("BrailleCode".to_string(), "UEB".to_string()).unwrap();
+        assert!(ueb_doc.set_preference("Verbosity".to_string(), "Loud".to_string()).is_err());
+        assert!(ueb_doc.set_preference("NotAPref".to_string(), "1".to_string()).is_err());
+        let mut nemeth_doc = DocumentContext::new();
+        ueb_doc.set_mathml("<math><mn>1</mn></math>".to_string()).unwrap();
+        nemeth_doc.set_mathml("<math><mn>1</mn></math>".to_string()).unwrap();
+        assert_eq!(ueb_doc.get_braille("".to_string()).unwrap(), "⠼⠁");
+        assert_eq!(nemeth_doc.get_braille("".to_string()).unwrap(), "⠼⠂");
+        assert_eq!(ueb_doc.get_preference("BrailleCode".to_string()).unwrap(), "UEB");
+        assert_eq!(nemeth_doc.get_preference("BrailleCode".to_string()).unwrap(), "Nemeth");
+        let mut vi_doc = DocumentContext::new();
+        vi_doc.set_preference("Language".to_string(), "vi".to_string()).unwrap();
+        vi_doc.set_mathml("<math><mi>x</mi><mo>+</mo><mn>1</mn></math>".to_string()).unwrap();
+        let vi_speech = vi_doc.get_spoken_text().unwrap();
+        assert_eq!(ueb_doc.get_spoken_text().unwrap(), "1");
+        assert_eq!(vi_doc.get_spoken_text().unwrap(), vi_speech);
+        assert!(!vi_speech.contains("plus"), "speech is '{}'", vi_speech);
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+
+        // changes to the global prefs are used by the documents
+        set_preference("Verbosity".to_string(), "Verbose".to_string()).unwrap();
+        ueb_doc.set_mathml("<math><msqrt><mi>x</mi></msqrt></math>".to_string()).unwrap();
+        assert_eq!(ueb_doc.get_spoken_text().unwrap(), "the square root of x, end root;");
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        assert_eq!(ueb_doc.get_spoken_text().unwrap(), "the square root of x,");
+
+        // the global MathML, navigation, and preferences are untouched
+        assert_eq!(get_preference("Language".to_string()).unwrap(), "en");
+        assert_eq!(get_preference("BrailleCode".to_string()).unwrap(), "Nemeth");
+        assert_eq!(get_spoken_text().unwrap(), "y");
+        nemeth_doc.do_navigate_command("ZoomIn".to_string()).unwrap();
+        let (global_nav_mathml, _) = get_navigation_mathml().unwrap();
+        assert!(global_nav_mathml.trim_start().starts_with("<math") && global_nav_mathml.contains(">y</mi>"));
+    }
+
+    #[test]
+    fn document_context_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi><mo>+</mo><mn>1</mn></math>".to_string()).unwrap();
+        let mut vi_doc = DocumentContext::new();
+        vi_doc.set_preference("Language".to_string(), "vi".to_string()).unwrap();
+        vi_doc.set_mathml("<math><mi>y</mi><mo>+</mo><mn>2</mn></math>".to_string()).unwrap();
+        let vi_speech = vi_doc.get_spoken_text().unwrap();
+
+        // e.g., a rule that panics in a host that catches the panic -- the document's state is swapped back out
+        let result = catch_unwind(AssertUnwindSafe(|| vi_doc.with_document(|| -> Result<()> { panic!("rule panic") })));
+        assert!(result.is_err());
+        assert_eq!(get_spoken_text().unwrap(), "x plus 1");
+        assert_eq!(vi_doc.get_spoken_text().unwrap(), vi_speech);
+
+        let result = catch_unwind(|| with_saved_mathml(|| {
+            set_mathml("<math><mi>z</mi></math>".to_string()).unwrap();
+            panic!("rule panic");
+        }));
+        assert!(result.is_err());
+        assert_eq!(get_spoken_text().unwrap(), "x plus 1");
+    }
+
+    #[test]
+    fn preference_listener() {
+        use std::sync::{Arc, Mutex};
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        set_preference("FractionVerbosity".to_string(), "Auto".to_string()).unwrap();
+        set_preference("MathSpeak".to_string(), "Auto".to_string()).unwrap();
+        set_preference("ATProfile".to_string(), "None".to_string()).unwrap();
+        let changes = Arc::new(Mutex::new(vec![]));
+        let listener_changes = changes.clone();
+        add_preference_listener(Box::new(move |name, old, new| {
+            listener_changes.lock().unwrap().push(format!("{}: {} -> {}", name, old, new));
+        })).unwrap();
+
+        // the effective value of the 'Auto' per-structure verbosities also change
+        set_preference("Verbosity".to_string(), "Verbose".to_string()).unwrap();
+        assert_eq!(changes.lock().unwrap().as_slice(), [
+            "FractionVerbosity: Medium -> Verbose",
+            "MathSpeak: Brief -> Verbose",
+            "RootVerbosity: Medium -> Verbose",
+            "ScriptVerbosity: Medium -> Verbose",
+            "Verbosity: Medium -> Verbose",
+        ]);
+        changes.lock().unwrap().clear();
+        set_preference("Verbosity".to_string(), "Verbose".to_string()).unwrap();
+        assert!(changes.lock().unwrap().is_empty());
+        set_preference("ATProfile".to_string(), "JAWS".to_string()).unwrap();
+        assert_eq!(changes.lock().unwrap().as_slice(), ["ATProfile: None -> JAWS"]);
+
+        clear_preference_listeners().unwrap();
+        changes.lock().unwrap().clear();
+        set_preference("ATProfile".to_string(), "None".to_string()).unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        assert!(changes.lock().unwrap().is_empty());
+
+        // the prefs aren't locked when the listeners are called, so a listener can use them
+        let rate = get_preference("Rate".to_string()).unwrap();
+        let listener_changes = changes.clone();
+        add_preference_listener(Box::new(move |name, _, _| {
+            if name == "Verbosity" {
+                listener_changes.lock().unwrap().push(get_preference(name.to_string()).unwrap());
+                set_preference("Rate".to_string(), "150".to_string()).unwrap();
+            }
+        })).unwrap();
+        set_preference("Verbosity".to_string(), "Terse".to_string()).unwrap();
+        assert_eq!(changes.lock().unwrap().as_slice(), ["Terse"]);
+        assert_eq!(get_preference("Rate".to_string()).unwrap(), "150");
+        clear_preference_listeners().unwrap();
+        set_preference("Verbosity".to_string(), "Medium".to_string()).unwrap();
+        set_preference("Rate".to_string(), rate).unwrap();
+    }
+
+    #[test]
+    fn table_of_contents() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_mathml("<math><mi>y</mi></math>".to_string()).unwrap();
+        let toc = get_table_of_contents(vec![
+            "<math><mi>f</mi><mo>(</mo><mi>x</mi><mo>)</mo><mo>=</mo><msup><mi>x</mi><mn>2</mn></msup></math>".to_string(),
+            "<math><munder><mo>lim</mo><mrow><mi>x</mi><mo>→</mo><mn>0</mn></mrow></munder><mi>x</mi><mo>=</mo><mn>0</mn></math>".to_string(),
+            "<math><mi>x</mi><mo>+</mo><mn>1</mn><mo>=</mo><mn>2</mn></math>".to_string(),
+        ]).unwrap();
+        assert_eq!(toc, "1: definition of f; 2: a limit; 3: an equation");
+        // the MathML that was set is restored
+        assert_eq!(get_spoken_text().unwrap(), "y");
+        assert_eq!(select_table_of_contents_entry(3).unwrap(), "x plus 1, is equal to 2");
+        assert_eq!(get_spoken_text().unwrap(), "x plus 1, is equal to 2");
+        assert!(select_table_of_contents_entry(0).is_err());
+        assert!(select_table_of_contents_entry(4).is_err());
+    }
+
+    #[test]
+    fn text_voice_and_pitch() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi><mo>&gt;</mo><mn>0</mn><mtext>for all cases</mtext></math>".to_string()).unwrap();
+        let plain_speech = get_spoken_text();
+        set_preference("Text_Pitch".to_string(), "20".to_string()).unwrap();
+        set_preference("Text_Voice".to_string(), "narrator".to_string()).unwrap();
+        let text_speech = get_spoken_text();
+        set_preference("Text_Pitch".to_string(), "0".to_string()).unwrap();
+        set_preference("Text_Voice".to_string(), "".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        assert!(!plain_speech.unwrap().contains("<voice"));
+        assert!(text_speech.unwrap().contains("<voice required='narrator'><prosody pitch='20%'>for all cases</prosody></voice>"));
+    }
+
+    #[test]
+    fn user_pronunciations() {
+        let dir = crate::TestDir::new("pronunciations");
+        let file = dir.join("pronunciations.yaml");
+        std::fs::write(&file, "ℏ: h bar\nsinc: sink\n").unwrap();
+        crate::prefs::TEST_PRONUNCIATIONS_FILE.with(|test_file| *test_file.borrow_mut() = Some(file.clone()));
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        set_mathml("<math><mi>ℏ</mi><mo>+</mo><mi>sinc</mi><mo>&#x2061;</mo><mi>x</mi></math>".to_string()).unwrap();
+        let speech = get_spoken_text();
+        let braille = get_braille("".to_string());
+
+        // an edited file is reread (a bad file is ignored)
+        set_preference("FileCheckInterval".to_string(), "0".to_string()).unwrap();
+        std::fs::write(&file, "- not a mapping\n").unwrap();
+        set_mathml("<math><mi>sinc</mi><mo>&#x2061;</mo><mi>x</mi></math>".to_string()).unwrap();
+        let bad_file_speech = get_spoken_text();
+
+        set_preference("FileCheckInterval".to_string(), "1".to_string()).unwrap();
+        crate::prefs::TEST_PRONUNCIATIONS_FILE.with(|test_file| *test_file.borrow_mut() = None);
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        assert_eq!(speech.unwrap(), "h bar plus sink of x");
+        assert!(!braille.unwrap().is_empty());
+        assert_eq!(bad_file_speech.unwrap(), "sinc of x");
+    }
+
+    #[test]
+    fn overview() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+        let large = "<math><mfrac>
+                <mrow><mi>a</mi><mo>+</mo><mi>b</mi><mo>+</mo><mi>c</mi><mo>-</mo><mi>d</mi><mo>+</mo><mn>1</mn></mrow>
+                <mrow><mi>x</mi><mo>&#x2062;</mo><mi>y</mi><mo>&#x2062;</mo><mi>z</mi><mo>&#x2062;</mo><mi>w</mi></mrow>
+            </mfrac></math>";
+        set_mathml(large.to_string()).unwrap();
+        assert_eq!(get_overview_text().unwrap(), "a fraction with a sum of 5 terms over a product of 4 factors");
+        assert!(get_spoken_text().unwrap().starts_with("the fraction with numerator"));
+
+        // with 'Overview', large expressions are described and short ones are read
+        set_preference("Overview".to_string(), "true".to_string()).unwrap();
+        let overview_speech = get_spoken_text();
+        set_mathml("<math><mi>y</mi><mo>=</mo><mfrac><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mn>2</mn></mfrac></math>".to_string()).unwrap();
+        let short_speech = get_spoken_text();
+        set_preference("OverviewSize".to_string(), "5".to_string()).unwrap();
+        let equation_speech = get_spoken_text();
+        set_preference("Overview".to_string(), "false".to_string()).unwrap();
+        set_preference("OverviewSize".to_string(), "12".to_string()).unwrap();
+        assert_eq!(overview_speech.unwrap(), "a fraction with a sum of 5 terms over a product of 4 factors");
+        assert_eq!(short_speech.unwrap(), "y is equal to; the fraction with numerator; a plus b; and denominator 2;");
+        assert_eq!(equation_speech.unwrap(), "y is equal to a fraction");
+    }
+
+    #[test]
+    fn document_statistics() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_mathml("<math><mi>y</mi></math>".to_string()).unwrap();
+        let statistics = get_document_statistics(vec![
+            "<math><mfrac><mi>a</mi><mi>b</mi></mfrac><mo>+</mo><mn>2</mn></math>".to_string(),
+            "<math><msub><mi>H</mi><mn>2</mn></msub><mi>O</mi></math>".to_string(),
+            "<math><mi>&#x1F600;</mi><mo>&#xE000;</mo><mi>&#x1F600;</mi><mtext>&#x1F600;</mtext></math>".to_string(),
+            "<math><mi>a</mi>".to_string(),
+        ]).unwrap();
+        assert_eq!(statistics.expression_count, 4);
+        assert_eq!(statistics.error_count, 1);
+        assert_eq!(statistics.element_counts["mfrac"], 1);
+        assert_eq!(statistics.element_counts["msub"], 1);
+        assert_eq!(statistics.element_counts["math"], 3);
+        assert_eq!(statistics.chemistry_count, 1);
+        assert_eq!(statistics.unknown_characters, BTreeMap::from([('😀', 2), ('\u{E000}', 1)]));     // mtext isn't included
+        let element_total: usize = statistics.element_counts.values().sum();
+        assert_eq!(statistics.average_complexity, element_total as f64 / 3.0);
+        // the MathML that was set is restored
+        assert_eq!(get_spoken_text().unwrap(), "y");
+    }
+
+    #[test]
+    fn spoken_text_with_mapping() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("TTS".to_string(), "SSML".to_string()).unwrap();
+        set_mathml("<math id='m'><msqrt id='sqrt'><mi id='x'>x</mi></msqrt><mo id='plus'>+</mo><mn id='two'>2</mn></math>".to_string()).unwrap();
+        let changed = listen_for_pref_changes();
+        let (speech, mapping) = get_spoken_text_with_mapping().unwrap();
+        clear_preference_listeners().unwrap();
+        assert!(changed.lock().unwrap().is_empty(), "prefs changed: {:?}", changed.lock().unwrap());
+        assert_eq!(speech, "the square root of x; plus 2");
+        let spoken = |range: &SpokenRange| (speech[range.start..range.end].to_string(), range.id.clone());
+        assert_eq!(mapping.iter().map(spoken).collect::<Vec<(String, String)>>(), [
+            ("the square root of".to_string(), "sqrt".to_string()),
+            ("x".to_string(), "x".to_string()),
+            ("plus".to_string(), "plus".to_string()),
+            ("2".to_string(), "two".to_string()),
+        ]);
+        assert_eq!(get_preference("TTS".to_string()).unwrap(), "SSML");
+        set_preference("TTS".to_string(), "None".to_string()).unwrap();
+
+        // the mapping is for the speech of the expression, not its overview
+        set_preference("Overview".to_string(), "true".to_string()).unwrap();
+        set_preference("OverviewSize".to_string(), "1".to_string()).unwrap();
+        let overview = get_spoken_text();
+        let with_overview = get_spoken_text_with_mapping();
+        set_preference("Overview".to_string(), "false".to_string()).unwrap();
+        set_preference("OverviewSize".to_string(), "12".to_string()).unwrap();
+        assert_ne!(overview.unwrap(), speech);
+        assert_eq!(with_overview.unwrap(), (speech, mapping));
+    }
+
+    #[test]
+    fn mathml_with_intent() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "SimpleSpeak".to_string()).unwrap();
+        let mathml = "<math><msubsup><mi>x</mi><mn>1</mn><mn>2</mn></msubsup><mo>+</mo>
+                        <mrow><mo>|</mo><mi>y</mi><mo>|</mo></mrow><mo>+</mo>
+                        <mfrac intent='ratio($p,$q)'><mi arg='p'>a</mi><mi arg='q'>b</mi></mfrac></math>";
+        set_mathml(mathml.to_string()).unwrap();
+        let speech = get_spoken_text().unwrap();
+        let exported = get_mathml_with_intent().unwrap();
+        assert!(exported.contains("<msubsup intent='power(sub($a1,$a2),$a3)'>"));
+        assert!(exported.contains("<mi arg='a1'>x</mi>") && exported.contains("<mn arg='a3'>2</mn>"));
+        assert!(exported.contains("<mrow intent='absolute-value($a4)'>") && exported.contains("<mi arg='a4'>y</mi>"));
+        assert!(exported.contains("<mfrac intent='ratio($p,$q)'>"));     // the author's intent is kept
+        assert!(!exported.contains("id=") && !exported.contains("data-"));
+
+        // the exported MathML is read the same way
+        set_mathml(exported).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), speech);
+    }
+
+    #[test]
+    fn multiple_math_roots() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("Language".to_string(), "en".to_string()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        set_preference("BrailleCode".to_string(), "Nemeth".to_string()).unwrap();
+        set_mathml("<math><mi>x</mi></math><math><mn>2</mn></math>".to_string()).unwrap();
+        assert_eq!(get_mathml_count(), 2);
+        assert_eq!(get_spoken_text().unwrap(), "x");
+        select_mathml(1).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "2");
+        assert_eq!(get_braille("".to_string()).unwrap(), "⠼⠆");
+        assert_eq!(do_navigate_command("ZoomIn".to_string()).unwrap(), "zoomed in all of the way; 2");
+        select_mathml(0).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "x");
+        assert!(select_mathml(2).is_err());
+
+        // math inside of some other markup (e.g., a list)
+        let mathml = set_mathml("<ol><li><p>Solve <math><mi>y</mi></math></p></li><li><math><mi>z</mi></math></li></ol>".to_string()).unwrap();
+        assert_eq!(mathml.matches("<math").count(), 2);
+        assert_eq!(get_mathml_count(), 2);
+        select_mathml(1).unwrap();
+        assert_eq!(get_spoken_text().unwrap(), "z");
+
+        set_mathml("<math><mi>a</mi></math>".to_string()).unwrap();
+        assert_eq!(get_mathml_count(), 1);
+        assert!(select_mathml(1).is_err());
+        assert!(set_mathml("<p>no math</p><p>here</p>".to_string()).is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let valid = "<math><mfrac><mrow><mi>a</mi><mo>+</mo><mn>1</mn></mrow><mi>b</mi></mfrac></math>";
+        assert!(validate_mathml(valid.to_string()).unwrap().is_empty());
+
+        let invalid = "<math mode='display'>
+                <mfrac><mi>a</mi><mo>+</mo><mn>1</mn></mfrac>
+                <mfenced><mi fontweight='bold'>x</mi></mfenced>
+                <foo><mi>y</mi></foo>
+                <mi><mrow><mi>z</mi></mrow></mi>
+                <mtable><mtd><mn>1</mn></mtd></mtable>
+            </math>";
+        let diagnostics = validate_mathml(invalid.to_string()).unwrap();
+        let found: Vec<(DiagnosticKind, &str)> = diagnostics.iter()
+            .map(|diagnostic| (diagnostic.kind, diagnostic.location.as_str()))
+            .collect();
+        assert_eq!(found, [
+            (DiagnosticKind::DeprecatedAttribute, "/math"),
+            (DiagnosticKind::WrongNumberOfChildren, "/math/mfrac[1]"),
+            (DiagnosticKind::DeprecatedElement, "/math/mfenced[1]"),
+            (DiagnosticKind::DeprecatedAttribute, "/math/mfenced[1]/mi[1]"),
+            (DiagnosticKind::UnknownElement, "/math/foo[1]"),
+            (DiagnosticKind::InvalidTokenContent, "/math/mi[1]"),
+            (DiagnosticKind::InvalidChild, "/math/mtable[1]/mtd[1]"),
+        ]);
+        assert_eq!(diagnostics[1].suggestion, "Group the parts of each argument with an 'mrow'");
+        assert!(validate_mathml("<math><mi>x</mi>".to_string()).is_err());
+    }
+
+    #[test]
+    fn debug_tree() {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml("<math><mrow intent='f($x)'><mi arg='x'>x</mi><mo>!</mo></mrow></math>".to_string()).unwrap();
+        let plain = get_debug_tree(false, FormatOptions::default()).unwrap();
+        MATHML_INSTANCE.with(|package_instance| {
+            assert_eq!(plain, mml_to_string(&get_element(&package_instance.borrow())));
+        });
+        let intent = get_debug_tree(true, FormatOptions{ color: false, indent_width: 4 }).unwrap();
+        assert!(intent.contains("\n    <f>\n        <mi arg='x'"), "intent tree is\n{}", intent);
+        let colored = get_debug_tree(true, FormatOptions{ color: true, indent_width: 2 }).unwrap();
+        assert!(colored.contains("<\x1b[1;35mf\x1b[0m>"), "colored intent tree is\n{}", colored);
+        assert!(colored.contains("<\x1b[34mmi\x1b[0m \x1b[33marg='x'\x1b[0m"), "colored intent tree is\n{}", colored);
+    }
+}
@@ -0,0 +1,400 @@
+//! Download and install language packs -- the Rules for a language that aren't shipped with an AT -- so that an AT
+//! can offer in-app language downloads rather than shipping every language. This module is part of the `language-packs` feature.
+//!
+//! The language packs available are listed in an index file (YAML) that is found at a URL chosen by the AT:
+//! ```yaml
+//! - language: de
+//!   version: 0.2.3
+//!   url: de.zip        # relative URLs are relative to the index file
+//!   sha256: 5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03
+//! ```
+//! A language pack is a zip file of a language's dir (e.g., the contents of `Rules/Languages/de`).
+//! It is installed in [`crate::prefs::PreferenceManager::language_packs_dir`] after its hash is verified.
+//! Languages that are in the Rules dir are always used in preference to an installed language pack.
+//!
+//! MathCAT doesn't include a network library: the AT passes in a function to fetch a URL (see [`Fetch`]).
+//! [`read_file_url`] handles `file:` URLs and paths.
+#![allow(clippy::needless_return)]
+
+use crate::errors::*;
+use crate::prefs::{PreferenceManager, is_language_code};
+use crate::shim_filesystem::{is_dir_shim, is_file_shim, read_to_string_shim, list_dir_shim, write_shim, create_dir_all_shim,
+                             remove_dir_all_shim, rename_shim};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+/// A function that returns the contents of a URL (e.g., using the AT's HTTP library)
+pub type Fetch<'f> = &'f dyn Fn(&str) -> Result<Vec<u8>>;
+
+/// The name of the file in an installed language pack's dir that records what was installed
+const INSTALLED_PACK_FILE: &str = "language-pack.yaml";
+
+/// A language pack that is available (from an index file) or installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguagePack {
+    /// The language code (e.g., "de" or "pt-br")
+    pub language: String,
+    /// The version of the language pack (e.g., "0.2.3")
+    pub version: String,
+    /// Where the zipped language pack is
+    pub url: String,
+    /// The SHA-256 hash (in hex) of the zipped language pack
+    pub sha256: String,
+}
+
+/// Return the language packs listed in the index file at `index_url`.
+pub fn get_available_language_packs(index_url: &str, fetch: Fetch) -> Result<Vec<LanguagePack>> {
+    let index = fetch(index_url).chain_err(|| format!("while trying to read the language pack index '{}'", index_url))?;
+    let index = String::from_utf8(index).chain_err(|| format!("the language pack index '{}' is not UTF-8", index_url))?;
+    return parse_index(&index, index_url).chain_err(|| format!("in the language pack index '{}'", index_url));
+}
+
+/// Return the language packs that are installed (not the languages in the Rules dir).
+pub fn get_installed_language_packs() -> Result<Vec<LanguagePack>> {
+    let packs_dir = match PreferenceManager::language_packs_dir() {
+        Some(dir) if is_dir_shim(&dir) => dir,
+        _ => return Ok( vec![] ),
+    };
+    let mut packs = vec![];
+    add_installed_language_packs(&packs_dir, &mut packs)?;
+    packs.sort_by(|a, b| a.language.cmp(&b.language));
+    return Ok(packs);
+}
+
+/// Add the language packs installed in the subdirs of `dir` to `packs`.
+/// Regional packs are in their language's dir (e.g., 'pt-br' is in 'pt/br'), so the subdirs are searched also.
+fn add_installed_language_packs(dir: &Path, packs: &mut Vec<LanguagePack>) -> Result<()> {
+    for lang_dir in list_dir_shim(dir)? {
+        // skip files and dirs that aren't part of a language code (e.g., 'pt.new' left behind by a failed install)
+        let is_lang_part = lang_dir.file_name().and_then(|name| name.to_str())
+                .is_some_and(|name| name.chars().all(|ch| ch.is_ascii_alphanumeric()));
+        if !is_lang_part || !is_dir_shim(&lang_dir) {
+            continue;
+        }
+        let file = lang_dir.join(INSTALLED_PACK_FILE);
+        if is_file_shim(&file) {
+            let contents = read_to_string_shim(&file)?;
+            let mut pack = parse_index(&contents, "").chain_err(|| format!("in {}", file.display()))?;
+            packs.append(&mut pack);
+        }
+        add_installed_language_packs(&lang_dir, packs)?;
+    }
+    return Ok(());
+}
+
+/// Download `pack`, verify its hash, and install it (replacing any installed version of the language).
+/// A regional pack (e.g., 'pt-br') is installed in its language's dir ('pt/br'), the same as in the Rules dir.
+/// Installing a language doesn't remove its installed regional packs and vice versa.
+/// Returns the dir it was installed in.
+pub fn install_language_pack(pack: &LanguagePack, fetch: Fetch) -> Result<PathBuf> {
+    let packs_dir = match PreferenceManager::language_packs_dir() {
+        Some(dir) => dir,
+        None => bail!("Language packs can't be installed when the Rules are sandboxed"),
+    };
+    if !is_language_code(&pack.language) {
+        bail!("'{}' is not a valid language code for a language pack", pack.language);
+    }
+    let zipped = fetch(&pack.url).chain_err(|| format!("while trying to download the language pack '{}'", pack.url))?;
+    let hash = sha256_hex(&zipped);
+    if !hash.eq_ignore_ascii_case(pack.sha256.trim()) {
+        bail!("The language pack '{}' is corrupt: its SHA-256 hash is {} instead of {}", pack.url, hash, pack.sha256);
+    }
+
+    // unzip into a new dir and then swap it in so that a failure doesn't leave a partially installed language
+    let lang_dir = pack.language.split('-').fold(packs_dir, |dir, part| dir.join(part));
+    let new_dir = lang_dir.with_extension("new");
+    let _ = remove_dir_all_shim(&new_dir);
+    let result = create_dir_all_shim(&new_dir)
+        .and_then(|_| unzip(&zipped, &new_dir))
+        .and_then(|_| {
+            let record = format!("- language: {}\n  version: {}\n  url: {}\n  sha256: {}\n", pack.language, pack.version, pack.url, hash);
+            write_shim(&new_dir.join(INSTALLED_PACK_FILE), record.as_bytes()).chain_err(|| "while trying to record the language pack")
+        });
+    if let Err(e) = result {
+        let _ = remove_dir_all_shim(&new_dir);
+        return Err(e).chain_err(|| format!("while trying to install the language pack '{}'", pack.url));
+    }
+    if is_dir_shim(&lang_dir) {
+        // keep the installed regional packs (e.g., 'pt/br' when 'pt' is updated)
+        for regional_dir in list_dir_shim(&lang_dir)? {
+            if is_file_shim(&regional_dir.join(INSTALLED_PACK_FILE)) {
+                let new_regional_dir = new_dir.join(regional_dir.file_name().unwrap());
+                if is_dir_shim(&new_regional_dir) {
+                    remove_dir_all_shim(&new_regional_dir)
+                        .chain_err(|| format!("while trying to replace {}", new_regional_dir.display()))?;
+                }
+                rename_shim(&regional_dir, &new_regional_dir)
+                    .chain_err(|| format!("while trying to keep the language pack {}", regional_dir.display()))?;
+            }
+        }
+        remove_dir_all_shim(&lang_dir).chain_err(|| format!("while trying to remove the old language pack {}", lang_dir.display()))?;
+    }
+    rename_shim(&new_dir, &lang_dir).chain_err(|| format!("while trying to install the language pack in {}", lang_dir.display()))?;
+    info!("Installed the '{}' language pack (version {}) in {}", pack.language, pack.version, lang_dir.display());
+    return Ok(lang_dir);
+}
+
+/// Install the newer versions of the installed language packs that are listed in the index file at `index_url`.
+/// Returns the languages that were updated.
+pub fn update_language_packs(index_url: &str, fetch: Fetch) -> Result<Vec<String>> {
+    let available = get_available_language_packs(index_url, fetch)?;
+    let mut updated = vec![];
+    for installed in get_installed_language_packs()? {
+        if let Some(pack) = available.iter().find(|pack| pack.language == installed.language) {
+            if crate::prefs::is_older_version(&installed.version, &pack.version) {
+                install_language_pack(pack, fetch)?;
+                updated.push(pack.language.clone());
+            }
+        }
+    }
+    return Ok(updated);
+}
+
+/// A [`Fetch`] function for `file:` URLs and paths.
+pub fn read_file_url(url: &str) -> Result<Vec<u8>> {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    if path.contains("://") {
+        bail!("'{}' is not a file -- the AT needs to provide a function to download it", url);
+    }
+    return std::fs::read(path).chain_err(|| format!("while trying to read {}", path));
+}
+
+fn parse_index(index: &str, index_url: &str) -> Result<Vec<LanguagePack>> {
+    let docs = YamlLoader::load_from_str(index).chain_err(|| "the language pack index isn't valid YAML")?;
+    let entries = match docs.first() {
+        None | Some(Yaml::Null) => return Ok( vec![] ),
+        Some(Yaml::Array(entries)) => entries,
+        Some(_) => bail!("the language pack index must be a list of language packs"),
+    };
+    return entries.iter()
+        .map(|entry| {
+            let field = |name: &str| -> Result<String> {
+                return match &entry[name] {
+                    Yaml::String(value) | Yaml::Real(value) => Ok(value.trim().to_string()),
+                    Yaml::Integer(value) => Ok(value.to_string()),
+                    _ => bail!("'{}' is missing from a language pack entry", name),
+                };
+            };
+            return Ok( LanguagePack {
+                language: field("language")?,
+                version: field("version")?,
+                url: resolve_url(index_url, &field("url")?),
+                sha256: field("sha256")?,
+            } );
+        })
+        .collect();
+}
+
+/// `url` relative to `base_url` (the URL of the index file)
+fn resolve_url(base_url: &str, url: &str) -> String {
+    if url.contains("://") || url.starts_with('/') || Path::new(url).is_absolute() {
+        return url.to_string();
+    }
+    return match base_url.rfind(['/', '\\']) {
+        Some(i) => format!("{}{}", &base_url[..=i], url),
+        None => url.to_string(),
+    };
+}
+
+fn unzip(zipped: &[u8], dir: &Path) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(zipped)).chain_err(|| "the language pack is not a zip file")?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).chain_err(|| "while trying to read the language pack")?;
+        let path = match file.enclosed_name() {
+            Some(path) => dir.join(path),
+            None => bail!("the language pack contains the file '{}' that is outside of the language's dir", file.name()),
+        };
+        if file.is_dir() {
+            create_dir_all_shim(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            create_dir_all_shim(parent)?;
+        }
+        let mut contents = vec![];
+        file.read_to_end(&mut contents).chain_err(|| format!("while trying to read {} from the language pack", file.name()))?;
+        write_shim(&path, &contents)?;
+    }
+    return Ok(());
+}
+
+/// The SHA-256 hash of `data` as lower case hex
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    return Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip_files(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zipped = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut zipped);
+            let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            for (name, contents) in files {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(contents.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        return zipped.into_inner();
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn index() {
+        let index = "- language: de\n  version: 0.2.3\n  url: de.zip\n  sha256: ABC\n- language: fr\n  version: 1\n  url: https://example.com/fr.zip\n  sha256: def\n";
+        let packs = parse_index(index, "https://example.com/packs/index.yaml").unwrap();
+        assert_eq!(packs[0], LanguagePack {
+            language: "de".to_string(), version: "0.2.3".to_string(), url: "https://example.com/packs/de.zip".to_string(), sha256: "ABC".to_string()
+        });
+        assert_eq!(packs[1].version, "1");
+        assert_eq!(packs[1].url, "https://example.com/fr.zip");
+        assert!(parse_index("- language: de\n  version: 0.2.3\n", "").is_err());
+    }
+
+    #[test]
+    fn install_and_update() {
+        let dir = crate::TestDir::new("language-packs");
+        let packs_dir = dir.join("Languages");
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(packs_dir.clone()));
+
+        let old_pack = zip_files(&[("ClearSpeak_Rules.yaml", "---\n"), ("SharedRules/default.yaml", "---\n")]);
+        let new_pack = zip_files(&[("ClearSpeak_Rules.yaml", "--- # new\n")]);
+        let write_index = |version: &str, pack: &[u8]| {
+            std::fs::write(dir.join("tlh.zip"), pack).unwrap();
+            std::fs::write(dir.join("index.yaml"),
+                format!("- language: tlh\n  version: {}\n  url: tlh.zip\n  sha256: {}\n", version, sha256_hex(pack))).unwrap();
+        };
+        let index_url = dir.join("index.yaml").to_str().unwrap().to_string();
+
+        write_index("1.0", &old_pack);
+        let available = get_available_language_packs(&index_url, &read_file_url).unwrap();
+        assert_eq!(get_installed_language_packs().unwrap(), vec![]);
+        let lang_dir = install_language_pack(&available[0], &read_file_url).unwrap();
+        assert_eq!(lang_dir, packs_dir.join("tlh"));
+        assert!(lang_dir.join("SharedRules/default.yaml").is_file());
+        let installed = get_installed_language_packs().unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].version, "1.0");
+
+        // nothing newer
+        assert_eq!(update_language_packs(&index_url, &read_file_url).unwrap(), Vec::<String>::new());
+        write_index("1.1", &new_pack);
+        assert_eq!(update_language_packs(&index_url, &read_file_url).unwrap(), vec!["tlh".to_string()]);
+        assert_eq!(std::fs::read_to_string(lang_dir.join("ClearSpeak_Rules.yaml")).unwrap(), "--- # new\n");
+        assert!(!lang_dir.join("SharedRules").exists());    // the old version was replaced
+        assert_eq!(get_installed_language_packs().unwrap()[0].version, "1.1");
+
+        // a corrupt download isn't installed
+        let mut pack = get_available_language_packs(&index_url, &read_file_url).unwrap().remove(0);
+        pack.sha256 = sha256_hex(b"something else");
+        assert!(install_language_pack(&pack, &read_file_url).is_err());
+        assert_eq!(get_installed_language_packs().unwrap()[0].version, "1.1");
+
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = None);
+    }
+
+    #[test]
+    fn regional_pack() {
+        let dir = crate::TestDir::new("regional-language-packs");
+        let packs_dir = dir.join("Languages");
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(packs_dir.clone()));
+
+        let install = |language: &str, version: &str, pack: &[u8]| {
+            let zip_file = dir.join(format!("{}.zip", language));
+            std::fs::write(&zip_file, pack).unwrap();
+            let pack = LanguagePack {
+                language: language.to_string(), version: version.to_string(),
+                url: zip_file.to_str().unwrap().to_string(), sha256: sha256_hex(pack)
+            };
+            return install_language_pack(&pack, &read_file_url).unwrap();
+        };
+        let regional_dir = install("tlh-qa", "1.0", &zip_files(&[("unicode.yaml", "--- # regional\n")]));
+        assert_eq!(regional_dir, packs_dir.join("tlh").join("qa"));
+        let lang_dir = install("tlh", "1.0", &zip_files(&[("ClearSpeak_Rules.yaml", "---\n")]));
+        assert_eq!(lang_dir, packs_dir.join("tlh"));
+        assert!(regional_dir.join("unicode.yaml").is_file());    // installing the language kept the regional pack
+
+        install("tlh", "1.1", &zip_files(&[("ClearSpeak_Rules.yaml", "--- # new\n"), ("qa/unicode.yaml", "--- # from tlh\n")]));
+        assert_eq!(std::fs::read_to_string(regional_dir.join("unicode.yaml")).unwrap(), "--- # regional\n");
+        install("tlh-qa", "1.1", &zip_files(&[("unicode.yaml", "--- # regional new\n")]));
+        assert_eq!(std::fs::read_to_string(lang_dir.join("ClearSpeak_Rules.yaml")).unwrap(), "--- # new\n");
+
+        let installed = get_installed_language_packs().unwrap();
+        assert_eq!(installed.iter().map(|pack| (pack.language.as_str(), pack.version.as_str())).collect::<Vec<_>>(),
+                   vec![("tlh", "1.1"), ("tlh-qa", "1.1")]);
+
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = None);
+    }
+
+    #[test]
+    fn installed_pack_is_spoken() {
+        use crate::interface::*;
+        // the files in 'dir' (and its subdirs) as (relative path, contents) -- the 'gb' region isn't included
+        fn read_lang_dir(dir: &Path, prefix: &str, files: &mut Vec<(String, String)>) {
+            for entry in std::fs::read_dir(dir).unwrap().flatten() {
+                let name = format!("{}{}", prefix, entry.file_name().to_str().unwrap());
+                if entry.path().is_dir() {
+                    if name != "gb" {
+                        read_lang_dir(&entry.path(), &(name + "/"), files);
+                    }
+                } else {
+                    files.push( (name, std::fs::read_to_string(entry.path()).unwrap()) );
+                }
+            }
+        }
+
+        let dir = crate::TestDir::new("spoken-language-packs");
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir.join("Languages")));
+        let install = |language: &str, files: &[(&str, &str)]| {
+            let pack = zip_files(files);
+            let zip_file = dir.join(format!("{}.zip", language));
+            std::fs::write(&zip_file, &pack).unwrap();
+            let pack = LanguagePack {
+                language: language.to_string(), version: "1.0".to_string(),
+                url: zip_file.to_str().unwrap().to_string(), sha256: sha256_hex(&pack)
+            };
+            install_language_pack(&pack, &read_file_url).unwrap();
+        };
+
+        // 'tlh' is 'en' with a different word for '+'
+        let mut en_files = vec![];
+        read_lang_dir(&Path::new(&crate::abs_rules_dir_path()).join("Languages").join("en"), "", &mut en_files);
+        let tlh_files: Vec<(&str, String)> = en_files.iter()
+            .map(|(name, contents)| (name.as_str(), if name == "unicode.yaml" {contents.replace("[t: \"plus\"]", "[t: \"qoch\"]")} else {contents.clone()}))
+            .collect();
+        install("tlh", &tlh_files.iter().map(|(name, contents)| (*name, contents.as_str())).collect::<Vec<_>>());
+        // a region of a language in the Rules dir
+        install("en-qa", &[("unicode.yaml", " - \"+\": [t: \"plus plus\"]\n")]);
+
+        set_rules_dir(crate::abs_rules_dir_path()).unwrap();
+        set_preference("SpeechStyle".to_string(), "ClearSpeak".to_string()).unwrap();
+        let speak = |language: &str| {
+            set_preference("Language".to_string(), language.to_string())?;
+            set_mathml("<math><mi>x</mi><mo>+</mo><mn>1</mn></math>".to_string())?;
+            return get_spoken_text();
+        };
+        let tlh_speech = speak("tlh");
+        let regional_speech = speak("en-qa");
+        let en_speech = speak("en");
+
+        crate::prefs::TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = None);
+        set_rules_dir(crate::abs_rules_dir_path()).unwrap();
+        assert_eq!(tlh_speech.unwrap(), "x qoch 1");
+        assert_eq!(regional_speech.unwrap(), "x plus plus 1");
+        assert_eq!(en_speech.unwrap(), "x plus 1");
+    }
+}
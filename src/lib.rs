@@ -29,6 +29,24 @@ pub mod errors {
         //     Io(std::io::Error);
         //     HttpRequest(reqwest::Error);
         // }
+        errors {
+            /// `set_preference` was given a name that isn't a known preference (e.g., a typo)
+            UnknownPreference(name: String) {
+                display("'{}' is not a known preference", name)
+            }
+            /// `set_preference` was given a value that isn't allowed for the preference; `allowed` describes the allowed values
+            InvalidPreferenceValue(name: String, value: String, allowed: String) {
+                display("'{}' is not a valid value for the preference '{}' -- it must be {}", value, name, allowed)
+            }
+            /// `set_preference` tried to change a preference the site administrator locked in system-prefs.yaml; `value` is the locked value
+            LockedPreference(name: String, value: String) {
+                display("The preference '{}' is locked to '{}' by the system-prefs.yaml file", name, value)
+            }
+            /// The Rules dir wasn't set (see `set_rules_dir`) or it couldn't be read; `reason` says why
+            NotInitialized(reason: String) {
+                display("MathCAT is not initialized: {}", reason)
+            }
+        }
     }
 }
 
@@ -57,10 +75,17 @@ mod xpath_functions;
 mod definitions;
 mod pretty_print;
 mod chemistry;
+pub mod document;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "language-packs")]
+pub mod language_packs;
 
 pub mod shim_filesystem; // really just for override_file_for_debugging_rules, but the config seems to throw it off
-pub use shim_filesystem::ZIPPED_RULE_FILES;
+pub use shim_filesystem::{ZIPPED_RULE_FILES, FileSystem};
 pub use interface::*;
+pub use prefs::{PreferenceListener, PreferenceDescription, PreferenceType, SharedPreferences};
+pub use speech::SpeechPostProcessor;
 
 #[cfg(test)]
 pub fn init_logger() {
@@ -81,6 +106,44 @@ pub fn abs_rules_dir_path() -> String {
                 .to_str().unwrap().to_string();
 }
 
+#[cfg(test)]
+/// An empty dir in the system temp dir for a test's files.
+/// It is removed when dropped, so a failing test doesn't leave files behind.
+pub struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// 'name' needs to be unique to the test because tests are run in parallel
+    pub fn new(name: &str) -> TestDir {
+        let dir = std::env::temp_dir().join(format!("MathCAT-{}-test-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);      // left over from a crashed run
+        std::fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = std::path::Path;
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TestDir {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 pub fn are_strs_canonically_equal(test: &str, target: &str) -> bool {
     use crate::interface::*;
@@ -224,6 +224,13 @@ fn main() {
     panic!("Error: exiting -- {}", errors_to_string(&e));
   };
 
+  // the log goes to stderr -- only color the tree if that is a terminal
+  let tree_format = FormatOptions{ color: std::io::IsTerminal::is_terminal(&std::io::stderr()), indent_width: 2 };
+  match get_debug_tree(true, tree_format) {
+    Ok(tree) => info!("Intent tree:\n{}", tree),
+    Err(e) => panic!("{}", errors_to_string(&e)),
+  }
+
   match get_spoken_text() {
     Ok(speech) => info!("Computed speech string:\n   '{}'", speech),
     Err(e) => panic!("{}", errors_to_string(&e)),
@@ -4,16 +4,18 @@
 
 use std::cell::{Ref, RefCell, RefMut};
 use sxd_xpath::{Context, Factory, Value};
-use sxd_document::dom::{Element, ChildOfElement};
+use sxd_document::dom::{Document, Element, ChildOfElement};
 use sxd_document::Package;
 
 use std::fmt;
 use crate::pretty_print::mml_to_string;
+use crate::prefs::SharedPreferences;
 use crate::speech::{NAVIGATION_RULES, CONCAT_INDICATOR, CONCAT_STRING, SpeechRules, SpeechRulesWithContext};
 #[cfg(not(target_family = "wasm"))]
 use std::time::{Instant};
 use crate::errors::*;
-use crate::canonicalize::as_element;
+use crate::canonicalize::{name, as_element, as_text, create_mathml_element, ANSWER_BLANK_ATTR};
+use regex::Regex;
 use phf::phf_set;
 
 
@@ -32,10 +34,13 @@ pub static NAV_COMMANDS: phf::Set<&str> = phf_set! {
     "MoveCellPrevious", "MoveCellNext", "MoveCellUp", "MoveCellDown", "MoveColumnStart", "MoveColumnEnd", 
     "ZoomIn", "ZoomOut", "ZoomOutAll", "ZoomInAll", 
     "MoveLastLocation", 
+    "MovePreviousSentence", "MoveNextSentence", "MovePreviousWord", "MoveNextWord",
     "ReadPrevious", "ReadNext", "ReadCurrent", "ReadCellCurrent", "ReadStart", "ReadEnd", "ReadLineStart", "ReadLineEnd", 
-    "DescribePrevious", "DescribeNext", "DescribeCurrent", 
+    "DescribePrevious", "DescribeNext", "DescribeCurrent", "DescribeSiblings", 
     "WhereAmI", "WhereAmIAll", 
     "ToggleZoomLockUp", "ToggleZoomLockDown", "ToggleSpeakMode", 
+    "ToggleGranularity", "GranularityCoarser", "GranularityFiner", "MovePreviousAtGranularity", "MoveNextAtGranularity",
+    "MovePreviousAnswerBlank", "MoveNextAnswerBlank",
     "Exit", 
     "MoveTo0","MoveTo1","MoveTo2","MoveTo3","MoveTo4","MoveTo5","MoveTo6","MoveTo7","MoveTo8","MoveTo9",
     "Read0","Read1","Read2","Read3","Read4","Read5","Read6","Read7","Read8","Read9",
@@ -107,7 +112,7 @@ impl fmt::Display for NavigationState {
 }
 
 impl NavigationState {
-    fn new() -> NavigationState {
+    pub(crate) fn new() -> NavigationState {
         return NavigationState {
             position_stack: Vec::with_capacity(1024),
             command_stack: Vec::with_capacity(1024),
@@ -191,6 +196,11 @@ impl NavigationState {
         }
     }
 
+    /// Make the node with the given `id` the current navigation node (the previous position can be restored with `MoveLastLocation`)
+    pub fn set_navigation_node(&mut self, id: &str) {
+        self.push(NavigationPosition{ current_node: id.to_string(), current_node_offset: 0 }, "None");
+    }
+
     fn init_navigation_context(&self, context: &mut Context, command: &'static str,
                                nav_state_top: Option<(&NavigationPosition, &'static str)>) {
         context.set_variable("NavCommand", command);
@@ -265,6 +275,133 @@ pub fn get_node_by_id<'a>(mathml: Element<'a>, id: &str) -> Option<Element<'a>>
     return None;
 }
 
+// Node paths are a bookmarkable alternative to ids (which are regenerated each time the MathML is set).
+// A path is a list of '/' separated steps from the root (e.g., "/fraction[1]/numerator/identifier[2]").
+// A child of an element whose children have fixed roles (e.g., mfrac) uses the role name.
+// Otherwise, the step is the kind of child along with its (1-based) position among siblings of the same kind.
+// "[1]" is optional when resolving a path. The root is "/".
+
+/// The roles of the children of elements whose children have a fixed meaning (extra children have no role)
+fn child_roles(name: &str) -> &'static [&'static str] {
+    return match name {
+        "mfrac" => &["numerator", "denominator"],
+        "msqrt" => &["radicand"],
+        "mroot" => &["radicand", "index"],
+        "msub" => &["base", "subscript"],
+        "msup" => &["base", "superscript"],
+        "msubsup" => &["base", "subscript", "superscript"],
+        "munder" => &["base", "underscript"],
+        "mover" => &["base", "overscript"],
+        "munderover" => &["base", "underscript", "overscript"],
+        "mlongdiv" => &["divisor", "quotient", "dividend"],
+        _ => &[],
+    };
+}
+
+/// The name used in a path step for a child that doesn't have a role
+pub(crate) fn node_kind(name: &str) -> &str {
+    return match name {
+        "mi" => "identifier",
+        "mn" => "number",
+        "mo" => "operator",
+        "mtext" => "text",
+        "mrow" => "group",
+        "mfrac" => "fraction",
+        "msqrt" => "sqrt",
+        "mroot" => "root",
+        "msub" | "msup" | "msubsup" | "mmultiscripts" => "scripted",
+        "munder" | "mover" | "munderover" => "underover",
+        "mtable" => "table",
+        "mtr" | "mlabeledtr" => "row",
+        "mtd" => "cell",
+        _ => name,
+    };
+}
+
+/// Return the path from 'mathml' (the root) to 'node'
+pub fn path_to_node(mathml: Element, node: Element) -> Result<String> {
+    let mut steps = vec![];
+    let mut node = node;
+    while node != mathml {
+        let parent = match node.parent().and_then(|parent| parent.element()) {
+            Some(parent) => parent,
+            None => bail!("path_to_node: node is not a descendant of the root:\n{}", mml_to_string(&node)),
+        };
+        let siblings: Vec<Element> = parent.children().into_iter().map(as_element).collect();
+        let position = siblings.iter().position(|&sibling| sibling == node).unwrap();
+        let roles = child_roles(name(&parent));
+        if position < roles.len() {
+            steps.push(roles[position].to_string());
+        } else {
+            let kind = node_kind(name(&node));
+            let index = siblings[roles.len()..position].iter()
+                    .filter(|&&sibling| node_kind(name(&sibling)) == kind)
+                    .count() + 1;
+            steps.push(format!("{}[{}]", kind, index));
+        }
+        node = parent;
+    }
+    steps.reverse();
+    return Ok( "/".to_string() + &steps.join("/") );
+}
+
+/// Return the node that 'path' refers to (see path_to_node)
+pub fn get_node_by_path<'a>(mathml: Element<'a>, path: &str) -> Result<Element<'a>> {
+    lazy_static! {
+        static ref STEP: Regex = Regex::new(r"^([\w-]+)(?:\[(\d+)\])?$").unwrap();
+    }
+    let path = path.trim();
+    if !path.starts_with('/') {
+        bail!("Node path '{}' must start with '/'", path);
+    }
+    let mut node = mathml;
+    for step in path[1..].split('/').filter(|step| !step.is_empty()) {
+        let captures = match STEP.captures(step) {
+            Some(captures) => captures,
+            None => bail!("Illegal step '{}' in node path '{}'", step, path),
+        };
+        let step_name = &captures[1];
+        let index = captures.get(2).map_or(Ok(1), |index| index.as_str().parse::<usize>())
+                .chain_err(|| format!("Illegal index in step '{}' in node path '{}'", step, path))?;
+        if index == 0 {
+            bail!("Indexes start at 1: illegal step '{}' in node path '{}'", step, path);
+        }
+        let children: Vec<Element> = if crate::xpath_functions::is_leaf(node) {
+                vec![]
+            } else {
+                node.children().into_iter().map(as_element).collect()
+            };
+        let roles = child_roles(name(&node));
+        let child = match roles.iter().position(|&role| role == step_name) {
+            Some(position) if index == 1 => children.get(position).copied(),
+            _ => children.iter().skip(roles.len())
+                    .filter(|&&child| node_kind(name(&child)) == step_name)
+                    .nth(index - 1)
+                    .copied(),
+        };
+        node = match child {
+            Some(child) => child,
+            None => bail!("Step '{}' in node path '{}' was not found", step, path),
+        };
+    }
+    return Ok(node);
+}
+
+/// Return the node that 'path' refers to or, if the path doesn't lead anywhere (e.g., the MathML was edited),
+/// the deepest node on the path that does exist (the root if even the first step doesn't exist)
+pub fn get_closest_node_by_path<'a>(mathml: Element<'a>, path: &str) -> Element<'a> {
+    let mut path = path.trim();
+    loop {
+        if let Ok(node) = get_node_by_path(mathml, path) {
+            return node;
+        }
+        match path.rfind('/') {
+            None | Some(0) => return mathml,
+            Some(i) => path = &path[..i],
+        }
+    }
+}
+
 // FIX: think of a better place to put this, and maybe a better interface
 pub fn context_get_variable<'c>(context: &Context<'c>, var_name: &str, mathml: Element<'c>) -> Result<(Option<String>, Option<f64>)> {
     // First return tuple value is string-value (if string, bool, or single node) or None
@@ -319,7 +456,20 @@ pub fn context_get_variable<'c>(context: &Context<'c>, var_name: &str, mathml: E
 pub fn do_mathml_navigate_key_press(mathml: Element,
             key: usize, shift_key: bool, control_key: bool, alt_key: bool, meta_key: bool) -> Result<String> {
     let (command, param) = key_press_to_command_and_param(key, shift_key, control_key, alt_key, meta_key)?;
-    return do_navigate_command_and_param(mathml, command, param);
+    let granularity = crate::prefs::read_prefs(&crate::prefs::PreferenceManager::get())?.get_user_prefs().to_string("NavGranularity");
+    if granularity == "Off" {
+        return do_navigate_command_and_param(mathml, command, param);
+    }
+
+    // like text review in a screen reader: up/down change the granularity and left/right move by it
+    let nav_command = match navigation_command_string(command, param) {
+        "ZoomOut" => "GranularityCoarser",
+        "ZoomIn" => "GranularityFiner",
+        "MovePrevious" => "MovePreviousAtGranularity",
+        "MoveNext" => "MoveNextAtGranularity",
+        nav_command => nav_command,
+    };
+    return do_navigate_command_string(mathml, nav_command);
 }
 
 fn do_navigate_command_and_param(mathml: Element, command: NavigationCommand, param: NavigationParam) -> Result<String> {
@@ -328,7 +478,7 @@ fn do_navigate_command_and_param(mathml: Element, command: NavigationCommand, pa
 
 pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) -> Result<String> {   
     // first check to see if nav file has been changed -- don't bother checking in loop below
-    SpeechRules::update();
+    SpeechRules::update()?;
     NAVIGATION_RULES.with(|rules| { rules.borrow_mut().read_files() })?;
 
     if mathml.children().is_empty() {
@@ -346,13 +496,43 @@ pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) ->
             }, "None")
         };
 
+        // sentence/word navigation is done here (not by the rules) -- outside of text or past its ends, it is a normal move
+        let nav_command = match nav_command {
+            "MovePreviousSentence" | "MoveNextSentence" | "MovePreviousWord" | "MoveNextWord" => {
+                if let Some(speech) = move_within_text(mathml, nav_command, &mut nav_state)? {
+                    return Ok(speech);
+                }
+                if nav_command.starts_with("MoveNext") {"MoveNext"} else {"MovePrevious"}
+            },
+            _ => nav_command,
+        };
+
         return NAVIGATION_RULES.with(|rules| {
             let rules = rules.borrow();
             let new_package = Package::new();
             let mut rules_with_context = SpeechRulesWithContext::new(&rules, new_package.as_document(), "".to_string()); 
+
+            // moves at a granularity are done here -- the rules speak what happens at the start/end of the math
+            if nav_command == "MovePreviousAtGranularity" || nav_command == "MoveNextAtGranularity" {
+                let granularity = crate::prefs::read_prefs(&rules.pref_manager)?.get_user_prefs().to_string("NavGranularity");
+                if let Some(speech) = move_at_granularity(mathml, nav_command, &granularity, &mut nav_state, &mut rules_with_context)? {
+                    return Ok(speech);
+                }
+            }
+            if nav_command == "MovePreviousAnswerBlank" || nav_command == "MoveNextAnswerBlank" {
+                if let Some(speech) = move_to_answer_blank(mathml, nav_command, &mut nav_state, &mut rules_with_context)? {
+                    return Ok(speech);
+                }
+            }
+            // the columns of elementary math (mstack and mlongdiv) are digits, so moving in them is done here
+            if matches!(nav_command, "MoveCellPrevious" | "MoveCellNext" | "MoveCellUp" | "MoveCellDown") {
+                if let Some(speech) = move_in_elementary_math(mathml, nav_command, &mut nav_state, &mut rules_with_context)? {
+                    return Ok(speech);
+                }
+            }
             
             // if nav_state.mode.is_empty() {
-                nav_state.mode = rules.pref_manager.as_ref().borrow().get_user_prefs().to_string("NavMode");
+                nav_state.mode = crate::prefs::read_prefs(&rules.pref_manager)?.get_user_prefs().to_string("NavMode");
             // }
 
             nav_state.init_navigation_context(rules_with_context.get_context(), nav_command, nav_state.top());
@@ -410,12 +590,17 @@ pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) ->
 
         let raw_speech_string = rules_with_context.match_pattern::<String>(start_node)
                     .chain_err(|| "Pattern match/replacement failure during math navigation!")?;
-        let speech = rules.pref_manager.borrow().get_tts()
-                    .merge_pauses(crate::speech::remove_optional_indicators(
+        let speech = {
+            let pref_manager = crate::prefs::read_prefs(&rules.pref_manager)?;
+            let tts = pref_manager.get_tts();
+            let speech = tts.merge_pauses(crate::speech::remove_optional_indicators(
                         &raw_speech_string.replace(CONCAT_STRING, "")
                                                 .replace(CONCAT_INDICATOR, "")                            
                                     )
                     .trim());
+            let speech = crate::speech::post_process_speech(&tts, speech);
+            tts.apply_at_profile(&pref_manager, speech)?
+        };
         // debug!("Nav Speech: {}", speech);
 
         // FIX: add things that need to do a speech replacement based on some marker for "where am i" and others that loop ([Speak: id])???
@@ -424,13 +609,23 @@ pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) ->
         // transfer some values that might have been set into the prefs
         let context = rules_with_context.get_context();     // need to recompute or we have a multiple borrow problem
         nav_state.mode = context_get_variable(context, "NavMode", mathml)?.0.unwrap();
-        rules.pref_manager.as_ref().borrow_mut().set_user_prefs("NavMode", &nav_state.mode);
+        let granularity = context_get_variable(context, "NavGranularity", mathml)?.0.unwrap();
+        {
+            let mut pref_manager = crate::prefs::write_prefs(&rules.pref_manager)?;
+            pref_manager.set_user_prefs("NavMode", &nav_state.mode);
+            pref_manager.set_user_prefs("NavGranularity", &granularity);
+        }
 
         let nav_position = match context_get_variable(context, "NavNode", mathml)?.0 {
             None => NavigationPosition::default(),
-            Some(node) => NavigationPosition {
-                current_node: node,
-                current_node_offset: context_get_variable(context, "NavNodeOffset", mathml)?.1.unwrap() as usize
+            Some(node) => {
+                // the offset is only meaningful for the node it was set for (e.g., a sentence in an mtext)
+                let offset = if node == start_node.attribute_value("id").unwrap_or_default() {
+                    context_get_variable(context, "NavNodeOffset", mathml)?.1.unwrap() as usize
+                } else {
+                    0
+                };
+                NavigationPosition { current_node: node, current_node_offset: offset }
             }
         };
 
@@ -466,7 +661,15 @@ pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) ->
         let nav_mathml = get_node_by_id(mathml, &nav_position.current_node);
         if nav_mathml.is_some() && context_get_variable(context, "SpeakExpression", mathml)?.0.unwrap() == "true" {
             // Speak/Overview of where we landed (if we are supposed to speak it)
-            let node_speech = speak(&mut rules_with_context, nav_mathml.unwrap(), use_read_rules)?;
+            let nav_mathml = nav_mathml.unwrap();
+            let sentences = if name(&nav_mathml) == "mtext" {sentence_spans(as_text(nav_mathml))} else {vec![]};
+            let node_speech = if sentences.len() > 1 && use_read_rules &&
+                                 (nav_command.starts_with("Move") || nav_command.starts_with("Zoom")) {
+                // don't read a whole paragraph at each stop -- MoveNextSentence reads the rest
+                speak_text_span(as_text(nav_mathml), sentences[0])?
+            } else {
+                speak(&mut rules_with_context, nav_mathml, use_read_rules)?
+            };
             // debug!("node_speech: '{}'", node_speech);
             if node_speech.is_empty() {
                 // try again in loop
@@ -505,30 +708,434 @@ pub fn do_navigate_command_string(mathml: Element, nav_command: &'static str) ->
     }
 }
 
+/// Returns the (start, end) char offsets of the sentences in 'text'.
+/// A sentence ends with '.', '?', or '!' (possibly followed by closing quotes/parens) that is followed by whitespace
+///   and then something that isn't a lower case letter (so abbreviations such as "p.m. today" don't end a sentence).
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = vec![];
+    let mut start = None;
+    let mut i = 0;
+    while i < chars.len() {
+        if start.is_none() && !chars[i].is_whitespace() {
+            start = Some(i);
+        }
+        if let Some(sentence_start) = start {
+            if matches!(chars[i], '.' | '?' | '!') {
+                let mut end = i + 1;
+                while end < chars.len() && matches!(chars[end], '.' | '?' | '!' | '"' | '\'' | ')' | '”' | '’') {
+                    end += 1;
+                }
+                let next = chars[end..].iter().find(|ch| !ch.is_whitespace());
+                if end == chars.len() || (chars[end].is_whitespace() && !next.is_some_and(|ch| ch.is_lowercase())) {
+                    spans.push( (sentence_start, end) );
+                    start = None;
+                }
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if let Some(sentence_start) = start {
+        let end = chars.len() - chars.iter().rev().take_while(|ch| ch.is_whitespace()).count();
+        spans.push( (sentence_start, end) );
+    }
+    return spans;
+}
+
+/// Returns the (start, end) char offsets of the (whitespace separated) words in 'text'
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = None;
+    for (i, ch) in text.chars().enumerate() {
+        match (start, ch.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(word_start), true) => {
+                spans.push( (word_start, i) );
+                start = None;
+            },
+            _ => (),
+        }
+    }
+    if let Some(word_start) = start {
+        spans.push( (word_start, text.chars().count()) );
+    }
+    return spans;
+}
+
+/// Move to the previous/next sentence or word in the current node if it is an 'mtext'.
+/// Returns the speech for the new sentence/word or None if the current node isn't text or there is nothing more in that direction.
+fn move_within_text(mathml: Element, nav_command: &'static str, nav_state: &mut NavigationState) -> Result<Option<String>> {
+    let (node, offset) = nav_state.get_navigation_mathml(mathml)?;
+    if name(&node) != "mtext" {
+        return Ok(None);
+    }
+    let text = as_text(node);
+    let spans = if nav_command.ends_with("Sentence") {sentence_spans(text)} else {word_spans(text)};
+    let current = spans.iter().rposition(|&(start, _)| start <= offset);
+    let new_index = if nav_command.starts_with("MoveNext") {
+        current.map_or(0, |i| i + 1)
+    } else {
+        match current {
+            Some(i) if i > 0 => i - 1,
+            _ => return Ok(None),
+        }
+    };
+    if new_index >= spans.len() {
+        return Ok(None);
+    }
+    let id = node.attribute_value("id").unwrap().to_string();
+    nav_state.push(NavigationPosition{ current_node: id, current_node_offset: spans[new_index].0 }, nav_command);
+    return Ok( Some(speak_text_span(text, spans[new_index])?) );
+}
+
+/// Speak the part of 'text' given by the (start, end) char offsets in 'span'
+fn speak_text_span(text: &str, span: (usize, usize)) -> Result<String> {
+    return speak_leaf_span("mtext", text, span);
+}
+
+/// Speak the part of 'text' given by the (start, end) char offsets in 'span' as if it were the contents of a 'leaf_name' element
+fn speak_leaf_span(leaf_name: &str, text: &str, span: (usize, usize)) -> Result<String> {
+    let text: String = text.chars().skip(span.0).take(span.1 - span.0).collect();
+    let package = Package::new();
+    let doc = package.as_document();
+    let math = create_mathml_element(&doc, "math");
+    let leaf = create_mathml_element(&doc, leaf_name);
+    leaf.set_text(&text);
+    math.append_child(leaf);
+    doc.root().append_child(math);
+    return crate::speech::speak_intent(math);
+}
+
+/// Operators that separate the terms of an expression for "Term" granularity
+static TERM_SEPARATORS: phf::Set<&str> = phf_set! {
+    "+", "-", "−", "±", "∓", "=", "≠", "<", ">", "≤", "≥", "≈", "≡", "→", ",", ";",
+};
+
+/// A place that a move at a granularity can stop at: the node, the char offset in it, and the (start, end) char offsets in the math
+struct GranularityStop<'a> {
+    node: Element<'a>,
+    offset: usize,
+    start: usize,
+    end: usize,
+}
+
+/// Move to the previous/next character, token, or term ('granularity' is the 'NavGranularity' pref).
+/// Invisible operators and white space are skipped.
+/// Returns the speech for the new location or None if there is nothing more in that direction or 'granularity' is "Full".
+fn move_at_granularity<'c, 'm:'c>(mathml: Element<'m>, nav_command: &'static str, granularity: &str,
+            nav_state: &mut NavigationState, rules_with_context: &mut SpeechRulesWithContext<'c, '_, 'm>) -> Result<Option<String>> {
+    let leaves = leaf_extents(mathml);
+    let extent = |node: Element<'m>| -> (usize, usize) {
+        let mut node_leaves = leaves.iter().filter(|(leaf, _, _)| is_ancestor_or_self(node, *leaf));
+        return match node_leaves.next() {
+            None => (0, 0),
+            Some(&(_, start, end)) => (start, node_leaves.next_back().map_or(end, |&(_, _, end)| end)),
+        };
+    };
+
+    let mut stops = vec![];
+    match granularity {
+        "Character" | "Token" => {
+            for &(leaf, start, end) in &leaves {
+                let text = as_text(leaf);
+                if name(&leaf) == "mo" && text.chars().all(|ch| ('\u{2061}'..='\u{2064}').contains(&ch)) {
+                    continue;
+                }
+                if granularity == "Token" {
+                    if !text.trim().is_empty() {
+                        stops.push( GranularityStop{ node: leaf, offset: 0, start, end } );
+                    }
+                } else {
+                    for (i, ch) in text.chars().enumerate() {
+                        if !ch.is_whitespace() {
+                            stops.push( GranularityStop{ node: leaf, offset: i, start: start + i, end: start + i + 1 } );
+                        }
+                    }
+                }
+            }
+        },
+        "Term" => {
+            let mut terms = vec![];
+            term_nodes(mathml, &mut terms);
+            for term in terms {
+                let (start, end) = extent(term);
+                stops.push( GranularityStop{ node: term, offset: 0, start, end } );
+            }
+        },
+        _ => return Ok(None),       // "Full" -- there is only one place to be
+    }
+
+    // the current location is a char in a leaf for "Character" granularity, otherwise the whole node
+    // if nothing has been selected yet (the 'math' element), we are before the first stop
+    let (node, offset) = nav_state.get_navigation_mathml(mathml)?;
+    let (current_start, current_end) = if name(&node) == "math" {
+        (0, 0)
+    } else {
+        match leaves.iter().find(|(leaf, _, _)| *leaf == node) {
+            Some(&(_, start, _)) if granularity == "Character" => (start + offset, start + offset + 1),
+            _ => extent(node),
+        }
+    };
+    let new_stop = if nav_command == "MoveNextAtGranularity" {
+        stops.iter().find(|stop| stop.start >= current_end && stop.end > current_start)
+    } else {
+        stops.iter().rev().find(|stop| stop.end <= current_start && stop.start < current_end)
+    };
+    let new_stop = match new_stop {
+        None => return Ok(None),
+        Some(stop) => stop,
+    };
+
+    let id = new_stop.node.attribute_value("id").unwrap().to_string();
+    nav_state.push(NavigationPosition{ current_node: id, current_node_offset: new_stop.offset }, nav_command);
+    let speech = if granularity == "Character" {
+        speak_leaf_span(name(&new_stop.node), as_text(new_stop.node), (new_stop.offset, new_stop.offset + 1))?
+    } else {
+        speak(rules_with_context, new_stop.node, true)?
+    };
+    return Ok( Some(speech) );
+
+    /// The token elements in document order along with their (start, end) char offsets in the math
+    fn leaf_extents(mathml: Element) -> Vec<(Element, usize, usize)> {
+        let mut leaves = vec![];
+        add_leaves(mathml, &mut leaves);
+        let mut start = 0;
+        return leaves.into_iter()
+                .map(|leaf| {
+                    let end = start + as_text(leaf).chars().count();
+                    let extent = (leaf, start, end);
+                    start = end;
+                    extent
+                })
+                .collect();
+
+        fn add_leaves<'a>(node: Element<'a>, leaves: &mut Vec<Element<'a>>) {
+            if matches!(name(&node), "mi" | "mn" | "mo" | "mtext" | "ms") {
+                leaves.push(node);
+            } else {
+                for child in node.children() {
+                    if let ChildOfElement::Element(child) = child {
+                        add_leaves(child, leaves);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The terms and the operators that separate them (e.g., "2x", "+", "3y", "=", "7") in document order
+    fn term_nodes<'a>(node: Element<'a>, terms: &mut Vec<Element<'a>>) {
+        let children: Vec<Element> = node.children().into_iter()
+                .filter_map(|child| child.element())
+                .collect();
+        let is_separator = |child: &Element| name(child) == "mo" && TERM_SEPARATORS.contains(as_text(*child));
+        if matches!(name(&node), "math" | "mrow") && children.iter().any(is_separator) {
+            for child in children {
+                if is_separator(&child) {
+                    terms.push(child);
+                } else {
+                    term_nodes(child, terms);
+                }
+            }
+        } else if name(&node) == "math" && children.len() == 1 {
+            term_nodes(children[0], terms);
+        } else {
+            terms.push(node);
+        }
+    }
+}
+
+fn is_ancestor_or_self(ancestor: Element, node: Element) -> bool {
+    let mut node = node;
+    loop {
+        if node == ancestor {
+            return true;
+        }
+        match node.parent().and_then(|parent| parent.element()) {
+            None => return false,
+            Some(parent) => node = parent,
+        }
+    }
+}
+
+/// Move to the previous/next answer blank (an editable slot in an interactive widget -- see [`crate::canonicalize::is_answer_blank`]).
+/// Like a form field, an answer blank is spoken with its index (e.g., "answer blank 2").
+/// Returns the speech for the answer blank or None if there isn't one in that direction.
+fn move_to_answer_blank<'c, 'm:'c>(mathml: Element<'m>, nav_command: &'static str,
+            nav_state: &mut NavigationState, rules_with_context: &mut SpeechRulesWithContext<'c, '_, 'm>) -> Result<Option<String>> {
+    let mut nodes = vec![];
+    add_nodes(mathml, &mut nodes);
+    let (current_node, _) = nav_state.get_navigation_mathml(mathml)?;
+    let current_start = nodes.iter().position(|&(node, _)| node == current_node).unwrap_or(0);
+    let mut blanks = nodes.iter().enumerate()
+            .filter(|(_, (node, _))| node.attribute(ANSWER_BLANK_ATTR).is_some());
+    let new_blank = if nav_command == "MoveNextAnswerBlank" {
+        blanks.find(|&(start, _)| start > current_start)
+    } else {
+        blanks.rev().find(|&(_, &(_, end))| end <= current_start)     // skip an answer blank the current node is in
+    };
+    let new_blank = match new_blank {
+        None => return Ok(None),
+        Some( (_, &(blank, _)) ) => blank,
+    };
+
+    let id = new_blank.attribute_value("id").unwrap().to_string();
+    nav_state.push(NavigationPosition{ current_node: id, current_node_offset: 0 }, nav_command);
+    return Ok( Some(speak(rules_with_context, new_blank, true)?) );
+
+    /// The nodes in document order along with the index just past their descendants
+    fn add_nodes<'a>(node: Element<'a>, nodes: &mut Vec<(Element<'a>, usize)>) {
+        let start = nodes.len();
+        nodes.push( (node, start) );
+        for child in node.children() {
+            if let ChildOfElement::Element(child) = child {
+                add_nodes(child, nodes);
+            }
+        }
+        nodes[start].1 = nodes.len();
+    }
+}
+
+/// Move to the previous/next column or to the row above/below in elementary math (an mstack or the rows of an mlongdiv).
+/// Each digit of a number is in its own column and the rows are aligned on the right (a carry is above the digit it is carried to).
+/// Rows without anything in the column (e.g., an msline) are skipped when moving up/down.
+/// Returns the speech for the new location or None if the current node isn't in a row of elementary math or there is nothing in that direction.
+fn move_in_elementary_math<'c, 'm:'c>(mathml: Element<'m>, nav_command: &'static str,
+            nav_state: &mut NavigationState, rules_with_context: &mut SpeechRulesWithContext<'c, '_, 'm>) -> Result<Option<String>> {
+    let (node, offset) = nav_state.get_navigation_mathml(mathml)?;
+    let rows = match elementary_math_rows(node) {
+        None => return Ok(None),
+        Some(rows) => rows,
+    };
+    let row_index = match rows.iter().position(|row| is_ancestor_or_self(*row, node)) {
+        None => return Ok(None),        // the divisor of an mlongdiv
+        Some(i) => i,
+    };
+    let cells = row_cells(rows[row_index]);
+    let position = cells.iter().position(|cell| *cell == Some( (node, offset) ))
+            .or_else(|| cells.iter().position(|cell| matches!(cell, Some( (leaf, _) ) if is_ancestor_or_self(node, *leaf))));
+    let position = match position {
+        None => return Ok(None),
+        Some(position) => position,
+    };
+
+    // columns are counted from the right because that's where the rows line up
+    let column = cells.len() - 1 - position;
+    let cell_in_column = |row: &Element<'m>| -> Option<(Element<'m>, usize)> {
+        let cells = row_cells(*row);
+        return if column < cells.len() {cells[cells.len() - 1 - column]} else {None};
+    };
+    let new_cell = match nav_command {
+        "MoveCellPrevious" => cells[..position].iter().rev().find_map(|cell| *cell),
+        "MoveCellNext" => cells[position+1..].iter().find_map(|cell| *cell),
+        "MoveCellUp" => rows[..row_index].iter().rev().find_map(cell_in_column),
+        _ => rows[row_index+1..].iter().find_map(cell_in_column),
+    };
+    let (leaf, offset) = match new_cell {
+        None => return Ok(None),
+        Some(cell) => cell,
+    };
+
+    let id = leaf.attribute_value("id").unwrap().to_string();
+    nav_state.push(NavigationPosition{ current_node: id, current_node_offset: offset }, nav_command);
+    let speech = if name(&leaf) == "mn" && as_text(leaf).chars().count() > 1 {
+        speak_leaf_span("mn", as_text(leaf), (offset, offset + 1))?
+    } else {
+        speak(rules_with_context, leaf, true)?
+    };
+    return Ok( Some(speech) );
+
+    /// The rows of the elementary math 'node' is in (None if it isn't in elementary math).
+    /// The divisor of an mlongdiv is to the left of the other rows, so it isn't one of them.
+    fn elementary_math_rows(node: Element) -> Option<Vec<Element>> {
+        let mut node = node;
+        loop {
+            let parent = node.parent()?.element()?;
+            match name(&parent) {
+                "mstack" => {
+                    let mut rows = vec![];
+                    for child in parent.children().into_iter().filter_map(|child| child.element()) {
+                        if name(&child) == "msgroup" {
+                            rows.extend(child.children().into_iter().filter_map(|child| child.element()));
+                        } else {
+                            rows.push(child);
+                        }
+                    }
+                    return Some(rows);
+                },
+                "mlongdiv" => return Some( parent.children().into_iter().filter_map(|child| child.element()).skip(1).collect() ),
+                "math" => return None,
+                _ => node = parent,
+            }
+        }
+    }
+
+    /// The (leaf, char offset) in each column of 'row' from left to right (None for an empty column).
+    /// The digits of a number are in separate columns; anything else is a single column.
+    fn row_cells(row: Element) -> Vec<Option<(Element, usize)>> {
+        let mut cells = vec![];
+        match name(&row) {
+            "msline" => (),
+            "mscarries" => {
+                for carry in row.children().into_iter().filter_map(|child| child.element()) {
+                    let carry = if name(&carry) == "mscarry" {carry.children().into_iter().find_map(|child| child.element())} else {Some(carry)};
+                    cells.push( carry.filter(|carry| name(carry) != "none").map(|carry| (carry, 0)) );
+                }
+            },
+            "msrow" => {
+                for child in row.children().into_iter().filter_map(|child| child.element()) {
+                    add_cells(child, &mut cells);
+                }
+            },
+            _ => add_cells(row, &mut cells),
+        }
+        return cells;
+
+        fn add_cells<'a>(node: Element<'a>, cells: &mut Vec<Option<(Element<'a>, usize)>>) {
+            match name(&node) {
+                "mn" => cells.extend(as_text(node).chars().enumerate()
+                                        .filter(|(_, ch)| !ch.is_whitespace())
+                                        .map(|(i, _)| Some( (node, i) ))),
+                "none" => cells.push(None),
+                _ => cells.push(Some( (node, 0) )),
+            }
+        }
+    }
+}
+
 fn speak<'r, 'c, 's:'c, 'm:'c>(rules_with_context: &'r mut SpeechRulesWithContext<'c,'s,'m>, mathml: Element<'c>, full_read: bool) -> Result<String> {
+    let pref_manager = rules_with_context.get_pref_manager().clone();
     if full_read {
-        // Some rules require context to speak correctly -- invisible times is a particularly important one
-        // Most only require knowing siblings
-        // FIX: check the rules -- grandparents might be needed
-        // Here, we temporarily mark the current node, get the intent reading of the parent and then find the node in the parent.
-        // If it isn't present, we skip context and retry
-        mathml.set_attribute_value(MARKED_NODE, "nav");
-        let context_mathml = if let Some(parent) = mathml.parent() {parent.element().unwrap()} else {mathml};
-        // debug!("context_mathml: {}", mml_to_string(&context_mathml));
-        let intent = crate::speech::intent_from_mathml(context_mathml, rules_with_context.get_document())?;
-        debug!("intent: {}", mml_to_string(&intent));
-        let intent = if let Some(found) = find_marked_node(intent) {
-            // debug!("Found node: {}", mml_to_string(&found));
-            found
-        } else {
-            crate::speech::intent_from_mathml(mathml, rules_with_context.get_document())?
-        };
-        mathml.remove_attribute(MARKED_NODE);
-        return crate::speech::speak_intent(intent);
+        return speak_in_context(mathml, rules_with_context.get_document(), &pref_manager);
     } else {
         // FIX: overview not implemented
-        return crate::speech::overview_mathml(mathml);
+        return crate::speech::overview_mathml_with_prefs(mathml, &pref_manager);
     }
+}
+
+/// Speak 'mathml' (a node in the MathML tree) taking into account the context it is in.
+/// The preferences in `pref_manager` are used (see [`crate::speech::speak_intent_with_prefs`]).
+pub fn speak_in_context(mathml: Element, doc: Document, pref_manager: &SharedPreferences) -> Result<String> {
+    // Some rules require context to speak correctly -- invisible times is a particularly important one
+    // Most only require knowing siblings
+    // FIX: check the rules -- grandparents might be needed
+    // Here, we temporarily mark the current node, get the intent reading of the parent and then find the node in the parent.
+    // If it isn't present, we skip context and retry
+    mathml.set_attribute_value(MARKED_NODE, "nav");
+    let context_mathml = mathml.parent().and_then(|parent| parent.element()).unwrap_or(mathml);
+    // debug!("context_mathml: {}", mml_to_string(&context_mathml));
+    let intent = crate::speech::intent_from_mathml_with_prefs(context_mathml, doc, pref_manager);
+    mathml.remove_attribute(MARKED_NODE);
+    let intent = intent?;
+    debug!("intent: {}", mml_to_string(&intent));
+    let intent = if let Some(found) = find_marked_node(intent) {
+        // debug!("Found node: {}", mml_to_string(&found));
+        found
+    } else {
+        crate::speech::intent_from_mathml_with_prefs(mathml, doc, pref_manager)?
+    };
+    return crate::speech::speak_intent_with_prefs(intent, pref_manager);
 
     fn find_marked_node<'m>(intent: Element<'m>) -> Option<Element<'m>> {
         if intent.attribute(MARKED_NODE).is_some() {
@@ -563,6 +1170,7 @@ const VK_HOME: usize = 0x24;
 const VK_END: usize = 0x23;
 const VK_BACK: usize = 0x08;
 const VK_ESCAPE: usize = 0x1B;
+const VK_TAB: usize = 0x09;
 
 // Utilities that returns one of four commands/params based on shift/control key combinations
 
@@ -576,6 +1184,8 @@ enum NavigationCommand {
     Locate,
     ChangeNavMode,
     ToggleSpeakMode,
+    ToggleGranularity,
+    MoveAnswerBlank,
     SetPlacemarker,
     Exit,
     Last,
@@ -682,7 +1292,7 @@ fn key_press_to_command_and_param(
             param =   choose_param(  shift_key, control_key, NavigationParam::Next, NavigationParam::Next,          NavigationParam::CellDown, NavigationParam::End);
             },
         VK_RETURN => {
-            command = choose_command(shift_key, control_key, NavigationCommand::Locate,  NavigationCommand::Last, NavigationCommand::Locate, NavigationCommand::Last);
+            command = choose_command(shift_key, control_key, NavigationCommand::Locate,  NavigationCommand::ToggleGranularity, NavigationCommand::Locate, NavigationCommand::Last);
             param =   choose_param(  shift_key, control_key, NavigationParam::Previous,NavigationParam::Last, NavigationParam::Last,    NavigationParam::Last);
             },
         VK_SPACE => {
@@ -702,6 +1312,10 @@ fn key_press_to_command_and_param(
             command = NavigationCommand::MoveLastLocation;
             param = NavigationParam::Last;
             },
+        VK_TAB => {
+            command = NavigationCommand::MoveAnswerBlank;
+            param = if shift_key {NavigationParam::Previous} else {NavigationParam::Next};
+            },
         VK_ESCAPE => {
             command = NavigationCommand::Exit;
             param = NavigationParam::Last;
@@ -822,6 +1436,16 @@ fn navigation_command_string(command: NavigationCommand, param: NavigationParam)
         NavigationCommand::ToggleSpeakMode => {
             return "ToggleSpeakMode";
         },
+        NavigationCommand::ToggleGranularity => {
+            return "ToggleGranularity";
+        },
+        NavigationCommand::MoveAnswerBlank => {
+            if param == NavigationParam::Previous {
+                return "MovePreviousAnswerBlank";
+            } else if param == NavigationParam::Next {
+                return "MoveNextAnswerBlank";
+            }
+        },
         NavigationCommand::SetPlacemarker => {
             if param < NavigationParam::Placemarker0 || param > NavigationParam::Placemarker9 {
                 panic!("Internal Error: Found illegal value for param of NavigationCommand::SetPlacemarker");
@@ -885,6 +1509,84 @@ mod tests {
         });
     }
     
+    #[test]
+    fn long_division() -> Result<()> {
+        let mathml_str = "<math id='math'><mlongdiv id='div'>
+                <mn id='divisor'>5</mn><mn id='quotient'>25</mn><mn id='dividend'>125</mn>
+                <msrow id='step1'><mo id='minus1'>-</mo><mn id='ten'>10</mn></msrow>
+                <msline/>
+                <msrow id='step2'><mn id='twenty-five'>25</mn></msrow>
+            </mlongdiv></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert!(test_command("ZoomIn", mathml, "divisor").contains("divisor"));
+            assert!(test_command("MoveNext", mathml, "quotient").contains("quotient"));
+            assert!(test_command("MoveNext", mathml, "dividend").contains("dividend"));
+            assert!(test_command("MoveNext", mathml, "step1").contains("step 1"));
+            // the msline is skipped
+            assert!(test_command("MoveNext", mathml, "step2").contains("step 2"));
+            return Ok( () );
+        });
+    }
+
+    #[test]
+    fn node_paths() -> Result<()> {
+        let mathml_str = "<math id='math'><mfrac id='mfrac'>
+                <mrow id='num'><mi id='a'>a</mi><mo id='plus'>+</mo><mi id='b'>b</mi></mrow>
+                <msup id='msup'><mi id='base'>x</mi><mn id='exp'>2</mn></msup>
+            </mfrac></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        assert_eq!(get_node_path("math".to_string())?, "/");
+        assert_eq!(get_node_path("b".to_string())?, "/fraction[1]/numerator/identifier[2]");
+        assert_eq!(get_node_path("exp".to_string())?, "/fraction[1]/denominator/superscript");
+        assert_eq!(set_navigation_node_from_path("/fraction/numerator/identifier[2]".to_string())?, "b");
+        assert_eq!(get_navigation_node_path()?, "/fraction[1]/numerator/identifier[2]");
+        MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            test_command("MovePrevious", mathml, "plus");
+        });
+        assert_eq!(get_navigation_node_path()?, "/fraction[1]/numerator/operator[1]");
+        assert!(set_navigation_node_from_path("/fraction[1]/numerator/identifier[3]".to_string()).is_err());
+        assert!(set_navigation_node_from_path("/fraction[1]/denominator/base[0]".to_string()).is_err());
+        assert!(set_navigation_node_from_path("fraction[1]".to_string()).is_err());
+        return Ok( () );
+    }
+
+    #[test]
+    fn update_mathml_keeps_position() -> Result<()> {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        // ids from the host are used when they are there
+        set_mathml("<math id='math'><mrow id='r'><mi id='a'>a</mi><mo id='plus'>+</mo><mi id='b'>b</mi></mrow></math>".to_string())?;
+        set_navigation_node_from_path(get_node_path("b".to_string())?)?;
+        update_mathml("<math id='math'><mrow id='r'><mi id='b'>b</mi><mo id='plus'>+</mo><mi id='a'>a</mi></mrow></math>".to_string())?;
+        assert_eq!(get_navigation_mathml_id()?.0, "b");
+
+        // otherwise, the node at the same place is used
+        set_mathml("<math><mfrac><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mi>c</mi></mfrac></math>".to_string())?;
+        set_navigation_node_from_path("/fraction/numerator/identifier[2]".to_string())?;
+        update_mathml("<math><mfrac><mrow><mi>a</mi><mo>+</mo><mi>x</mi></mrow><mi>c</mi></mfrac></math>".to_string())?;
+        assert_eq!(get_navigation_node_path()?, "/fraction[1]/numerator/identifier[2]");
+        assert!(get_navigation_mathml()?.0.contains(">x</mi>"));
+
+        // ...or its closest ancestor if it is gone
+        update_mathml("<math><mfrac><mi>a</mi><mi>c</mi></mfrac></math>".to_string())?;
+        assert_eq!(get_navigation_node_path()?, "/fraction[1]/numerator");
+
+        // not navigating => nothing to keep
+        set_mathml("<math><mi>a</mi><mo>+</mo><mi>b</mi></math>".to_string())?;
+        update_mathml("<math><mi>a</mi><mo>+</mo><mi>c</mi></math>".to_string())?;
+        NAVIGATION_STATE.with(|nav_stack| assert!(nav_stack.borrow().position_stack.is_empty()));
+        return Ok( () );
+    }
+
     #[test]
     fn zoom_in_parens() -> Result<()> {
         // init_logger();
@@ -1284,6 +1986,133 @@ mod tests {
         });
     }
     
+    #[test]
+    fn text_spans() {
+        assert_eq!(sentence_spans("A train leaves at 3 p.m. today. How fast is it? "), vec![(0, 31), (32, 47)]);
+        assert_eq!(sentence_spans("He said \"stop.\" Then"), vec![(0, 15), (16, 20)]);
+        assert_eq!(sentence_spans("no end"), vec![(0, 6)]);
+        assert_eq!(word_spans(" two  words"), vec![(1, 4), (6, 11)]);
+    }
+
+    #[test]
+    fn move_by_sentence_and_word() -> Result<()> {
+        let mathml_str = "<math id='math'><mrow id='mrow'>
+                <mtext id='text'>Sam has 3 apples. Pat has 2 more. How many does Pat have?</mtext>
+                <mi id='x'>x</mi>
+            </mrow></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        set_preference("NavVerbosity".to_string(), "Verbose".to_string())?;
+        set_preference("Language".to_string(), "en".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            let speech = test_command("ZoomIn", mathml, "text");
+            assert!(speech.ends_with("Sam has 3 apples."), "speech is '{}'", speech);
+            assert_eq!(test_command("MoveNextSentence", mathml, "text"), "Pat has 2 more.");
+            assert_eq!(test_command("MoveNextWord", mathml, "text"), "has");
+            assert_eq!(test_command("MoveNextSentence", mathml, "text"), "How many does Pat have?");
+            assert_eq!(test_command("MovePreviousWord", mathml, "text"), "more.");
+            assert_eq!(test_command("MovePreviousSentence", mathml, "text"), "Sam has 3 apples.");
+            // outside of the text, sentence navigation is a normal move
+            test_command("MovePreviousSentence", mathml, "text");
+            test_command("MoveNextSentence", mathml, "text");
+            test_command("MoveNextSentence", mathml, "text");
+            test_command("MoveNextSentence", mathml, "x");
+            return Ok( () );
+        });
+    }
+
+    #[test]
+    fn move_by_granularity() -> Result<()> {
+        let mathml_str = "<math id='math'><mrow id='mrow'>
+                <mrow id='lhs'><mn id='two'>2</mn><mo id='times'>&#x2062;</mo><mi id='x'>x</mi></mrow>
+                <mo id='plus'>+</mo>
+                <mn id='num'>31</mn>
+                <mo id='eq'>=</mo>
+                <mn id='rhs'>7</mn>
+            </mrow></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        set_preference("NavVerbosity".to_string(), "Verbose".to_string())?;
+        set_preference("NavGranularity".to_string(), "Term".to_string())?;
+        set_preference("Language".to_string(), "en".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert_eq!(test_command("MoveNextAtGranularity", mathml, "lhs"), "2 x");
+            assert_eq!(test_command("MoveNextAtGranularity", mathml, "plus"), "plus");
+            assert_eq!(test_command("GranularityFiner", mathml, "plus"), "by token");
+            assert_eq!(test_command("MoveNextAtGranularity", mathml, "num"), "31");
+            assert_eq!(test_command("GranularityFiner", mathml, "num"), "by character");
+            // the invisible times is skipped
+            assert_eq!(test_command("MovePreviousAtGranularity", mathml, "plus"), "plus");
+            assert_eq!(test_command("MovePreviousAtGranularity", mathml, "x"), "x");
+            assert_eq!(test_command("MovePreviousAtGranularity", mathml, "two"), "2");
+            assert_eq!(test_command("MovePreviousAtGranularity", mathml, "two"), "cannot move left, start of math");
+            assert_eq!(test_command("GranularityCoarser", mathml, "two"), "by token");
+            assert_eq!(test_command("GranularityCoarser", mathml, "two"), "by term");
+            assert_eq!(test_command("GranularityCoarser", mathml, ""), "whole expression; 2 x plus 31, is equal to 7");
+            assert_eq!(test_command("MoveNextAtGranularity", mathml, ""), "cannot move right, end of math");
+            return Ok( () );
+        });
+    }
+
+    #[test]
+    fn toggle_granularity() -> Result<()> {
+        let mathml_str = "<math id='math'><mrow id='mrow'><mn id='num'>31</mn><mo id='plus'>+</mo><mi id='x'>x</mi></mrow></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        set_preference("NavGranularity".to_string(), "Off".to_string())?;
+        set_preference("Language".to_string(), "en".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_RETURN, true, false, false, false)?, "by token");
+            assert_eq!(get_preference("NavGranularity".to_string())?, "Token");
+            // right arrow moves by token, down arrow is a finer granularity
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_RIGHT, false, false, false, false)?, "31");
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_DOWN, false, false, false, false)?, "by character");
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_RIGHT, false, false, false, false)?, "1");
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_RETURN, true, false, false, false)?, "zoom navigation");
+            assert_eq!(get_preference("NavGranularity".to_string())?, "Off");
+            return Ok( () );
+        });
+    }
+
+    #[test]
+    fn move_to_answer_blanks() -> Result<()> {
+        let mathml_str = "<math id='math'><mrow id='mrow'>
+                <mn id='two'>2</mn><mo id='plus'>+</mo><mi id='blank1' data-role='answer'></mi><mo id='eq'>=</mo>
+                <mrow id='blank2' role='textbox'><mn id='five'>5</mn><mo id='minus'>-</mo><mi id='y'>y</mi></mrow>
+            </mrow></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        set_preference("NavGranularity".to_string(), "Off".to_string())?;
+        set_preference("Language".to_string(), "en".to_string())?;
+        set_mathml("<math id='math'><mn id='one'>1</mn></math>".to_string()).unwrap();
+        MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert_eq!(test_command("MoveNextAnswerBlank", mathml, "math"), "no answer blanks");
+        });
+        set_mathml(mathml_str.to_string()).unwrap();
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert_eq!(test_command("MovePreviousAnswerBlank", mathml, "math"), "no previous answer blanks");
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_TAB, false, false, false, false)?, "answer blank 1");
+            assert_eq!(test_command("MoveNextAnswerBlank", mathml, "blank2"), "answer blank 2 containing 5 minus y,");
+            assert_eq!(test_command("ZoomIn", mathml, "five"), "5");
+            assert_eq!(test_command("MoveNextAnswerBlank", mathml, "five"), "no more answer blanks");
+            assert_eq!(do_mathml_navigate_key_press(mathml, VK_TAB, true, false, false, false)?, "answer blank 1");
+            return Ok( () );
+        });
+    }
+
     #[test]
     fn move_enhanced_times() -> Result<()> {
         let mathml_str = "<math display='block' id='id-0' data-id-added='true'>
@@ -1438,6 +2267,42 @@ mod tests {
         });
     }
     
+    #[test]
+    fn move_in_elementary_math() -> Result<()> {
+        let mathml_str = "<math id='math'><mstack id='stack'>
+                <mscarries id='carries'><mn id='carry'>1</mn><none id='none'/></mscarries>
+                <mn id='top'>48</mn>
+                <msrow id='row'><mo id='plus'>+</mo><mn id='bottom'>34</mn></msrow>
+                <msline id='line'/>
+                <mn id='sum'>82</mn>
+            </mstack></math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Enhanced".to_string())?;
+        set_preference("NavVerbosity".to_string(), "Verbose".to_string())?;
+        set_preference("Language".to_string(), "en".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            assert_eq!(test_command("ZoomIn", mathml, "carries"), "zoom in; in carries; carry 1 to the tens column");
+            assert_eq!(test_command("MoveNext", mathml, "top"), "move right, in row 1; 48");
+            // the digits of a number are in separate columns
+            assert_eq!(test_command("MoveCellNext", mathml, "top"), "8");
+            assert_eq!(test_command("MoveCellNext", mathml, "top"), "no next column");
+            assert_eq!(test_command("MoveCellDown", mathml, "bottom"), "4");
+            // the line is skipped
+            assert_eq!(test_command("MoveCellDown", mathml, "sum"), "2");
+            assert_eq!(test_command("MoveCellDown", mathml, "sum"), "no next row");
+            assert_eq!(test_command("MoveCellPrevious", mathml, "sum"), "8");
+            assert_eq!(test_command("MoveCellUp", mathml, "bottom"), "3");
+            assert_eq!(test_command("MoveCellUp", mathml, "top"), "4");
+            assert_eq!(test_command("MoveCellUp", mathml, "carry"), "carry 1 to the tens column");
+            assert_eq!(test_command("MoveCellUp", mathml, "carry"), "no previous row");
+            assert_eq!(test_command("MoveCellNext", mathml, "carry"), "no next column");
+            return Ok( () );
+        });
+    }
+
     #[test]
     fn move_cell_char_mode() -> Result<()> {
         let mathml_str = "<math id='nav-0' data-id-added='true'>
@@ -1560,6 +2425,36 @@ mod tests {
         });
     }
 
+    #[test]
+    fn describe_siblings() -> Result<()> {
+        let mathml_str = "<math display='block' id='math'>
+        <mrow displaystyle='true' id='mrow'>
+          <mi id='a'>a</mi>
+          <mo id='plus-1'>+</mo>
+          <mi id='b'>b</mi>
+          <mo id='plus-2'>+</mo>
+          <mi id='c'>c</mi>
+        </mrow>
+        </math>";
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        set_mathml(mathml_str.to_string()).unwrap();
+        set_preference("NavMode".to_string(), "Character".to_string())?;
+        return MATHML_INSTANCE.with(|package_instance| {
+            let package_instance = package_instance.borrow();
+            let mathml = get_element(&package_instance);
+            test_command("MoveStart", mathml, "a");
+            test_command("MoveNext", mathml, "plus-1");
+            test_command("MoveNext", mathml, "b");
+            // the focus doesn't move
+            assert_eq!(test_command("DescribeSiblings", mathml, "b"), "term 2 of 3; b");
+            test_command("MoveNext", mathml, "plus-2");
+            assert_eq!(test_command("DescribeSiblings", mathml, "plus-2"), "operator 2 of 2; plus");
+            test_command("ZoomOutAll", mathml, "mrow");
+            assert!(test_command("DescribeSiblings", mathml, "mrow").starts_with("no siblings;"));
+            return Ok( () );
+        });
+    }
+
     #[test]
     fn where_am_i_all() -> Result<()> {
         let mathml_str = "<math id='math'><mfrac id='mfrac'>
@@ -15,6 +15,14 @@
 //! If there are multiple definitions, the later ones overwrite the former ones.
 //! This means that region-specific variants will overwrite more general variants.
 //!
+//! The value of a preference comes from (later ones take precedence):
+//! 1. The defaults built into MathCAT
+//! 2. The Rules dir's prefs.yaml
+//! 3. The user's prefs.yaml
+//! 4. An environment variable `MATHCAT_PREF_<name>` (e.g., `MATHCAT_PREF_Verbosity=Terse`) -- useful for batch processing and testing
+//! 5. The active profile (see [`PreferenceManager::set_profile`])
+//! 6. Calls to `set_preference`
+//!
 //! Note: there are a number of public 'get_xxx' functions that really are meant to be public only to the [crate::speech] module as speech needs access
 //! to the preferences to generate the speech.
 #![allow(clippy::needless_return)]
@@ -22,10 +30,10 @@ use yaml_rust::{Yaml, YamlLoader};
 use crate::pretty_print::yaml_to_string;
 use crate::tts::TTS;
 extern crate dirs;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{SystemTime, Instant, Duration};
 use std::env;
 use crate::speech::{as_str_checked, RulesFor};
 use crate::interface::errors_to_string;
@@ -36,9 +44,39 @@ use crate::errors::*;
 /// Use to indicate preference not found with Preference::to_string()
 pub static NO_PREFERENCE: &str = "\u{FFFF}";
 
+/// Prefs that override "Verbosity" for a kind of structure -- when their value is "Auto", the value of "Verbosity" is used
+static STRUCTURE_VERBOSITY_PREFS: &[&str] = &["FractionVerbosity", "RootVerbosity", "ScriptVerbosity"];
+
+/// Prefs that select which rule files are used -- changing one of them means that rules have to be read again
+static RULE_FILE_PREFS: &[&str] = &["Language", "LanguageFallback", "SpeechStyle", "BrailleCode"];
+
+/// Prefix of the environment variables that override a preference (e.g., `MATHCAT_PREF_Verbosity`)
+static ENV_PREF_PREFIX: &str = "MATHCAT_PREF_";
+
+/// Delays (in ms) before retrying to read a prefs file that couldn't be read or looks partly written (see [`Preferences::is_partly_written`])
+static PREFS_READ_RETRY_DELAYS: &[u64] = &[10, 50, 200];
+
+/// The version written to the "Version" key of a prefs file -- files with an older (or no) version are migrated when read
+static PREFS_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A pref whose name changed in MathCAT release `version`.
+/// The value of `old` is given to each of the `new` prefs (one for a rename, several when the pref was split up).
+struct PrefMigration {
+    version: &'static str,
+    old: &'static str,
+    new: &'static [&'static str],
+}
+
+/// The pref name changes in the order they were made (see [`migrate_prefs`]).
+/// No released pref has been renamed yet -- add an entry here when one is.
+static PREF_MIGRATIONS: &[PrefMigration] = &[];
+
 // Preferences are recorded here
 /// Preferences are stored in a HashMap. It maps the name of the pref (a String) to its value (stored as YAML string/float)
 pub type PreferenceHashMap = HashMap<String, Yaml>;
+/// The YAML last successfully read from each prefs file -- used when a file can't be read (see [`Preferences::read_file`])
+/// and to get the locks and profiles in it (see [`Preferences::read_locks`] and [`Preferences::read_profiles`])
+type PrefsDocs = HashMap<PathBuf, Yaml>;
 #[derive(Debug, Clone, Default)]
 pub struct Preferences {
     prefs: PreferenceHashMap        // FIX: pub so can get at iterator, should add iterator to Preferences instead
@@ -61,19 +99,43 @@ impl Preferences{
     fn user_defaults() -> Preferences {
         let mut prefs = PreferenceHashMap::with_capacity(39);
         prefs.insert("Language".to_string(), Yaml::String("en".to_string()));
+        prefs.insert("LanguageFallback".to_string(), Yaml::String("en".to_string()));
         prefs.insert("SpeechStyle".to_string(), Yaml::String("ClearSpeak".to_string()));
         prefs.insert("Verbosity".to_string(), Yaml::String("medium".to_string()));
+        prefs.insert("FractionVerbosity".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("RootVerbosity".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("ScriptVerbosity".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("MathSpeak".to_string(), Yaml::String("Auto".to_string()));
         prefs.insert("SpeechOverrides_CapitalLetters".to_string(), Yaml::String("".to_string())); // important for testing
         prefs.insert("Blind".to_string(), Yaml::Boolean(true));
         prefs.insert("MathRate".to_string(), Yaml::String("100.0".to_string()));
         prefs.insert("PauseFactor".to_string(), Yaml::String("100.0".to_string()));
+        prefs.insert("ScalePausesWithRate".to_string(), Yaml::Boolean(true));
+        prefs.insert("ExamMode".to_string(), Yaml::Boolean(false));
+        prefs.insert("VariedSpeech".to_string(), Yaml::Boolean(false));
+        prefs.insert("BrailleEcho".to_string(), Yaml::Boolean(false));
+        prefs.insert("FunctionInverse".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("TrigInverse".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("FractionStyle".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("MatrixDimensions".to_string(), Yaml::Boolean(true));
+        prefs.insert("ExplainConstants".to_string(), Yaml::Boolean(true));
+        prefs.insert("ScriptOrder_Postscripts".to_string(), Yaml::String("SubSuper".to_string()));
+        prefs.insert("ScriptOrder_Prescripts".to_string(), Yaml::String("SubSuper".to_string()));
+        prefs.insert("ScriptOrder_Chemistry".to_string(), Yaml::String("SuperSub".to_string()));
+        prefs.insert("DecimalDigits".to_string(), Yaml::String("Auto".to_string()));
+        prefs.insert("DecimalDigitsMax".to_string(), Yaml::Integer(0));
+        prefs.insert("OrdinalDenominatorMax".to_string(), Yaml::Integer(10));
+        prefs.insert("IdNumbers".to_string(), Yaml::String("Auto".to_string()));
         prefs.insert("NavMode".to_string(), Yaml::String("enhanced".to_string()));
-        prefs.insert("Overview".to_string(), Yaml::String("read".to_string()));
-        prefs.insert("ResetOverView".to_string(), Yaml::Boolean(true));
-        prefs.insert("NavVerbosity".to_string(), Yaml::String("verbose".to_string()));
+        prefs.insert("Overview".to_string(), Yaml::Boolean(false));
+        prefs.insert("ResetOverview".to_string(), Yaml::Boolean(true));
+        prefs.insert("OverviewSize".to_string(), Yaml::Integer(12));
+        prefs.insert("NavVerbosity".to_string(), Yaml::String("Medium".to_string()));
         prefs.insert("AutoZoomOut".to_string(), Yaml::Boolean(true));
         prefs.insert("BrailleCode".to_string(), Yaml::String("Nemeth".to_string()));
         prefs.insert("BrailleNavHighlight".to_string(), Yaml::String("EndPoints".to_string()));
+        prefs.insert("BrfLineLength".to_string(), Yaml::Integer(40));
+        prefs.insert("BrfPageLength".to_string(), Yaml::Integer(25));
     
         return Preferences{ prefs };
     }
@@ -91,22 +153,49 @@ impl Preferences{
         prefs.insert("CapitalLetters_UseWord".to_string(), Yaml::Boolean(true));
         prefs.insert("CapitalLetters_Pitch".to_string(), Yaml::Real("0.0".to_string()));
         prefs.insert("CapitalLetters_Beep".to_string(), Yaml::Boolean(false));
+        prefs.insert("Text_Pitch".to_string(), Yaml::Real("0.0".to_string()));
+        prefs.insert("Text_Voice".to_string(), Yaml::String("".to_string()));
         prefs.insert("IntentErrorRecovery".to_string(), Yaml::String("IgnoreIntent".to_string()));    // also Error
+        prefs.insert("FileCheckInterval".to_string(), Yaml::Real("1.0".to_string()));    // seconds between checks for changed files
+        prefs.insert("AutoReload".to_string(), Yaml::Boolean(true));     // false: never check for changed files
+        prefs.insert("ATProfile".to_string(), Yaml::String("None".to_string()));     // screen reader specific output (see Rules/ATProfiles)
         return Preferences{ prefs };
     }
 
     // Before we can get the other files, we need the preferences.
     // To get them we need to read pref files, so the pref file reading is different than the other files
-    fn from_file(rules_dir: &Path) -> Result<(Preferences, FileAndTime)> {
-        let files = Preferences::get_prefs_file_and_time(rules_dir);
+    // The files are Rules/prefs.yaml, the site administrator's system-prefs.yaml, and the user's prefs.yaml (later ones win).
+    // Also returns the prefs locked by system-prefs.yaml along with their values.
+    fn from_file(rules_dir: &Path, track_time: bool, last_good: &mut PrefsDocs) -> Result<(Preferences, FileAndTime, Preferences)> {
+        let files = Preferences::get_prefs_file_and_time(rules_dir, track_time);
         return DEFAULT_USER_PREFERENCES.with(|defaults| {
-            let system_prefs = Preferences::read_file(&files.files[0], defaults.clone())?;
-            let system_prefs = Preferences::read_file(&files.files[1], system_prefs)?;
-            return Ok((system_prefs, files));
+            let system_prefs = Preferences::read_file(&files.files[0], defaults.clone(), last_good)?;
+            let system_prefs = Preferences::read_file(&files.files[1], system_prefs, last_good)?;
+            let locked = Preferences::read_locks(&files.files[1], &system_prefs, last_good);
+            let user_prefs = Preferences::read_file(&files.files[2], system_prefs, last_good)?;
+            return Ok((user_prefs, files, locked));
         });
     }
 
-    fn get_prefs_file_and_time(rules_dir: &Path) -> FileAndTime {
+    /// The machine-wide prefs file a site administrator can use to set (and lock) prefs for all users:
+    /// `%ProgramData%\MathCAT\system-prefs.yaml` on Windows, `/Library/Application Support/MathCAT/system-prefs.yaml` on macOS,
+    /// and `/etc/MathCAT/system-prefs.yaml` elsewhere.
+    fn system_prefs_file() -> Option<PathBuf> {
+        #[cfg(test)]
+        if let Some(file) = TEST_SYSTEM_PREFS_FILE.with(|file| file.borrow().clone()) {
+            return Some(file);
+        }
+        let dir = if cfg!(target_os = "windows") {
+            PathBuf::from(env::var_os("ProgramData")?)
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from("/Library/Application Support")
+        } else {
+            PathBuf::from("/etc")
+        };
+        return Some( dir.join("MathCAT").join("system-prefs.yaml") );
+    }
+
+    fn get_prefs_file_and_time(rules_dir: &Path, track_time: bool) -> FileAndTime {
         let mut system_prefs_file = rules_dir.to_path_buf();
         system_prefs_file.push("prefs.yaml");
 
@@ -118,62 +207,181 @@ impl Preferences{
                         system_prefs_file.to_str().unwrap());
         }
 
-        let user_dir = dirs::config_dir();
+        // sandboxed AT can't look at the machine-wide or user's config dirs
+        let system_prefs_file = if is_using_in_memory_rules() {None} else {Preferences::system_prefs_file()};
+        if let Some(system_prefs_file) = system_prefs_file {
+            if is_file_shim(&system_prefs_file) {
+                result[1] = Some( system_prefs_file );
+            }
+        }
+
+        let user_dir = if is_using_in_memory_rules() {None} else {dirs::config_dir()};
         if let Some(mut user_prefs_file) = user_dir {
             user_prefs_file.push("MathCAT/prefs.yaml");
             if is_file_shim(&user_prefs_file) {
-                result[1] = Some( user_prefs_file );
+                result[2] = Some( user_prefs_file );
             }            
         }
 
         return FileAndTime {
-            time: if cfg!(target_family = "wasm") {None} else {Some( SystemTime::now() )},
+            time: if cfg!(target_family = "wasm") || !track_time {None} else {Some( SystemTime::now() )},
             files: result
         }
     }
 
-    fn read_file(file: &Option<PathBuf>, mut base_prefs: Preferences) -> Result<Preferences> {
+    /// Add the prefs in `file` to `base_prefs`.
+    ///
+    /// A settings UI might be in the middle of writing `file`, so a read that fails because the file couldn't be read
+    /// or looks partly written (see [`Preferences::is_partly_written`]) is retried a few times.
+    /// If it still fails, the prefs that were last successfully read from `file` (kept in `last_good`) are used so that the settings don't "reset".
+    fn read_file(file: &Option<PathBuf>, mut base_prefs: Preferences, last_good: &mut PrefsDocs) -> Result<Preferences> {
         let unwrapped_file = match file {
             None => return Ok(base_prefs),
             Some(f) => f.as_path(),
         };
 
         let file_name = unwrapped_file.to_str().unwrap();
-        let docs;
-        match read_to_string_shim(unwrapped_file) {
-            Err(e) => {
-                bail!("Couldn't read file {}\n{}", file_name, e);
+        let mut contents = read_to_string_shim(unwrapped_file);
+        let mut doc = Preferences::parse_prefs_doc(&contents, file_name);
+        if !cfg!(target_family = "wasm") {      // wasm can't sleep
+            for delay in PREFS_READ_RETRY_DELAYS {
+                if matches!(doc, Ok(Ok(_))) || !Preferences::is_partly_written(&contents) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(*delay));
+                contents = read_to_string_shim(unwrapped_file);
+                doc = Preferences::parse_prefs_doc(&contents, file_name);
+            }
+        }
+        let doc = match doc {
+            Ok(Ok(doc)) => {
+                last_good.insert(unwrapped_file.to_path_buf(), doc.clone());
+                doc
             },
-            Ok( file_contents) => {
-                match YamlLoader::load_from_str(&file_contents) {
-                    Err(e) => {
-                        error!("Yaml parse error ('{}') in file {}.\nUsing fallback defaults which may be inappropriate.",
-                                    e, file_name);
+            failure => match last_good.get(unwrapped_file).cloned() {
+                Some(doc) => {
+                    let message = match failure {
+                        Err(e) => errors_to_string(&e),
+                        Ok(message) => message.unwrap_err(),
+                    };
+                    warn!("{}\nUsing the preferences that were last read from that file.", message);
+                    doc
+                },
+                None => match failure {
+                    Err(e) => return Err(e),
+                    Ok(message) => {
+                        error!("{}\nUsing fallback defaults which may be inappropriate.", message.unwrap_err());
                         return Ok(base_prefs);
                     },
-                    Ok(d) => docs = d,
-                }
+                },
+            },
+        };
 
-            }
+        let mut file_prefs = PreferenceHashMap::default();
+        add_prefs(&mut file_prefs, &doc["Speech"], "", file_name);
+        add_prefs(&mut file_prefs, &doc["Navigation"], "", file_name);
+        add_prefs(&mut file_prefs, &doc["Braille"], "", file_name);
+        migrate_prefs(&mut file_prefs, &prefs_file_version(&doc), PREF_MIGRATIONS, file_name);
+        base_prefs.prefs.extend(file_prefs);
+        return Ok(base_prefs);
+    }
+
+    /// Get the prefs named in the top-level "locked" list of the system prefs `file` along with their values in `prefs`.
+    /// The file must have been read by [`Preferences::read_file`] with the same `last_good`.
+    fn read_locks(file: &Option<PathBuf>, prefs: &Preferences, last_good: &PrefsDocs) -> Preferences {
+        let mut locked = Preferences::default();
+        let file = match file {
+            None => return locked,
+            Some(file) => file,
+        };
+        let file_name = file.to_str().unwrap();
+        let doc = match last_good.get(file) {
+            None => return locked,
+            Some(doc) => doc,
+        };
+        let names = &doc["locked"];
+        if names.is_badvalue() {
+            return locked;
         }
-        if docs.len() != 1 {
-            error!("Yaml error in file {}.\nFound {} 'documents' -- should only be 1.",
-                        file_name, docs.len());
-            return Ok(base_prefs);
+        let names = match names.as_vec() {
+            None => {
+                error!("Yaml error in file {}.\n'locked' key is not a list. Value found is {}.", file_name, yaml_to_string(names, 1));
+                return locked;
+            },
+            Some(names) => names,
+        };
+        for name in names {
+            match name.as_str().and_then(|name| prefs.prefs.get_key_value(name.trim())) {
+                Some( (name, value) ) => {
+                    locked.prefs.insert(name.clone(), value.clone());
+                },
+                None => warn!("'{}' in the 'locked' list in file {} is not a known preference -- it is not locked", yaml_to_string(name, 0), file_name),
+            }
         }
+        return locked;
+    }
 
-        let doc = &docs[0];
-        verify_keys(doc, "Speech", file_name)?;
-        verify_keys(doc, "Navigation", file_name)?;
-        verify_keys(doc, "Braille", file_name)?;
-
-        let prefs = &mut base_prefs.prefs;
-        add_prefs(prefs, &doc["Speech"], "", file_name);
-        add_prefs(prefs, &doc["Navigation"], "", file_name);
-        add_prefs(prefs, &doc["Braille"], "", file_name);
-        return Ok( Preferences{ prefs: prefs.to_owned() } );
+    /// Get the profiles (named sets of prefs) in the "Profiles" section of the prefs `files`.
+    /// A profile in a later file (e.g., the user's prefs.yaml) adds to and overrides the one with the same name in an earlier file.
+    /// The files must have been read by [`Preferences::read_file`] with the same `last_good`.
+    fn read_profiles(files: &Locations, last_good: &PrefsDocs) -> HashMap<String, Preferences> {
+        let mut profiles: HashMap<String, Preferences> = HashMap::new();
+        for file in files.iter().flatten() {
+            let file_name = file.to_str().unwrap();
+            let doc = match last_good.get(file) {
+                None => continue,
+                Some(doc) => doc,
+            };
+            let file_profiles = &doc["Profiles"];
+            if file_profiles.is_badvalue() {
+                continue;
+            }
+            let file_profiles = match file_profiles.as_hash() {
+                None => {
+                    error!("Yaml error in file {}.\n'Profiles' key is not a dictionary. Value found is {}.", file_name, yaml_to_string(file_profiles, 1));
+                    continue;
+                },
+                Some(file_profiles) => file_profiles,
+            };
+            for (name, profile) in file_profiles {
+                let name = match as_str_checked(name) {
+                    Err(e) => {
+                        error!("{}", e.chain_err(|| format!("profile name '{}' is not a string in file {}", yaml_to_string(name, 0), file_name)));
+                        continue;
+                    },
+                    Ok(name) => name,
+                };
+                let mut profile_prefs = PreferenceHashMap::default();
+                for category in ["Speech", "Navigation", "Braille"] {
+                    add_prefs(&mut profile_prefs, &profile[category], "", file_name);
+                }
+                migrate_prefs(&mut profile_prefs, &prefs_file_version(doc), PREF_MIGRATIONS, file_name);
+                profiles.entry(name.to_string()).or_default().prefs.extend(profile_prefs);
+            }
+        }
+        return profiles;
+    }
 
+    /// Parse the `contents` of the prefs file `file_name`.
+    /// A YAML syntax error is returned as `Ok(Err(message))` because fallback defaults can be used in that case.
+    fn parse_prefs_doc(contents: &Result<String>, file_name: &str) -> Result<std::result::Result<Yaml, String>> {
+        let file_contents = match contents {
+            Err(e) => bail!("Couldn't read file {}\n{}", file_name, e),
+            Ok(file_contents) => file_contents,
+        };
+        let mut docs = match YamlLoader::load_from_str(file_contents) {
+            Err(e) => return Ok( Err(format!("Yaml parse error ('{}') in file {}.", e, file_name)) ),
+            Ok(docs) => docs,
+        };
+        if docs.len() != 1 {
+            return Ok( Err(format!("Yaml error in file {}.\nFound {} 'documents' -- should only be 1.", file_name, docs.len())) );
+        }
 
+        let doc = docs.pop().unwrap();
+        verify_keys(&doc, "Speech", file_name)?;
+        verify_keys(&doc, "Navigation", file_name)?;
+        verify_keys(&doc, "Braille", file_name)?;
+        return Ok( Ok(doc) );
 
         fn verify_keys(dict: &Yaml, key: &str, file_name: &str) -> Result<()> {
             let prefs = &dict[key];
@@ -186,33 +394,23 @@ impl Preferences{
             }
             return Ok(());
         }
+    }
 
-        fn add_prefs(map: &mut PreferenceHashMap, new_prefs: &Yaml, name_prefix: &str, file_name: &str) {
-            if new_prefs.is_badvalue() || new_prefs.as_hash().is_none() {
-                return;
-            }
-            let new_prefs = new_prefs.as_hash().unwrap();
-            for (yaml_name, yaml_value) in new_prefs {
-                let name = as_str_checked(yaml_name);
-                if let Err(e) = name {
-                    error!("{}", (&e.chain_err(||
-                        format!("name '{}' is not a string in file {}", yaml_to_string(yaml_name, 0), file_name))));                   
-                } else if yaml_value.as_hash().is_some() {
-                        add_prefs(map, yaml_value, &(name.unwrap().to_string() + "_"), file_name);
-                } else if yaml_value.as_vec().is_some() {
-                    error!("name '{}' has illegal array value {} in file '{}'",
-                            yaml_to_string(yaml_name, 0), yaml_to_string(yaml_value, 0), file_name);
-                    return;
-                } else {
-                    let trimmed_name = name_prefix.to_string() + name.unwrap().trim();
-                    let mut trimmed_yaml_value = yaml_value.to_owned();
-                    if let Some(value) = trimmed_yaml_value.as_str() {
-                        trimmed_yaml_value = Yaml::String(value.trim().to_string());
-                    }
-                    map.insert(trimmed_name, trimmed_yaml_value);
-                }
-            }
-        }
+    /// True if the prefs file couldn't be read or its `contents` look like a file that is in the middle of being written:
+    /// it is empty, the YAML stops partway through, or the "Speech", "Navigation", or "Braille" section is missing.
+    /// Other errors (e.g., a section that isn't a dictionary) won't go away by reading the file again.
+    fn is_partly_written(contents: &Result<String>) -> bool {
+        let contents = match contents {
+            Err(_) => return true,
+            Ok(contents) => contents,
+        };
+        return match YamlLoader::load_from_str(contents) {
+            Err(e) => e.marker().index() >= contents.chars().count(),
+            Ok(docs) => match docs.first() {
+                None => true,
+                Some(doc) => ["Speech", "Navigation", "Braille"].iter().any(|key| doc[*key].is_badvalue()),
+            },
+        };
     }
 
     /// returns value associated with 'name' or string NO_PREFERENCE
@@ -232,6 +430,39 @@ impl Preferences{
         }
     }
 
+    /// returns the boolean value associated with 'name' or None if it isn't set or isn't a boolean
+    /// 
+    /// Values that were set as strings (e.g., by `set_user_prefs`) count if they are "true" or "false" (in any case).
+    pub fn to_bool(&self, name: &str) -> Option<bool> {
+        return match self.prefs.get(name)? {
+            Yaml::Boolean(b) => Some(*b),
+            Yaml::String(s) if s.trim().eq_ignore_ascii_case("true") => Some(true),
+            Yaml::String(s) if s.trim().eq_ignore_ascii_case("false") => Some(false),
+            _ => None,
+        };
+    }
+
+    /// returns the integer value associated with 'name' or None if it isn't set or isn't a whole number (e.g., 40, 40.0, or "40")
+    pub fn to_int(&self, name: &str) -> Option<i64> {
+        return match self.prefs.get(name)? {
+            Yaml::Integer(i) => Some(*i),
+            Yaml::Real(s) | Yaml::String(s) => {
+                let s = s.trim();
+                s.parse::<i64>().ok().or_else(|| s.parse::<f64>().ok().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+            },
+            _ => None,
+        };
+    }
+
+    /// returns the number associated with 'name' or None if it isn't set or isn't a number
+    pub fn to_float(&self, name: &str) -> Option<f64> {
+        return match self.prefs.get(name)? {
+            Yaml::Integer(i) => Some(*i as f64),
+            Yaml::Real(s) | Yaml::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+    }
+
     #[allow(dead_code)]     // used in testing
     fn set_string_value(&mut self, name: &str, value: &str) {
         self.prefs.insert(name.to_string(), Yaml::String(value.trim().to_string()));
@@ -244,6 +475,222 @@ impl Preferences{
 }
 
 
+/// Add the prefs in the dictionary `new_prefs` to `map` -- nested dictionaries become names joined with "_" (e.g., "ClearSpeak_Fractions")
+fn add_prefs(map: &mut PreferenceHashMap, new_prefs: &Yaml, name_prefix: &str, file_name: &str) {
+    if new_prefs.is_badvalue() || new_prefs.as_hash().is_none() {
+        return;
+    }
+    let new_prefs = new_prefs.as_hash().unwrap();
+    for (yaml_name, yaml_value) in new_prefs {
+        let name = as_str_checked(yaml_name);
+        if let Err(e) = name {
+            error!("{}", (&e.chain_err(||
+                format!("name '{}' is not a string in file {}", yaml_to_string(yaml_name, 0), file_name))));                   
+        } else if yaml_value.as_hash().is_some() {
+                add_prefs(map, yaml_value, &(name.unwrap().to_string() + "_"), file_name);
+        } else if yaml_value.as_vec().is_some() && name.as_ref().is_ok_and(|name| name.trim() == "LanguageFallback") {
+            // the one pref that is a list -- stored as a comma separated string so it can be set like any other pref
+            let languages = yaml_value.as_vec().unwrap().iter().map(|language| yaml_to_string(language, 0).trim().to_string()).collect::<Vec<String>>();
+            map.insert(name_prefix.to_string() + "LanguageFallback", Yaml::String(languages.join(", ")));
+        } else if yaml_value.as_vec().is_some() {
+            error!("name '{}' has illegal array value {} in file '{}'",
+                    yaml_to_string(yaml_name, 0), yaml_to_string(yaml_value, 0), file_name);
+            return;
+        } else {
+            let trimmed_name = name_prefix.to_string() + name.unwrap().trim();
+            let mut trimmed_yaml_value = yaml_value.to_owned();
+            if let Some(value) = trimmed_yaml_value.as_str() {
+                trimmed_yaml_value = Yaml::String(value.trim().to_string());
+            }
+            map.insert(trimmed_name, trimmed_yaml_value);
+        }
+    }
+}
+
+/// The "Version" of the prefs file `doc` ("0" if it doesn't have one -- files written before versions were added)
+fn prefs_file_version(doc: &Yaml) -> String {
+    return match &doc["Version"] {
+        Yaml::String(version) | Yaml::Real(version) => version.trim().to_string(),
+        Yaml::Integer(version) => version.to_string(),
+        _ => "0".to_string(),
+    };
+}
+
+/// Returns true if the dotted version `a` (e.g., "0.2.3") is older than `b` -- missing parts count as 0
+pub(crate) fn is_older_version(a: &str, b: &str) -> bool {
+    fn parts(version: &str) -> Vec<u32> {
+        return version.split('.').map(|part| part.trim().parse::<u32>().unwrap_or(0)).collect();
+    }
+    let (mut a, mut b) = (parts(a), parts(b));
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    return a < b;
+}
+
+/// Rename the prefs in `map` that were read from a file written by MathCAT `file_version` and whose names have since changed.
+/// This keeps a user's customizations from being silently dropped after an update.
+/// A pref that is already set under its new name is not overwritten.
+fn migrate_prefs(map: &mut PreferenceHashMap, file_version: &str, migrations: &[PrefMigration], file_name: &str) {
+    for migration in migrations {
+        if !is_older_version(file_version, migration.version) {
+            continue;
+        }
+        if let Some(value) = map.remove(migration.old) {
+            info!("The pref '{}' in file {} was replaced by '{}' in MathCAT {} -- using its value for them",
+                    migration.old, file_name, migration.new.join("', '"), migration.version);
+            for new_name in migration.new {
+                map.entry(new_name.to_string()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+
+/// The kind of value a preference takes. See [`PreferenceDescription`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreferenceType {
+    String,
+    Float,
+    Bool,
+    /// One of the values listed in [`PreferenceDescription::values`]
+    Enum,
+}
+
+/// A description of a preference MathCAT understands so that an AT can build a settings UI for it.
+/// See [`PreferenceManager::get_preference_descriptions`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreferenceDescription {
+    /// The name used with `get_preference`/`set_preference` (nested names in prefs.yaml are joined with '_', e.g., "ClearSpeak_Fractions")
+    pub name: &'static str,
+    /// "Speech", "Navigation", or "Braille" (the sections of prefs.yaml) or "API" for prefs that are set by the AT and not the user
+    pub category: &'static str,
+    pub value_type: PreferenceType,
+    /// The allowed values for an [`PreferenceType::Enum`] (empty for other types)
+    pub values: &'static [&'static str],
+    /// The value used if neither the user nor the AT set the preference
+    pub default: &'static str,
+    /// The speech style or braille code that uses the preference (None if it is always used)
+    pub applies_to: Option<&'static str>,
+    /// A short description suitable for a tooltip
+    pub description: &'static str,
+}
+
+const fn pref(category: &'static str, name: &'static str, value_type: PreferenceType, values: &'static [&'static str],
+              default: &'static str, applies_to: Option<&'static str>, description: &'static str) -> PreferenceDescription {
+    return PreferenceDescription { name, category, value_type, values, default, applies_to, description };
+}
+
+const fn enum_pref(category: &'static str, name: &'static str, values: &'static [&'static str], default: &'static str, description: &'static str) -> PreferenceDescription {
+    return pref(category, name, PreferenceType::Enum, values, default, None, description);
+}
+
+const fn clearspeak_pref(name: &'static str, values: &'static [&'static str], default: &'static str, description: &'static str) -> PreferenceDescription {
+    return pref("Speech", name, PreferenceType::Enum, values, default, Some("ClearSpeak"), description);
+}
+
+const VERBOSITY_OVERRIDES: &[&str] = &["Auto", "Terse", "Medium", "Verbose"];
+
+/// The preferences in Rules/prefs.yaml (and [`Preferences::user_defaults`]) followed by the ones set by the AT (see [`Preferences::api_defaults`])
+static PREFERENCE_DESCRIPTIONS: &[PreferenceDescription] = &[
+    enum_pref("Speech", "Impairment", &["Blindness", "LowVision", "LearningDisability"], "Blindness", "The user's impairment"),
+    pref("Speech", "Blind", PreferenceType::Bool, &[], "true", None, "Older form of 'Impairment' that some languages' rules still use"),
+    pref("Speech", "Language", PreferenceType::String, &[], "en", None, "Language code with an optional region (e.g., 'en' or 'en-gb')"),
+    pref("Speech", "LanguageFallback", PreferenceType::String, &[], "en", None, "Comma separated languages tried in order when a file isn't found for 'Language' ('en' is always tried last)"),
+    enum_pref("Speech", "SpeechSound", &["None", "Beep"], "None", "Sound made when math speech starts and ends"),
+    enum_pref("Speech", "Verbosity", &["Terse", "Medium", "Verbose"], "Medium", "How many words are used to describe the math"),
+    enum_pref("Speech", "FractionVerbosity", VERBOSITY_OVERRIDES, "Auto", "Overrides 'Verbosity' for fractions ('Auto' uses 'Verbosity')"),
+    enum_pref("Speech", "RootVerbosity", VERBOSITY_OVERRIDES, "Auto", "Overrides 'Verbosity' for roots ('Auto' uses 'Verbosity')"),
+    enum_pref("Speech", "ScriptVerbosity", VERBOSITY_OVERRIDES, "Auto", "Overrides 'Verbosity' for sub/superscripts ('Auto' uses 'Verbosity')"),
+    pref("Speech", "MathRate", PreferenceType::Float, &[], "100", None, "Change from the text speech rate (%)"),
+    pref("Speech", "PauseFactor", PreferenceType::Float, &[], "100", None, "Change from the normal pause length (%)"),
+    pref("Speech", "ScalePausesWithRate", PreferenceType::Bool, &[], "true", None, "Make pauses shorter (longer) when the speech rate is faster (slower)"),
+    pref("Speech", "SpeechStyle", PreferenceType::String, &[], "ClearSpeak", None, "Any speech style known for the language (falls back to ClearSpeak)"),
+    enum_pref("Speech", "SubjectArea", &["General", "Calculus", "Geometry", "NumberTheory", "Physics", "SetTheory", "Statistics"], "General", "The subject area used to disambiguate notation"),
+    enum_pref("Speech", "Chemistry", &["SpellOut", "AsCompound", "Off"], "SpellOut", "How chemistry is read ('Off' reads it as math)"),
+    pref("Speech", "ExamMode", PreferenceType::Bool, &[], "false", None, "Don't make inferences that could give away an answer"),
+    pref("Speech", "VariedSpeech", PreferenceType::Bool, &[], "false", None, "Rotate between equivalent phrasings to reduce monotony"),
+    pref("Speech", "BrailleEcho", PreferenceType::Bool, &[], "false", None, "Speak the braille cells (e.g., \"numeric indicator, 3\") instead of the math -- for learning braille"),
+    enum_pref("Speech", "FunctionInverse", &["Auto", "Literal"], "Auto", "How an inverse function such as f⁻¹ is read"),
+    enum_pref("Speech", "TrigInverse", &["Auto", "Inverse", "TrigInverse", "ArcTrig"], "Auto", "How an inverse trig function is read"),
+    enum_pref("Speech", "FractionStyle", &["Auto", "ByKind", "Over", "DividedBy", "Per"], "Auto", "Whether fractions are read with 'over', 'divided by', or 'per'"),
+    pref("Speech", "MatrixDimensions", PreferenceType::Bool, &[], "true", None, "Say the size of a matrix or vector before its entries (\"the 2 by 3 matrix\")"),
+    pref("Speech", "ExplainConstants", PreferenceType::Bool, &[], "true", None, "At Verbose verbosity, say what a named constant is (\"e, the base of the natural log\")"),
+    enum_pref("Speech", "ScriptOrder_Postscripts", &["SubSuper", "SuperSub"], "SubSuper", "Order to speak a subscript/superscript pair"),
+    enum_pref("Speech", "ScriptOrder_Prescripts", &["SubSuper", "SuperSub"], "SubSuper", "Order to speak a presubscript/presuperscript pair"),
+    enum_pref("Speech", "ScriptOrder_Chemistry", &["SubSuper", "SuperSub"], "SuperSub", "Order to speak the atomic number and mass number of a nuclide"),
+    enum_pref("Speech", "DecimalDigits", &["Auto", "Digits", "Grouped"], "Auto", "How the digits after a decimal point are read"),
+    enum_pref("Speech", "IdNumbers", &["Auto", "Always", "Never"], "Auto", "When numbers in text (e.g., phone numbers) are read digit by digit"),
+    pref("Speech", "DecimalDigitsMax", PreferenceType::Float, &[], "0", None, "Most digits read after a decimal point before saying how many more there are (0 -- no limit)"),
+    pref("Speech", "OrdinalDenominatorMax", PreferenceType::Float, &[], "10", None, "Largest denominator of a fraction read with an ordinal (\"three fifths\") instead of \"3 over 5\""),
+
+    clearspeak_pref("ClearSpeak_CapitalLetters", &["Auto", "SayCaps"], "Auto", "Say 'cap' or use pitch for capital letters"),
+    clearspeak_pref("ClearSpeak_AbsoluteValue", &["Auto", "AbsEnd", "Cardinality", "Determinant"], "Auto", "How vertical bars around an expression are read"),
+    clearspeak_pref("ClearSpeak_Fractions", &["Auto", "Ordinal", "Over", "FracOver", "General", "EndFrac", "GeneralEndFrac", "OverEndFrac", "Per"], "Auto", "How fractions are read"),
+    clearspeak_pref("ClearSpeak_Exponents", &["Auto", "Ordinal", "OrdinalPower", "AfterPower"], "Auto", "How exponents are read"),
+    clearspeak_pref("ClearSpeak_Roots", &["Auto", "PosNegSqRoot", "RootEnd", "PosNegSqRootEnd"], "Auto", "How roots are read"),
+    clearspeak_pref("ClearSpeak_Functions", &["Auto", "None"], "Auto", "Whether names followed by parentheses are read as functions"),
+    clearspeak_pref("ClearSpeak_Trig", &["Auto", "TrigInverse", "ArcTrig"], "Auto", "How inverse trig functions are read"),
+    clearspeak_pref("ClearSpeak_Log", &["Auto", "LnAsNaturalLog"], "Auto", "How 'ln' is read"),
+    clearspeak_pref("ClearSpeak_ImpliedTimes", &["Auto", "MoreImpliedTimes", "None"], "Auto", "When invisible multiplication is read as 'times'"),
+    clearspeak_pref("ClearSpeak_Paren", &["Auto", "Speak", "SpeakNestingLevel", "Silent", "CoordPoint", "Interval"], "Auto", "How parentheses are read"),
+    clearspeak_pref("ClearSpeak_Matrix", &["Auto", "SpeakColNum", "SilentColNum", "EndMatrix", "Vector", "EndVector", "Combinatorics"], "Auto", "How matrices and vectors are read"),
+    clearspeak_pref("ClearSpeak_MultiLineLabel", &["Auto", "Case", "Constraint", "Equation", "Line", "None", "Row", "Step"], "Auto", "The word used for each line of a multi-line expression"),
+    clearspeak_pref("ClearSpeak_MultiLineOverview", &["Auto", "None"], "Auto", "Whether the number of lines is said before a multi-line expression"),
+    clearspeak_pref("ClearSpeak_MultiLinePausesBetweenColumns", &["Short", "Long"], "Short", "Length of the pause between columns of a multi-line expression"),
+    clearspeak_pref("ClearSpeak_Sets", &["Auto", "woAll", "SilentBracket"], "Auto", "How set braces are read"),
+    clearspeak_pref("ClearSpeak_MultSymbolX", &["Auto", "By", "Cross"], "Auto", "How '×' is read"),
+    clearspeak_pref("ClearSpeak_MultSymbolDot", &["Auto", "Dot"], "Auto", "How '·' is read"),
+    clearspeak_pref("ClearSpeak_TriangleSymbol", &["Auto", "Delta"], "Auto", "How '△' is read"),
+    clearspeak_pref("ClearSpeak_Ellipses", &["Auto", "AndSoOn"], "Auto", "How '…' is read"),
+    clearspeak_pref("ClearSpeak_VerticalLine", &["Auto", "SuchThat", "Divides", "Given"], "Auto", "How '|' is read"),
+    clearspeak_pref("ClearSpeak_SetMemberSymbol", &["Auto", "Belongs", "Element", "Member", "In"], "Auto", "How '∈' is read"),
+    clearspeak_pref("ClearSpeak_Prime", &["Auto", "Angle", "Length"], "Auto", "How primes are read"),
+    clearspeak_pref("ClearSpeak_CombinationPermutation", &["Auto", "ChoosePermute"], "Auto", "How combinations and permutations are read"),
+    clearspeak_pref("ClearSpeak_Bar", &["Auto", "Bar", "Conjugate", "Mean"], "Auto", "How an overbar is read"),
+
+    pref("Speech", "MathSpeak", PreferenceType::Enum, &["Auto", "Verbose", "Brief", "SuperBrief"], "Auto", Some("MathSpeak"),
+         "MathSpeak verbosity -- Auto uses Verbosity (Verbose, Medium -> Brief, Terse -> SuperBrief)"),
+    pref("Speech", "SpeechOverrides_CapitalLetters", PreferenceType::String, &[], "", None, "Word said for capital letters (empty leaves it to the AT)"),
+    pref("Speech", "SpeechOverrides_LeftParen", PreferenceType::String, &[], "", None, "Word said for '(' (not implemented)"),
+    pref("Speech", "SpeechOverrides_RightParen", PreferenceType::String, &[], "", None, "Word said for ')' (not implemented)"),
+
+    enum_pref("Navigation", "NavMode", &["Enhanced", "Simple", "Character"], "Enhanced", "How navigation moves through the expression"),
+    pref("Navigation", "ResetNavMode", PreferenceType::Bool, &[], "false", None, "Reset 'NavMode' to its value in prefs.yaml for each new expression"),
+    enum_pref("Navigation", "NavGranularity", &["Off", "Character", "Token", "Term", "Full"], "Off", "What left/right move by when up/down change the granularity instead of zooming"),
+    pref("Navigation", "Overview", PreferenceType::Bool, &[], "false", None, "Speak an overview of the expression instead of the expression"),
+    pref("Navigation", "ResetOverview", PreferenceType::Bool, &[], "true", None, "Reset 'Overview' to its value in prefs.yaml for each new expression"),
+    pref("Navigation", "OverviewSize", PreferenceType::Float, &[], "12", None, "Expressions with at least this many symbols are described by their structure in an overview"),
+    enum_pref("Navigation", "NavVerbosity", &["Terse", "Medium", "Verbose"], "Medium", "How many words are said for a navigation command"),
+    pref("Navigation", "AutoZoomOut", PreferenceType::Bool, &[], "true", None, "Automatically zoom out of 2D expressions"),
+
+    enum_pref("Braille", "BrailleNavHighlight", &["Off", "FirstChar", "EndPoints", "All"], "EndPoints", "Highlight the current navigation node with dots 7 & 8"),
+    pref("Braille", "BrailleCode", PreferenceType::String, &[], "Nemeth", None, "Any supported braille code (e.g., 'Nemeth', 'UEB')"),
+    pref("Braille", "BrfLineLength", PreferenceType::Float, &[], "40", None, "Cells per line when exporting a BRF file"),
+    pref("Braille", "BrfPageLength", PreferenceType::Float, &[], "25", None, "Lines per page when exporting a BRF file (0 -- no page breaks)"),
+    pref("Braille", "UEB_DoubleStruck", PreferenceType::String, &[], "⠈", Some("UEB"), "Typeform prefix used for double-struck letters"),
+    pref("Braille", "UEB_Fraktur", PreferenceType::String, &[], "⠈", Some("UEB"), "Typeform prefix used for Fraktur letters"),
+    pref("Braille", "UEB_SansSerif", PreferenceType::String, &[], "⠈⠼", Some("UEB"), "Typeform prefix used for sans-serif letters"),
+
+    enum_pref("API", "TTS", &["None", "SSML", "SAPI5"], "None", "The speech markup used in the speech string"),
+    pref("API", "Pitch", PreferenceType::Float, &[], "0", None, "Speech pitch"),
+    pref("API", "Rate", PreferenceType::Float, &[], "180", None, "Speech rate in words per minute (should match the AT's rate)"),
+    pref("API", "Volume", PreferenceType::Float, &[], "100", None, "Speech volume"),
+    pref("API", "Voice", PreferenceType::String, &[], "none", None, "Voice to use (not implemented)"),
+    pref("API", "Gender", PreferenceType::String, &[], "none", None, "Pick any voice of the given gender (not implemented)"),
+    pref("API", "Bookmark", PreferenceType::Bool, &[], "false", None, "Include marks/bookmarks in the speech (used for sync highlighting)"),
+    pref("API", "CapitalLetters_UseWord", PreferenceType::Bool, &[], "true", None, "Say a word for capital letters"),
+    pref("API", "CapitalLetters_Pitch", PreferenceType::Float, &[], "0", None, "Pitch change for capital letters"),
+    pref("API", "CapitalLetters_Beep", PreferenceType::Bool, &[], "false", None, "Beep before capital letters"),
+    pref("API", "Text_Pitch", PreferenceType::Float, &[], "0", None, "Pitch change for text (e.g., 'such that') in the math"),
+    pref("API", "Text_Voice", PreferenceType::String, &[], "", None, "Voice for text in the math (empty uses the voice for the math)"),
+    enum_pref("API", "IntentErrorRecovery", &["IgnoreIntent", "Error"], "IgnoreIntent", "What to do when an 'intent' attribute has an error"),
+    pref("API", "FileCheckInterval", PreferenceType::Float, &[], "1", None, "Seconds between checks for changed files"),
+    pref("API", "AutoReload", PreferenceType::Bool, &[], "true", None, "Check for changed files ('false' never checks)"),
+    enum_pref("API", "ATProfile", &["None", "JAWS", "NVDA", "VoiceOver"], "None", "The screen reader the speech is given to"),
+];
+
+
 /// When looking for a file, there are up to three possible locations tracked by this type
 /// in a non-error situation, at least the first slot should be Some(...).
 ///
@@ -269,10 +716,98 @@ struct FileAndTime {
     time: Option<SystemTime>       // ~time file was read (used to see if it was updated and needs to be re-read) 
 }
 
+impl FileAndTime {
+    fn new(files: Locations, track_time: bool) -> FileAndTime {
+        return FileAndTime {
+            // use the newest file -- a region file might be newer than the language file
+            time: if cfg!(target_family = "wasm") || !track_time {
+                None
+            } else {
+                files.iter().flatten().filter_map(|file| modified_time_shim(file)).max()
+            },
+            files
+        };
+    }
+
+    /// `files` along with all the files they include (directly or indirectly) -- see `PreferenceManager::add_included_file`
+    fn all_files(&self, included_files: &HashMap<PathBuf, Vec<PathBuf>>) -> Vec<PathBuf> {
+        let mut result: Vec<PathBuf> = self.files.iter().flatten().cloned().collect();
+        let mut i = 0;
+        while i < result.len() {
+            if let Some(included) = included_files.get(&result[i]) {
+                for file in included {
+                    if !result.contains(file) {
+                        result.push(file.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        return result;
+    }
+
+    /// The newest modification time of the files and the files they include (`None` if no time could be found)
+    fn newest_time(&self, included_files: &HashMap<PathBuf, Vec<PathBuf>>) -> Option<SystemTime> {
+        return self.all_files(included_files).iter()
+                .filter_map(|file| modified_time_shim(file))
+                .max();
+    }
+
+    /// Returns true if any of the files (including the ones they include) changed since they were read.
+    /// If so, the time is updated so that the change is only reported once.
+    /// Files that can't be found (e.g., an editor is in the middle of replacing them) are ignored.
+    fn check_for_changes(&mut self, included_files: &HashMap<PathBuf, Vec<PathBuf>>) -> bool {
+        let time = match self.time {
+            None => return false,      // wasn't able to determine a time -- just claim it is up to date
+            Some(time) => time,
+        };
+        return match self.newest_time(included_files) {
+            Some(newest) if newest > time => {
+                self.time = Some(newest);
+                true
+            },
+            _ => false,
+        };
+    }
+}
+
+/// The speech files (style rules, unicode, ...) that are looked for in a language dir, in the order used by `LanguageLocations`.
+/// The speech style file isn't in the list -- it comes first.
+const LANGUAGE_FILES: [&str; 6] = ["overview.yaml", "navigate.yaml", "unicode.yaml", "unicode-full.yaml", "intent.yaml", "definitions.yaml"];
+
+/// The locations of the speech files found for a (language, speech style) pair.
+/// Finding them means probing several dirs for each file, so they are remembered for when the language is used again
+/// (e.g., switching between documents in different languages).
+/// The dirs that were searched are remembered with their modification times: if a file is added to or removed from one,
+/// its time changes and the files are searched for again.
+#[derive(Debug, Clone, Default)]
+struct LanguageLocations {
+    dir_times: Vec<(PathBuf, Option<SystemTime>)>,
+    files: Vec<Locations>,      // the style file followed by the LANGUAGE_FILES
+}
+
+impl LanguageLocations {
+    fn is_current(&self) -> bool {
+        return self.dir_times.iter().all(|(dir, time)| modified_time_shim(dir) == *time);
+    }
+}
+
+fn is_zip_file(path: &Path) -> bool {
+    return path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip")) && is_file_shim(path);
+}
+
+/// True if `code` is a BCP-47 style language code: a 2 or 3 letter language optionally followed by '-' separated subtags
+/// (e.g., "en", "tlh", "pt-br", "zh-hant-tw"). Each part is a dir in Rules/Languages (or an installed language pack).
+pub(crate) fn is_language_code(code: &str) -> bool {
+    let mut parts = code.split('-');
+    let language = parts.next().unwrap_or_default();
+    return (2..=3).contains(&language.len()) && language.chars().all(|ch| ch.is_ascii_alphabetic()) &&
+           parts.all(|part| (1..=8).contains(&part.len()) && part.chars().all(|ch| ch.is_ascii_alphanumeric()));
+}
+
 impl PartialEq for FileAndTime {
     fn eq(&self, other: &Self) -> bool {
-        // FIX: anticipating changing Locations to single PathBuf
-        return self.files[0] == other.files[0] && self.time == other.time;
+        return self.files == other.files && self.time == other.time;
     }
 }
 impl Eq for FileAndTime {}
@@ -280,11 +815,110 @@ impl Eq for FileAndTime {}
 thread_local!{
     static DEFAULT_USER_PREFERENCES: Preferences = Preferences::user_defaults();
     static DEFAULT_API_PREFERENCES: Preferences = Preferences::api_defaults();
-    static PREF_MANAGER: Rc<RefCell<PreferenceManager>> = 
-            Rc::new( RefCell::new( PreferenceManager::default() ) );
+    /// Each thread has its own PreferenceManager unless it is shared (see [`PreferenceManager::share_with_this_thread`])
+    static PREF_MANAGER: RefCell<SharedPreferences> =
+            RefCell::new( Arc::new( RwLock::new( PreferenceManager::default() ) ) );
+    /// The `PreferenceManager::change_counts` when this thread last called [`PreferenceManager::is_up_to_date`]
+    static SEEN_CHANGE_COUNTS: Cell<FileChangeCounts> = const { Cell::new([0; FILE_KINDS]) };
+    /// Used instead of the machine-wide system-prefs.yaml in tests (see [`Preferences::system_prefs_file`])
+    #[cfg(test)]
+    static TEST_SYSTEM_PREFS_FILE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    /// Used instead of the user's language packs dir in tests (see [`PreferenceManager::language_packs_dir`])
+    #[cfg(test)]
+    pub(crate) static TEST_LANGUAGE_PACKS_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    /// Used instead of the user's pronunciations.yaml in tests (see [`PreferenceManager::user_pronunciations_file`])
+    #[cfg(test)]
+    pub(crate) static TEST_PRONUNCIATIONS_FILE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// A function that is called with the name, old value, and new value of a preference whose effective value changed.
+/// A value is [`NO_PREFERENCE`] if the preference didn't (or no longer does) exist.
+/// The preferences might be shared by several threads, so the listener is called on the thread that changed them.
+pub type PreferenceListener = Box<dyn Fn(&str, &str, &str) + Send + Sync>;
+
+/// A PreferenceManager that can be used by several threads (see [`PreferenceManager::share_with_this_thread`])
+pub type SharedPreferences = Arc<RwLock<PreferenceManager>>;
+
+/// The error when a thread panicked while it had the preferences locked.
+/// The lock is cleared so that the preferences can be reset (e.g., with `set_rules_dir`).
+fn poisoned_prefs_error(pref_manager: &SharedPreferences) -> Error {
+    pref_manager.clear_poison();
+    return Error::from("The preferences might be inconsistent because a thread panicked while using them -- call 'set_rules_dir' to reset them");
+}
+
+/// Lock `pref_manager` for reading (see [`poisoned_prefs_error`] for the error).
+pub fn read_prefs(pref_manager: &SharedPreferences) -> Result<RwLockReadGuard<'_, PreferenceManager>> {
+    return pref_manager.read().map_err(|_| poisoned_prefs_error(pref_manager));
+}
+
+/// Lock `pref_manager` for reading where an error can't be returned (e.g., in the middle of generating speech).
+/// A poisoned lock is reported (see [`read_prefs`]) when speech, braille, or navigation is started, so here the preferences are used as is.
+pub fn read_prefs_unchecked(pref_manager: &SharedPreferences) -> RwLockReadGuard<'_, PreferenceManager> {
+    return pref_manager.read().unwrap_or_else(std::sync::PoisonError::into_inner);
+}
+
+/// Lock `pref_manager` for writing (see [`poisoned_prefs_error`] for the error).
+/// The preference listeners are called with the changes when the lock is released (see [`PreferencesWriteGuard`]).
+pub fn write_prefs(pref_manager: &SharedPreferences) -> Result<PreferencesWriteGuard<'_>> {
+    return match pref_manager.write() {
+        Ok(guard) => Ok( PreferencesWriteGuard(Some(guard)) ),
+        Err(_) => Err( poisoned_prefs_error(pref_manager) ),
+    };
+}
+
+/// A write lock on a [`SharedPreferences`] from [`write_prefs`].
+/// When it is dropped, the lock is released and then the listeners are called for the preferences that changed.
+/// Because the lock isn't held, the listeners can use the preferences (e.g., call `set_preference`).
+pub struct PreferencesWriteGuard<'a>(Option<RwLockWriteGuard<'a, PreferenceManager>>);
+
+impl<'a> std::ops::Deref for PreferencesWriteGuard<'a> {
+    type Target = PreferenceManager;
+    fn deref(&self) -> &PreferenceManager {
+        return self.0.as_ref().unwrap();
+    }
+}
+
+impl<'a> std::ops::DerefMut for PreferencesWriteGuard<'a> {
+    fn deref_mut(&mut self) -> &mut PreferenceManager {
+        return self.0.as_mut().unwrap();
+    }
+}
+
+impl<'a> Drop for PreferencesWriteGuard<'a> {
+    fn drop(&mut self) {
+        let mut pref_manager = self.0.take().unwrap();
+        if pref_manager.changes_to_report.is_empty() {
+            return;
+        }
+        let changes = std::mem::take(&mut pref_manager.changes_to_report);
+        let listeners = pref_manager.listeners.0.clone();
+        drop(pref_manager);
+        for (name, old_value, new_value) in changes {
+            for listener in listeners.iter() {
+                listener(&name, &old_value, &new_value);
+            }
+        }
+    }
+}
+
+/// The listeners added by [`PreferenceManager::add_listener`] (closures don't implement `Debug`)
+#[derive(Default)]
+struct PreferenceListeners(Vec<Arc<PreferenceListener>>);
 
+impl fmt::Debug for PreferenceListeners {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{} listeners", self.0.len());
+    }
 }
 
+/// The number of kinds of files in `FilesChanged`
+const FILE_KINDS: usize = 10;
+
+/// The number of times each kind of file in `FilesChanged` changed (in the order of its fields).
+/// Because a PreferenceManager can be shared, each thread remembers the counts it last saw so that
+/// the rules it read are updated even if another thread found the change.
+pub(crate) type FileChangeCounts = [usize; FILE_KINDS];
+
 /// PreferenceManager keeps track of user and api prefs along with current files
 ///
 /// If one one the `FileAndTime` files changes while the program is running, the values will auto-update
@@ -300,6 +934,7 @@ pub struct PreferenceManager {
     user_prefs: Preferences,
     api_prefs: Preferences,
     pref_files: FileAndTime,            // the "raw" user preference files (converted to 'user_prefs')
+    last_good_prefs: PrefsDocs,         // the YAML last successfully read from each of the 'pref_files'
     intent: FileAndTime,                // the intent rule style file(s)
     speech: FileAndTime,                // the speech rule style file(s)
     overview: FileAndTime,              // the overview rule file(s)
@@ -310,6 +945,22 @@ pub struct PreferenceManager {
     braille_unicode: FileAndTime,       // short braille unicode file
     braille_unicode_full: FileAndTime,  // full braille unicode file
     defs: FileAndTime,                  // the definition.yaml file(s)
+    pronunciations: FileAndTime,        // the user's pronunciations.yaml (read along with the short unicode file(s))
+    last_file_check: Option<Instant>,   // when the files were last checked for changes (see 'FileCheckInterval')
+    change_counts: FileChangeCounts,    // number of times each kind of file changed (see `is_up_to_date`)
+    included_files: HashMap<PathBuf, Vec<PathBuf>>, // files included by a file (via "include:") -- they are also checked for changes
+    reload_count: usize,                // number of times changed files were detected (see `is_up_to_date`)
+    prefs_file_change_count: usize,     // number of times the prefs files changed and were reread (see `is_up_to_date`)
+    last_reload: Option<SystemTime>,    // when changed files were last detected
+    effective_prefs: Option<HashMap<String, String>>,   // the merged prefs last reported to the listeners (None if there are no listeners)
+    listeners: PreferenceListeners,     // called when the effective value of a pref changes (see `add_listener`)
+    changes_to_report: Vec<(String, String, String)>,  // (name, old, new) for the listeners once the prefs are unlocked
+    profiles: HashMap<String, Preferences>, // named sets of user prefs from the "Profiles" section of the prefs files
+    profile: String,                    // the active profile ("" if none) -- see `set_profile`
+    profile_saved: HashMap<String, Option<Yaml>>,   // the user prefs the active profile replaced (None if it wasn't set)
+    locked: Preferences,                // prefs locked by the site administrator in system-prefs.yaml (see `apply_locks`)
+    language_locations: HashMap<(String, String), LanguageLocations>, // (languages tried, style file) -> files found (see `get_language_locations`)
+    is_copy: bool,                      // made by `with_overrides` -- the files are checked by the PreferenceManager it is a copy of
 }
 
 
@@ -317,7 +968,7 @@ pub struct PreferenceManager {
 impl fmt::Display for PreferenceManager {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "PreferenceManager:")?;
-        if self.error.is_empty() {
+        if !self.error.is_empty() {
             writeln!(f, "  not initialized!!! Error is {}", &self.error)?;
         } else {
             writeln!(f, "  user prefs:\n{}", self.user_prefs)?;
@@ -331,7 +982,7 @@ impl fmt::Display for PreferenceManager {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Debug)]
 pub struct FilesChanged {
     pub speech_rules: bool,
     pub speech_unicode_short: bool,
@@ -340,6 +991,8 @@ pub struct FilesChanged {
     pub braille_unicode_short: bool,
     pub braille_unicode_full: bool,
     pub intent: bool,
+    pub overview: bool,
+    pub navigation: bool,
     pub defs: bool
 }
 
@@ -347,13 +1000,12 @@ impl fmt::Display for FilesChanged {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "FilesChanged {{\n  Speech: rules {}, short {}, full {}", self.speech_rules, self.speech_unicode_short, self.speech_unicode_full)?;
         writeln!(f, "  Braille: rules {}, short {}, full {}", self.braille_rules, self.braille_unicode_short, self.braille_unicode_full)?;
-        writeln!(f, "  Intent {}, Defs {}", self.intent, self.defs)?;
+        writeln!(f, "  Intent {}, Overview {}, Navigation {}, Defs {}", self.intent, self.overview, self.navigation, self.defs)?;
         return Ok(());
     }
 }
 
 impl FilesChanged {
-    // FIX: this should include all files
     pub fn add_changes(&mut self, additional_changes: FilesChanged) {
         self.speech_rules |= additional_changes.speech_rules;
         self.speech_unicode_short |= additional_changes.speech_unicode_short;
@@ -362,26 +1014,93 @@ impl FilesChanged {
         self.braille_unicode_short |= additional_changes.braille_unicode_short;
         self.braille_unicode_full  |= additional_changes.braille_unicode_full;
         self.intent |= additional_changes.intent;
+        self.overview |= additional_changes.overview;
+        self.navigation |= additional_changes.navigation;
         self.defs |= additional_changes.defs;
     }
+
+    fn to_array(&self) -> [bool; FILE_KINDS] {
+        return [self.speech_rules, self.speech_unicode_short, self.speech_unicode_full,
+                self.braille_rules, self.braille_unicode_short, self.braille_unicode_full,
+                self.intent, self.overview, self.navigation, self.defs];
+    }
+
+    fn from_array(changes: [bool; FILE_KINDS]) -> FilesChanged {
+        let [speech_rules, speech_unicode_short, speech_unicode_full,
+             braille_rules, braille_unicode_short, braille_unicode_full,
+             intent, overview, navigation, defs] = changes;
+        return FilesChanged {
+            speech_rules, speech_unicode_short, speech_unicode_full,
+            braille_rules, braille_unicode_short, braille_unicode_full,
+            intent, overview, navigation, defs
+        };
+    }
+
+    fn add_to_counts(&self, counts: &mut FileChangeCounts) {
+        for (count, changed) in counts.iter_mut().zip(self.to_array()) {
+            if changed {
+                *count += 1;
+            }
+        }
+    }
+
+    fn has_changes(&self) -> bool {
+        return self.speech_rules ||
+               self.speech_unicode_short ||
+               self.speech_unicode_full ||
+               self.braille_rules ||
+               self.braille_unicode_short ||
+               self.braille_unicode_full ||
+               self.intent ||
+               self.overview ||
+               self.navigation ||
+               self.defs;
+    }
 }
 
 impl PreferenceManager {
+    /// A new PreferenceManager initialized from `rules_dir` (see [`PreferenceManager::initialize`]).
+    /// Unlike the thread's PreferenceManager, there is never an invalid one to deal with -- it is an error if the Rules can't be read.
+    pub fn try_new(rules_dir: PathBuf) -> Result<PreferenceManager> {
+        let mut pref_manager = PreferenceManager::default();
+        pref_manager.initialize(rules_dir)?;
+        return Ok(pref_manager);
+    }
+
     /// Initialize (the) PreferenceManager (a global var).
     /// 'rules_dir' is the path to "Rules" unless the env var MathCATRulesDir is set
     pub fn initialize(&mut self, rules_dir: PathBuf) -> Result<()> {
         // first, read in the preferences -- need to determine which files to read next
         // the prefs files are in the rules dir and the user dir; differs from other files
         if self.api_prefs.prefs.is_empty() {
-            self.api_prefs = Preferences{ prefs: DEFAULT_API_PREFERENCES.with(|defaults| defaults.prefs.clone()) };
+            let mut api_prefs = Preferences{ prefs: DEFAULT_API_PREFERENCES.with(|defaults| defaults.prefs.clone()) };
+            self.apply_env_prefs(&mut api_prefs, false);
+            self.api_prefs = api_prefs;
         }
 
-        match PreferenceManager::find_rules_dir(&rules_dir) {
+        let rules_dir = PreferenceManager::find_rules_dir(&rules_dir)
+                .or_else(PreferenceManager::use_built_in_rules);
+        match rules_dir {
             Ok(rules_dir) => {
-                let (user_prefs, pref_files) = Preferences::from_file(&rules_dir)?;
-                match self.set_all_files(&rules_dir, user_prefs, pref_files) {
-                    Ok(_) => {
+                let (mut user_prefs, pref_files, locked) = Preferences::from_file(&rules_dir, self.is_auto_reload(), &mut self.last_good_prefs)?;
+                self.locked = locked;
+                self.apply_env_prefs(&mut user_prefs, true);
+                self.profiles = Preferences::read_profiles(&pref_files.files, &self.last_good_prefs);
+                if !self.profile.is_empty() && !self.profiles.contains_key(&self.profile) {
+                    warn!("The profile '{}' is no longer in the prefs files -- no profile is used", self.profile);
+                    self.profile.clear();
+                }
+                self.apply_profile(&mut user_prefs);
+                self.apply_locks(&mut user_prefs);
+                let was_initialized = self.rules_dir.is_some();
+                match self.reset_all_files(&rules_dir, user_prefs, pref_files) {
+                    Ok(files_changed) => {
+                        if was_initialized {
+                            // the rules that were read (e.g., for another language) might not be valid anymore
+                            self.record_changes(&files_changed);
+                        }
                         self.error = String::new();
+                        self.notify_listeners();
                         return Ok(())
                     },
                     Err(e) => self.error = errors_to_string(&e),
@@ -391,21 +1110,372 @@ impl PreferenceManager {
                 self.error = errors_to_string(&e);
             },
         };
-        bail!("{}", self.error);
+        bail!(ErrorKind::NotInitialized(self.error.trim_end().to_string()));
+    }
+
+    /// The PreferenceManager used by this thread
+    pub fn get() -> SharedPreferences {
+        return PREF_MANAGER.with( |pf| pf.borrow().clone() );
+    }
+
+    /// Use `pref_manager` (e.g., from [`PreferenceManager::get`] on another thread) for this thread.
+    /// The threads then see the same preferences and the preference files are read once for all of them.
+    /// The rules this thread read are dropped because they might be for a different language, style, etc.
+    ///
+    /// Note: the threads need to use the same kind of file access (see [`crate::interface::set_rules_file_system`]) because that is per thread.
+    pub fn share_with_this_thread(pref_manager: SharedPreferences) {
+        let change_counts = read_prefs_unchecked(&pref_manager).change_counts;
+        PREF_MANAGER.with( |pf| *pf.borrow_mut() = pref_manager );
+        crate::speech::SpeechRules::reset_all();
+        SEEN_CHANGE_COUNTS.with( |seen| seen.set(change_counts) );
+    }
+
+    /// Exchange this thread's PreferenceManager and the change counts it has seen (see [`PreferenceManager::is_up_to_date`])
+    /// with `pref_manager` and `seen_change_counts`; calling it again undoes the exchange.
+    /// Unlike [`PreferenceManager::share_with_this_thread`], this thread's rules are not dropped -- the caller exchanges them also
+    /// (see `crate::speech::ThreadRules`).
+    pub(crate) fn swap_with_this_thread(pref_manager: &mut SharedPreferences, seen_change_counts: &mut FileChangeCounts) {
+        PREF_MANAGER.with( |pf| std::mem::swap(&mut *pf.borrow_mut(), pref_manager) );
+        SEEN_CHANGE_COUNTS.with( |seen| *seen_change_counts = seen.replace(*seen_change_counts) );
     }
 
-    pub fn get() -> Rc<RefCell<PreferenceManager>> {
-        return PREF_MANAGER.with( |pf| pf.clone() );
+    /// The number of times each kind of file changed (see [`PreferenceManager::is_up_to_date`])
+    pub(crate) fn get_change_counts(&self) -> FileChangeCounts {
+        return self.change_counts;
+    }
+
+    /// Make these preferences (e.g., from [`PreferenceManager::with_overrides`]) the next version of `old`:
+    /// the files that are different from those of `old` are counted as changed so that the rules read for `old` are updated.
+    pub(crate) fn continue_from(&mut self, old: &PreferenceManager) {
+        self.change_counts = old.change_counts;
+        if let Some(files_changed) = self.files_changed_from(old) {
+            self.record_changes(&files_changed);
+        }
+    }
+
+    /// A copy of these preferences with the values in `overrides` (name/value pairs such as ("Verbosity", "Verbose")) used instead.
+    /// This is used for a single call (e.g., to speak an expression verbosely) or for a document (see `DocumentContext`)
+    /// without changing these preferences, which might be shared with other threads.
+    /// The copy has no listeners and it doesn't check the files for changes itself -- this PreferenceManager does that.
+    /// The names and values are checked as in [`PreferenceManager::validate_preference`].
+    pub fn with_overrides(&self, overrides: &[(String, String)]) -> Result<PreferenceManager> {
+        self.check_initialized()?;
+        let mut pref_manager = PreferenceManager {
+            rules_dir: self.rules_dir.clone(),
+            user_prefs: self.user_prefs.clone(),
+            api_prefs: self.api_prefs.clone(),
+            pref_files: self.pref_files.clone(),
+            intent: self.intent.clone(),
+            speech: self.speech.clone(),
+            overview: self.overview.clone(),
+            navigation: self.navigation.clone(),
+            speech_unicode: self.speech_unicode.clone(),
+            speech_unicode_full: self.speech_unicode_full.clone(),
+            braille: self.braille.clone(),
+            braille_unicode: self.braille_unicode.clone(),
+            braille_unicode_full: self.braille_unicode_full.clone(),
+            defs: self.defs.clone(),
+            pronunciations: self.pronunciations.clone(),
+            change_counts: self.change_counts,
+            included_files: self.included_files.clone(),
+            profiles: self.profiles.clone(),
+            profile: self.profile.clone(),
+            locked: self.locked.clone(),
+            language_locations: self.language_locations.clone(),
+            is_copy: true,
+            ..PreferenceManager::default()
+        };
+        let mut rule_files_changed = false;
+        for (name, value) in overrides {
+            pref_manager.validate_preference(name, value)?;
+            if pref_manager.api_prefs.to_string(name) != NO_PREFERENCE {
+                pref_manager.set_api_pref_from_string(name, value)?;
+            } else if pref_manager.user_prefs.to_string(name) == NO_PREFERENCE {
+                bail!(ErrorKind::UnknownPreference(name.clone()));
+            } else {
+                pref_manager.user_prefs.set_string_value(name, value);
+                rule_files_changed |= RULE_FILE_PREFS.contains(&name.as_str());
+            }
+        }
+        if rule_files_changed {
+            let rules_dir = pref_manager.rules_dir.clone().unwrap();
+            let (user_prefs, pref_files) = (pref_manager.user_prefs.clone(), pref_manager.pref_files.clone());
+            pref_manager.reset_all_files(&rules_dir, user_prefs, pref_files)?;
+        }
+        return Ok(pref_manager);
+    }
+
+    /// True if `self` and `other` have the same preferences and files (e.g., to tell if a copy from [`PreferenceManager::with_overrides`] is out of date)
+    pub fn has_same_prefs_and_files(&self, other: &PreferenceManager) -> bool {
+        return self.rules_dir == other.rules_dir &&
+               self.error == other.error &&
+               self.user_prefs.prefs == other.user_prefs.prefs &&
+               self.api_prefs.prefs == other.api_prefs.prefs &&
+               self.locked.prefs == other.locked.prefs &&
+               self.files_changed_from(other).is_none();
+    }
+
+    /// The kinds of rule files that are different in `self` than in `old` (`None` if they are all the same).
+    /// A file that has been modified since `old` was made counts as different.
+    pub fn files_changed_from(&self, old: &PreferenceManager) -> Option<FilesChanged> {
+        let files_changed = FilesChanged {
+            speech_rules: self.speech != old.speech,
+            speech_unicode_short: self.speech_unicode != old.speech_unicode || self.pronunciations != old.pronunciations,
+            speech_unicode_full: self.speech_unicode_full != old.speech_unicode_full,
+            braille_rules: self.braille != old.braille,
+            braille_unicode_short: self.braille_unicode != old.braille_unicode,
+            braille_unicode_full: self.braille_unicode_full != old.braille_unicode_full,
+            intent: self.intent != old.intent,
+            overview: self.overview != old.overview,
+            navigation: self.navigation != old.navigation,
+            defs: self.defs != old.defs,
+        };
+        return if files_changed.has_changes() {Some(files_changed)} else {None};
+    }
+
+    /// Returns a description of every preference MathCAT understands (name, type, allowed values, default, ...)
+    /// so that an AT can generate a settings UI instead of hard-coding the list from the documentation.
+    pub fn get_preference_descriptions() -> &'static [PreferenceDescription] {
+        return PREFERENCE_DESCRIPTIONS;
+    }
+
+    /// Check that `name` is a known preference and that `value` is allowed for it (e.g., "Verbosity" must be "Terse", "Medium", or "Verbose").
+    /// The errors are [`ErrorKind::UnknownPreference`] and [`ErrorKind::InvalidPreferenceValue`] so that an AT can tell the user what is wrong.
+    /// Enum values are compared ignoring case.
+    /// A pref locked by the site administrator (see [`PreferenceManager::is_locked`]) can't be changed -- the error is [`ErrorKind::LockedPreference`].
+    pub fn validate_preference(&self, name: &str, value: &str) -> Result<()> {
+        if name == "Language" && !is_language_code(value) {
+            bail!(ErrorKind::InvalidPreferenceValue(name.to_string(), value.to_string(), "a language code such as 'en', 'en-gb', or 'tlh'".to_string()));
+        }
+        let locked_value = self.locked.to_string(name);
+        if locked_value != NO_PREFERENCE && !locked_value.eq_ignore_ascii_case(value.trim()) {
+            bail!(ErrorKind::LockedPreference(name.to_string(), locked_value));
+        }
+        let description = match PREFERENCE_DESCRIPTIONS.iter().find(|description| description.name == name) {
+            Some(description) => description,
+            None => {
+                // prefs.yaml can contain prefs that aren't described -- there is nothing to check for them
+                if self.api_prefs.to_string(name) == NO_PREFERENCE && self.user_prefs.to_string(name) == NO_PREFERENCE {
+                    bail!(ErrorKind::UnknownPreference(name.to_string()));
+                }
+                return Ok( () );
+            },
+        };
+        let allowed = match description.value_type {
+            PreferenceType::String => return Ok( () ),
+            PreferenceType::Float => {
+                if value.trim().parse::<f64>().is_ok_and(f64::is_finite) {
+                    return Ok( () );
+                }
+                "a number".to_string()
+            },
+            PreferenceType::Bool => {
+                if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+                    return Ok( () );
+                }
+                "'true' or 'false'".to_string()
+            },
+            PreferenceType::Enum => {
+                if description.values.iter().any(|allowed| allowed.eq_ignore_ascii_case(value)) {
+                    return Ok( () );
+                }
+                format!("one of {}", description.values.join(", "))
+            },
+        };
+        bail!(ErrorKind::InvalidPreferenceValue(name.to_string(), value.to_string(), allowed));
+    }
+
+    /// Override the values in `prefs` with the environment variables `MATHCAT_PREF_<name>` (the name is matched ignoring case).
+    /// Variables for names that aren't in `prefs` are skipped -- they are reported if `warn_unknown` is true and the name isn't an API pref.
+    /// Variables with values that aren't allowed for the preference are reported and skipped.
+    fn apply_env_prefs(&self, prefs: &mut Preferences, warn_unknown: bool) {
+        if is_using_in_memory_rules() {
+            return;     // sandboxed AT can't look at environment variables
+        }
+        for (var, value) in env::vars_os() {
+            let (var, value) = match (var.to_str(), value.to_str()) {
+                (Some(var), Some(value)) => (var.to_string(), value.to_string()),
+                _ => continue,
+            };
+            let name = match var.strip_prefix(ENV_PREF_PREFIX) {
+                None => continue,
+                Some(name) => name,
+            };
+            let name = match prefs.prefs.keys().find(|pref_name| pref_name.eq_ignore_ascii_case(name)) {
+                Some(name) => name.clone(),
+                None => {
+                    if warn_unknown && !self.api_prefs.prefs.keys().any(|pref_name| pref_name.eq_ignore_ascii_case(name)) {
+                        warn!("Ignoring the environment variable {} -- '{}' is not a known preference", var, name);
+                    }
+                    continue;
+                },
+            };
+            if let Err(e) = self.validate_preference(&name, &value) {
+                warn!("Ignoring the environment variable {}: {}", var, errors_to_string(&e));
+                continue;
+            }
+            let value = match &prefs.prefs[&name] {
+                Yaml::Real(_) if value.trim().parse::<f64>().is_ok() => Yaml::Real(value.trim().to_string()),
+                Yaml::Integer(_) if value.trim().parse::<i64>().is_ok() => Yaml::Integer(value.trim().parse::<i64>().unwrap()),
+                Yaml::Boolean(_) => Yaml::Boolean(value.trim().eq_ignore_ascii_case("true")),
+                _ => Yaml::String(value.trim().to_string()),
+            };
+            prefs.prefs.insert(name, value);
+        }
+    }
+
+    /// Returns true if the site administrator locked the pref `name` (it is in the "locked" list of system-prefs.yaml).
+    /// The user's prefs.yaml, profiles, environment variables, and [`crate::interface::set_preference`] can't change a locked pref.
+    pub fn is_locked(&self, name: &str) -> bool {
+        return self.locked.prefs.contains_key(name);
+    }
+
+    /// Set the locked prefs in `prefs` to the values the site administrator gave them
+    fn apply_locks(&self, prefs: &mut Preferences) {
+        prefs.prefs.extend(self.locked.prefs.iter().map(|(name, value)| (name.clone(), value.clone())));
+    }
+
+    /// Switch to the profile `name` (a named set of prefs in the "Profiles" section of prefs.yaml) -- "" means no profile.
+    /// The user prefs the previous profile changed are restored and then all of the prefs in the new profile are changed at once.
+    /// The rules are reread (if needed) the next time they are used.
+    pub fn set_profile(&mut self, name: &str) -> Result<()> {
+        if !self.error.is_empty() {
+            bail!("{}", self.error);
+        }
+        if !name.is_empty() && !self.profiles.contains_key(name) {
+            bail!("'{}' is not a known profile. The known profiles are: {}", name, self.get_profile_names().join(", "));
+        }
+        let mut user_prefs = std::mem::take(&mut self.user_prefs);
+        for (pref_name, value) in self.profile_saved.drain() {
+            match value {
+                None => user_prefs.prefs.remove(&pref_name),
+                Some(value) => user_prefs.prefs.insert(pref_name, value),
+            };
+        }
+        self.profile = name.to_string();
+        self.apply_profile(&mut user_prefs);
+        self.apply_locks(&mut user_prefs);
+        self.user_prefs = user_prefs;
+        self.notify_listeners();
+        if let Some(rules_dir) = self.rules_dir.clone() {
+            // the profile might change the language, speech style, or braille code
+            let files_changed = self.reset_all_files(&rules_dir, self.user_prefs.clone(), self.pref_files.clone())?;
+            self.record_changes(&files_changed);
+        }
+        return Ok( () );
+    }
+
+    /// The active profile ("" if none). See [`PreferenceManager::set_profile`].
+    pub fn get_profile(&self) -> &str {
+        return &self.profile;
+    }
+
+    /// The (sorted) names of the profiles in the prefs files
+    pub fn get_profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        return names;
+    }
+
+    /// Change `prefs` to the values in the active profile, remembering the values that were replaced
+    fn apply_profile(&mut self, prefs: &mut Preferences) {
+        self.profile_saved.clear();
+        if let Some(profile) = self.profiles.get(&self.profile) {
+            for (name, value) in &profile.prefs {
+                let old_value = prefs.prefs.insert(name.clone(), value.clone());
+                self.profile_saved.insert(name.clone(), old_value);
+            }
+        }
     }
 
     pub fn get_error(&self) -> &str {
         return &self.error;
     }
 
+    /// An `ErrorKind::NotInitialized` error if the Rules dir hasn't been (successfully) set.
+    /// Most of the other methods can only be used if this is `Ok`.
+    pub fn check_initialized(&self) -> Result<()> {
+        if !self.error.is_empty() {
+            bail!(ErrorKind::NotInitialized(self.error.trim_end().to_string()));
+        }
+        if self.rules_dir.is_none() {
+            bail!(ErrorKind::NotInitialized("the Rules directory has not been set (see 'set_rules_dir')".to_string()));
+        }
+        return Ok( () );
+    }
+
+    /// Add a function that is called whenever the effective value (see [`PreferenceManager::merge_prefs`]) of a preference changes.
+    /// This happens when a preference is set, when the user's prefs.yaml file is reread, and when the Rules dir is set.
+    ///
+    /// The listener is called after the preferences are unlocked (see [`write_prefs`]), so it can use them.
+    pub fn add_listener(&mut self, listener: PreferenceListener) {
+        self.listeners.0.push(Arc::new(listener));
+        if self.effective_prefs.is_none() {
+            self.effective_prefs = Some(self.effective_prefs_as_strings());
+        }
+    }
+
+    /// Remove all the preference listeners.
+    pub fn clear_listeners(&mut self) {
+        self.listeners.0.clear();
+        self.effective_prefs = None;
+        self.changes_to_report.clear();
+    }
+
+    fn effective_prefs_as_strings(&self) -> HashMap<String, String> {
+        let merged_prefs = Preferences{ prefs: self.merge_prefs() };
+        return merged_prefs.prefs.keys()
+                .map(|name| (name.clone(), merged_prefs.to_string(name)))
+                .collect();
+    }
+
+    /// Record each preference whose effective value changed since the listeners were last told about changes.
+    /// The listeners are called with them when the write lock is released (see [`PreferencesWriteGuard`]).
+    fn notify_listeners(&mut self) {
+        let old_prefs = match self.effective_prefs.take() {
+            None => return,         // no listeners
+            Some(prefs) => prefs,
+        };
+        let new_prefs = self.effective_prefs_as_strings();
+        let mut names: Vec<&String> = old_prefs.keys().chain(new_prefs.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let old_value = old_prefs.get(name).map_or(NO_PREFERENCE, |value| value.as_str());
+            let new_value = new_prefs.get(name).map_or(NO_PREFERENCE, |value| value.as_str());
+            if old_value != new_value {
+                self.changes_to_report.push( (name.clone(), old_value.to_string(), new_value.to_string()) );
+            }
+        }
+        self.effective_prefs = Some(new_prefs);
+    }
+
     /// Return a `PreferenceHashMap` that is the merger of the api prefs into the user prefs.
     pub fn merge_prefs(&self) -> PreferenceHashMap {
         let mut merged_prefs = self.user_prefs.prefs.clone();
         merged_prefs.extend(self.api_prefs.prefs.clone());
+        merged_prefs.extend(self.locked.prefs.clone());
+
+        // the per-structure verbosities override Verbosity unless they are 'Auto'
+        if let Some(verbosity) = merged_prefs.get("Verbosity").cloned() {
+            for &name in STRUCTURE_VERBOSITY_PREFS {
+                if let Some(value) = merged_prefs.get_mut(name) {
+                    if value.as_str().is_none_or(|value| value.eq_ignore_ascii_case("auto")) {
+                        *value = verbosity.clone();
+                    }
+                }
+            }
+            // MathSpeak has its own names for the verbosity levels
+            if let Some(value) = merged_prefs.get_mut("MathSpeak") {
+                if value.as_str().is_none_or(|value| value.eq_ignore_ascii_case("auto")) {
+                    *value = Yaml::String( match verbosity.as_str().unwrap_or_default().to_ascii_lowercase().as_str() {
+                        "verbose" => "Verbose",
+                        "terse" => "SuperBrief",
+                        _ => "Brief",
+                    }.to_string() );
+                }
+            }
+        }
         return merged_prefs;
     }
 
@@ -416,91 +1486,176 @@ impl PreferenceManager {
         let style_file_name = prefs.to_string("SpeechStyle") + "_Rules.yaml";
         // FIX: should look for other style files in the same language dir if one is not found before move to default
         
-        let language = prefs.to_string("Language");
-        let language = language.as_str();       // avoid 'temp value dropped while borrowed' error
+        let languages = PreferenceManager::get_languages(&prefs);
+        let languages = languages.iter().map(String::as_str).collect::<Vec<&str>>();
 
+        let track_time = self.is_auto_reload();
+        if self.rules_dir.as_deref() != Some(rules_dir) {
+            self.language_locations.clear();
+        }
         self.rules_dir = Some(rules_dir.to_path_buf());
         self.pref_files = pref_files;
         self.user_prefs = prefs.clone();
-        self.intent = PreferenceManager::get_file_and_time(
-            rules_dir, language, Some("en"), "intent.yaml")?;
         let mut speech_rules_dir = rules_dir.to_path_buf();
         speech_rules_dir.push("Languages");
-        self.speech = PreferenceManager::get_file_and_time(
-                        &speech_rules_dir, language, Some("en"), &style_file_name)?;
-        self.overview = PreferenceManager::get_file_and_time(
-                        &speech_rules_dir, language, Some("en"), "overview.yaml")?;
-        self.navigation = PreferenceManager::get_file_and_time(
-                        &speech_rules_dir, language, Some("en"), "navigate.yaml")?;
-
-        self.speech_unicode = PreferenceManager::get_file_and_time(
-                        &speech_rules_dir, language, Some("en"), "unicode.yaml")?;
-        self.speech_unicode_full = PreferenceManager::get_file_and_time(
-                        &speech_rules_dir, language, Some("en"), "unicode-full.yaml")?;
+        let mut files = self.get_language_locations(&speech_rules_dir, &languages, &style_file_name, track_time)?
+                            .into_iter()
+                            .map(|locations| FileAndTime::new(locations, track_time));
+        // the order is the style file followed by LANGUAGE_FILES
+        self.speech = files.next().unwrap();
+        self.overview = files.next().unwrap();
+        self.navigation = files.next().unwrap();
+        self.speech_unicode = files.next().unwrap();
+        self.speech_unicode_full = files.next().unwrap();
+        self.intent = files.next().unwrap();
+        self.defs = files.next().unwrap();
 
         let mut braille_rules_dir = rules_dir.to_path_buf();
         braille_rules_dir.push("Braille");
         let braille_code = prefs.to_string("BrailleCode");
         let braille_file = braille_code.clone() + "_Rules.yaml";
         self.braille = PreferenceManager::get_file_and_time(
-                        &braille_rules_dir, &braille_code, Some("Nemeth"), &(braille_file))?;
+                        &braille_rules_dir, &braille_code, &["Nemeth"], &(braille_file), track_time)?;
 
         self.braille_unicode = PreferenceManager::get_file_and_time(
-                        &braille_rules_dir, &braille_code, Some("Nemeth"), "unicode.yaml")?;
+                        &braille_rules_dir, &braille_code, &["Nemeth"], "unicode.yaml", track_time)?;
         self.braille_unicode_full = PreferenceManager::get_file_and_time(
-                        &braille_rules_dir, &braille_code, Some("Nemeth"), "unicode-full.yaml")?;
+                        &braille_rules_dir, &braille_code, &["Nemeth"], "unicode-full.yaml", track_time)?;
+
+        // the file is remembered even if it doesn't exist so that creating it is noticed
+        // if it is the same file, the time it was read is kept so that a change made since then is still noticed
+        let pronunciations_file = PreferenceManager::user_pronunciations_file();
+        if !track_time {
+            self.pronunciations = FileAndTime { time: None, files: [pronunciations_file, None, None] };
+        } else if self.pronunciations.time.is_none() || self.pronunciations.files[0] != pronunciations_file {
+            self.pronunciations = FileAndTime {
+                time: if cfg!(target_family = "wasm") {None} else {Some( SystemTime::now() )},
+                files: [pronunciations_file, None, None],
+            };
+        }
 
-        self.intent = PreferenceManager::get_file_and_time(
-            &speech_rules_dir, language, Some("en"), "intent.yaml")?;
-        self.defs = PreferenceManager::get_file_and_time(
-            &speech_rules_dir, language, Some("en"), "definitions.yaml")?;
+        // the included files might be newer than the files that include them
+        let included_files = &self.included_files;
+        for file_and_time in [&mut self.intent, &mut self.speech, &mut self.overview, &mut self.navigation,
+                              &mut self.speech_unicode, &mut self.speech_unicode_full,
+                              &mut self.braille, &mut self.braille_unicode, &mut self.braille_unicode_full, &mut self.defs] {
+            if file_and_time.time.is_some() {
+                file_and_time.time = file_and_time.newest_time(included_files);
+            }
+        }
         return Ok(());
     }
 
 
-    fn get_file_and_time(rules_dir: &Path, lang: &str, default_lang: Option<&str>, file_name: &str) -> Result<FileAndTime> {
-        use std::fs;
-        let files = PreferenceManager::get_files(rules_dir, lang, default_lang, file_name)?;
-        return Ok(FileAndTime {
-            time: if cfg!(target_family = "wasm") {None} else {get_metadata(&files[0])},
-            files
-        });
+    /// Call `set_all_files` and return which files changed
+    fn reset_all_files(&mut self, rules_dir: &Path, prefs: Preferences, pref_files: FileAndTime) -> Result<FilesChanged> {
+        let old_speech = self.speech.clone();
+        let old_speech_unicode= self.speech_unicode.clone();
+        let old_speech_unicode_full = self.speech_unicode_full.clone();
+        let old_braille = self.braille.clone();
+        let old_braille_unicode= self.braille_unicode.clone();
+        let old_braille_unicode_full = self.braille_unicode_full.clone();
+        let old_intent= self.intent.clone();
+        let old_overview= self.overview.clone();
+        let old_navigation= self.navigation.clone();
+        let old_defs= self.defs.clone();
+        let old_pronunciations = self.pronunciations.files.clone();
+
+        self.set_all_files(rules_dir, prefs, pref_files)?;
+        return Ok( FilesChanged {
+            speech_rules: old_speech != self.speech,
+            speech_unicode_short: old_speech_unicode != self.speech_unicode || old_pronunciations != self.pronunciations.files,
+            speech_unicode_full: old_speech_unicode_full != self.speech_unicode_full,
+            braille_rules: old_braille != self.braille,
+            braille_unicode_short: old_braille_unicode != self.braille_unicode,
+            braille_unicode_full: old_braille_unicode_full != self.braille_unicode_full,
+            intent: old_intent != self.intent,
+            overview: old_overview != self.overview,
+            navigation: old_navigation != self.navigation,
+            defs: old_defs != self.defs,
+        } );
+    }
+
+    fn get_file_and_time(rules_dir: &Path, lang: &str, fallback_langs: &[&str], file_name: &str, track_time: bool) -> Result<FileAndTime> {
+        let files = PreferenceManager::get_files(rules_dir, lang, fallback_langs, file_name)?;
+        return Ok( FileAndTime::new(files, track_time) );
+    }
+
+    /// Return the languages to look in for speech files: the `Language` pref followed by the `LanguageFallback` languages.
+    /// "en" is always last since it has all the files.
+    fn get_languages(prefs: &Preferences) -> Vec<String> {
+        let mut languages = vec![prefs.to_string("Language")];
+        let fallbacks = prefs.to_string("LanguageFallback");
+        let fallbacks = if fallbacks == NO_PREFERENCE {""} else {fallbacks.as_str()};
+        for language in fallbacks.split(',').map(|language| language.trim().to_ascii_lowercase()).chain(std::iter::once("en".to_string())) {
+            if !language.is_empty() && !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+        return languages;
+    }
+
+    /// Return the locations of `style_file_name` followed by the `LANGUAGE_FILES` for `languages[0]`
+    /// (falling back to the other `languages` in order -- see `get_languages`).
+    /// The locations are cached (see `LanguageLocations`); if `track_time` is false, the dirs aren't checked for changes.
+    fn get_language_locations(&mut self, speech_rules_dir: &Path, languages: &[&str], style_file_name: &str, track_time: bool) -> Result<Vec<Locations>> {
+        let key = (languages.join(","), style_file_name.to_string());
+        if let Some(cached) = self.language_locations.get(&key) {
+            if !track_time || cached.is_current() {
+                return Ok( cached.files.clone() );
+            }
+        }
 
-        fn get_metadata(path: &Option<PathBuf>) -> Option<SystemTime> {
-            if let Some(path) = path {
-                let metadata = fs::metadata(path);
-                if let Ok(metadata) = metadata {
-                    if let Ok(mod_time) = metadata.modified() {
-                        return Some(mod_time);
+        let files = std::iter::once(style_file_name).chain(LANGUAGE_FILES)
+                    .map(|file_name| PreferenceManager::get_files(speech_rules_dir, languages[0], &languages[1..], file_name))
+                    .collect::<Result<Vec<Locations>>>()?;
+        // the dirs searched are each language's dir (e.g., Languages/en/gb) up to Languages or the language packs dir
+        // (the language packs dir is watched so that a newly installed language is found)
+        let packs_dir = PreferenceManager::language_packs_dir();
+        let mut dirs: Vec<PathBuf> = vec![speech_rules_dir.to_path_buf()];
+        dirs.extend(packs_dir.clone());
+        for lang in languages {
+            if let Some(lang_dir) = PreferenceManager::get_language_dir(speech_rules_dir, lang) {
+                let is_watched = |dir: &Path| dir.starts_with(speech_rules_dir) || packs_dir.as_ref().is_some_and(|packs_dir| dir.starts_with(packs_dir));
+                for dir in PreferenceManager::language_search_dirs(speech_rules_dir, &lang_dir) {
+                    if is_watched(&dir) && !dirs.contains(&dir) {
+                        dirs.push(dir);
                     }
                 }
             }
-            return None;
         }
+        let dir_times = dirs.into_iter()
+                    .map(|dir| { let time = modified_time_shim(&dir); (dir, time) })
+                    .collect();
+        self.language_locations.insert(key, LanguageLocations { dir_times, files: files.clone() });
+        return Ok(files);
     }
 
-   fn get_files(rules_dir: &Path, lang: &str, default_lang: Option<&str>, file_name: &str) -> Result<Locations> {
+   fn get_files(rules_dir: &Path, lang: &str, fallback_langs: &[&str], file_name: &str) -> Result<Locations> {
         // rules_dir: is the root of the search
         //   to that we add the language dir(s)
-        //   if file_name doesn't exist in the language dir(s), we try to find it in the default dir
+        //   if file_name doesn't exist in the language dir(s), we try to find it in the fallback dirs (in order)
         // returns all the locations of the file_name from Rules downward
 
         // start by trying to find a dir that exists
         let mut lang_dir = PreferenceManager::get_language_dir(rules_dir, lang);
-        let mut default_lang = default_lang;
-        if lang_dir.is_none() {
-            // try again with the default lang if there is one
-            if default_lang.is_some() {
-                lang_dir = PreferenceManager::get_language_dir(rules_dir, default_lang.unwrap());
-                if lang_dir.is_none() {
+        let mut fallback_langs = fallback_langs;
+        while lang_dir.is_none() {
+            // try again with the next fallback lang if there is one
+            match fallback_langs.split_first() {
+                None => {
                     // We are done for -- MathCAT can't do anything without the required files!
                     bail!("Wasn't able to find/read directory for language {}\n
-                           Wasn't able to find/read MathCAT default language directory: {}",
-                          lang, rules_dir.join(default_lang.unwrap_or("")).as_os_str().to_str().unwrap());
-                }
-
-                // the default lang dir exists -- prevent retrying with it.
-                default_lang = None;
+                           Wasn't able to find/read MathCAT fallback language directories in: {}",
+                          lang, rules_dir.as_os_str().to_str().unwrap());
+                },
+                Some( (fallback_lang, rest) ) => {
+                    // if the fallback lang dir exists, this prevents retrying with it
+                    lang_dir = PreferenceManager::get_language_dir(rules_dir, fallback_lang);
+                    fallback_langs = rest;
+                },
+            }
+            if lang_dir.is_some() {
                 warn!("Couldn't find rules for language {}, ", lang)
             }
         }
@@ -511,15 +1666,12 @@ impl PreferenceManager {
         //   found files are added starting at the end
         let mut result: Locations = [None, None, None];
         let mut i = 3;
-        for os_path in lang_dir.unwrap().ancestors() {
-            let path = PathBuf::from(os_path).join(file_name);
+        for dir in PreferenceManager::language_search_dirs(rules_dir, &lang_dir.unwrap()) {
+            let path = dir.join(file_name);
             if is_file_shim(&path) {
                 i -= 1;
                 result[i] =  Some(path);
             };
-            if os_path.ends_with("Rules") {
-                break;
-            }
         }
 
         if i < 3 {
@@ -527,10 +1679,10 @@ impl PreferenceManager {
             return Ok(result);     // found at least one file
         }
 
-        if let Some(default_lang) = default_lang {
-            // didn't find a file -- retry with default
-            // FIX: give a warning that default dir is being used
-            return PreferenceManager::get_files(rules_dir, default_lang, None, file_name);
+        if let Some( (fallback_lang, rest) ) = fallback_langs.split_first() {
+            // didn't find a file -- retry with the fallbacks
+            // FIX: give a warning that a fallback dir is being used
+            return PreferenceManager::get_files(rules_dir, fallback_lang, rest, file_name);
         }
         
         // We are done for -- MathCAT can't do anything without the required files!
@@ -540,9 +1692,79 @@ impl PreferenceManager {
             rules_dir.to_str().unwrap(), lang, file_name);
     }
 
+    /// The dirs to search (deepest first) for the files of the language whose dir is `lang_dir`: it and its parents up to the Rules dir.
+    /// An installed language pack (see [`PreferenceManager::language_packs_dir`]) isn't in the Rules dir,
+    /// so its dirs are followed by `rules_dir` (the Languages dir) and the Rules dir.
+    /// The dir of a language in the Rules dir is used in place of the installed one (e.g., 'Languages/pt' for a 'pt-br' pack).
+    fn language_search_dirs(rules_dir: &Path, lang_dir: &Path) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+        let mut ancestors = lang_dir.ancestors();
+        if let Some(packs_dir) = PreferenceManager::language_packs_dir() {
+            if lang_dir.starts_with(&packs_dir) && !lang_dir.starts_with(rules_dir) {
+                for dir in ancestors.take_while(|dir| *dir != packs_dir) {
+                    let rules_lang_dir = rules_dir.join(dir.strip_prefix(&packs_dir).unwrap());
+                    dirs.push( if is_dir_shim(&rules_lang_dir) {rules_lang_dir} else {dir.to_path_buf()} );
+                }
+                ancestors = rules_dir.ancestors();
+            }
+        }
+        for dir in ancestors {
+            dirs.push(dir.to_path_buf());
+            if dir.ends_with("Rules") {
+                break;
+            }
+        }
+        return dirs;
+    }
+
+    /// The user's pronunciations file: `pronunciations.yaml` in the MathCAT dir of the user's config dir (the dir with the user's prefs.yaml).
+    /// It maps characters and tokens to the words to say for them (e.g., `ℏ: h bar`) and overrides the language's unicode files.
+    /// None is returned if the Rules are in memory (see [`crate::interface::set_rules_zip`]).
+    pub fn user_pronunciations_file() -> Option<PathBuf> {
+        #[cfg(test)]
+        if let Some(file) = TEST_PRONUNCIATIONS_FILE.with(|file| file.borrow().clone()) {
+            return Some(file);
+        }
+        if is_using_in_memory_rules() {
+            return None;
+        }
+        return dirs::config_dir().map(|dir| dir.join("MathCAT").join("pronunciations.yaml"));
+    }
+
+    /// The dir where language packs (Rules for languages that aren't in the Rules dir) are installed:
+    /// "MathCAT/Languages" in the user's config dir. There isn't one if the Rules are sandboxed (e.g., in-memory Rules).
+    /// A language in the Rules dir is always used in preference to an installed one.
+    pub fn language_packs_dir() -> Option<PathBuf> {
+        #[cfg(test)]
+        if let Some(dir) = TEST_LANGUAGE_PACKS_DIR.with(|dir| dir.borrow().clone()) {
+            return Some(dir);
+        }
+        if is_using_in_memory_rules() {
+            return None;
+        }
+        return dirs::config_dir().map(|dir| dir.join("MathCAT").join("Languages"));
+    }
+
     fn get_language_dir(rules_dir: &Path, lang: &str) -> Option<PathBuf> {
         // return 'Rules/Language/fr', 'Rules/Language/en/gb', etc, if they exist.
+        // if the language (or its region) isn't in the Rules dir, an installed language pack is used (if there is one)
         // fall back to main language, and then to default_dir if language dir doesn't exist
+        let main_lang = lang.split('-').next().unwrap_or_default();
+        if let Some(packs_dir) = PreferenceManager::language_packs_dir() {
+            if !main_lang.is_empty() && packs_dir != rules_dir {
+                if !is_dir_shim(&rules_dir.join(main_lang)) {
+                    if is_dir_shim(&packs_dir.join(main_lang)) {
+                        return PreferenceManager::get_language_dir(&packs_dir, lang);
+                    }
+                } else {
+                    // a regional pack (e.g., 'pt-br') for a language in the Rules dir
+                    let lang_path: PathBuf = lang.split('-').collect();
+                    if !is_dir_shim(&rules_dir.join(&lang_path)) && is_dir_shim(&packs_dir.join(&lang_path)) {
+                        return Some(packs_dir.join(lang_path));
+                    }
+                }
+            }
+        }
         let mut full_path = rules_dir.to_path_buf();
         let lang_parts = lang.split('-');
         for part in lang_parts {
@@ -562,39 +1784,105 @@ impl PreferenceManager {
     
     fn find_rules_dir(rules_dir: &Path) -> Result<PathBuf> {
         let mut bad_env_value = String::default();
+        if is_using_in_memory_rules() {
+            // sandboxed AT can't look at environment variables
+            if is_dir_shim(rules_dir) {
+                return Ok(PathBuf::from(rules_dir));
+            }
+            bail!("The in-memory Rules don't contain the rules dir {}", rules_dir.to_str().unwrap_or("rules dir is none???"));
+        }
         if let Ok(env_var) = env::var("MathCATRulesDir") {
             let path_buf = PathBuf::from(&env_var);
             if is_dir_shim(&path_buf) {
                 return Ok(path_buf);
             }
-            bad_env_value = format!("MathCATRulesDir value {} is not a directory -- ignoring\n", &env_var);
+            if is_zip_file(&path_buf) {
+                return PreferenceManager::use_zipped_rules(&path_buf);
+            }
+            bad_env_value = format!("MathCATRulesDir value {} is not a directory or zip file -- ignoring\n", &env_var);
             warn!("{}", &bad_env_value);
         }
         
         if is_dir_shim(rules_dir) {
             return Ok(PathBuf::from(rules_dir));
         };
+        if is_zip_file(rules_dir) {
+            return PreferenceManager::use_zipped_rules(rules_dir);
+        }
+        // an installer can ship a single "Rules.zip" file in place of the "Rules" dir
+        if rules_dir.extension().is_none() {
+            let zip_file = rules_dir.with_extension("zip");
+            if is_zip_file(&zip_file) {
+                return PreferenceManager::use_zipped_rules(&zip_file);
+            }
+        }
 
         // we are done for -- can't do anything without a rules dir
         bail!("MathCAT could not find a rules dir -- something failed in installation?\n{}Could not find rules dir in {} or lacking permissions to read the dir!",
                     &bad_env_value, rules_dir.to_str().unwrap_or("rules dir is none???"));
     }
 
+    /// Read the Rules from `zip_file` (the files in it should be in a "Rules" dir) and return the Rules dir to use.
+    /// The style, unicode, and definition files are found in the zip file the same way as they are in a Rules dir.
+    fn use_zipped_rules(zip_file: &Path) -> Result<PathBuf> {
+        set_zipped_rules_file(zip_file)?;
+        return Ok(PathBuf::from("Rules"));
+    }
+
+    /// With the `built-in-rules` feature, the Rules built into the library (`ZIPPED_RULE_FILES`) are used as the last resort
+    /// when no Rules dir was found (e.g., a sandboxed app where the Rules dir can't be installed or read).
+    /// Otherwise, `error` (the reason no Rules dir was found) is returned.
+    fn use_built_in_rules(error: Error) -> Result<PathBuf> {
+        if !cfg!(feature = "built-in-rules") || is_using_in_memory_rules() {
+            return Err(error);
+        }
+        warn!("{}\nUsing the Rules built into MathCAT", errors_to_string(&error));
+        if let Err(e) = set_in_memory_rules(ZIPPED_RULE_FILES) {
+            return Err(e).chain_err(|| errors_to_string(&error));
+        }
+        return PreferenceManager::find_rules_dir(Path::new("Rules"));
+    }
+
+    /// Return the files that changed since this thread last called this (`None` if nothing changed).
+    ///
+    /// Checking requires a `stat` of every file, so this is done at most once every `FileCheckInterval` seconds.
+    /// If `AutoReload` is false, the files are never checked.
     pub fn is_up_to_date(&mut self) -> Option<FilesChanged> {
+        // changes due to re-initializing (maybe by another thread) are reported even if the files aren't checked
+        if !self.is_auto_reload() || self.is_copy {
+            return self.unseen_changes();
+        }
+        if !cfg!(target_family = "wasm") {      // wasm doesn't have a clock (and doesn't check file times)
+            let now = Instant::now();
+            if let Some(last_check) = self.last_file_check {
+                if now.duration_since(last_check) < self.file_check_interval() {
+                    return self.unseen_changes();
+                }
+            }
+            self.last_file_check = Some(now);
+        }
+
         // this will work even if self is invalid
+        let included_files = &self.included_files;
         let mut files_changed = FilesChanged {
-            speech_rules: !PreferenceManager::is_file_up_to_date(&self.speech),
-            speech_unicode_short: !PreferenceManager::is_file_up_to_date(&self.speech_unicode),
-            speech_unicode_full: !PreferenceManager::is_file_up_to_date(&self.speech_unicode_full),
-            braille_rules: !PreferenceManager::is_file_up_to_date(&self.braille),
-            braille_unicode_short: !PreferenceManager::is_file_up_to_date(&self.braille_unicode),
-            braille_unicode_full: !PreferenceManager::is_file_up_to_date(&self.braille_unicode_full),
-            intent: !PreferenceManager::is_file_up_to_date(&self.intent),
-            defs: !PreferenceManager::is_file_up_to_date(&self.defs),
+            speech_rules: self.speech.check_for_changes(included_files),
+            // the user's pronunciations are read along with the short unicode file(s)
+            speech_unicode_short: self.speech_unicode.check_for_changes(included_files) | self.pronunciations.check_for_changes(included_files),
+            speech_unicode_full: self.speech_unicode_full.check_for_changes(included_files),
+            braille_rules: self.braille.check_for_changes(included_files),
+            braille_unicode_short: self.braille_unicode.check_for_changes(included_files),
+            braille_unicode_full: self.braille_unicode_full.check_for_changes(included_files),
+            intent: self.intent.check_for_changes(included_files),
+            overview: self.overview.check_for_changes(included_files),
+            navigation: self.navigation.check_for_changes(included_files),
+            defs: self.defs.check_for_changes(included_files),
         };
+        let mut is_reload = files_changed.has_changes();
 
-        if !PreferenceManager::is_file_up_to_date(&self.pref_files) {
-            let old_lang = self.user_prefs.to_string("Language");
+        if self.pref_files.check_for_changes(&HashMap::new()) {
+            is_reload = true;
+            self.prefs_file_change_count += 1;
+            let old_lang = PreferenceManager::get_languages(&self.user_prefs);
             let old_speech_style = self.user_prefs.to_string("SpeechStyle");
             let old_braille_code = self.user_prefs.to_string("BrailleCode");
             match self.initialize(self.rules_dir.clone().unwrap()) {
@@ -603,10 +1891,12 @@ impl PreferenceManager {
                     if old_speech_style != self.user_prefs.to_string("SpeechStyle") {
                         files_changed.speech_rules = true;
                     }
-                    if old_lang != self.user_prefs.to_string("Language") {
+                    if old_lang != PreferenceManager::get_languages(&self.user_prefs) {
                         files_changed.speech_rules = true;
                         files_changed.speech_unicode_short = true;
                         files_changed.speech_unicode_full = true;
+                        files_changed.overview = true;
+                        files_changed.navigation = true;
                     }
                     if old_braille_code != self.user_prefs.to_string("BrailleCode") {
                         files_changed.braille_rules = true;
@@ -616,47 +1906,90 @@ impl PreferenceManager {
                 }
             } 
         }
-        if files_changed.speech_rules ||
-           files_changed.speech_unicode_short ||
-           files_changed.speech_unicode_full ||
-           files_changed.braille_rules ||
-           files_changed.braille_unicode_short ||
-           files_changed.braille_unicode_full ||
-           files_changed.intent ||
-           files_changed.defs {
-            return Some(files_changed);
-        } else {
-            return None;
+        if is_reload {
+            self.reload_count += 1;
+            self.last_reload = Some(SystemTime::now());
         }
+        self.record_changes(&files_changed);
+        return self.unseen_changes();
     }
 
-    fn is_file_up_to_date(ft: &FileAndTime) -> bool {
-        if ft.time.is_none() {
-            // wasn't able to determine a time -- just claim it is up to date
-            return true;
-        }
-        let time = ft.time.unwrap();
-        return  is_older(&ft.files[0], time) &&
-                is_older(&ft.files[1], time) &&
-                is_older(&ft.files[2], time);
-
-        fn is_older(path: &Option<PathBuf>, time: SystemTime) -> bool {
-            // if let Some(path_buf) = path {
-            //     debug!("p={}", path_buf.to_str().unwrap());
-            //     debug!("p.metadata={:?}", path_buf.metadata());
-            //     debug!("p.metadata.modified={:?}", path_buf.metadata().unwrap().modified());
-            //     debug!("p.metadata.modified.duration_since={:?}", path_buf.metadata().unwrap().modified().unwrap().duration_since(time));    
-            // }
-            return match path {
-                Some(p) => {
-                    let file_mod_time = p.metadata().unwrap().modified().unwrap();
-                    return file_mod_time <= time;
-                },
-                None => true,
-            }           
+    /// Record that the files in `files_changed` changed so that `is_up_to_date` reports them (on every thread using this PreferenceManager)
+    fn record_changes(&mut self, files_changed: &FilesChanged) {
+        files_changed.add_to_counts(&mut self.change_counts);
+    }
+
+    /// The files that changed since this thread last called `is_up_to_date`
+    fn unseen_changes(&self) -> Option<FilesChanged> {
+        let seen = SEEN_CHANGE_COUNTS.with( |seen| seen.replace(self.change_counts) );
+        let files_changed = FilesChanged::from_array(std::array::from_fn(|i| seen[i] != self.change_counts[i]));
+        return if files_changed.has_changes() {Some(files_changed)} else {None};
+    }
+
+    /// Record that `including_file` includes `included_file` (via "include:") so that changes to it are noticed by `is_up_to_date`.
+    /// This is called when the included file is read.
+    pub fn add_included_file(&mut self, including_file: &Path, included_file: &Path) {
+        let included = self.included_files.entry(including_file.to_path_buf()).or_default();
+        if !included.iter().any(|file| file == included_file) {
+            included.push(included_file.to_path_buf());
+        }
+
+        // the included file was just read, so a newer modification time is not a change
+        let mod_time = modified_time_shim(included_file);
+        let included_files = &self.included_files;
+        for file_and_time in [&mut self.intent, &mut self.speech, &mut self.overview, &mut self.navigation,
+                              &mut self.speech_unicode, &mut self.speech_unicode_full,
+                              &mut self.braille, &mut self.braille_unicode, &mut self.braille_unicode_full, &mut self.defs] {
+            if file_and_time.time.is_some() && file_and_time.all_files(included_files).iter().any(|file| file == including_file) {
+                file_and_time.time = file_and_time.time.max(mod_time);
+            }
         }
     }
 
+    /// The number of times that `is_up_to_date` found changed rule or preference files (which are then re-read).
+    pub fn get_reload_count(&self) -> usize {
+        return self.reload_count;
+    }
+
+    /// The number of times that `is_up_to_date` found that the prefs files (e.g., the user's prefs.yaml) changed and reread them.
+    pub fn get_prefs_file_change_count(&self) -> usize {
+        return self.prefs_file_change_count;
+    }
+
+    /// When `is_up_to_date` last found changed rule or preference files (`None` if that hasn't happened).
+    pub fn get_last_reload_time(&self) -> Option<SystemTime> {
+        return self.last_reload;
+    }
+
+    /// False if the files should be treated as unchanging (e.g., a read-only deployment), so no times are tracked.
+    /// In-memory rules never change.
+    pub(crate) fn is_auto_reload(&self) -> bool {
+        return !is_using_in_memory_rules() && self.get_api_bool_pref("AutoReload");
+    }
+
+    fn file_check_interval(&self) -> Duration {
+        return match self.api_prefs.to_float("FileCheckInterval") {
+            // an interval that is too long for a Duration (e.g., 1e30) means the files are (practically) never checked
+            Some(seconds) if seconds >= 0.0 => Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX),
+            _ => {
+                warn!("FileCheckInterval ('{}') is not a non-negative number of seconds", self.api_prefs.to_string("FileCheckInterval"));
+                Duration::ZERO
+            }
+        };
+    }
+
+    /// Return the file locations of the rules for the speech style `style` (e.g., "SimpleSpeak") in the current language.
+    /// This is used to speak with a style other than the `SpeechStyle` pref.
+    pub fn get_style_rule_file(&self, style: &str) -> Result<Locations> {
+        let rules_dir = match &self.rules_dir {
+            Some(rules_dir) => rules_dir.join("Languages"),
+            None => bail!("Internal error: get_style_rule_file called before the Rules directory was set"),
+        };
+        let languages = PreferenceManager::get_languages(&self.user_prefs);
+        let languages = languages.iter().map(String::as_str).collect::<Vec<&str>>();
+        return PreferenceManager::get_files(&rules_dir, languages[0], &languages[1..], &(style.to_string() + "_Rules.yaml"));
+    }
+
     /// Return the speech rule style file locations.
     pub fn get_rule_file(&self, name: &RulesFor) -> &Locations {
         if !self.error.is_empty() {
@@ -672,12 +2005,12 @@ impl PreferenceManager {
         };
     }
 
-    /// Return the unicode.yaml file locations.
-    pub fn get_speech_unicode_file(&self) ->(PathBuf, PathBuf) {
+    /// Return the unicode.yaml and unicode-full.yaml file locations.
+    pub fn get_speech_unicode_file(&self) -> (&Locations, &Locations) {
         if !self.error.is_empty() {
             panic!("Internal error: get_speech_unicode_file called on invalid PreferenceManager -- error message\n{}", &self.error);
         };
-        return (self.speech_unicode.files[0].as_ref().unwrap().to_path_buf(), self.speech_unicode_full.files[0].as_ref().unwrap().to_path_buf());
+        return (&self.speech_unicode.files, &self.speech_unicode_full.files);
     }
 
     /// Return the speech rule style file locations.
@@ -689,13 +2022,18 @@ impl PreferenceManager {
         return &self.braille.files;
     }
 
-    /// Return the unicode.yaml file locations.
-    pub fn get_braille_unicode_file(&self) -> (PathBuf, PathBuf) {
+    /// Return the unicode.yaml and unicode-full.yaml file locations.
+    pub fn get_braille_unicode_file(&self) -> (&Locations, &Locations) {
         if !self.error.is_empty() {
             panic!("Internal error: get_braille_unicode_file called on invalid PreferenceManager -- error message\n{}", &self.error);
         };
 
-        return (self.braille_unicode.files[0].as_ref().unwrap().to_path_buf(), self.braille_unicode_full.files[0].as_ref().unwrap().to_path_buf());
+        return (&self.braille_unicode.files, &self.braille_unicode_full.files);
+    }
+
+    /// Return the user's pronunciations.yaml file (see [`PreferenceManager::user_pronunciations_file`]) if it exists.
+    pub fn get_pronunciations_file(&self) -> Option<&PathBuf> {
+        return self.pronunciations.files[0].as_ref().filter(|file| is_file_shim(file));
     }
 
     /// Return the definitions.yaml file locations.
@@ -707,6 +2045,19 @@ impl PreferenceManager {
         return &self.defs.files;
     }
 
+    /// Return the file for the `ATProfile` preference (Rules/ATProfiles/<ATProfile>.yaml) or `None` if no profile is in use.
+    pub fn get_at_profile_file(&self) -> Option<PathBuf> {
+        if !self.error.is_empty() {
+            panic!("Internal error: get_at_profile_file called on invalid PreferenceManager -- error message\n{}", &self.error);
+        };
+
+        let profile = self.api_prefs.to_string("ATProfile");
+        if profile == NO_PREFERENCE || profile.eq_ignore_ascii_case("none") {
+            return None;
+        }
+        return self.rules_dir.as_ref().map(|rules_dir| rules_dir.join("ATProfiles").join(profile + ".yaml"));
+    }
+
     /// Return the TTS engine currently in use.
     pub fn get_tts(&self) -> TTS {
         if !self.error.is_empty() {
@@ -724,6 +2075,26 @@ impl PreferenceManager {
         }
     }
 
+    /// Set the API preference `name` to `value`, converting it to a number or boolean for the prefs that have those types.
+    /// The value should have been checked with [`PreferenceManager::validate_preference`].
+    pub fn set_api_pref_from_string(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "Pitch" | "Rate" | "Volume" | "CapitalLetters_Pitch" | "Text_Pitch" | "FileCheckInterval" => {
+                match value.trim().parse::<f64>() {
+                    Ok(value) => self.set_api_float_pref(name, value),
+                    Err(_) => bail!("SetPreference: preference'{}'s value '{}' must be a float", name, value),
+                };
+            },
+            "Bookmark" | "CapitalLetters_UseWord" | "CapitalLetters_Beep" | "AutoReload" => {
+                self.set_api_bool_pref(name, value.to_lowercase()=="true");
+            },
+            _ => {
+                self.set_api_string_pref(name, value);
+            }
+        }
+        return Ok( () );
+    }
+
     /// Set the string-valued preference.
     pub fn set_api_string_pref(&mut self, key: &str, value: &str) {
         if !self.error.is_empty() {
@@ -731,6 +2102,7 @@ impl PreferenceManager {
         };
 
         self.api_prefs.prefs.insert(key.to_string(), Yaml::String(value.to_string()));
+        self.notify_listeners();
     }
 
     /// Set the number-valued preference.
@@ -741,14 +2113,54 @@ impl PreferenceManager {
         };
 
         self.api_prefs.prefs.insert(key.to_string(), Yaml::Real(value.to_string()));
+        self.notify_listeners();
+    }
+
+    /// Set the integer-valued preference.
+    pub fn set_api_int_pref(&mut self, key: &str, value: i64) {
+        if !self.error.is_empty() {
+            panic!("Internal error: set_api_int_pref called on invalid PreferenceManager -- error message\n{}", &self.error);
+        };
+
+        self.api_prefs.prefs.insert(key.to_string(), Yaml::Integer(value));
+        self.notify_listeners();
     }
 
-    pub fn set_api_boolean_pref(&mut self, key: &str, value: bool) {
+    /// Set the boolean-valued preference.
+    pub fn set_api_bool_pref(&mut self, key: &str, value: bool) {
         if !self.error.is_empty() {
-            panic!("Internal error: set_api_boolean_pref called on invalid PreferenceManager -- error message\n{}", &self.error);
+            panic!("Internal error: set_api_bool_pref called on invalid PreferenceManager -- error message\n{}", &self.error);
         };
 
         self.api_prefs.prefs.insert(key.to_string(), Yaml::Boolean(value));
+        self.notify_listeners();
+    }
+
+    /// Return the value of the boolean-valued API preference `key`.
+    /// If the value isn't a boolean, a warning is given and the default value is used (false if there is no default).
+    pub fn get_api_bool_pref(&self, key: &str) -> bool {
+        return self.api_prefs.to_bool(key).unwrap_or_else(|| {
+            warn!("API preference {} ('{}') is not 'true' or 'false'", key, self.api_prefs.to_string(key));
+            DEFAULT_API_PREFERENCES.with(|defaults| defaults.to_bool(key).unwrap_or(false))
+        });
+    }
+
+    /// Return the value of the integer-valued API preference `key`.
+    /// If the value isn't a whole number, a warning is given and the default value is used (0 if there is no default).
+    pub fn get_api_int_pref(&self, key: &str) -> i64 {
+        return self.api_prefs.to_int(key).unwrap_or_else(|| {
+            warn!("API preference {} ('{}') is not an integer", key, self.api_prefs.to_string(key));
+            DEFAULT_API_PREFERENCES.with(|defaults| defaults.to_int(key).unwrap_or(0))
+        });
+    }
+
+    /// Return the value of the number-valued API preference `key`.
+    /// If the value isn't a number, a warning is given and the default value is used (0 if there is no default).
+    pub fn get_api_float_pref(&self, key: &str) -> f64 {
+        return self.api_prefs.to_float(key).unwrap_or_else(|| {
+            warn!("API preference {} ('{}') can't be converted to a floating point number", key, self.api_prefs.to_string(key));
+            DEFAULT_API_PREFERENCES.with(|defaults| defaults.to_float(key).unwrap_or(0.0))
+        });
     }
 
     /// Return the current speech rate.
@@ -757,11 +2169,19 @@ impl PreferenceManager {
             panic!("Internal error: get_rate called on invalid PreferenceManager -- error message\n{}", &self.error);
         };
 
-        return match &self.api_prefs.to_string("Rate").parse::<f64>() {
-            Ok(val) => *val,
-            Err(_) => {
-                warn!("Rate ('{}') can't be converted to a floating point number", &self.api_prefs.to_string("Rate"));
-                DEFAULT_API_PREFERENCES.with(|defaults| defaults.prefs["Rate"].as_f64().unwrap())
+        return self.get_api_float_pref("Rate");
+    }
+
+    /// Return the rate (words/min) that math is spoken at when the surrounding speech is spoken at `host_rate`.
+    /// `MathRate` is a percentage of the host rate (e.g., 80 is 20% slower than normal speech).
+    /// If `host_rate` isn't positive (e.g., the AT doesn't know it), the `Rate` pref is used as the host rate.
+    pub fn get_effective_rate(&self, host_rate: f64) -> f64 {
+        let host_rate = if host_rate > 0.0 {host_rate} else {self.get_rate()};
+        return match self.user_prefs.to_float("MathRate") {
+            Some(percent) if percent > 0.0 => host_rate * percent / 100.0,
+            _ => {
+                warn!("MathRate ('{}') must be a positive number", self.user_prefs.to_string("MathRate"));
+                host_rate
             }
         };
     }
@@ -788,35 +2208,345 @@ impl PreferenceManager {
         if !self.error.is_empty() {
             panic!("Internal error: set_user_prefs called on invalid PreferenceManager -- error message\n{}", &self.error);
         };
+        if self.is_locked(name) {
+            return None;    // e.g., navigation toggling a locked pref -- the site administrator's value is kept
+        }
 
         self.user_prefs.set_string_value(name, value);
-        if name == "Language" || name == "SpeechStyle" || name == "BrailleCode" {
-            let old_speech = self.speech.clone();
-            let old_speech_unicode= self.speech_unicode.clone();
-            let old_speech_unicode_full = self.speech_unicode_full.clone();
-            let old_braille = self.braille.clone();
-            let old_braille_unicode= self.braille_unicode.clone();
-            let old_braille_unicode_full = self.braille_unicode_full.clone();
-            let old_intent= self.intent.clone();
-            let old_defs= self.defs.clone();
-
+        self.notify_listeners();
+        if RULE_FILE_PREFS.contains(&name) {
             if let Some(rules_dir) = self.rules_dir.clone() {
-                self.set_all_files(&rules_dir, self.user_prefs.clone(), self.pref_files.clone()).unwrap();
-                let changed = FilesChanged {
-                    speech_rules: old_speech != self.speech,
-                    speech_unicode_short: old_speech_unicode != self.speech_unicode,
-                    speech_unicode_full: old_speech_unicode_full != self.speech_unicode_full,
-                    braille_rules: old_braille != self.braille,
-                    braille_unicode_short: old_braille_unicode != self.braille_unicode,
-                    braille_unicode_full: old_braille_unicode_full != self.braille_unicode_full,
-                    intent: old_intent != self.intent,
-                    defs: old_defs != self.defs,
-                };
+                let changed = self.reset_all_files(&rules_dir, self.user_prefs.clone(), self.pref_files.clone()).unwrap();
+                // the caller updates this thread's rules -- other threads sharing the prefs find out via `is_up_to_date`
+                self.record_changes(&changed);
+                SEEN_CHANGE_COUNTS.with(|seen| {
+                    let mut counts = seen.get();
+                    changed.add_to_counts(&mut counts);
+                    seen.set(counts);
+                });
                 return Some(changed);
             }
         }
         return None;
     }
+
+
+    /// Write the user prefs that differ from the system defaults (Rules/prefs.yaml) to the user's prefs.yaml file
+    /// (the MathCAT subdirectory of the user's config dir) so that changes made at runtime persist.
+    /// Prefs that are already in the user's file are kept even if they have the default value.
+    /// If the file already exists, only the lines of prefs whose value changed are replaced and new prefs are added to the end of their section,
+    /// so the other lines (including the user's comments and profiles) are kept. Otherwise, the prefs are grouped into the Speech, Navigation,
+    /// and Braille sections and each one is commented with its description.
+    /// Only the prefs listed by [`PreferenceManager::get_preference_descriptions`] are written.
+    /// Returns the name of the file that was written.
+    pub fn save_user_prefs(&mut self) -> Result<PathBuf> {
+        if !self.error.is_empty() {
+            bail!("{}", self.error);
+        }
+        let rules_dir = match &self.rules_dir {
+            None => bail!("save_user_prefs: the Rules directory has not been set"),
+            Some(rules_dir) => rules_dir.clone(),
+        };
+        // sandboxed AT can't look at the user's config dir
+        let user_dir = if is_using_in_memory_rules() {None} else {dirs::config_dir()};
+        let mut user_prefs_file = user_dir.ok_or("save_user_prefs: there is no user config directory")?;
+        user_prefs_file.push("MathCAT/prefs.yaml");
+        self.write_user_prefs(&user_prefs_file)?;
+        // start tracking the file (it might not have existed) -- this is read after the file was written, so it isn't re-read
+        self.pref_files = Preferences::get_prefs_file_and_time(&rules_dir, self.is_auto_reload());
+        return Ok(user_prefs_file);
+    }
+
+    fn write_user_prefs(&mut self, file: &Path) -> Result<()> {
+        let system_prefs = DEFAULT_USER_PREFERENCES.with(|defaults|
+            Preferences::read_file(&self.pref_files.files[0], defaults.clone(), &mut self.last_good_prefs))?;
+        let saved_prefs = if is_file_shim(file) {
+            Preferences::read_file(&Some(file.to_path_buf()), Preferences::default(), &mut self.last_good_prefs)?
+        } else {
+            Preferences::default()
+        };
+
+        // the values changed by the active profile aren't saved
+        let mut user_prefs = self.user_prefs.clone();
+        for (name, value) in &self.profile_saved {
+            match value {
+                None => user_prefs.prefs.remove(name),
+                Some(value) => user_prefs.prefs.insert(name.clone(), value.clone()),
+            };
+        }
+
+        let categories = ["Speech", "Navigation", "Braille"].map(|category| {
+            let prefs = PREFERENCE_DESCRIPTIONS.iter()
+                .filter(|description| description.category == category)
+                .filter_map(|description| {
+                    let value = user_prefs.to_string(description.name);
+                    if value == NO_PREFERENCE || self.is_locked(description.name) ||
+                       (!saved_prefs.prefs.contains_key(description.name) && is_same_value(description, &value, &system_prefs.to_string(description.name))) {
+                        return None;
+                    }
+                    return Some( (description, value) );
+                })
+                .collect::<Vec<PrefToWrite>>();
+            return (category, prefs);
+        });
+
+        // if the user's file is good, only the lines that change are written so the user's comments (and profiles) are kept
+        let file_name = file.to_str().unwrap();
+        let old_contents = read_to_string_shim(file).ok()
+            .filter(|contents| matches!(Preferences::parse_prefs_doc(&Ok(contents.clone()), file_name), Ok(Ok(_))));
+        let contents = match old_contents {
+            Some(old_contents) => edit_user_prefs(&old_contents, &categories, &saved_prefs),
+            None => {
+                let mut contents = "---\n# MathCAT user preferences -- these override the values in the Rules directory's prefs.yaml\n".to_string();
+                contents += &format!("Version: {}{}\n", PREFS_VERSION, VERSION_COMMENT);
+                for (category, prefs) in &categories {
+                    if prefs.is_empty() {
+                        contents += &format!("{}: {{}}\n", category);
+                    } else {
+                        contents += &format!("{}:\n{}", category, pref_lines(prefs, "  "));
+                    }
+                }
+
+                // keep the user's profiles (the file might be in the middle of being written, so the last good ones are used)
+                let profiles = self.last_good_prefs.get(file).map(|doc| doc["Profiles"].clone());
+                if let Some(profiles) = profiles.filter(|profiles| !profiles.is_badvalue()) {
+                    let mut section = yaml_rust::yaml::Hash::new();
+                    section.insert(Yaml::String("Profiles".to_string()), profiles);
+                    let mut yaml = String::new();
+                    yaml_rust::YamlEmitter::new(&mut yaml).dump(&Yaml::Hash(section))
+                        .chain_err(|| format!("while writing the profiles to {}", file.display()))?;
+                    contents += yaml.trim_start_matches("---\n");
+                    contents += "\n";
+                }
+                contents
+            },
+        };
+
+        if let Some(dir) = file.parent() {
+            create_dir_all_shim(dir).chain_err(|| format!("while creating the directory {}", dir.display()))?;
+        }
+        write_shim(file, contents.as_bytes()).chain_err(|| format!("while writing the user preferences to {}", file.display()))?;
+        return Ok( () );
+    }
+}
+
+/// A pref to write to the user's prefs file along with its value (see [`PreferenceManager::save_user_prefs`])
+type PrefToWrite = (&'static PreferenceDescription, String);
+
+/// The comment after the "Version" key written to the user's prefs file
+static VERSION_COMMENT: &str = "    # the MathCAT version that wrote this file (used to update old pref names)";
+
+fn is_same_value(description: &PreferenceDescription, value: &str, default: &str) -> bool {
+    if description.value_type == PreferenceType::Float {
+        if let (Ok(value), Ok(default)) = (value.parse::<f64>(), default.parse::<f64>()) {
+            return value == default;
+        }
+    }
+    return value == default;
+}
+
+fn yaml_value(description: &PreferenceDescription, value: &str) -> String {
+    let is_plain = match description.value_type {
+        PreferenceType::Float => value.parse::<f64>().is_ok(),
+        PreferenceType::Bool => value == "true" || value == "false",
+        PreferenceType::Enum => description.values.contains(&value),
+        PreferenceType::String => false,
+    };
+    return if is_plain {value.to_string()} else {format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))};
+}
+
+/// The lines for `prefs` in a section of the user's prefs file, each commented with its description.
+/// Nested prefs such as "ClearSpeak_Fractions" are written under "ClearSpeak:".
+fn pref_lines(prefs: &[PrefToWrite], indent: &str) -> String {
+    let mut lines = String::new();
+    let mut group = "";
+    for (description, value) in prefs {
+        let (prefix, name) = description.name.split_once('_').unwrap_or(("", description.name));
+        if prefix != group && !prefix.is_empty() {
+            lines += &format!("{}{}:\n", indent, prefix);
+        }
+        group = prefix;
+        let indent = if prefix.is_empty() {indent.to_string()} else {indent.repeat(2)};
+        lines += &format!("{}{}: {}    # {}\n", indent, name, yaml_value(description, value), description.description);
+    }
+    return lines;
+}
+
+/// Edit the `contents` of the user's prefs file so that it has the `prefs` in each category.
+/// Only the lines of prefs whose value differs from `saved_prefs` (the values in the file) are changed and the user's comment
+/// on those lines is kept. New prefs are added at the end of their section, so all other lines in the file (e.g., comments and profiles) are kept.
+fn edit_user_prefs(contents: &str, categories: &[(&str, Vec<PrefToWrite>)], saved_prefs: &Preferences) -> String {
+    let mut lines = contents.lines().map(|line| line.to_string()).collect::<Vec<String>>();
+    match lines.iter().position(|line| line.starts_with("Version:")) {
+        Some(i) => {
+            let (_, comment) = split_yaml_comment(&lines[i]["Version:".len()..]);
+            lines[i] = format!("Version: {}{}", PREFS_VERSION, comment);
+        },
+        None => {
+            let i = lines.iter().position(|line| is_top_level_key(line)).unwrap_or(lines.len());
+            lines.insert(i, format!("Version: {}{}", PREFS_VERSION, VERSION_COMMENT));
+        },
+    }
+    for (category, prefs) in categories {
+        lines = edit_section(lines, category, prefs, saved_prefs);
+    }
+    return lines.join("\n") + "\n";
+
+    fn is_top_level_key(line: &str) -> bool {
+        return line.starts_with(|ch: char| ch.is_alphanumeric());
+    }
+
+    fn edit_section(lines: Vec<String>, category: &str, prefs: &[PrefToWrite], saved_prefs: &Preferences) -> Vec<String> {
+        let is_saved = |(description, value): &PrefToWrite|
+            saved_prefs.prefs.contains_key(description.name) && is_same_value(description, value, &saved_prefs.to_string(description.name));
+        let header = format!("{}:", category);
+        let start = match lines.iter().position(|line| line.starts_with(&header)) {
+            None => return lines,       // can't happen -- the file was checked to have all the sections
+            Some(start) => start,
+        };
+        let (header_value, header_comment) = split_yaml_comment(&lines[start][header.len()..]);
+        let is_empty_dict = header_value.starts_with('{') && header_value[1..].trim() == "}";
+        let mut is_flow = !header_value.is_empty() && !is_empty_dict;
+        let header_comment = header_comment.to_string();
+
+        // find the prefs and the groups of nested prefs (e.g., "ClearSpeak:") in the section
+        // each is recorded as its first and last line (a group or a list value is more than one line), the indent of those lines, and its key
+        let mut found: HashMap<String, (usize, usize, usize, String)> = HashMap::new();
+        let mut group: Option<String> = None;
+        let mut group_child_indent: HashMap<String, usize> = HashMap::new();
+        let mut item_indent = None;
+        let mut last = start;
+        for (i, line) in lines.iter().enumerate().skip(start + 1) {
+            if is_top_level_key(line) {
+                break;
+            }
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            last = i;
+            let indent = line.len() - trimmed.len();
+            if let Some(group_name) = &group {
+                let group_line = found.get_mut(group_name).unwrap();
+                if indent > group_line.2 {
+                    group_line.1 = i;
+                    group_child_indent.entry(group_name.clone()).or_insert(indent);
+                } else {
+                    group = None;
+                }
+            }
+            let (key, rest) = match trimmed.split_once(':') {
+                Some(key_value) if !trimmed.starts_with('-') => key_value,
+                _ => continue,      // an item in a list
+            };
+            let (value, _) = split_yaml_comment(rest);
+            if value.starts_with('{') {
+                is_flow = true;
+            }
+            let key = key.trim().to_string();
+            let name = match &group {
+                None => {
+                    item_indent.get_or_insert(indent);
+                    key.clone()
+                },
+                Some(group_name) => format!("{}_{}", group_name, key),
+            };
+            if value.is_empty() && group.is_none() {
+                group = Some(name.clone());
+            }
+            found.insert(name, (i, i, indent, key));
+        }
+
+        if is_flow {
+            // not worth editing in place -- the section is replaced if anything in it changed
+            if prefs.iter().all(is_saved) {
+                return lines;
+            }
+            let mut section = vec![format!("{}:{}", category, header_comment)];
+            section.extend(pref_lines(prefs, "  ").lines().map(|line| line.to_string()));
+            return lines[..start].iter().cloned()
+                .chain(section)
+                .chain(lines[last + 1..].iter().cloned())
+                .collect();
+        }
+
+        let indent = " ".repeat(item_indent.unwrap_or(2));
+        let mut replaced: HashMap<usize, (String, usize)> = HashMap::new();     // first line -> (new line, last line replaced)
+        let mut group_additions: HashMap<usize, Vec<String>> = HashMap::new();  // line -> lines to add after it (in a group)
+        let mut additions: Vec<PrefToWrite> = Vec::new();                       // added at the end of the section
+        for pref in prefs {
+            let (description, value) = pref;
+            if let Some((first, last, line_indent, key)) = found.get(description.name) {
+                if !is_saved(pref) {
+                    let (_, comment) = split_yaml_comment(lines[*first].split_once(':').unwrap().1);
+                    let line = format!("{}{}: {}{}", " ".repeat(*line_indent), key, yaml_value(description, value), comment);
+                    replaced.insert(*first, (line, *last));
+                }
+                continue;
+            }
+            let group_line = description.name.split_once('_')
+                .and_then(|(prefix, name)| found.get(prefix).map(|group_line| (prefix, name, group_line)));
+            match group_line {
+                Some((prefix, name, (_, group_last, group_indent, _))) => {
+                    let child_indent = group_child_indent.get(prefix).copied().unwrap_or(group_indent + indent.len());
+                    let line = format!("{}{}: {}    # {}", " ".repeat(child_indent), name, yaml_value(description, value), description.description);
+                    group_additions.entry(*group_last).or_default().push(line);
+                },
+                None => additions.push((*description, value.clone())),
+            }
+        }
+
+        let mut result = Vec::with_capacity(lines.len() + prefs.len());
+        let mut skip_through = None;
+        for (i, line) in lines.into_iter().enumerate() {
+            if i == start && is_empty_dict && !(group_additions.is_empty() && additions.is_empty()) {
+                result.push(format!("{}:{}", category, header_comment));
+            } else if let Some((new_line, last_replaced)) = replaced.remove(&i) {
+                result.push(new_line);
+                skip_through = Some(last_replaced);
+            } else if skip_through.is_none_or(|skip_through| i > skip_through) {
+                result.push(line);
+            }
+            if let Some(group_lines) = group_additions.remove(&i) {
+                result.extend(group_lines);
+            }
+            if i == last {
+                result.extend(pref_lines(&additions, &indent).lines().map(|line| line.to_string()));
+            }
+        }
+        return result;
+    }
+}
+
+/// Split the text after the ':' of a YAML "key: value" line into the value and the comment after it
+/// (the comment includes the spaces before the '#' so that it can be put back as it was).
+fn split_yaml_comment(text: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut is_escaped = false;
+    let mut previous = ' ';
+    for (i, ch) in text.char_indices() {
+        match quote {
+            Some(quote_char) => {
+                if is_escaped {
+                    is_escaped = false;
+                } else if ch == '\\' && quote_char == '"' {
+                    is_escaped = true;
+                } else if ch == quote_char {
+                    quote = None;
+                }
+            },
+            None => {
+                if ch == '"' || ch == '\'' {
+                    quote = Some(ch);
+                } else if ch == '#' && previous.is_whitespace() {
+                    let value = text[..i].trim_end();
+                    return (value.trim_start(), &text[value.len()..]);
+                }
+            },
+        }
+        previous = ch;
+    }
+    return (text.trim(), "");
 }
 
 
@@ -850,10 +2580,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn try_new() {
+        let pref_manager = PreferenceManager::try_new(abs_rules_dir_path()).unwrap();
+        assert!(pref_manager.check_initialized().is_ok());
+        assert!(!pref_manager.get_user_prefs().to_string("Language").is_empty());
+        if cfg!(not(feature = "built-in-rules")) {     // otherwise, the built-in Rules are used
+            match PreferenceManager::try_new(PathBuf::from("someInvalidRulesDir")) {
+                Err(e) => assert!(matches!(e.kind(), ErrorKind::NotInitialized(_))),
+                Ok(_) => panic!("'someInvalidRulesDir' should not be a valid Rules dir"),
+            }
+        }
+        assert!(PreferenceManager::default().check_initialized().is_err());
+    }
+
     #[test]
     fn find_simple_style() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
             assert_eq!(rel_path(&pref_manager.rules_dir, &pref_manager.speech.files[0]), PathBuf::from("Languages/en/ClearSpeak_Rules.yaml"));
@@ -863,7 +2608,8 @@ mod tests {
     #[test]
     fn find_style_other_language() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
             pref_manager.set_user_prefs("Language", "zz");
@@ -874,7 +2620,8 @@ mod tests {
     #[test]
     fn find_unicode_files() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
             pref_manager.set_user_prefs("Language", "zz-aa");
@@ -884,10 +2631,30 @@ mod tests {
         });
     }
 
+    #[test]
+    fn reinitialize_reports_changes() {
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.set_api_bool_pref("AutoReload", false);     // only report the changes due to initialize
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            pref_manager.set_user_prefs("Language", "en-gb");
+            assert_eq!(rel_path(&pref_manager.rules_dir, &pref_manager.speech_unicode.files[1]), PathBuf::from("Languages/en/gb/unicode.yaml"));
+
+            // re-initializing resets the language to the prefs.yaml value (en), so the region file is no longer used
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            let files_changed = pref_manager.is_up_to_date().unwrap();
+            assert!(files_changed.speech_unicode_short);
+            assert!(!files_changed.braille_rules);
+            assert!(pref_manager.is_up_to_date().is_none());
+        });
+    }
+
     #[test]
     fn find_style_no_sublanguage() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
             pref_manager.set_user_prefs("Language", "zz-ab");
@@ -907,7 +2674,8 @@ mod tests {
         }
 
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
             pref_manager.set_user_prefs("Language", "zz-aa");
@@ -933,7 +2701,8 @@ mod tests {
     #[test]
     fn file_found_order() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("Language", "zz-aa");
 
@@ -948,7 +2717,8 @@ mod tests {
     #[test]
     fn test_prefs() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             let prefs = pref_manager.get_user_prefs();
             assert_eq!(prefs.to_string("Language").as_str(), "en");
@@ -963,7 +2733,8 @@ mod tests {
     #[test]
     fn test_language_change() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("SpeechStyle", "ClearSpeak");
 
@@ -975,10 +2746,131 @@ mod tests {
         });
     }
 
+    #[test]
+    fn language_pack_is_used() {
+        let dir = crate::TestDir::new("language-pack");
+        fs::create_dir_all(dir.join("tlh")).unwrap();
+        fs::create_dir_all(dir.join("en")).unwrap();
+        fs::write(dir.join("tlh/ClearSpeak_Rules.yaml"), "---\n").unwrap();
+        fs::write(dir.join("tlh/definitions.yaml"), "---\n").unwrap();
+        fs::write(dir.join("en/ClearSpeak_Rules.yaml"), "---\n").unwrap();
+        TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir.to_path_buf()));
+        let rules_dir = abs_rules_dir_path();
+        let languages_dir = rules_dir.join("Languages");
+        let style_files = PreferenceManager::get_files(&languages_dir, "tlh", &["en"], "ClearSpeak_Rules.yaml");
+        let definitions = PreferenceManager::get_files(&languages_dir, "tlh", &["en"], "definitions.yaml");
+        let en_style_files = PreferenceManager::get_files(&languages_dir, "en", &[], "ClearSpeak_Rules.yaml");
+        TEST_LANGUAGE_PACKS_DIR.with(|test_dir| *test_dir.borrow_mut() = None);
+
+        assert_eq!(style_files.unwrap(), [Some(dir.join("tlh/ClearSpeak_Rules.yaml")), None, None]);
+        // the files in the Rules dir are still used
+        assert_eq!(definitions.unwrap(), [Some(rules_dir.join("definitions.yaml")), Some(dir.join("tlh/definitions.yaml")), None]);
+        // a language in the Rules dir is used in preference to an installed one
+        assert_eq!(en_style_files.unwrap()[0], Some(languages_dir.join("en/ClearSpeak_Rules.yaml")));
+    }
+
+    #[test]
+    fn language_locations_are_cached() {
+        use std::thread::sleep;
+        use std::time::Duration;
+        let dir = crate::TestDir::new("locations");
+        fs::create_dir_all(dir.join("en")).unwrap();
+        fs::create_dir_all(dir.join("zz")).unwrap();
+        for file_name in std::iter::once("ClearSpeak_Rules.yaml").chain(LANGUAGE_FILES) {
+            fs::write(dir.join("en").join(file_name), "---\n").unwrap();
+        }
+        fs::write(dir.join("zz/ClearSpeak_Rules.yaml"), "---\n").unwrap();
+        let mut pref_manager = PreferenceManager::default();
+        let overview = |files: &[Locations]| files[1][0].clone().unwrap();
+
+        let files = pref_manager.get_language_locations(&dir, &["zz", "en"], "ClearSpeak_Rules.yaml", true).unwrap();
+        assert_eq!(files[0][0], Some(dir.join("zz/ClearSpeak_Rules.yaml")));
+        assert_eq!(overview(&files), dir.join("en/overview.yaml"));     // falls back to 'en'
+        pref_manager.get_language_locations(&dir, &["en"], "ClearSpeak_Rules.yaml", true).unwrap();
+        assert_eq!(pref_manager.language_locations.len(), 2);
+
+        // adding a file changes the dir's time, so the files are found again (unless the time isn't tracked)
+        sleep(Duration::from_millis(10));
+        fs::write(dir.join("zz/overview.yaml"), "---\n").unwrap();
+        let files = pref_manager.get_language_locations(&dir, &["zz", "en"], "ClearSpeak_Rules.yaml", false).unwrap();
+        assert_eq!(overview(&files), dir.join("en/overview.yaml"));
+        let files = pref_manager.get_language_locations(&dir, &["zz", "en"], "ClearSpeak_Rules.yaml", true).unwrap();
+        assert_eq!(overview(&files), dir.join("zz/overview.yaml"));
+    }
+
+    #[test]
+    fn typed_prefs() {
+        let mut prefs = Preferences::user_defaults();
+        assert_eq!(prefs.to_bool("Blind"), Some(true));
+        prefs.set_string_value("Blind", "False");
+        assert_eq!(prefs.to_bool("Blind"), Some(false));
+        assert_eq!(prefs.to_bool("Language"), None);
+        assert_eq!(prefs.to_bool("NoSuchPref"), None);
+        assert_eq!(prefs.to_int("BrfLineLength"), Some(40));
+        prefs.set_string_value("BrfLineLength", "32.0");
+        assert_eq!(prefs.to_int("BrfLineLength"), Some(32));
+        prefs.set_string_value("BrfLineLength", "32.5");
+        assert_eq!(prefs.to_int("BrfLineLength"), None);
+        assert_eq!(prefs.to_float("BrfLineLength"), Some(32.5));
+        assert_eq!(prefs.to_float("MathRate"), Some(100.0));
+
+        let mut pref_manager = PreferenceManager {
+            error: String::new(),
+            api_prefs: Preferences::api_defaults(),
+            ..Default::default()
+        };
+        assert!(!pref_manager.get_api_bool_pref("Bookmark"));
+        pref_manager.set_api_bool_pref("Bookmark", true);
+        assert!(pref_manager.get_api_bool_pref("Bookmark"));
+        pref_manager.set_api_string_pref("Bookmark", "yes");
+        assert!(!pref_manager.get_api_bool_pref("Bookmark"));     // not a boolean -- the default is used
+        pref_manager.set_api_int_pref("Volume", 80);
+        assert_eq!(pref_manager.get_api_int_pref("Volume"), 80);
+        assert_eq!(pref_manager.get_api_float_pref("Volume"), 80.0);
+        pref_manager.set_api_string_pref("Rate", "fast");
+        assert_eq!(pref_manager.get_rate(), 180.0);
+    }
+
+    #[test]
+    fn language_fallback() {
+        let dir = crate::TestDir::new("fallback");
+        for lang in ["en", "es", "gl"] {
+            fs::create_dir_all(dir.join(lang)).unwrap();
+        }
+        for file_name in std::iter::once("ClearSpeak_Rules.yaml").chain(LANGUAGE_FILES) {
+            fs::write(dir.join("en").join(file_name), "---\n").unwrap();
+        }
+        fs::write(dir.join("es/ClearSpeak_Rules.yaml"), "---\n").unwrap();
+        fs::write(dir.join("es/unicode.yaml"), "---\n").unwrap();
+        fs::write(dir.join("gl/unicode.yaml"), "---\n").unwrap();
+
+        let mut prefs = Preferences::user_defaults();
+        prefs.set_string_value("Language", "gl");
+        assert_eq!(PreferenceManager::get_languages(&prefs), ["gl", "en"]);
+        prefs.set_string_value("LanguageFallback", "pt, ES, en, gl");
+        assert_eq!(PreferenceManager::get_languages(&prefs), ["gl", "pt", "es", "en"]);
+
+        let mut pref_manager = PreferenceManager::default();
+        let files = pref_manager.get_language_locations(&dir, &["gl", "pt", "es", "en"], "ClearSpeak_Rules.yaml", true).unwrap();
+        assert_eq!(files[0][0], Some(dir.join("es/ClearSpeak_Rules.yaml")));
+        assert_eq!(files[1][0], Some(dir.join("en/overview.yaml")));
+        assert_eq!(files[3][0], Some(dir.join("gl/unicode.yaml")));
+        // a language whose dir doesn't exist starts with the first fallback that does
+        let files = pref_manager.get_language_locations(&dir, &["pt", "es", "en"], "ClearSpeak_Rules.yaml", true).unwrap();
+        assert_eq!(files[3][0], Some(dir.join("es/unicode.yaml")));
+
+        // prefs.yaml can give the fallbacks as a list
+        let mut map = PreferenceHashMap::default();
+        let yaml = YamlLoader::load_from_str("LanguageFallback: [pt-br, pt, es]").unwrap();
+        add_prefs(&mut map, &yaml[0], "", "test");
+        assert_eq!(map.get("LanguageFallback"), Some(&Yaml::String("pt-br, pt, es".to_string())));
+    }
+
     #[test]
     fn test_some_changes() {
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("Verbosity", "Terse");
 
@@ -996,9 +2888,11 @@ mod tests {
         use std::thread::sleep;
         use std::time::Duration;
         PREF_MANAGER.with(|pref_manager| {
-            let mut pref_manager = pref_manager.borrow_mut();
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
             pref_manager.initialize(abs_rules_dir_path()).unwrap();
             pref_manager.set_user_prefs("Language", "zz-aa");
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
             
             // First test to make sure the up_to_date check works -- need to do in this test since the order of testing is random
             let files_changed = pref_manager.is_up_to_date();        
@@ -1009,7 +2903,7 @@ mod tests {
             // Don't change a speech related file because 'test_is_up_to_date' might fail 
             if let Some(file_name) = &pref_manager.get_definitions_file()[0] {
                 let file_name_as_str = file_name.to_str().unwrap();
-                let contents = fs::read(file_name).expect(&format!("Failed to write file {} during test", file_name_as_str));
+                let contents = fs::read(file_name).unwrap_or_else(|_| panic!("Failed to write file {} during test", file_name_as_str));
                 #[allow(unused_must_use)] { 
                     fs::write(file_name, contents);
                     sleep(Duration::from_millis(10));
@@ -1027,4 +2921,293 @@ mod tests {
             // open the file, read all the contents, then write them back so the time changes
         });
     }
+
+    #[test]
+    fn test_file_check_interval() {
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            pref_manager.set_api_float_pref("FileCheckInterval", 1000.0);
+            assert!(pref_manager.is_up_to_date().is_none());
+
+            // pretend the files were read long ago -- the change isn't noticed until the interval has passed
+            pref_manager.defs.time = Some(SystemTime::UNIX_EPOCH);
+            assert!(pref_manager.is_up_to_date().is_none());
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
+            assert!(pref_manager.is_up_to_date().unwrap().defs);
+
+            // values that don't fit in a Duration don't panic
+            for seconds in [f64::INFINITY, 1e30, f64::NAN, -1.0] {
+                pref_manager.set_api_float_pref("FileCheckInterval", seconds);
+                assert!(pref_manager.is_up_to_date().is_none());
+            }
+            assert!(pref_manager.validate_preference("FileCheckInterval", "inf").is_err());
+            assert!(pref_manager.validate_preference("FileCheckInterval", "NaN").is_err());
+            assert!(pref_manager.validate_preference("FileCheckInterval", "1e30").is_ok());
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
+        });
+    }
+
+    #[test]
+    fn test_no_auto_reload() {
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.set_api_bool_pref("AutoReload", false);
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            assert!(pref_manager.pref_files.time.is_none());
+            assert!(pref_manager.speech.time.is_none());
+            assert!(pref_manager.defs.time.is_none());
+
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
+            pref_manager.defs.time = Some(SystemTime::UNIX_EPOCH);
+            assert!(pref_manager.is_up_to_date().is_none());
+            assert!(pref_manager.last_file_check.is_none());
+        });
+    }
+
+    #[test]
+    fn test_included_file_changes() {
+        use std::thread::sleep;
+        use std::time::Duration;
+        let dir = crate::TestDir::new("include");
+        let main_file = dir.join("overview.yaml");
+        let included_file = dir.join("included.yaml");
+        fs::write(&main_file, "- include: included.yaml\n").unwrap();
+        fs::write(&included_file, "---\n").unwrap();
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
+            pref_manager.overview = FileAndTime { files: [Some(main_file.clone()), None, None], time: modified_time_shim(&main_file) };
+            pref_manager.add_included_file(&main_file, &included_file);
+            // only look at 'overview' -- other tests might touch the real rule files
+            assert!(!pref_manager.is_up_to_date().is_some_and(|changes| changes.overview));
+
+            let reload_count = pref_manager.get_reload_count();
+            sleep(Duration::from_millis(10));
+            fs::write(&included_file, "--- # changed\n").unwrap();
+            assert!(pref_manager.is_up_to_date().unwrap().overview);
+            assert!(pref_manager.get_reload_count() > reload_count);
+            assert!(pref_manager.get_last_reload_time().is_some());
+
+            // the change is only reported once
+            assert!(!pref_manager.is_up_to_date().is_some_and(|changes| changes.overview));
+        });
+    }
+
+    #[test]
+    fn test_prefs_file_change_count() {
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            pref_manager.set_api_float_pref("FileCheckInterval", 0.0);
+            let count = pref_manager.get_prefs_file_change_count();
+            pref_manager.is_up_to_date();
+            assert_eq!(pref_manager.get_prefs_file_change_count(), count);
+
+            // pretend prefs.yaml was edited after it was read
+            pref_manager.pref_files.time = Some(SystemTime::UNIX_EPOCH);
+            pref_manager.is_up_to_date();
+            assert_eq!(pref_manager.get_prefs_file_change_count(), count + 1);
+            pref_manager.is_up_to_date();
+            assert_eq!(pref_manager.get_prefs_file_change_count(), count + 1);
+        });
+    }
+
+    #[test]
+    fn preference_descriptions_match_prefs() {
+        let file_prefs = Preferences::read_file(&Some(abs_rules_dir_path().join("prefs.yaml")), Preferences::default(), &mut PrefsDocs::default()).unwrap();
+        let user_prefs = Preferences::user_defaults();
+        let api_prefs = Preferences::api_defaults();
+        let descriptions = PreferenceManager::get_preference_descriptions();
+        for (prefs, is_api) in [(&file_prefs, false), (&user_prefs, false), (&api_prefs, true)] {
+            for name in prefs.prefs.keys() {
+                let description = descriptions.iter().find(|description| description.name == name)
+                        .unwrap_or_else(|| panic!("No description for '{}'", name));
+                assert_eq!(description.category == "API", is_api, "Wrong category for '{}'", name);
+                let value = prefs.to_string(name);
+                match description.value_type {
+                    PreferenceType::Float => assert_eq!(value.parse::<f64>().unwrap(), description.default.parse::<f64>().unwrap(), "Wrong default for '{}'", name),
+                    _ => assert!(value.eq_ignore_ascii_case(description.default), "Wrong default '{}' for '{}'", description.default, name),
+                }
+            }
+        }
+        for description in descriptions {
+            assert!([&file_prefs, &user_prefs, &api_prefs].iter().any(|prefs| prefs.prefs.contains_key(description.name)),
+                    "'{}' is not a preference", description.name);
+            assert_eq!(description.value_type == PreferenceType::Enum, !description.values.is_empty(), "Values for '{}'", description.name);
+            if description.value_type == PreferenceType::Enum {
+                assert!(description.values.contains(&description.default), "Default for '{}' isn't one of its values", description.name);
+            }
+        }
+    }
+
+    #[test]
+    fn write_user_prefs() {
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            pref_manager.set_user_prefs("Verbosity", "Terse");
+            pref_manager.set_user_prefs("ClearSpeak_Fractions", "Over");
+            pref_manager.set_user_prefs("ClearSpeak_Roots", "RootEnd");
+            pref_manager.set_user_prefs("MathRate", "80");
+            pref_manager.set_user_prefs("PauseFactor", "100.0");      // same as the default
+
+            let dir = crate::TestDir::new("write-prefs");
+            let file = dir.join("prefs.yaml");
+            pref_manager.write_user_prefs(&file).unwrap();
+            let saved = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut PrefsDocs::default()).unwrap();
+            assert_eq!(saved.to_string("Verbosity"), "Terse");
+            assert_eq!(saved.to_string("ClearSpeak_Fractions"), "Over");
+            assert_eq!(saved.to_string("ClearSpeak_Roots"), "RootEnd");
+            assert_eq!(saved.to_string("MathRate"), "80");
+            assert_eq!(saved.to_string("PauseFactor"), NO_PREFERENCE);
+            assert_eq!(saved.to_string("Language"), NO_PREFERENCE);
+            assert!(fs::read_to_string(&file).unwrap().contains("Navigation: {}"));
+
+            // once in the file, a pref is kept even if it is changed back to the default
+            pref_manager.set_user_prefs("Verbosity", "Medium");
+            pref_manager.write_user_prefs(&file).unwrap();
+            let saved = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut PrefsDocs::default()).unwrap();
+            assert_eq!(saved.to_string("Verbosity"), "Medium");
+
+            // the profiles in the file are kept
+            fs::write(&file, "Speech: {}\nNavigation: {}\nBraille: {}\nProfiles:\n  Review:\n    Speech: {Verbosity: Terse}\n").unwrap();
+            pref_manager.write_user_prefs(&file).unwrap();
+            let mut last_good = PrefsDocs::default();
+            Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut last_good).unwrap();
+            let profiles = Preferences::read_profiles(&[Some(file.clone()), None, None], &last_good);
+            assert_eq!(profiles["Review"].to_string("Verbosity"), "Terse");
+
+            // only the lines that change are written -- the user's comments are kept
+            fs::write(&file, "# my settings\nSpeech:\n  # how much to say\n  Verbosity: Terse   # I like it short\n  ClearSpeak:\n    Fractions: Over\n\
+                               Navigation: {}\nBraille: {}   # not used\nProfiles:\n  Review:\n    Speech: {Verbosity: Verbose}   # for reviewing\n").unwrap();
+            pref_manager.write_user_prefs(&file).unwrap();
+            let contents = fs::read_to_string(&file).unwrap();
+            assert!(contents.starts_with(&format!("# my settings\nVersion: {}    # ", PREFS_VERSION)), "{}", contents);
+            assert!(contents.contains("Speech:\n  # how much to say\n  Verbosity: Medium   # I like it short\n  ClearSpeak:\n    Fractions: Over\n    Roots: RootEnd    # "), "{}", contents);
+            assert!(contents.contains("\n  MathRate: 80    # "), "{}", contents);
+            assert!(contents.contains("\nNavigation: {}\nBraille: {}   # not used\nProfiles:\n  Review:\n    Speech: {Verbosity: Verbose}   # for reviewing\n"), "{}", contents);
+            let saved = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut PrefsDocs::default()).unwrap();
+            assert_eq!(saved.to_string("Verbosity"), "Medium");
+            assert_eq!(saved.to_string("ClearSpeak_Roots"), "RootEnd");
+            assert_eq!(saved.to_string("MathRate"), "80");
+
+            // a section written as a flow mapping is replaced
+            fs::write(&file, "Version: 0.1   # old\nSpeech: {Verbosity: Terse}   # short\nNavigation: {}\nBraille: {}\n").unwrap();
+            pref_manager.write_user_prefs(&file).unwrap();
+            let contents = fs::read_to_string(&file).unwrap();
+            assert!(contents.starts_with(&format!("Version: {}   # old\nSpeech:   # short\n  Verbosity: Medium    # ", PREFS_VERSION)), "{}", contents);
+            assert!(contents.ends_with("\nNavigation: {}\nBraille: {}\n"), "{}", contents);
+        });
+    }
+
+    #[test]
+    fn locked_system_prefs() {
+        let dir = crate::TestDir::new("system-prefs");
+        let file = dir.join("system-prefs.yaml");
+        fs::write(&file, "Speech:\n  Verbosity: Terse\nNavigation: {}\nBraille:\n  BrailleCode: UEB\nlocked: [BrailleCode, NotAPref]\n").unwrap();
+        TEST_SYSTEM_PREFS_FILE.with(|test_file| *test_file.borrow_mut() = Some(file));
+        let pref_manager = PreferenceManager::try_new(abs_rules_dir_path());
+        TEST_SYSTEM_PREFS_FILE.with(|test_file| *test_file.borrow_mut() = None);
+        let mut pref_manager = pref_manager.unwrap();
+
+        assert_eq!(pref_manager.get_user_prefs().to_string("Verbosity"), "Terse");
+        assert_eq!(pref_manager.get_user_prefs().to_string("BrailleCode"), "UEB");
+        assert!(pref_manager.is_locked("BrailleCode"));
+        assert!(!pref_manager.is_locked("Verbosity") && !pref_manager.is_locked("NotAPref"));
+
+        match pref_manager.validate_preference("BrailleCode", "Nemeth") {
+            Err(e) => assert!(matches!(e.kind(), ErrorKind::LockedPreference(name, value) if name == "BrailleCode" && value == "UEB")),
+            Ok(_) => panic!("BrailleCode should be locked"),
+        }
+        assert!(pref_manager.validate_preference("BrailleCode", "UEB").is_ok());
+        assert!(pref_manager.validate_preference("Verbosity", "Verbose").is_ok());
+
+        assert!(pref_manager.set_user_prefs("BrailleCode", "Nemeth").is_none());
+        pref_manager.set_user_prefs("Verbosity", "Verbose");
+        assert_eq!(pref_manager.get_user_prefs().to_string("BrailleCode"), "UEB");
+        assert_eq!(pref_manager.get_user_prefs().to_string("Verbosity"), "Verbose");
+
+        pref_manager.api_prefs.set_string_value("BrailleCode", "Nemeth");
+        assert_eq!(pref_manager.merge_prefs()["BrailleCode"].as_str(), Some("UEB"));
+    }
+
+    #[test]
+    fn prefs_file_mid_write() {
+        let dir = crate::TestDir::new("prefs-mid-write");
+        let file = dir.join("prefs.yaml");
+        let truncated = "Speech:\n  Verbosity: Verbose\nNavigation:\n  NavMode: Simple\n";    // no "Braille" key
+        let mut last_good = PrefsDocs::default();
+        fs::write(&file, truncated).unwrap();
+        assert!(Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut last_good).is_err());
+
+        fs::write(&file, "Speech:\n  Verbosity: Verbose\nNavigation: {}\nBraille:\n  BrailleCode: UEB\n").unwrap();
+        let prefs = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut last_good).unwrap();
+        assert_eq!(prefs.to_string("Verbosity"), "Verbose");
+
+        // the prefs that were last read are used rather than failing or resetting to the defaults
+        for bad_contents in [truncated, "", "  \n", "Speech: [\n", "Speech: {Verbosity: Verbose\n"] {
+            assert!(Preferences::is_partly_written(&Ok(bad_contents.to_string())), "{:?}", bad_contents);
+            fs::write(&file, bad_contents).unwrap();
+            let prefs = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut last_good).unwrap();
+            assert_eq!(prefs.to_string("Verbosity"), "Verbose");
+            assert_eq!(prefs.to_string("BrailleCode"), "UEB");
+        }
+
+        // errors that reading the file again won't fix aren't retried
+        assert!(Preferences::is_partly_written(&Err("Couldn't read".into())));
+        for bad_contents in ["Speech: [\nNavigation: {}\nBraille: {}\n", "Speech: 3\nNavigation: {}\nBraille: {}\n"] {
+            assert!(!Preferences::is_partly_written(&Ok(bad_contents.to_string())), "{:?}", bad_contents);
+        }
+    }
+
+    #[test]
+    fn migrate_old_prefs() {
+        assert!(is_older_version("0", "0.2.3"));
+        assert!(is_older_version("0.2", "0.2.3"));
+        assert!(!is_older_version("0.2.3", "0.2.3"));
+        assert!(!is_older_version("0.10", "0.2.3"));
+
+        let migrations = &[
+            PrefMigration{ version: "0.2.0", old: "OldName", new: &["NewName"] },
+            PrefMigration{ version: "0.3.0", old: "Both", new: &["First", "Second"] },
+        ];
+        let mut prefs = PreferenceHashMap::default();
+        prefs.insert("OldName".to_string(), Yaml::String("a".to_string()));
+        prefs.insert("Both".to_string(), Yaml::String("b".to_string()));
+        prefs.insert("Second".to_string(), Yaml::String("kept".to_string()));
+        migrate_prefs(&mut prefs, "0.1", migrations, "test");
+        assert_eq!(prefs.get("NewName"), Some(&Yaml::String("a".to_string())));
+        assert_eq!(prefs.get("First"), Some(&Yaml::String("b".to_string())));
+        assert_eq!(prefs.get("Second"), Some(&Yaml::String("kept".to_string())));     // already set with the new name
+        assert!(!prefs.contains_key("OldName") && !prefs.contains_key("Both"));
+
+        // a file written by a newer version isn't changed
+        let mut prefs = PreferenceHashMap::default();
+        prefs.insert("OldName".to_string(), Yaml::String("a".to_string()));
+        migrate_prefs(&mut prefs, "0.2.0", migrations, "test");
+        assert!(prefs.contains_key("OldName") && !prefs.contains_key("NewName"));
+
+        // an old user prefs file (no "Version") is read and the version is written when it is saved
+        PREF_MANAGER.with(|pref_manager| {
+            let pref_manager = pref_manager.borrow();
+            let mut pref_manager = pref_manager.write().unwrap();
+            pref_manager.initialize(abs_rules_dir_path()).unwrap();
+            let dir = crate::TestDir::new("migrate-prefs");
+            let file = dir.join("prefs.yaml");
+            fs::write(&file, "Speech:\n  Verbosity: Terse\nNavigation: {}\nBraille: {}\n").unwrap();
+            let saved = Preferences::read_file(&Some(file.clone()), Preferences::default(), &mut PrefsDocs::default()).unwrap();
+            assert_eq!(saved.to_string("Verbosity"), "Terse");
+
+            pref_manager.write_user_prefs(&file).unwrap();
+            let contents = fs::read_to_string(&file).unwrap();
+            assert!(contents.contains(&format!("Version: {}", PREFS_VERSION)));
+        });
+    }
 }
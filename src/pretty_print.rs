@@ -21,13 +21,45 @@ pub fn mml_to_string(e: &Element) -> String {
 /// Pretty-print the MathML represented by `element`.
 /// * `indent` -- the amount of indentation to start with
 pub fn format_element(e: &Element, indent: usize) -> String {
+    return format_element_with_options(e, indent, &FormatOptions::default());
+}
+
+/// Options for [`format_element_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Use ANSI escape codes to color the output for a terminal: MathML element names are blue, other element names
+    /// (e.g., intent names) are magenta, intent attributes are yellow, other attributes are cyan/green, and invisible chars are red.
+    pub color: bool,
+    /// The number of spaces to indent each level of the tree
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        return FormatOptions { color: false, indent_width: 2 };
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_MATHML_NAME: &str = "\x1b[34m";       // blue
+const ANSI_INTENT_NAME: &str = "\x1b[1;35m";     // bold magenta
+const ANSI_INTENT_ATTR: &str = "\x1b[33m";       // yellow
+const ANSI_ATTR_NAME: &str = "\x1b[36m";         // cyan
+const ANSI_ATTR_VALUE: &str = "\x1b[32m";        // green
+const ANSI_INVISIBLE_CHAR: &str = "\x1b[31m";    // red
+
+/// Pretty-print the MathML (or intent tree) represented by `element` using `options`.
+/// * `indent` -- the amount of indentation to start with
+pub fn format_element_with_options(e: &Element, indent: usize, options: &FormatOptions) -> String {
     // let namespace = match e.name().namespace_uri() {
     //     None => "".to_string(),
     //     Some(prefix) => prefix.to_string() + ":",
     // };
     // let namespace = namespace.as_str();
     let namespace = "";
-    let mut answer = format!("{:in$}<{ns}{name}{attrs}>", " ", in=2*indent, ns=namespace, name=e.name().local_part(), attrs=format_attrs(&e.attributes()));
+    let spaces = options.indent_width * indent;
+    let name = colorize(e.name().local_part(), element_name_color(e.name().local_part()), options);
+    let mut answer = format!("{:in$}<{ns}{name}{attrs}>", " ", in=spaces, ns=namespace, name=name, attrs=format_attrs(&e.attributes(), options));
     let children = e.children();
     let has_element = children.iter().find(|&&c| matches!(c, ChildOfElement::Element(_x)));
     if has_element.is_none() {
@@ -36,7 +68,7 @@ pub fn format_element(e: &Element, indent: usize) -> String {
                 .map(|c| if let ChildOfElement::Text(t) = c {t.text()} else {""})
                 .collect::<Vec<&str>>()
                 .join("");
-        return format!("{}{}</{}{}>\n", answer, &make_invisible_chars_visible(&content), namespace, e.name().local_part());
+        return format!("{}{}</{}{}>\n", answer, &make_invisible_chars_visible(&content, options), namespace, name);
         // for child in children {
         //     if let ChildOfElement::Text(t) = child {
         //         return format!("{}{}</{}{}>\n", answer, &make_invisible_chars_visible(t.text()), namespace, e.name().local_part());
@@ -47,28 +79,44 @@ pub fn format_element(e: &Element, indent: usize) -> String {
         // recurse on each Element child
         for c in e.children() {
             if let ChildOfElement::Element(e) = c {
-                answer += &format_element(&e, indent+1);
+                answer += &format_element_with_options(&e, indent+1, options);
             }
         }
     }
-    return answer + &format!("{:in$}</{ns}{name}>\n", " ", in=2*indent, ns=namespace, name=e.name().local_part());
+    return answer + &format!("{:in$}</{ns}{name}>\n", " ", in=spaces, ns=namespace, name=name);
 
     // Use the &#x....; representation for invisible chars when printing
 }
 
+fn element_name_color(name: &str) -> &'static str {
+    return if crate::canonicalize::is_mathml_element_name(name) {ANSI_MATHML_NAME} else {ANSI_INTENT_NAME};
+}
+
+fn colorize(text: &str, color: &str, options: &FormatOptions) -> String {
+    return if options.color {format!("{}{}{}", color, text, ANSI_RESET)} else {text.to_string()};
+}
+
 /// Format a vector of attributes as a string with a leading space
-pub fn format_attrs(attrs: &[Attribute]) -> String {
+pub fn format_attrs(attrs: &[Attribute], options: &FormatOptions) -> String {
     let mut result = String::new();
     for attr in attrs {
-        result += format!(" {}='{}'", attr.name().local_part(), &make_invisible_chars_visible(attr.value())).as_str();
+        let name = attr.name().local_part();
+        let value = make_invisible_chars_visible(attr.value(), options);
+        if !options.color {
+            result += format!(" {}='{}'", name, &value).as_str();
+        } else if name == "intent" || name == "arg" || name.starts_with("data-intent") {
+            result += format!(" {}{}='{}'{}", ANSI_INTENT_ATTR, name, &value, ANSI_RESET).as_str();
+        } else {
+            result += format!(" {}='{}'", colorize(name, ANSI_ATTR_NAME, options), colorize(&value, ANSI_ATTR_VALUE, options)).as_str();
+        }
     }
     result
 }
 
-fn make_invisible_chars_visible(text: &str) -> String {
+fn make_invisible_chars_visible(text: &str, options: &FormatOptions) -> String {
     return text.chars().map(|ch| {
         if ('\u{2061}'..'\u{2064}').contains(&ch) {
-            return format!("&#x{:x};", ch as u32)
+            return colorize(&format!("&#x{:x};", ch as u32), ANSI_INVISIBLE_CHAR, options);
         } else {
             return ch.to_string();
         }    
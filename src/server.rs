@@ -0,0 +1,195 @@
+//! A JSON-RPC 2.0 interface to MathCAT for applications that can't use the library directly (e.g., Java, .NET, Node).
+//! This is used by the `mathcat-server` binary (built with the `server` feature).
+//!
+//! Each request is one line of JSON and each response is one line of JSON. For example:
+//! ```text
+//! {"jsonrpc": "2.0", "id": 1, "method": "setMathML", "params": {"session": "doc1", "mathml": "<math><mi>x</mi></math>"}}
+//! {"jsonrpc": "2.0", "id": 2, "method": "getSpeech", "params": {"session": "doc1"}}
+//! ```
+//! The methods are:
+//! * `setMathML` (`mathml`) -- returns the canonical MathML
+//! * `getSpeech` -- returns the speech for the MathML that was set
+//! * `getBraille` (optional `navNodeId`) -- returns the braille for the MathML that was set
+//! * `navigate` (`command`, e.g., "ZoomIn") -- returns the speech for the new location
+//! * `setPreference` (`name`, `value`) and `getPreference` (`name`)
+//! * `closeSession` -- forget the session's MathML, navigation state, and preferences
+//! * `getVersion`
+//!
+//! `session` is optional for all of them ("" is used if it isn't given). Each session is a [`DocumentContext`],
+//! so sessions can have different MathML and preferences (e.g., language or braille code) at the same time.
+//! A request without an `id` is a notification and doesn't get a response.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use serde_json::{json, Map, Value};
+use crate::interface::{DocumentContext, errors_to_string, get_version};
+
+// error codes from the JSON-RPC 2.0 spec
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A MathCAT call failed (e.g., the MathML couldn't be parsed) -- the message is the MathCAT error
+const MATHCAT_ERROR: i64 = -32000;
+
+/// The sessions for one client (e.g., the stdio of the server or one socket connection)
+#[derive(Default)]
+pub struct Server {
+    sessions: HashMap<String, DocumentContext>,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Read requests from `input` (one per line) and write the responses to `output` until `input` is closed
+    pub fn serve(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_request(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok( () )
+    }
+
+    /// Handle one JSON-RPC request and return the response (None for a notification)
+    pub fn handle_request(&mut self, request: &str) -> Option<String> {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(e) => return Some( error_response(Value::Null, PARSE_ERROR, &e.to_string()) ),
+        };
+        let id = request.get("id").cloned();
+        let response_id = id.clone().unwrap_or(Value::Null);
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => return Some( error_response(response_id, INVALID_REQUEST, "the request doesn't have a 'method'") ),
+        };
+        let empty_params = Map::new();
+        let params = match request.get("params") {
+            None => &empty_params,
+            Some(Value::Object(params)) => params,
+            Some(_) => return Some( error_response(response_id, INVALID_PARAMS, "'params' must be an object") ),
+        };
+
+        let result = self.call(method, params);
+        id.as_ref()?;      // notification -- no response (even for an error)
+        Some( match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": response_id, "result": result}).to_string(),
+            Err( (code, message) ) => error_response(response_id, code, &message),
+        } )
+    }
+
+    fn call(&mut self, method: &str, params: &Map<String, Value>) -> std::result::Result<Value, (i64, String)> {
+        let session = match params.get("session") {
+            None => "",
+            Some(Value::String(session)) => session.as_str(),
+            Some(_) => return Err( (INVALID_PARAMS, "'session' must be a string".to_string()) ),
+        };
+        match method {
+            "getVersion" => return Ok( Value::String(get_version()) ),
+            "closeSession" => {
+                self.sessions.remove(session);
+                return Ok( Value::Null );
+            },
+            _ => (),
+        }
+
+        let document = self.sessions.entry(session.to_string()).or_default();
+        let result = match method {
+            "setMathML" => document.set_mathml(string_param(params, "mathml")?).map(Value::String),
+            "getSpeech" => document.get_spoken_text().map(Value::String),
+            "getBraille" => {
+                let nav_node_id = match params.get("navNodeId") {
+                    None => String::new(),
+                    Some(_) => string_param(params, "navNodeId")?,
+                };
+                document.get_braille(nav_node_id).map(Value::String)
+            },
+            "navigate" => document.do_navigate_command(string_param(params, "command")?).map(Value::String),
+            "setPreference" => {
+                let name = string_param(params, "name")?;
+                let value = match params.get("value") {
+                    // allow numbers and booleans for convenience (e.g., "Volume": 80)
+                    Some(Value::Number(n)) => n.to_string(),
+                    Some(Value::Bool(b)) => b.to_string(),
+                    _ => string_param(params, "value")?,
+                };
+                document.set_preference(name, value).map(|_| Value::Null)
+            },
+            "getPreference" => document.get_preference(string_param(params, "name")?).map(Value::String),
+            _ => return Err( (METHOD_NOT_FOUND, format!("'{}' is not a known method", method)) ),
+        };
+        result.map_err(|e| (MATHCAT_ERROR, errors_to_string(&e).trim_end().to_string()))
+    }
+}
+
+fn string_param(params: &Map<String, Value>, name: &str) -> std::result::Result<String, (i64, String)> {
+    match params.get(name) {
+        Some(Value::String(value)) => Ok( value.clone() ),
+        Some(_) => Err( (INVALID_PARAMS, format!("'{}' must be a string", name)) ),
+        None => Err( (INVALID_PARAMS, format!("'{}' is missing", name)) ),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init() -> Server {
+        crate::interface::set_rules_dir(super::super::abs_rules_dir_path()).unwrap();
+        Server::new()
+    }
+
+    #[test]
+    fn speech_and_braille() {
+        let mut server = init();
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "setPreference", "params": {"name": "BrailleCode", "value": "Nemeth"}}"#);
+        assert_eq!(response.unwrap(), r#"{"id":1,"jsonrpc":"2.0","result":null}"#);
+        server.handle_request(r#"{"jsonrpc": "2.0", "id": 2, "method": "setMathML", "params": {"mathml": "<math><mn>1</mn></math>"}}"#).unwrap();
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 3, "method": "getSpeech"}"#);
+        assert_eq!(response.unwrap(), r#"{"id":3,"jsonrpc":"2.0","result":"1"}"#);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": "b", "method": "getBraille", "params": {}}"#);
+        assert_eq!(response.unwrap(), r#"{"id":"b","jsonrpc":"2.0","result":"⠼⠂"}"#);
+        // notification
+        assert_eq!(server.handle_request(r#"{"jsonrpc": "2.0", "method": "getSpeech"}"#), None);
+    }
+
+    #[test]
+    fn sessions() {
+        let mut server = init();
+        server.handle_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "setPreference", "params": {"session": "ueb", "name": "BrailleCode", "value": "UEB"}}"#).unwrap();
+        server.handle_request(r#"{"jsonrpc": "2.0", "id": 2, "method": "setPreference", "params": {"session": "nemeth", "name": "BrailleCode", "value": "Nemeth"}}"#).unwrap();
+        server.handle_request(r#"{"jsonrpc": "2.0", "id": 3, "method": "setMathML", "params": {"session": "ueb", "mathml": "<math><mn>1</mn></math>"}}"#).unwrap();
+        server.handle_request(r#"{"jsonrpc": "2.0", "id": 4, "method": "setMathML", "params": {"session": "nemeth", "mathml": "<math><mn>1</mn></math>"}}"#).unwrap();
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 5, "method": "getBraille", "params": {"session": "ueb"}}"#);
+        assert_eq!(response.unwrap(), r#"{"id":5,"jsonrpc":"2.0","result":"⠼⠁"}"#);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 6, "method": "getBraille", "params": {"session": "nemeth"}}"#);
+        assert_eq!(response.unwrap(), r#"{"id":6,"jsonrpc":"2.0","result":"⠼⠂"}"#);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 7, "method": "navigate", "params": {"session": "ueb", "command": "ZoomIn"}}"#);
+        assert_eq!(response.unwrap(), r#"{"id":7,"jsonrpc":"2.0","result":"zoomed in all of the way; 1"}"#);
+    }
+
+    #[test]
+    fn errors() {
+        let mut server = init();
+        let response = server.handle_request("{not json").unwrap();
+        assert!(response.contains(r#""code":-32700"#), "response is {}", response);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "speak"}"#).unwrap();
+        assert!(response.contains(r#""code":-32601"#), "response is {}", response);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 2, "method": "setMathML", "params": {}}"#).unwrap();
+        assert!(response.contains(r#""code":-32602"#), "response is {}", response);
+        let response = server.handle_request(r#"{"jsonrpc": "2.0", "id": 3, "method": "setPreference", "params": {"name": "Verbosity", "value": "Loud"}}"#).unwrap();
+        assert!(response.contains(r#""code":-32000"#) && response.contains("Loud"), "response is {}", response);
+    }
+}
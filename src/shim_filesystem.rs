@@ -6,6 +6,7 @@
 //! but changes are pretty rare and it didn't seem worth it (this may need to be revisited).
 
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 // The zipped files are needed by WASM builds.
 // However, they are also useful for other builds because there really isn't another good way to get at the rules.
@@ -13,6 +14,41 @@ use std::path::{Path, PathBuf};
 // I'm not thrilled with this solution as it seems hacky, but I don't know another way for crates to allow for each access to data.
 pub static ZIPPED_RULE_FILES: &[u8] = include_bytes!(concat!(env!("OUT_DIR"),"/rules.zip"));
 
+/// The file system operations MathCAT needs to find and read the rule files (and the user's prefs.yaml).
+///
+/// The default uses the real file system. Other implementations (e.g., rules in a zip archive, built into the program,
+/// or fetched from a server) can be plugged in with `set_file_system` -- the logic that finds the files
+/// (e.g., falling back from a region to its language) is the same no matter where the files come from.
+pub trait FileSystem {
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf>;
+    fn read_to_string(&self, path: &Path) -> crate::errors::Result<String>;
+    /// The time `path` was last modified (`None` if unknown -- the file is then never re-read because it changed)
+    fn modified(&self, path: &Path) -> Option<SystemTime>;
+    /// The files and directories in the directory `path`
+    fn list_dir(&self, path: &Path) -> crate::errors::Result<Vec<PathBuf>>;
+    /// If true, only these files are used: the environment (e.g., MathCATRulesDir) and the user's config dir are never looked at.
+    fn is_sandboxed(&self) -> bool {
+        return false;
+    }
+    /// Write `contents` to the file `path` (used to install language packs and save the user's prefs). By default, the file system is read-only.
+    fn write(&self, path: &Path, _contents: &[u8]) -> crate::errors::Result<()> {
+        bail!("Can't write {}: the file system is read-only", path.to_str().unwrap());
+    }
+    /// Create the directory `path` and any missing parent directories. By default, the file system is read-only.
+    fn create_dir_all(&self, path: &Path) -> crate::errors::Result<()> {
+        bail!("Can't create {}: the file system is read-only", path.to_str().unwrap());
+    }
+    /// Remove the directory `path` and everything in it. By default, the file system is read-only.
+    fn remove_dir_all(&self, path: &Path) -> crate::errors::Result<()> {
+        bail!("Can't remove {}: the file system is read-only", path.to_str().unwrap());
+    }
+    /// Rename (move) the file or directory `from` to `to`. By default, the file system is read-only.
+    fn rename(&self, from: &Path, _to: &Path) -> crate::errors::Result<()> {
+        bail!("Can't rename {}: the file system is read-only", from.to_str().unwrap());
+    }
+}
 
 cfg_if! {
     if #[cfg(target_family = "wasm")] {
@@ -120,6 +156,68 @@ cfg_if! {
             return Ok(contents);
         }
 
+        // The rules are always built in for WASM, so the in-memory functions are trivial
+        pub fn set_in_memory_rules(_zipped_rules: &[u8]) -> Result<(), crate::errors::Error> {
+            bail!("WASM builds always use the built in Rules");
+        }
+
+        pub fn clear_in_memory_rules() {
+        }
+
+        pub fn set_zipped_rules_file(_zip_file: &Path) -> Result<(), crate::errors::Error> {
+            bail!("WASM builds always use the built in Rules");
+        }
+
+        pub fn set_file_system(_file_system: std::rc::Rc<dyn FileSystem>) -> Result<(), crate::errors::Error> {
+            bail!("WASM builds always use the built in Rules");
+        }
+
+        pub fn modified_time_shim(_path: &Path) -> Option<SystemTime> {
+            return None;    // the built in Rules never change
+        }
+
+        pub fn write_shim(path: &Path, _contents: &[u8]) -> Result<(), crate::errors::Error> {
+            bail!("Can't write {}: WASM builds only have the built in Rules", path.to_str().unwrap());
+        }
+
+        pub fn create_dir_all_shim(path: &Path) -> Result<(), crate::errors::Error> {
+            bail!("Can't create {}: WASM builds only have the built in Rules", path.to_str().unwrap());
+        }
+
+        pub fn remove_dir_all_shim(path: &Path) -> Result<(), crate::errors::Error> {
+            bail!("Can't remove {}: WASM builds only have the built in Rules", path.to_str().unwrap());
+        }
+
+        pub fn rename_shim(from: &Path, _to: &Path) -> Result<(), crate::errors::Error> {
+            bail!("Can't rename {}: WASM builds only have the built in Rules", from.to_str().unwrap());
+        }
+
+        pub fn list_dir_shim(path: &Path) -> Result<Vec<PathBuf>, crate::errors::Error> {
+            use sxd_document::dom::*;
+            use crate::interface::get_element;
+            if !is_dir_shim(path) {
+                bail!("{} is not a directory in the built in Rules", path.to_str().unwrap());
+            }
+            return DIRECTORY_TREE.with(|files| {
+                let files = files.borrow();
+                let mut dir = get_element(&*files);
+                for component in path.iter().skip(1) {      // skip "Rules"
+                    dir = dir.children().iter()
+                        .filter_map(|child| child.element())
+                        .find(|child| child.attribute_value("name") == component.to_str())
+                        .unwrap();
+                }
+                return Ok( dir.children().iter()
+                    .filter_map(|child| child.element())
+                    .map(|child| path.join(child.attribute_value("name").unwrap()))
+                    .collect() );
+            });
+        }
+
+        pub fn is_using_in_memory_rules() -> bool {
+            return true;
+        }
+
         thread_local! {
             // For debugging rules files (mainly nav file)
             static OVERRIDE_FILE_NAME: RefCell<String> = RefCell::new("".to_string());
@@ -134,7 +232,7 @@ cfg_if! {
                     crate::prefs::FilesChanged{
                         speech_rules: true, speech_unicode_short: false, speech_unicode_full: false, 
                         braille_rules: true, braille_unicode_short: false, braille_unicode_full: false, 
-                        intent: false, defs: false }
+                        intent: false, overview: false, navigation: true, defs: false }
             ));
         }
 
@@ -181,6 +279,9 @@ cfg_if! {
                                 <file name='SimpleSpeak_Rules.yaml'/>
                                 <file name='unicode.yaml'/>
                                 <file name='unicode-full.yaml'/>
+                                <dir name='gb'>
+                                    <file name='unicode.yaml'/>
+                                </dir>
                             </dir>
                         </dir>
                     </dir>")
@@ -189,21 +290,275 @@ cfg_if! {
         }        
     } else {
         use crate::errors::*;
+        use std::cell::RefCell;
+        use std::collections::HashMap;
+        use std::rc::Rc;
+
+        thread_local! {
+            // Where the rule files come from (see 'set_file_system')
+            static FILE_SYSTEM: RefCell<Rc<dyn FileSystem>> = RefCell::new( Rc::new(StdFileSystem) );
+        }
+
+        /// Use `file_system` to find and read the rule files (and the user's prefs.yaml).
+        /// How the files are found (e.g., falling back from a region to its language) doesn't change.
+        pub fn set_file_system(file_system: Rc<dyn FileSystem>) -> Result<()> {
+            FILE_SYSTEM.with(|fs| *fs.borrow_mut() = file_system);
+            return Ok( () );
+        }
+
+        /// The normal file system
+        pub struct StdFileSystem;
+
+        impl FileSystem for StdFileSystem {
+            fn is_file(&self, path: &Path) -> bool {
+                return path.is_file();
+            }
+
+            fn is_dir(&self, path: &Path) -> bool {
+                return path.is_dir();
+            }
+
+            fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+                return path.canonicalize();
+            }
+
+            fn read_to_string(&self, path: &Path) -> Result<String> {
+                return std::fs::read_to_string(path).chain_err(|| format!("while trying to read {}", path.to_str().unwrap()));
+            }
+
+            fn modified(&self, path: &Path) -> Option<SystemTime> {
+                return match std::fs::metadata(path) {
+                    Ok(metadata) => metadata.modified().ok(),
+                    Err(_) => None,
+                };
+            }
+
+            fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+                let entries = std::fs::read_dir(path).chain_err(|| format!("while trying to list {}", path.to_str().unwrap()))?;
+                return Ok( entries.flatten().map(|entry| entry.path()).collect() );
+            }
+
+            fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+                return std::fs::write(path, contents).chain_err(|| format!("while trying to write {}", path.to_str().unwrap()));
+            }
+
+            fn create_dir_all(&self, path: &Path) -> Result<()> {
+                return std::fs::create_dir_all(path).chain_err(|| format!("while trying to create {}", path.to_str().unwrap()));
+            }
+
+            fn remove_dir_all(&self, path: &Path) -> Result<()> {
+                return std::fs::remove_dir_all(path).chain_err(|| format!("while trying to remove {}", path.to_str().unwrap()));
+            }
+
+            fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+                return std::fs::rename(from, to)
+                    .chain_err(|| format!("while trying to rename {} to {}", from.to_str().unwrap(), to.to_str().unwrap()));
+            }
+        }
+
+        /// Rule files that are in memory (e.g., from a zip file) -- the file system and environment are never touched.
+        /// This is needed for sandboxed AT where file access is not allowed.
+        pub struct InMemoryFileSystem {
+            // file contents keyed by their '/' separated path (e.g., "Rules/prefs.yaml")
+            files: HashMap<String, String>,
+            // if true, files that aren't in memory (e.g., the user's prefs.yaml) are read from the file system
+            use_file_system: bool,
+        }
+
+        impl InMemoryFileSystem {
+            /// The files in `zipped_rules` (a zip of the Rules dir such as `ZIPPED_RULE_FILES`).
+            /// Paths in the zip file should start with "Rules".
+            pub fn from_zip(zipped_rules: &[u8]) -> Result<InMemoryFileSystem> {
+                use std::io::{Cursor, Read};
+                let mut archive = zip::ZipArchive::new(Cursor::new(zipped_rules))
+                        .chain_err(|| "while trying to read the zipped Rules")?;
+                let mut files = HashMap::with_capacity(archive.len());
+                for i in 0..archive.len() {
+                    let mut file = archive.by_index(i).chain_err(|| "while trying to read the zipped Rules")?;
+                    if file.is_dir() {
+                        continue;
+                    }
+                    let mut contents = String::new();
+                    file.read_to_string(&mut contents)
+                        .chain_err(|| format!("while trying to read {} from the zipped Rules", file.name()))?;
+                    files.insert(path_key(Path::new(file.name())), contents);
+                }
+                if !files.keys().any(|file| file.starts_with("Rules/")) {
+                    bail!("The zipped Rules don't contain a 'Rules' directory");
+                }
+                return Ok( InMemoryFileSystem { files, use_file_system: false } );
+            }
+
+            /// The files in the zip file `zip_file` (paths in it should start with "Rules").
+            /// Unlike `from_zip`, other files (e.g., the user's prefs.yaml) are read from the file system.
+            pub fn from_zip_file(zip_file: &Path) -> Result<InMemoryFileSystem> {
+                let zipped_rules = std::fs::read(zip_file)
+                        .chain_err(|| format!("while trying to read {}", zip_file.to_str().unwrap()))?;
+                let mut file_system = InMemoryFileSystem::from_zip(&zipped_rules)
+                        .chain_err(|| format!("in {}", zip_file.to_str().unwrap()))?;
+                file_system.use_file_system = true;
+                return Ok(file_system);
+            }
+
+            /// True if `path` is a directory of the in-memory files
+            fn is_in_memory_dir(&self, path: &Path) -> bool {
+                let dir = path_key(path) + "/";
+                return self.files.keys().any(|file| file.starts_with(&dir));
+            }
+        }
+
+        impl FileSystem for InMemoryFileSystem {
+            fn is_file(&self, path: &Path) -> bool {
+                return self.files.contains_key(&path_key(path)) ||
+                       (self.use_file_system && StdFileSystem.is_file(path));
+            }
+
+            fn is_dir(&self, path: &Path) -> bool {
+                return self.is_in_memory_dir(path) || (self.use_file_system && StdFileSystem.is_dir(path));
+            }
+
+            fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+                if self.use_file_system && !self.files.contains_key(&path_key(path)) {
+                    return StdFileSystem.canonicalize(path);
+                }
+                return Ok( path.to_path_buf() );
+            }
+
+            fn read_to_string(&self, path: &Path) -> Result<String> {
+                return match self.files.get(&path_key(path)) {
+                    Some(contents) => Ok(contents.clone()),
+                    None if self.use_file_system => StdFileSystem.read_to_string(path),
+                    None => bail!("{} is not in the in-memory Rules", path.to_str().unwrap()),
+                };
+            }
+
+            fn modified(&self, path: &Path) -> Option<SystemTime> {
+                if self.use_file_system && !self.files.contains_key(&path_key(path)) {
+                    return StdFileSystem.modified(path);
+                }
+                return None;        // never changes
+            }
+
+            fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+                let dir = path_key(path) + "/";
+                let mut entries: Vec<PathBuf> = self.files.keys()
+                    .filter_map(|file| file.strip_prefix(&dir))
+                    .map(|rest| PathBuf::from(dir.clone() + rest.split('/').next().unwrap()))
+                    .collect();
+                if entries.is_empty() {
+                    if self.use_file_system {
+                        return StdFileSystem.list_dir(path);
+                    }
+                    bail!("{} is not a directory in the in-memory Rules", path.to_str().unwrap());
+                }
+                entries.sort();
+                entries.dedup();
+                return Ok(entries);
+            }
+
+            fn is_sandboxed(&self) -> bool {
+                return !self.use_file_system;
+            }
+
+            fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+                if !self.use_file_system || self.files.contains_key(&path_key(path)) {
+                    bail!("Can't write {}: it is in the in-memory Rules", path.to_str().unwrap());
+                }
+                return StdFileSystem.write(path, contents);
+            }
+
+            fn create_dir_all(&self, path: &Path) -> Result<()> {
+                if !self.use_file_system {
+                    bail!("Can't create {}: the in-memory Rules are read-only", path.to_str().unwrap());
+                }
+                return StdFileSystem.create_dir_all(path);
+            }
+
+            fn remove_dir_all(&self, path: &Path) -> Result<()> {
+                if !self.use_file_system || self.is_in_memory_dir(path) {
+                    bail!("Can't remove {}: it is in the in-memory Rules", path.to_str().unwrap());
+                }
+                return StdFileSystem.remove_dir_all(path);
+            }
+
+            fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+                if !self.use_file_system || self.files.contains_key(&path_key(from)) || self.is_in_memory_dir(from) {
+                    bail!("Can't rename {}: it is in the in-memory Rules", from.to_str().unwrap());
+                }
+                return StdFileSystem.rename(from, to);
+            }
+        }
+
+        /// Use the files in `zipped_rules` (a zip of the Rules dir such as `ZIPPED_RULE_FILES`) in place of the file system.
+        /// Paths in the zip file should start with "Rules".
+        pub fn set_in_memory_rules(zipped_rules: &[u8]) -> Result<()> {
+            return set_file_system( Rc::new(InMemoryFileSystem::from_zip(zipped_rules)?) );
+        }
+
+        /// Use the Rules in the zip file `zip_file` (paths in it should start with "Rules") in place of a Rules dir.
+        /// Unlike `set_in_memory_rules`, the user's prefs.yaml is still used.
+        pub fn set_zipped_rules_file(zip_file: &Path) -> Result<()> {
+            return set_file_system( Rc::new(InMemoryFileSystem::from_zip_file(zip_file)?) );
+        }
+
+        /// Go back to reading the rule files from the file system.
+        pub fn clear_in_memory_rules() {
+            FILE_SYSTEM.with(|fs| *fs.borrow_mut() = Rc::new(StdFileSystem));
+        }
+
+        /// Returns true if the rule files come from a sandboxed file system such as `set_in_memory_rules`
+        /// (the real file system and environment are not used).
+        pub fn is_using_in_memory_rules() -> bool {
+            return file_system().is_sandboxed();
+        }
+
+        fn file_system() -> Rc<dyn FileSystem> {
+            return FILE_SYSTEM.with(|fs| fs.borrow().clone());
+        }
+
+        fn path_key(path: &Path) -> String {
+            return path.to_str().unwrap_or_default().replace('\\', "/").trim_end_matches('/').to_string();
+        }
+
         pub fn is_file_shim(path: &Path) -> bool {
-            return path.is_file();
+            return file_system().is_file(path);
         }
         
         pub fn is_dir_shim(path: &Path) -> bool {
-            return path.is_dir();
+            return file_system().is_dir(path);
         }
         
         pub fn canonicalize_shim(path: &Path) -> std::io::Result<PathBuf> {
-            return path.canonicalize();
+            return file_system().canonicalize(path);
         }
         
         pub fn read_to_string_shim(path: &Path) -> Result<String> {
             debug!("Reading file '{}'", path.to_str().unwrap());
-            return std::fs::read_to_string(path).chain_err(|| format!("while trying to read {}", path.to_str().unwrap()));
-        }     
+            return file_system().read_to_string(path);
+        }
+
+        pub fn modified_time_shim(path: &Path) -> Option<SystemTime> {
+            return file_system().modified(path);
+        }
+
+        pub fn list_dir_shim(path: &Path) -> Result<Vec<PathBuf>> {
+            return file_system().list_dir(path);
+        }
+
+        pub fn write_shim(path: &Path, contents: &[u8]) -> Result<()> {
+            return file_system().write(path, contents);
+        }
+
+        pub fn create_dir_all_shim(path: &Path) -> Result<()> {
+            return file_system().create_dir_all(path);
+        }
+
+        pub fn remove_dir_all_shim(path: &Path) -> Result<()> {
+            return file_system().remove_dir_all(path);
+        }
+
+        pub fn rename_shim(from: &Path, to: &Path) -> Result<()> {
+            return file_system().rename(from, to);
+        }
     }
 }
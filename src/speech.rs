@@ -19,6 +19,7 @@ use crate::tts::*;
 use crate::pretty_print::{mml_to_string, yaml_to_string};
 use std::path::Path;
 use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 use crate::shim_filesystem::read_to_string_shim;
 use crate::canonicalize::{as_element, create_mathml_element, set_mathml_name, name};
 
@@ -34,27 +35,80 @@ use crate::canonicalize::{as_element, create_mathml_element, set_mathml_name, na
 /// A string is returned in call cases.
 /// If there is an error, the speech string will indicate an error.
 pub fn intent_from_mathml<'a, 'm>(mathml: Element<'a>, doc: Document<'m>) -> Result<Element<'m>> {
-    let intent_tree = intent_rules(&INTENT_RULES, doc, mathml)?;
+    return intent_from_mathml_with_prefs(mathml, doc, &PreferenceManager::get());
+}
+
+/// Same as [`intent_from_mathml`], but `pref_manager` (e.g., from [`PreferenceManager::with_overrides`]) is used instead of this thread's preferences.
+/// The rule files must be the same as the ones for this thread's preferences.
+pub fn intent_from_mathml_with_prefs<'a, 'm>(mathml: Element<'a>, doc: Document<'m>, pref_manager: &SharedPreferences) -> Result<Element<'m>> {
+    let intent_tree = intent_rules(&INTENT_RULES, doc, mathml, pref_manager)?;
     doc.root().append_child(intent_tree);
     return Ok(intent_tree);
 }
 
 pub fn speak_intent(mathml: Element) -> Result<String> {
-    return speak_rules(&SPEECH_RULES, mathml);
+    return speak_rules(&SPEECH_RULES, mathml, &PreferenceManager::get());
+}
+
+/// Speak `mathml` (an intent tree) with `pref_manager` (e.g., from [`PreferenceManager::with_overrides`]) instead of this thread's preferences.
+/// The preferences are not changed (they might be shared with other threads), so nothing needs to be restored.
+/// If `pref_manager` has a different `SpeechStyle`, the rules for that style are used (see [`speak_intent_with_style`]);
+/// the other rule files must be the same as the ones for this thread's preferences.
+pub fn speak_intent_with_prefs(mathml: Element, pref_manager: &SharedPreferences) -> Result<String> {
+    let style = crate::prefs::read_prefs(pref_manager)?.get_user_prefs().to_string("SpeechStyle");
+    return speak_with_style(mathml, &style, pref_manager, false);
+}
+
+/// Speak `mathml` (an intent tree) using the speech style `style` rather than the one given by the `SpeechStyle` preference.
+/// The preferences are not changed. The rules for each style are cached, so switching between styles only reads the rule files once.
+pub fn speak_intent_with_style(mathml: Element, style: &str) -> Result<String> {
+    return speak_with_style(mathml, style, &PreferenceManager::get(), false);
+}
+
+/// Speak `mathml` with the rules for `style` and `pref_manager` (see [`speak_with_rules`])
+fn speak_with_style(mathml: Element, style: &str, pref_manager: &SharedPreferences, mark_sources: bool) -> Result<String> {
+    let current_style = crate::prefs::read_prefs(&PreferenceManager::get())?.get_user_prefs().to_string("SpeechStyle");
+    if style == current_style {
+        SpeechRules::update()?;
+        return SPEECH_RULES.with(|rules| {
+            rules.borrow_mut().read_files()?;
+            return speak_with_rules(&rules.borrow(), mathml, mark_sources, pref_manager);
+        });
+    }
+    SpeechRules::update()?;
+    return STYLE_RULES.with(|style_rules| {
+        let mut style_rules = style_rules.borrow_mut();
+        let rule_file = crate::prefs::read_prefs(&PreferenceManager::get())?.get_style_rule_file(style)?;
+        let (locations, rules) = style_rules.entry(style.to_string())
+                .or_insert_with(|| (rule_file.clone(), SpeechRules::new_for_style(style)));
+        if !are_locations_same(locations, &rule_file) {
+            // the language changed
+            *locations = rule_file;
+            rules.rules.clear();
+        }
+        rules.read_files()?;
+        return speak_with_rules(rules, mathml, mark_sources, pref_manager);
+    });
 }
 
 pub fn overview_mathml(mathml: Element) -> Result<String> {
-    return speak_rules(&OVERVIEW_RULES, mathml);
+    return speak_rules(&OVERVIEW_RULES, mathml, &PreferenceManager::get());
+}
+
+/// Same as [`overview_mathml`], but `pref_manager` is used instead of this thread's preferences (see [`speak_intent_with_prefs`])
+pub fn overview_mathml_with_prefs(mathml: Element, pref_manager: &SharedPreferences) -> Result<String> {
+    return speak_rules(&OVERVIEW_RULES, mathml, pref_manager);
 }
 
 
-fn intent_rules<'c, 'm>(rules: &'static std::thread::LocalKey<RefCell<SpeechRules>>, doc: Document<'m>, mathml: Element<'c>) -> Result<Element<'m>> {
-    SpeechRules::update();
+fn intent_rules<'c, 'm>(rules: &'static std::thread::LocalKey<RefCell<SpeechRules>>, doc: Document<'m>, mathml: Element<'c>,
+                        pref_manager: &SharedPreferences) -> Result<Element<'m>> {
+    SpeechRules::update()?;
     rules.with(|rules| {
         rules.borrow_mut().read_files()?;
         let rules = rules.borrow();
         // debug!("speak_rules:\n{}", mml_to_string(&mathml));
-        let mut rules_with_context = SpeechRulesWithContext::new(&rules, doc, "".to_string());
+        let mut rules_with_context = SpeechRulesWithContext::new_with_prefs(&rules, doc, "".to_string(), pref_manager.clone());
         let intent =  rules_with_context.match_pattern::<Element<'m>>(mathml)
                     .chain_err(|| "Pattern match/replacement failure!")?;
         if name(&intent) == "TEMP_NAME" {   // unneeded extra layer
@@ -66,25 +120,75 @@ fn intent_rules<'c, 'm>(rules: &'static std::thread::LocalKey<RefCell<SpeechRule
     })
 }
 
-fn speak_rules(rules: &'static std::thread::LocalKey<RefCell<SpeechRules>>, mathml: Element) -> Result<String> {
-    SpeechRules::update();
+/// Speak `mathml` (an intent tree) and return the words of the speech, each with the `id` of the innermost element it comes from
+/// (an empty string if the word doesn't come from an element with an `id`).
+/// The words are the same as those of [`speak_intent_with_prefs`], although pauses that come from different elements might not be merged.
+pub fn speak_intent_with_sources(mathml: Element, pref_manager: &SharedPreferences) -> Result<Vec<(String, String)>> {
+    let style = crate::prefs::read_prefs(pref_manager)?.get_user_prefs().to_string("SpeechStyle");
+    let speech = speak_with_style(mathml, &style, pref_manager, true)?;
+
+    let mut words = vec![];
+    let mut ids: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut word_id = String::new();
+    let mut chars = speech.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            SOURCE_START => ids.push( chars.by_ref().take_while(|&ch| ch != SOURCE_ID_END).collect() ),
+            SOURCE_END => {
+                ids.pop();
+            },
+            ch if ch.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push( (std::mem::take(&mut word), std::mem::take(&mut word_id)) );
+                }
+            },
+            ch => {
+                if word.is_empty() {
+                    word_id = ids.last().cloned().unwrap_or_default();
+                }
+                word.push(ch);
+            },
+        }
+    }
+    if !word.is_empty() {
+        words.push( (word, word_id) );
+    }
+    return Ok(words);
+}
+
+fn speak_rules(rules: &'static std::thread::LocalKey<RefCell<SpeechRules>>, mathml: Element, pref_manager: &SharedPreferences) -> Result<String> {
+    SpeechRules::update()?;
     rules.with(|rules| {
         rules.borrow_mut().read_files()?;
-        let rules = rules.borrow();
-        // debug!("speak_rules:\n{}", mml_to_string(&mathml));
-        let new_package = Package::new();
-        let mut rules_with_context = SpeechRulesWithContext::new(&rules, new_package.as_document(), "".to_string());
-        let speech_string = rules_with_context.match_pattern::<String>(mathml)
-                    .chain_err(|| "Pattern match/replacement failure!")?;
-        return Ok( rules.pref_manager.borrow().get_tts()
-                    .merge_pauses(remove_optional_indicators(
-                        &speech_string.replace(CONCAT_STRING, "")
-                                            .replace(CONCAT_INDICATOR, "")                            
-                                    )
-                    .trim()) );
+        return speak_with_rules(&rules.borrow(), mathml, false, pref_manager);
     })
 }
 
+/// Speak `mathml` with `rules` and the preferences in `pref_manager`. If `mark_sources` is true, the speech for each element with an `id` is bracketed by
+/// `SOURCE_START`, `id`, `SOURCE_ID_END` ... `SOURCE_END` (see [`speak_intent_with_sources`]).
+fn speak_with_rules(rules: &SpeechRules, mathml: Element, mark_sources: bool, pref_manager: &SharedPreferences) -> Result<String> {
+    // debug!("speak_rules:\n{}", mml_to_string(&mathml));
+    let new_package = Package::new();
+    let mut rules_with_context = SpeechRulesWithContext::new_with_prefs(rules, new_package.as_document(), "".to_string(), pref_manager.clone());
+    rules_with_context.mark_sources = mark_sources;
+    let speech_string = rules_with_context.match_pattern::<String>(mathml)
+                .chain_err(|| "Pattern match/replacement failure!")?;
+    if rules.name == RulesFor::Speech {
+        LAST_PHRASE_SOURCES.with(|sources| *sources.borrow_mut() = std::mem::take(&mut rules_with_context.phrase_sources));
+    }
+    let pref_manager = crate::prefs::read_prefs(pref_manager)?;
+    let tts = pref_manager.get_tts();
+    let speech_string = tts.merge_pauses(remove_optional_indicators(
+                    &speech_string.replace(CONCAT_STRING, "")
+                                        .replace(CONCAT_INDICATOR, "")                            
+                                )
+                .trim());
+    // the source markers are inside the words, so the post processors would see (and might mangle) them
+    let speech_string = if mark_sources {speech_string} else {post_process_speech(&tts, speech_string)};
+    return tts.apply_at_profile(&pref_manager, speech_string);
+}
+
 
 /// Converts its argument to a string that can be used in a debugging message.
 pub fn yaml_to_type(yaml: &Yaml) -> String {
@@ -163,6 +267,12 @@ pub const CONCAT_INDICATOR: &str = "\u{F8FE}";
 pub const CONCAT_STRING: &str = " \u{F8FE}";
 
 // a similar hack to potentially delete (repetitive) optional replacements
+// These bracket the speech for an element when the source of the speech is wanted (see `speak_intent_with_sources`):
+//   SOURCE_START id SOURCE_ID_END speech SOURCE_END
+const SOURCE_START: char = '\u{F8F7}';
+const SOURCE_ID_END: char = '\u{F8F8}';
+const SOURCE_END: char = '\u{F8F9}';
+
 // the OPTIONAL_INDICATOR is added by "ot:" before and after the optional string
 const OPTIONAL_INDICATOR: &str  = "\u{F8FD}";
 const OPTIONAL_INDICATOR_LEN: usize = OPTIONAL_INDICATOR.len();
@@ -204,6 +314,8 @@ fn process_include<F>(current_file: &Path, new_file_name: &str, mut read_new_fil
                                  new_file.to_str().unwrap(), msg),
     };
 
+    // remember the included file so that it is checked for changes along with the file that included it
+    crate::prefs::write_prefs(&PreferenceManager::get())?.add_included_file(current_file, &new_file);
     return read_new_file(new_file.as_path());
 }
 
@@ -216,6 +328,7 @@ pub trait TreeOrString<'c, 'm:'c, T> {
     fn replace<'s:'c, 'r>(ra: &ReplacementArray, rules_with_context: &'r mut SpeechRulesWithContext<'c, 's,'m>, mathml: Element<'c>) -> Result<T>;
     fn replace_nodes<'s:'c, 'r>(rules: &'r mut SpeechRulesWithContext<'c, 's,'m>, nodes: Vec<Node<'c>>, mathml: Element<'c>) -> Result<T>;
     fn highlight_braille(braille: T, highlight_style: String) -> T;
+    fn mark_source(speech: T, id: &str) -> T;
 }
 
 impl<'c, 'm:'c> TreeOrString<'c, 'm, String> for String {
@@ -242,6 +355,10 @@ impl<'c, 'm:'c> TreeOrString<'c, 'm, String> for String {
     fn highlight_braille(braille: String, highlight_style: String) -> String {
         return SpeechRulesWithContext::highlight_braille_string(braille, highlight_style);
     }
+
+    fn mark_source(speech: String, id: &str) -> String {
+        return format!("{}{}{}{}{}", SOURCE_START, id, SOURCE_ID_END, speech, SOURCE_END);
+    }
 }
 
 impl<'c, 'm:'c> TreeOrString<'c, 'm, Element<'m>> for Element<'m> {
@@ -250,6 +367,10 @@ impl<'c, 'm:'c> TreeOrString<'c, 'm, Element<'m>> for Element<'m> {
     }
 
     fn from_string(s: String, doc: Document<'m>) -> Result<Element<'m>> {
+        if s.is_empty() {
+            // nothing was generated (e.g., a 'test' that didn't match) -- an empty TEMP_NAME is removed when it is lifted
+            return Ok( create_mathml_element(&doc, "TEMP_NAME") );
+        }
         // FIX: is 'mi' really ok?  Don't want to use TEMP_NAME because this name needs to move to the outside world
         let leaf = create_mathml_element(&doc, "mi");
         leaf.set_text(&s);
@@ -271,6 +392,10 @@ impl<'c, 'm:'c> TreeOrString<'c, 'm, Element<'m>> for Element<'m> {
     fn highlight_braille(_braille: Element<'c>, _highlight_style: String) -> Element<'m> {
         panic!("Internal error: highlight_braille called on a tree");
     }
+
+    fn mark_source(_speech: Element<'m>, _id: &str) -> Element<'m> {
+        panic!("Internal error: mark_source called on a tree");
+    }
 }
 
 /// 'Replacement' is an enum that contains all the potential replacement types/structs
@@ -526,21 +651,21 @@ impl<'r> Intent {
         return T::from_element(result);
 
         /// "lift" up the children any "TEMP_NAME" child -- could short circuit when only one child
+        /// "Unknown" children (from a 'test' with several replacements) are also lifted, as are empty results (from a 'test' that doesn't match)
         fn lift_children(result: Element) -> Element {
             // debug!("lift_children:\n{}", mml_to_string(&result));
             result.replace_children(
                 result.children().iter()
-                    .map(|&child_of_element| {
+                    .flat_map(|&child_of_element| {
                         match child_of_element {
                             ChildOfElement::Element(child) => {
-                                if name(&child) == "TEMP_NAME" {
-                                    assert_eq!(child.children().len(), 1);
-                                    child.children()[0]
+                                if name(&child) == "TEMP_NAME" || name(&child) == "Unknown" {
+                                    child.children()
                                 } else {
-                                    child_of_element
+                                    vec![child_of_element]
                                 }
                             },
-                            _ => child_of_element,      // text()
+                            _ => vec![child_of_element],      // text()
                         }
                     })
                     .collect::<Vec<ChildOfElement>>()
@@ -663,7 +788,11 @@ impl<'r> TranslateExpression {
                     match crate::navigate::get_node_by_id(mathml, &id) { // FIX: should use root of MathML
                         None => bail!("'translate' value '{}' was not an 'id' found in {}", &id, mml_to_string(&mathml)),
                         // FIX: ?? see speak() in navigate.rs about maybe using context to generate proper speech
-                        Some(element) => speak_intent(intent_from_mathml(element, rules_with_context.get_document())?)?,
+                        Some(element) => {
+                            let pref_manager = rules_with_context.pref_manager.clone();
+                            let intent = intent_from_mathml_with_prefs(element, rules_with_context.get_document(), &pref_manager)?;
+                            speak_intent_with_prefs(intent, &pref_manager)?
+                        },
                     }
                 }
             };
@@ -755,9 +884,10 @@ impl<'r> ReplacementArray {
             if replacement_strings[i].contains(PAUSE_AUTO_STR) {
                 let before = if i == 0 {""} else {&replacement_strings[i-1]};
                 let after = if i+1 == replacement_strings.len() {""} else {&replacement_strings[i+1]};
+                let pref_manager = crate::prefs::read_prefs(&rules_with_context.pref_manager)?;
                 replacement_strings[i] = replacement_strings[i].replace(
                     PAUSE_AUTO_STR,
-                    &rules_with_context.speech_rules.pref_manager.borrow().get_tts().compute_auto_pause(&rules_with_context.speech_rules.pref_manager.borrow(), before, after));
+                    &pref_manager.get_tts().compute_auto_pause(&pref_manager, before, after));
             }
         }
 
@@ -1050,7 +1180,11 @@ impl<'r> MyXPath {
                     }
                     rules_with_context.replace_nodes(nodes.document_order(), mathml)
                 },
-                Value::String(t) => T::from_string(t, rules_with_context.doc),
+                Value::String(t) => {
+                    // multi-char tokens (e.g., "sinc") and intent names are strings, so this is where users' pronunciations of them are used
+                    let t = user_pronunciation(rules_with_context.speech_rules, &t).unwrap_or(t);
+                    T::from_string(t, rules_with_context.doc)
+                },
                 Value::Number(num) => T::from_string(num.to_string(), rules_with_context.doc ),
                 Value::Boolean(b) => T::from_string(b.to_string(), rules_with_context.doc ),          // FIX: is this right???
         };
@@ -1077,6 +1211,129 @@ impl<'r> MyXPath {
 
 // 'SpeechPattern' holds a single pattern.
 // Some info is not needed beyond converting the Yaml to the SpeechPattern, but is useful for error reporting.
+/// A named, parameterized list of replacements that can be used in place of a replacement in a rule.
+/// A macro is defined in a rule file with an entry in place of a speech rule:
+/// ```yaml
+/// - macro: open-close
+///   parameters: [open, close]
+///   replace: [t: "{open}", x: "*[1]", t: "{close}"]
+/// ```
+/// and is used by listing it (along with values for the parameters) in a replacement array:
+/// ```yaml
+///   replace:
+///   - macro: open-close
+///     open: "open paren"
+///     close: "close paren"
+/// ```
+/// Each `{parameter}` in a string of the macro's replacements is replaced with the value given for the parameter.
+/// Macros are expanded when the rule that uses them is read, so a macro needs to be defined before it is used.
+#[derive(Debug, Clone)]
+struct RuleMacro {
+    parameters: Vec<String>,
+    replacements: Yaml,                 // always an array
+}
+
+/// Guard against a macro that (indirectly) uses itself
+const MAX_MACRO_DEPTH: usize = 20;
+
+impl RuleMacro {
+    fn build(dict: &Yaml) -> Result<RuleMacro> {
+        let parameters = match &dict["parameters"] {
+            Yaml::BadValue => vec![],
+            Yaml::Array(parameters) => parameters.iter()
+                    .map(|parameter| as_str_checked(parameter).map(|parameter| parameter.to_string()))
+                    .collect::<Result<Vec<String>>>()?,
+            parameters => bail!(yaml_type_err(parameters, "array")),
+        };
+        let replacements = match &dict["replace"] {
+            Yaml::BadValue => bail!("Did not find 'replace' in\n{}", yaml_to_string(dict, 1)),
+            Yaml::Array(_) => dict["replace"].clone(),
+            replacement => Yaml::Array(vec![replacement.clone()]),
+        };
+        return Ok( RuleMacro{ parameters, replacements } );
+    }
+
+    /// Returns the replacements with the parameters replaced by their values in 'call' (the `- macro: name` entry)
+    fn instantiate(&self, call: &Yaml) -> Result<Yaml> {
+        let call_hash = as_hash_checked(call)?;
+        let mut args: Vec<(String, String)> = Vec::with_capacity(self.parameters.len());
+        for (key, value) in call_hash {
+            let key = as_str_checked(key)?;
+            if key == "macro" {
+                continue;
+            }
+            if !self.parameters.iter().any(|parameter| parameter == key) {
+                bail!("'{}' is not a parameter of the macro (parameters are {:?})", key, self.parameters);
+            }
+            let value = match value {
+                Yaml::String(value) => value.clone(),
+                Yaml::Integer(value) => value.to_string(),
+                Yaml::Real(value) => value.clone(),
+                Yaml::Boolean(value) => value.to_string(),
+                _ => bail!("the value of macro parameter '{}' must be a string, not {}", key, yaml_to_type(value)),
+            };
+            args.push( (format!("{{{}}}", key), value) );
+        }
+        if args.len() != self.parameters.len() {
+            let missing = self.parameters.iter()
+                    .filter(|&parameter| !args.iter().any(|(name, _)| name[1..name.len()-1] == *parameter))
+                    .cloned()
+                    .collect::<Vec<String>>();
+            bail!("missing value(s) for the macro parameter(s) {:?}", missing);
+        }
+        return Ok( substitute(&self.replacements, &args) );
+
+        fn substitute(yaml: &Yaml, args: &[(String, String)]) -> Yaml {
+            return match yaml {
+                Yaml::String(str) => Yaml::String(
+                    args.iter().fold(str.clone(), |str, (name, value)| str.replace(name, value))
+                ),
+                Yaml::Array(array) => Yaml::Array( array.iter().map(|item| substitute(item, args)).collect() ),
+                Yaml::Hash(hash) => Yaml::Hash(
+                    hash.iter().map(|(key, value)| (key.clone(), substitute(value, args))).collect()
+                ),
+                _ => yaml.clone(),
+            };
+        }
+    }
+}
+
+/// Replace each `- macro: name` entry in an array in 'yaml' with the replacements of the macro (see [`RuleMacro`])
+fn expand_macros(yaml: &Yaml, macros: &HashMap<String, RuleMacro>, depth: usize) -> Result<Yaml> {
+    return match yaml {
+        Yaml::Array(items) => {
+            let mut expanded = Vec::with_capacity(items.len());
+            for item in items {
+                if let Some(macro_name) = find_str(item, "macro") {
+                    if depth >= MAX_MACRO_DEPTH {
+                        bail!("macro '{}' is nested more than {} deep -- does it use itself?", macro_name, MAX_MACRO_DEPTH);
+                    }
+                    let rule_macro = match macros.get(macro_name) {
+                        Some(rule_macro) => rule_macro,
+                        None => bail!("Unknown macro '{}' (macros must be defined before they are used)", macro_name),
+                    };
+                    let replacements = rule_macro.instantiate(item)
+                            .chain_err(|| format!("using macro '{}'", macro_name))?;
+                    if let Yaml::Array(mut replacements) = expand_macros(&replacements, macros, depth+1)? {
+                        expanded.append(&mut replacements);
+                    }
+                } else {
+                    expanded.push( expand_macros(item, macros, depth)? );
+                }
+            }
+            Ok( Yaml::Array(expanded) )
+        },
+        Yaml::Hash(hash) => {
+            let mut expanded = Hash::new();
+            for (key, value) in hash {
+                expanded.insert(key.clone(), expand_macros(value, macros, depth)?);
+            }
+            Ok( Yaml::Hash(expanded) )
+        },
+        _ => Ok( yaml.clone() ),
+    };
+}
+
 // The two main parts are the pattern to be matched and the replacements to do if there is a match.
 // Any variables/prefs that are defined/set are also stored.
 #[derive(Debug)]
@@ -1114,6 +1371,22 @@ impl SpeechPattern  {
             return process_include(file, include_file_name, do_include_fn);
         }
 
+        if let Some(macro_name) = find_str(dict, "macro") {
+            let rule_macro = RuleMacro::build(dict)
+                    .chain_err(|| format!("in the definition of macro '{}'", macro_name))?;
+            rules.macros.insert(macro_name.to_string(), rule_macro);
+            return Ok( () );
+        }
+
+        let expanded_dict;
+        let dict = if rules.macros.is_empty() {
+            dict
+        } else {
+            expanded_dict = expand_macros(dict, &rules.macros, 0)
+                    .chain_err(|| format!("in rule '{}'", find_str(dict, "name").unwrap_or_default()))?;
+            &expanded_dict
+        };
+
         let pattern_name = find_str(dict, "name");
 
         // tag_named can be either a string (most common) or an array of strings
@@ -1617,6 +1890,9 @@ fn yaml_to_value<'a, 'b>(yaml: &'a Yaml) -> Value<'b> {
 }
 
 
+/// Keys that can be given along with a char in unicode.yaml to say how its speech is pronounced (see 'pronounce' in tts.rs)
+static PHONEME_HINT_KEYS: &[&str] = &["ipa", "sapi5", "eloquence"];
+
 // Information for matching a Unicode char (defined in unicode.yaml) and building its replacement
 struct UnicodeDef {
     ch: u32,
@@ -1644,11 +1920,25 @@ impl UnicodeDef {
         }
 
         let dictionary = dictionary.unwrap();
-        if dictionary.len() != 1 {
+        // besides the char, there can be phoneme hints for its speech (e.g., '- "χ": [t: "chi"]' followed by 'ipa: "kaɪ"')
+        let is_phoneme_hint = |key: &Yaml| key.as_str().is_some_and(|key| PHONEME_HINT_KEYS.contains(&key));
+        let phoneme_hints = dictionary.iter().filter(|(key, _)| is_phoneme_hint(key)).collect::<Vec<(&Yaml, &Yaml)>>();
+        if dictionary.len() != 1 + phoneme_hints.len() {
             bail!("Expected a unicode definition (e.g, '+':[t: \"plus\"]'), found {}", yaml_to_string(unicode_def, 0));
         }
 
-        let (ch, replacements) = dictionary.iter().next().ok_or_else(||  format!("Expected a unicode definition (e.g, '+':[t: \"plus\"]'), found {}", yaml_to_string(unicode_def, 0)))?;
+        let (ch, replacements) = dictionary.iter().find(|(key, _)| !is_phoneme_hint(key)).ok_or_else(||  format!("Expected a unicode definition (e.g, '+':[t: \"plus\"]'), found {}", yaml_to_string(unicode_def, 0)))?;
+        let replacements_with_hints;
+        let replacements = if phoneme_hints.is_empty() {
+            replacements
+        } else {
+            if ch.as_str().is_some_and(|str| str.chars().count() > 1) {
+                bail!("Phoneme hints can only be given for a single char, not for '{}'", yaml_to_string(ch, 0));
+            }
+            replacements_with_hints = add_phoneme_hints(replacements, &phoneme_hints)
+                    .chain_err(|| format!("In definition of char: '{}'", yaml_to_string(ch, 0)))?;
+            &replacements_with_hints
+        };
         let mut unicode_table = if use_short {
             speech_rules.unicode_short.borrow_mut()
         } else {
@@ -1699,6 +1989,30 @@ impl UnicodeDef {
             return Ok( () );            
         }
 
+        /// Convert the speech '[t: "chi"]' with the hints 'ipa: "kaɪ"' to '[pronounce: [{text: "chi"}, {ipa: "kaɪ"}]]'
+        fn add_phoneme_hints(replacements: &Yaml, phoneme_hints: &[(&Yaml, &Yaml)]) -> Result<Yaml> {
+            let replacement = match replacements {
+                Yaml::Array(v) if v.len() == 1 => &v[0],
+                Yaml::Array(_) => bail!("Phoneme hints require the speech to be a single 't:', found {}", yaml_to_string(replacements, 0)),
+                _ => replacements,
+            };
+            let text = match replacement.as_hash() {
+                Some(dict) if dict.len() == 1 => dict.get(&Yaml::String("t".to_string())).or_else(|| dict.get(&Yaml::String("T".to_string()))),
+                _ => None,
+            };
+            let text = text.ok_or_else(|| format!("Phoneme hints require the speech to be a single 't:', found {}", yaml_to_string(replacements, 0)))?;
+            let key_value = |key: Yaml, value: Yaml| {
+                let mut hash = Hash::new();
+                hash.insert(key, value);
+                Yaml::Hash(hash)
+            };
+            let mut pronounce = vec![ key_value(Yaml::String("text".to_string()), text.clone()) ];
+            for &(key, value) in phoneme_hints {
+                pronounce.push( key_value(key.clone(), value.clone()) );
+            }
+            return Ok( Yaml::Array(vec![ key_value(Yaml::String("pronounce".to_string()), Yaml::Array(pronounce)) ]) );
+        }
+
         fn substitute_ch(yaml: &Yaml, ch: &str) -> Yaml {
             return match yaml {
                 Yaml::Array(ref v) => {
@@ -1779,16 +2093,21 @@ impl UnicodeDef {
 pub struct SpeechRules {
     error: String,
     name: RulesFor,
-    pub pref_manager: Rc<RefCell<PreferenceManager>>,
+    pub pref_manager: SharedPreferences,
     rules: RuleTable,                       // the speech rules used (partitioned into MathML tags in hashmap, then linearly searched)
+    macros: HashMap<String, RuleMacro>,     // the macros defined in the rule files (only needed while reading them)
     translate_single_chars_only: bool,      // strings like "half" don't want 'a's translated, but braille does
     unicode_short: UnicodeTable,            // the short list of rules used for Unicode characters
     unicode_full:  UnicodeTable,            // the long remaining rules used for Unicode characters
+    style: Option<String>,                  // speech style to use instead of the SpeechStyle pref (see speak_intent_with_style)
 }
 
 impl fmt::Display for SpeechRules {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "SpeechRules '{}'\n{})", self.name, self.pref_manager.borrow())?;
+        match crate::prefs::read_prefs(&self.pref_manager) {
+            Ok(pref_manager) => writeln!(f, "SpeechRules '{}'\n{})", self.name, pref_manager)?,
+            Err(e) => writeln!(f, "SpeechRules '{}'\n{})", self.name, crate::interface::errors_to_string(&e))?,
+        };
         let mut rules_vec: Vec<(&String, &Vec<Box<SpeechPattern>>)> = self.rules.iter().collect();
         rules_vec.sort_by(|(tag_name1, _), (tag_name2, _)| tag_name1.cmp(tag_name2));
         for (tag_name, rules) in rules_vec {
@@ -1804,10 +2123,14 @@ impl fmt::Display for SpeechRules {
 /// Because speech rules can define variables, there is also a context that is carried with them
 pub struct SpeechRulesWithContext<'c, 's:'c, 'm:'c> {
     speech_rules: &'s SpeechRules,
+    pref_manager: SharedPreferences,    // usually the same as speech_rules.pref_manager, but might have per-call overrides
     context_stack: ContextStack<'c>,   // current value of (context) variables
     doc: Document<'m>,
     nav_node_id: String,
     pub inside_spell: bool,     // hack to allow 'spell' to avoid infinite loop (see 'spell' implementation in tts.rs)
+    rule_source: RuleSource<'s>,        // the rule whose replacements are being done
+    phrase_sources: Vec<PhraseSource>,  // where the text came from (only for speech -- see `record_phrase`)
+    mark_sources: bool,                 // bracket the speech for each element with its id (see `speak_intent_with_sources`)
 }
 
 impl<'c, 's:'c, 'm:'c> fmt::Display for SpeechRulesWithContext<'c, 's,'m> {
@@ -1823,6 +2146,10 @@ thread_local!{
         
     static SPEECH_UNICODE_FULL: UnicodeTable =
         Rc::new( RefCell::new( HashMap::with_capacity(497) ) );
+
+    /// The user's pronunciations of characters and tokens (from their pronunciations.yaml); these override the unicode files.
+    /// They are (re)read along with the short speech unicode file(s).
+    static USER_PRONUNCIATIONS: RefCell<HashMap<String, String>> = RefCell::new( HashMap::new() );
         
     /// The current set of speech rules
     // maybe this should be a small cache of rules in case people switch rules/prefs?
@@ -1840,19 +2167,186 @@ thread_local!{
 
     pub static BRAILLE_RULES: RefCell<SpeechRules> =
             RefCell::new( SpeechRules::new(RulesFor::Braille, false) );
+
+    /// Speech rules for styles other than the SpeechStyle pref (see `speak_intent_with_style`) along with the rule files they use
+    static STYLE_RULES: RefCell<HashMap<String, (Locations, SpeechRules)>> = RefCell::new( HashMap::new() );
+
+    /// Where each piece of the last speech generated came from (see [`PhraseSource`])
+    static LAST_PHRASE_SOURCES: RefCell<Vec<PhraseSource>> = const { RefCell::new( Vec::new() ) };
+
+    /// The post processors added by [`crate::interface::add_speech_post_processor`] (called in the order they were added)
+    static SPEECH_POST_PROCESSORS: RefCell<Vec<Box<dyn SpeechPostProcessor>>> = const { RefCell::new( Vec::new() ) };
+}
+
+/// The state of a thread that depends on the preferences: the PreferenceManager, the rules read for it, and the definitions.
+/// A [`crate::interface::DocumentContext`] keeps its own state and exchanges it with the thread's for each call (see [`ThreadRules::swap`]),
+/// so a document with its own language or braille code doesn't change the thread's preferences or cause its rules to be read again.
+pub(crate) struct ThreadRules {
+    pref_manager: SharedPreferences,
+    seen_change_counts: FileChangeCounts,
+    // the rules are created the first time they are swapped in (they use the thread's PreferenceManager and definitions)
+    intent: Option<SpeechRules>,
+    speech: Option<SpeechRules>,
+    overview: Option<SpeechRules>,
+    navigation: Option<SpeechRules>,
+    braille: Option<SpeechRules>,
+    style_rules: HashMap<String, (Locations, SpeechRules)>,
+    // the speech rules share these with the intent, overview, and navigation rules
+    speech_unicode_short: HashMap<u32,Vec<Replacement>>,
+    speech_unicode_full: HashMap<u32,Vec<Replacement>>,
+    user_pronunciations: HashMap<String, String>,
+    definitions: crate::definitions::Definitions,
+    definitions_locations: Locations,
+}
+
+impl ThreadRules {
+    /// State for `pref_manager` -- nothing is read until the state is used
+    pub(crate) fn new(pref_manager: PreferenceManager) -> ThreadRules {
+        return ThreadRules {
+            seen_change_counts: pref_manager.get_change_counts(),
+            pref_manager: Arc::new( RwLock::new(pref_manager) ),
+            intent: None,
+            speech: None,
+            overview: None,
+            navigation: None,
+            braille: None,
+            style_rules: HashMap::new(),
+            speech_unicode_short: HashMap::new(),
+            speech_unicode_full: HashMap::new(),
+            user_pronunciations: HashMap::new(),
+            definitions: crate::definitions::Definitions::default(),
+            definitions_locations: Locations::default(),
+        };
+    }
+
+    /// Use `pref_manager` from now on. The rules whose files are different are read again when they are next used.
+    pub(crate) fn set_prefs(&mut self, mut pref_manager: PreferenceManager) {
+        pref_manager.continue_from(&crate::prefs::read_prefs_unchecked(&self.pref_manager));
+        self.pref_manager = Arc::new( RwLock::new(pref_manager) );
+    }
+
+    /// Exchange this state with the thread's; calling it again undoes the exchange.
+    /// Nothing is read and the preferences are not changed, so this can't fail.
+    pub(crate) fn swap(&mut self) {
+        PreferenceManager::swap_with_this_thread(&mut self.pref_manager, &mut self.seen_change_counts);
+        crate::definitions::swap_definitions(&mut self.definitions, &mut self.definitions_locations);
+        SPEECH_UNICODE_SHORT.with(|unicode| std::mem::swap(&mut *unicode.borrow_mut(), &mut self.speech_unicode_short));
+        SPEECH_UNICODE_FULL.with(|unicode| std::mem::swap(&mut *unicode.borrow_mut(), &mut self.speech_unicode_full));
+        USER_PRONUNCIATIONS.with(|pronunciations| std::mem::swap(&mut *pronunciations.borrow_mut(), &mut self.user_pronunciations));
+        STYLE_RULES.with(|style_rules| std::mem::swap(&mut *style_rules.borrow_mut(), &mut self.style_rules));
+        swap_rules(&INTENT_RULES, &mut self.intent, RulesFor::Intent, true);
+        swap_rules(&SPEECH_RULES, &mut self.speech, RulesFor::Speech, true);
+        swap_rules(&OVERVIEW_RULES, &mut self.overview, RulesFor::OverView, true);
+        swap_rules(&NAVIGATION_RULES, &mut self.navigation, RulesFor::Navigation, true);
+        swap_rules(&BRAILLE_RULES, &mut self.braille, RulesFor::Braille, false);
+
+        fn swap_rules(rules: &'static std::thread::LocalKey<RefCell<SpeechRules>>, saved: &mut Option<SpeechRules>,
+                      name: RulesFor, translate_single_chars_only: bool) {
+            rules.with(|rules| match saved {
+                Some(saved) => std::mem::swap(&mut *rules.borrow_mut(), saved),
+                // the PreferenceManager and definitions were swapped in first, so the new rules use them
+                None => *saved = Some( rules.replace(SpeechRules::new(name, translate_single_chars_only)) ),
+            });
+        }
+    }
+}
+
+/// Implemented by embedders that want to change the words of the speech before it is returned
+/// (e.g., swapping words for young audiences or tagging words for telemetry). See [`crate::interface::add_speech_post_processor`].
+pub trait SpeechPostProcessor {
+    /// Change the `words` of the speech -- words can be changed, removed, or added.
+    /// The words are the whitespace-separated pieces of the speech; TTS markup (e.g., SSML tags) isn't included.
+    /// The words between two TTS tags are processed separately from those before/after them.
+    fn process(&self, words: &mut Vec<String>);
+}
+
+pub(crate) fn add_speech_post_processor(processor: Box<dyn SpeechPostProcessor>) {
+    SPEECH_POST_PROCESSORS.with(|processors| processors.borrow_mut().push(processor));
+}
+
+pub(crate) fn clear_speech_post_processors() {
+    SPEECH_POST_PROCESSORS.with(|processors| processors.borrow_mut().clear());
+}
+
+/// Run the words in `speech` through the post processors (see [`SpeechPostProcessor`]), leaving the TTS markup alone.
+pub(crate) fn post_process_speech(tts: &TTS, speech: String) -> String {
+    lazy_static! {
+        static ref TTS_TAG: regex::Regex = regex::Regex::new(r"<[^>]*>").unwrap();
+    }
+    return SPEECH_POST_PROCESSORS.with(|processors| {
+        let processors = processors.borrow();
+        if processors.is_empty() {
+            return speech;
+        }
+        let process = |text: &str| {
+            let mut words = text.split_whitespace().map(|word| word.to_string()).collect::<Vec<String>>();
+            if words.is_empty() {
+                return text.to_string();
+            }
+            for processor in processors.iter() {
+                processor.process(&mut words);
+            }
+            // keep the spacing around the TTS tags
+            let before = if text.starts_with(char::is_whitespace) {" "} else {""};
+            let after = if text.ends_with(char::is_whitespace) {" "} else {""};
+            return before.to_string() + &words.join(" ") + after;
+        };
+        if *tts == TTS::None {
+            return process(&speech);
+        }
+        let mut result = String::with_capacity(speech.len());
+        let mut start = 0;
+        for tag in TTS_TAG.find_iter(&speech) {
+            result += &process(&speech[start..tag.start()]);
+            result += tag.as_str();
+            start = tag.end();
+        }
+        result += &process(&speech[start..]);
+        return result;
+    });
+}
+
+/// The rule that produced a piece of the speech -- used to answer "where does this word come from?" (see [`crate::interface::explain_phrase`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhraseSource {
+    /// The text in the rule (e.g., "over")
+    pub text: String,
+    /// The name of the rule (e.g., "fraction-over-simple") or, for a unicode file, the character (e.g., "+")
+    pub rule: String,
+    /// The tag the rule is for (e.g., "fraction") or "unicode" for a unicode file
+    pub tag: String,
+    /// The file the rule is in
+    pub file: String,
+}
+
+/// Return where each piece of the last speech that was generated came from
+pub fn get_last_phrase_sources() -> Vec<PhraseSource> {
+    return LAST_PHRASE_SOURCES.with(|sources| sources.borrow().clone());
+}
+
+/// The rule currently being applied (used to record `PhraseSource`s)
+#[derive(Clone, Copy)]
+enum RuleSource<'s> {
+    None,
+    Pattern(&'s SpeechPattern),
+    Unicode(char, bool),        // the char and whether it is from the full unicode file
 }
 
 impl SpeechRules {
     pub fn new(name: RulesFor, translate_single_chars_only: bool) -> SpeechRules {
         use crate::definitions::read_definitions_file;
         let pref_manager = PreferenceManager::get();
-        let mut error = pref_manager.borrow().get_error().to_string();
+        let (mut error, definitions_file) = match crate::prefs::read_prefs(&pref_manager) {
+            Ok(pref_manager) if pref_manager.get_error().is_empty() => (String::new(), Some(pref_manager.get_definitions_file().clone())),
+            Ok(pref_manager) => (pref_manager.get_error().to_string(), None),
+            Err(e) => (crate::interface::errors_to_string(&e), None),
+        };
 
-        if pref_manager.borrow().get_error().is_empty() {
-            let read_files = read_definitions_file(pref_manager.borrow().get_definitions_file());
+        if let Some(definitions_file) = definitions_file {
+            let read_files = read_definitions_file(&definitions_file);
             match read_files {
                 Ok(_) => {
-                    // debug!("SpeechRules new for {}, tts {}", name, pref_manager.borrow().get_api_prefs().to_string("TTS"));
+                    // debug!("SpeechRules new for {}, tts {}", name, pref_manager.read().unwrap().get_api_prefs().to_string("TTS"));
                     let unicode = if name == RulesFor::Braille {
                         (
                             Rc::new( RefCell::new (HashMap::with_capacity(497)) ),
@@ -1869,9 +2363,11 @@ impl SpeechRules {
                         error: Default::default(),
                         name,
                         rules: HashMap::with_capacity(if name == RulesFor::Intent {1023} else {31}),                       // lazy load them
+                        macros: HashMap::new(),
                         unicode_short: unicode.0,       // lazy load them
                         unicode_full: unicode.1,        // lazy load them
                         translate_single_chars_only,
+                        style: None,
                         pref_manager,
                     };
                     return rules;
@@ -1883,13 +2379,22 @@ impl SpeechRules {
             error,
             name,
             rules: HashMap::with_capacity(1),
+            macros: HashMap::new(),
             unicode_short: Rc::new( RefCell::new (HashMap::with_capacity(1)) ),
             unicode_full: Rc::new( RefCell::new (HashMap::with_capacity(1)) ),
             translate_single_chars_only: true,
+            style: None,
             pref_manager,
         };
     }
 
+    /// Speech rules for `style` that are used instead of the rules for the `SpeechStyle` preference
+    fn new_for_style(style: &str) -> SpeechRules {
+        let mut rules = SpeechRules::new(RulesFor::Speech, true);
+        rules.style = Some(style.to_string());
+        return rules;
+    }
+
     pub fn get_error(&self) -> Option<&str> {
         return if self.error.is_empty() {
              None
@@ -1899,6 +2404,7 @@ impl SpeechRules {
     }
 
     pub fn initialize_all_rules() -> Result<()> {
+        crate::prefs::read_prefs(&PreferenceManager::get())?.check_initialized()?;
         // this forces initialization of things beyond just the speech rules (e.g, the defs.yaml files get read)
         INTENT_RULES.with(|speech_rules| -> Result<()> {
             if let Some(e) = speech_rules.borrow().get_error() {bail!("{}", e)} else {Ok(())}
@@ -1919,8 +2425,13 @@ impl SpeechRules {
     }
 
     pub fn read_files(&mut self) -> Result<()> {
+        crate::prefs::read_prefs(&self.pref_manager)?.check_initialized()?;
         if self.rules.is_empty() {
-            let rule_file = self.pref_manager.borrow().get_rule_file(&self.name).clone();
+            let rule_file = match &self.style {
+                None => crate::prefs::read_prefs(&self.pref_manager)?.get_rule_file(&self.name).clone(),
+                Some(style) => crate::prefs::read_prefs(&self.pref_manager)?.get_style_rule_file(style)?,
+            };
+            self.macros.clear();
             self.read_patterns(&rule_file)?;
         }
         if self.unicode_short.borrow().is_empty()  {
@@ -1929,8 +2440,27 @@ impl SpeechRules {
         return Ok( () );
     }
 
+    /// True if `ch` has a definition in the unicode files (the full file is read if needed).
+    /// Characters without a definition are spoken as themselves. `read_files` should be called first.
+    pub fn has_unicode_def(&self, ch: char) -> Result<bool> {
+        let ch_as_u32 = ch as u32;
+        if self.unicode_short.borrow().contains_key(&ch_as_u32) {
+            return Ok(true);
+        }
+        if self.unicode_full.borrow().is_empty() {
+            self.read_unicode(None, false)?;
+        }
+        return Ok( self.unicode_full.borrow().contains_key(&ch_as_u32) );
+    }
+
     pub fn invalidate(&mut self, changes: FilesChanged) {
-        if self.name == RulesFor::Braille {
+        if self.name == RulesFor::Navigation || self.name == RulesFor::OverView {
+            let rules_changed = if self.name == RulesFor::Navigation {changes.navigation} else {changes.overview};
+            if rules_changed {
+                self.rules.clear();
+            }
+            // the unicode files are shared with speech and cleared there
+        } else if self.name == RulesFor::Braille {
             if changes.braille_rules {
                 self.rules.clear();
             }
@@ -1951,10 +2481,49 @@ impl SpeechRules {
                 self.unicode_full.borrow_mut().clear();
             }
         }
+        if changes.defs {
+            // the definitions are shared by all the rules -- they were read for the old language when the rules were created
+            let read_files = crate::prefs::read_prefs(&self.pref_manager)
+                    .and_then(|pref_manager| crate::definitions::read_definitions_file(pref_manager.get_definitions_file()));
+            if let Err(e) = read_files {
+                error!("Failed to read the definitions file(s): {}", crate::interface::errors_to_string(&e));
+            }
+        }
     }
 
-    pub fn update() {
-        if let Some(files_changed) = PreferenceManager::get().borrow_mut().is_up_to_date() {
+    /// Replace this thread's rules with (not yet read) rules that use the current PreferenceManager.
+    /// This is needed when the thread starts sharing another thread's preferences (see [`PreferenceManager::share_with_this_thread`]).
+    pub fn reset_all() {
+        crate::definitions::invalidate_definitions_file();
+        SPEECH_UNICODE_SHORT.with(|unicode| unicode.borrow_mut().clear());
+        SPEECH_UNICODE_FULL.with(|unicode| unicode.borrow_mut().clear());
+        INTENT_RULES.with(|rules| *rules.borrow_mut() = SpeechRules::new(RulesFor::Intent, true));
+        SPEECH_RULES.with(|rules| *rules.borrow_mut() = SpeechRules::new(RulesFor::Speech, true));
+        OVERVIEW_RULES.with(|rules| *rules.borrow_mut() = SpeechRules::new(RulesFor::OverView, true));
+        NAVIGATION_RULES.with(|rules| *rules.borrow_mut() = SpeechRules::new(RulesFor::Navigation, true));
+        BRAILLE_RULES.with(|rules| *rules.borrow_mut() = SpeechRules::new(RulesFor::Braille, false));
+        STYLE_RULES.with(|style_rules| style_rules.borrow_mut().clear());
+    }
+
+    /// Rules that were created while the PreferenceManager was invalid (e.g., the Rules dir wasn't found) keep that error.
+    /// If that happened and the PreferenceManager is now valid, the rules are recreated (see [`SpeechRules::reset_all`]).
+    pub fn reset_if_invalid() {
+        if crate::prefs::read_prefs(&PreferenceManager::get()).and_then(|pref_manager| pref_manager.check_initialized()).is_err() {
+            return;
+        }
+        let has_error = [&INTENT_RULES, &SPEECH_RULES, &OVERVIEW_RULES, &NAVIGATION_RULES, &BRAILLE_RULES].iter()
+                .any(|rules| rules.with(|rules| rules.borrow().get_error().is_some()));
+        if has_error {
+            SpeechRules::reset_all();
+        }
+    }
+
+    /// Clear the rules (and unicode definitions) whose files changed since they were read so they are re-read when next used.
+    /// This is called before generating speech, braille, or navigation, so edits to the rule files are used without a restart.
+    pub fn update() -> Result<()> {
+        // note: the PreferenceManager can't stay borrowed because reading the definitions below borrows it
+        let files_changed = crate::prefs::write_prefs(&PreferenceManager::get())?.is_up_to_date();
+        if let Some(files_changed) = files_changed {
             SPEECH_RULES.with(|rules| {
                 let mut rules = rules.borrow_mut();
                 if files_changed.speech_rules {
@@ -1979,6 +2548,9 @@ impl SpeechRules {
                     rules.unicode_full.borrow_mut().clear();
                 }
             });
+            if files_changed.speech_rules {
+                STYLE_RULES.with(|style_rules| style_rules.borrow_mut().clear());
+            }
             INTENT_RULES.with(|rules| {
                 let mut rules = rules.borrow_mut();
                 if files_changed.intent {
@@ -1986,27 +2558,45 @@ impl SpeechRules {
                 }
                 // unicode files are shared with speech and updated/cleared there
             });
-            
-            // FIX: need to add overview and navigation to the update rules
+            if files_changed.overview {
+                OVERVIEW_RULES.with(|rules| rules.borrow_mut().rules.clear());
+            }
+            if files_changed.navigation {
+                NAVIGATION_RULES.with(|rules| rules.borrow_mut().rules.clear());
+            }
+            if files_changed.defs {
+                use crate::definitions::{invalidate_definitions_file, read_definitions_file};
+                invalidate_definitions_file();
+                let pref_manager = PreferenceManager::get();
+                let pref_manager = crate::prefs::read_prefs(&pref_manager)?;
+                if let Err(e) = read_definitions_file(pref_manager.get_definitions_file()) {
+                    error!("Failed to reread the definitions file(s): {}", crate::interface::errors_to_string(&e));
+                }
+            }
         }
+        return Ok( () );
     }
 
     fn read_patterns(&mut self, path: &Locations) -> Result<()> {
-        if let Some(p) = &path[0] {
+        // the language file is read first -- rules in a region file (e.g., "en/gb") replace rules of the same name
+        for p in path.iter().flatten() {
             // info!("Reading rule file: {}", p.to_str().unwrap());
             let rule_file_contents = read_to_string_shim(p.as_path()).expect("cannot read file");
             let rules_build_fn = |pattern: &Yaml| {
                 self.build_speech_patterns(pattern, p)
                     .chain_err(||format!("in file {:?}", p.to_str().unwrap()))
             };
-            return compile_rule(&rule_file_contents, rules_build_fn)
-                    .chain_err(||format!("in file {:?}", p.to_str().unwrap()));
+            compile_rule(&rule_file_contents, rules_build_fn)
+                    .chain_err(||format!("in file {:?}", p.to_str().unwrap()))?;
         }
         return Ok(());
     }
 
     fn build_speech_patterns(&mut self, patterns: &Yaml, file_name: &Path) -> Result<()> {
         // Rule::SpeechPatternList
+        if patterns.is_null() {
+            return Ok( () );    // empty file (e.g., a region file with no overrides)
+        }
         let patterns_vec = patterns.as_vec();
         if patterns_vec.is_none() {
             bail!(yaml_type_err(patterns, "array"));
@@ -2023,18 +2613,27 @@ impl SpeechRules {
         let path = match path {
             Some(p) => p,
             None => {
-                // get the path to either the short or long unicode file
-                let pref_manager = self.pref_manager.borrow();
-                let unicode_files = if self.name == RulesFor::Braille {
-                    pref_manager.get_braille_unicode_file()
-                } else {
-                    pref_manager.get_speech_unicode_file()
+                // get the paths to either the short or long unicode files
+                let unicode_files = {
+                    let pref_manager = crate::prefs::read_prefs(&self.pref_manager)?;
+                    let unicode_files = if self.name == RulesFor::Braille {
+                        pref_manager.get_braille_unicode_file()
+                    } else {
+                        pref_manager.get_speech_unicode_file()
+                    };
+                    if use_short {unicode_files.0.clone()} else {unicode_files.1.clone()}
                 };
-                if use_short {unicode_files.0} else {unicode_files.1}
+                // read the language file first, then the region file (if any) overrides the definitions
+                for path in unicode_files.iter().flatten() {
+                    self.read_unicode(Some(path.to_path_buf()), use_short)?;
+                }
+                if use_short && self.name != RulesFor::Braille {
+                    self.read_pronunciations();
+                }
+                return Ok( () );
             }
         };
 
-        // FIX: should read first (lang), then supplement with second (region)
         info!("Reading unicode file {}", path.to_str().unwrap());
         let unicode_file_contents = read_to_string_shim(&path)?;
         let unicode_build_fn = |unicode_def_list: &Yaml| {
@@ -2052,6 +2651,59 @@ impl SpeechRules {
         return compile_rule(&unicode_file_contents, unicode_build_fn)
                     .chain_err(||format!("in file {:?}", path.to_str().unwrap()));
     }
+
+    /// Read the user's pronunciations.yaml (if any) into `USER_PRONUNCIATIONS`.
+    /// The file is a mapping from a character or token to what to say for it (e.g., `ℏ: h bar`).
+    /// A bad file shouldn't stop speech from working, so problems are logged and the file is ignored.
+    fn read_pronunciations(&self) {
+        let path = crate::prefs::read_prefs(&self.pref_manager).map(|pref_manager| pref_manager.get_pronunciations_file().cloned());
+        let pronunciations = match path {
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                error!("{}", crate::interface::errors_to_string(&e));
+                HashMap::new()
+            },
+            Ok(Some(path)) => {
+                info!("Reading pronunciations file {}", path.to_string_lossy());
+                match read_pronunciations_file(&path) {
+                    Ok(pronunciations) => pronunciations,
+                    Err(e) => {
+                        error!("{}", crate::interface::errors_to_string(&e.chain_err(|| format!("in file {}", path.to_string_lossy()))));
+                        HashMap::new()
+                    },
+                }
+            },
+        };
+        USER_PRONUNCIATIONS.with(|user_pronunciations| *user_pronunciations.borrow_mut() = pronunciations);
+
+        fn read_pronunciations_file(path: &Path) -> Result<HashMap<String, String>> {
+            let docs = YamlLoader::load_from_str(&read_to_string_shim(path)?)
+                    .chain_err(|| "yaml syntax error")?;
+            let mut pronunciations = HashMap::new();
+            let doc = match docs.first() {
+                None => return Ok(pronunciations),      // empty file
+                Some(doc) => doc,
+            };
+            let entries = match doc.as_hash() {
+                None => bail!("the file should be a mapping of characters/tokens to what to say for them, not {}", yaml_to_type(doc)),
+                Some(entries) => entries,
+            };
+            for (key, value) in entries {
+                // keys such as '1' or 'true' aren't strings in YAML
+                let key = match key {
+                    Yaml::String(key) | Yaml::Real(key) => key.trim().to_string(),
+                    Yaml::Integer(key) => key.to_string(),
+                    Yaml::Boolean(key) => key.to_string(),
+                    _ => bail!("'{}' is not a character or token", yaml_to_type(key)),
+                };
+                match value.as_str() {
+                    Some(value) if !key.is_empty() => pronunciations.insert(key, value.trim().to_string()),
+                    _ => bail!("the pronunciation of '{}' should be a string, not {}", key, yaml_to_type(value)),
+                };
+            }
+            return Ok(pronunciations);
+        }
+    }
 }
 
 use crate::prefs::FilesChanged;
@@ -2061,12 +2713,26 @@ use crate::prefs::FilesChanged;
 ///   'r -- the lifetime of the reference (this seems to be key to keep the rust memory checker happy)
 impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
     pub fn new(speech_rules: &'s SpeechRules, doc: Document<'m>, nav_node_id: String) -> SpeechRulesWithContext<'c, 's, 'm> {
+        return SpeechRulesWithContext::new_with_prefs(speech_rules, doc, nav_node_id, speech_rules.pref_manager.clone());
+    }
+
+    /// Same as `new`, but the preferences come from `pref_manager` rather than from the `speech_rules`
+    pub fn new_with_prefs(speech_rules: &'s SpeechRules, doc: Document<'m>, nav_node_id: String, pref_manager: SharedPreferences) -> SpeechRulesWithContext<'c, 's, 'm> {
+        crate::xpath_functions::reset_variation_indices();     // a new tree is about to be matched
+        let mut context_stack = ContextStack::new(&crate::prefs::read_prefs_unchecked(&pref_manager));
+        if let Some(style) = &speech_rules.style {
+            context_stack.base.set_variable("SpeechStyle", yaml_to_value(&Yaml::String(style.clone())));
+        }
         return SpeechRulesWithContext {
             speech_rules,
-            context_stack: ContextStack::new(&speech_rules.pref_manager.borrow()),
+            pref_manager,
+            context_stack,
             doc,
             nav_node_id,
             inside_spell: false,
+            rule_source: RuleSource::None,
+            phrase_sources: Vec::new(),
+            mark_sources: false,
         }
     }
 
@@ -2074,6 +2740,11 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
         return self.speech_rules;
     }
 
+    /// The preferences to use when matching (these might differ from the rules' preferences -- see `new_with_prefs`)
+    pub fn get_pref_manager(&self) -> &SharedPreferences {
+        return &self.pref_manager;
+    }
+
     pub fn get_context(&mut self) -> &mut Context<'c> {
         return &mut self.context_stack.base;
     }
@@ -2110,7 +2781,7 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
         // no rules matched -- poorly written rule file -- let flow through to default error
         // report error message with file name
         let mut file_name = "unknown";
-        let speech_manager = self.speech_rules.pref_manager.borrow();
+        let speech_manager = crate::prefs::read_prefs(&self.pref_manager)?;
         if let Some(path) = &speech_manager.get_rule_file(&self.speech_rules.name)[0] {
             file_name= path.to_str().unwrap();
         }
@@ -2118,7 +2789,7 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
         bail!("\nNo match found!\nMissing patterns in {} for MathML.\n{}", file_name, mml_to_string(&mathml)); 
     }
 
-    fn find_match<T:TreeOrString<'c, 'm, T>>(&'r mut self, rule_vector: &[Box<SpeechPattern>], mathml: Element<'c>) -> Result<Option<T>> {
+    fn find_match<T:TreeOrString<'c, 'm, T>>(&'r mut self, rule_vector: &'s [Box<SpeechPattern>], mathml: Element<'c>) -> Result<Option<T>> {
         for pattern in rule_vector {
             // debug!("Pattern: {}", pattern);
             // pushing and popping around the is_match would be a little cleaner, but push/pop is relatively expensive, so we optimize
@@ -2130,7 +2801,9 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
                 if !pattern.match_uses_var_defs && pattern.var_defs.len() > 0 { // don't push them on twice
                     self.context_stack.push(pattern.var_defs.clone(), mathml)?;
                 }
+                let outer_source = std::mem::replace(&mut self.rule_source, RuleSource::Pattern(pattern));
                 let result: Result<T> = pattern.replacements.replace(self, mathml);
+                self.rule_source = outer_source;
                 if pattern.var_defs.len() > 0 {
                     self.context_stack.pop();
                 }
@@ -2142,12 +2815,17 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
                                 None => {},
                                 Some(id) => {
                                     if self.nav_node_id == id {
-                                        let highlight_style =  self.speech_rules.pref_manager.borrow().get_user_prefs().to_string("BrailleNavHighlight");
+                                        let highlight_style =  crate::prefs::read_prefs(&self.pref_manager)?.get_user_prefs().to_string("BrailleNavHighlight");
                                         return Ok( Some( T::highlight_braille(s, highlight_style) ) );
                                     }
                                 }
                             }
                         }
+                        if self.mark_sources {
+                            if let Some(id) = mathml.attribute_value("id") {
+                                return Ok( Some( T::mark_source(s, id) ) );
+                            }
+                        }
                         Ok( Some(s) )
                     },
                     Err(e) => Err( e.chain_err(||
@@ -2238,11 +2916,16 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
     fn replace<T:TreeOrString<'c, 'm, T>>(&'r mut self, replacement: &Replacement, mathml: Element<'c>) -> Result<T> {
         return Ok(
             match replacement {
-                Replacement::Text(t) => T::from_string(t.clone(), self.doc)?,
+                Replacement::Text(t) => {
+                    self.record_phrase(t);
+                    T::from_string(t.clone(), self.doc)?
+                },
                 Replacement::XPath(xpath) => xpath.replace(self, mathml)?,
                 Replacement::TTS(tts) => {
+                    let pref_manager = self.pref_manager.clone();
+                    let pref_manager = crate::prefs::read_prefs(&pref_manager)?;
                     T::from_string(
-                        self.speech_rules.pref_manager.borrow().get_tts().replace(tts, &self.speech_rules.pref_manager.borrow(), self, mathml)?,
+                        pref_manager.get_tts().replace(tts, &pref_manager, self, mathml)?,
                         self.doc
                     )?
                 },
@@ -2268,6 +2951,29 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
         )
     }
 
+    /// Remember which rule `text` came from so that [`crate::interface::explain_phrase`] can find it (only done for speech)
+    fn record_phrase(&mut self, text: &str) {
+        if self.speech_rules.name != RulesFor::Speech {
+            return;
+        }
+        let text = text.replace([OPTIONAL_INDICATOR.chars().next().unwrap(), CONCAT_INDICATOR.chars().next().unwrap()], "");
+        if text.trim().is_empty() {
+            return;
+        }
+        let (rule, tag, file) = match self.rule_source {
+            RuleSource::None => return,
+            RuleSource::Pattern(pattern) => (pattern.pattern_name.clone(), pattern.tag_name.clone(), pattern.file_name.clone()),
+            RuleSource::Unicode(ch, is_full) => {
+                let pref_manager = crate::prefs::read_prefs_unchecked(&self.pref_manager);
+                let (short_files, full_files) = pref_manager.get_speech_unicode_file();
+                let files = if is_full {full_files} else {short_files};
+                let file = files.iter().flatten().last().map(|file| file.to_string_lossy().to_string()).unwrap_or_default();
+                (ch.to_string(), "unicode".to_string(), file)
+            },
+        };
+        self.phrase_sources.push( PhraseSource { text, rule, tag, file } );
+    }
+
     /// Iterate over all the nodes, concatenating the result strings together with a ' ' between them
     /// If the node is an element, pattern match it
     /// For 'Text' and 'Attribute' nodes, convert them to strings
@@ -2333,6 +3039,9 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
     /// Note: TTS is not supported here (not needed and a little less efficient)
     pub fn replace_chars(&'r mut self, str: &str, mathml: Element<'c>) -> Result<String> {
         let rules = self.speech_rules;
+        if let Some(pronunciation) = user_pronunciation(rules, str) {
+            return Ok(pronunciation);
+        }
         let mut chars = str.chars();
         // in a string, avoid "a" -> "eigh", "." -> "point", etc
         if rules.translate_single_chars_only {
@@ -2353,9 +3062,13 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
         return Ok( result );
 
         fn replace_single_char<'c, 's:'c, 'm, 'r>(rules_with_context: &'r mut SpeechRulesWithContext<'c,'s,'m>, ch: char, mathml: Element<'c>) -> Result<String> {
+            if let Some(pronunciation) = user_pronunciation(rules_with_context.speech_rules, ch.encode_utf8(&mut [0; 4])) {
+                return Ok(pronunciation);
+            }
             let ch_as_u32 = ch as u32;
             let mut unicode = rules_with_context.speech_rules.unicode_short.borrow();
             let mut replacements = unicode.get( &ch_as_u32 );
+            let mut is_full = false;
             if replacements.is_none() {
                 // see if it in the full unicode table (if it isn't loaded already)
                 if rules_with_context.speech_rules.unicode_full.borrow().is_empty() {
@@ -2370,22 +3083,32 @@ impl<'c, 's:'c, 'r, 'm:'c> SpeechRulesWithContext<'c, 's,'m> {
                     // debug!("*** Did not find unicode {} for char '{}'/{:#06x}", rules_with_context.speech_rules.name, ch, ch_as_u32);
                     return Ok(String::from(ch));   // no replacement, so just return the char and hope for the best
                 }
+                is_full = true;
             };
 
             // map across all the parts of the replacement, collect them up into a Vec, and then concat them together
-            return Ok(
-                replacements.unwrap()
+            let outer_source = std::mem::replace(&mut rules_with_context.rule_source, RuleSource::Unicode(ch, is_full));
+            let result = replacements.unwrap()
                             .iter()
                             .map(|replacement|
                                 rules_with_context.replace(replacement, mathml)
                                         .chain_err(|| format!("Unicode replacement error: {}", replacement)) )
-                            .collect::<Result<Vec<String>>>()?
-                            .join(" ")
-            );
+                            .collect::<Result<Vec<String>>>();
+            rules_with_context.rule_source = outer_source;
+            return Ok( result?.join(" ") );
         }
     }
 }
 
+/// The user's pronunciation of `str` (if any) from their pronunciations.yaml.
+/// These are for speech, so they aren't used for braille or when inferring intent.
+fn user_pronunciation(rules: &SpeechRules, str: &str) -> Option<String> {
+    if rules.name == RulesFor::Braille || rules.name == RulesFor::Intent {
+        return None;
+    }
+    return USER_PRONUNCIATIONS.with(|pronunciations| pronunciations.borrow().get(str).cloned());
+}
+
 // Hack to allow replacement of `str` with braille chars.
 pub fn braille_replace_chars(str: &str, mathml: Element) -> Result<String> {
     return BRAILLE_RULES.with(|rules| {
@@ -2444,6 +3167,49 @@ mod tests {
         assert_eq!(speech_pattern.replacements.replacements.len(), 2, "\nreplacement failure");
     }
 
+    #[test]
+    fn test_read_macro() {
+        let str = r#"---
+        - macro: fenced
+          parameters: [open, close]
+          replace: [t: "{open}", x: "*[1]", t: "{close}"]
+        - macro: fenced-twice
+          parameters: [name]
+          replace: [{macro: fenced, open: "open {name}", close: "close {name}"}, t: "again"]
+        - {name: default, tag: mrow, match: ".", replace: [t: "the", {macro: fenced-twice, name: paren}] }"#;
+        let doc = YamlLoader::load_from_str(str).unwrap();
+        let mut rules = SpeechRules::new(RulesFor::Speech, true);
+        rules.build_speech_patterns(&doc[0], Path::new("testing")).unwrap();
+        assert_eq!(rules.macros.len(), 2);
+
+        let replacements = &rules.rules["mrow"][0].replacements.replacements;
+        let replacements = replacements.iter().map(|replacement| replacement.to_string()).collect::<Vec<String>>();
+        assert_eq!(replacements, vec![r#"t: "the""#, r#"t: "open paren""#, r#"x: "*[1]""#, r#"t: "close paren""#, r#"t: "again""#]);
+    }
+
+    #[test]
+    fn test_read_macro_errors() {
+        let str = r#"---
+        - macro: fenced
+          parameters: [open, close]
+          replace: [t: "{open}", x: "*[1]", t: "{close}"]
+        - macro: recursive
+          replace: [macro: recursive]"#;
+        let doc = YamlLoader::load_from_str(str).unwrap();
+        let mut rules = SpeechRules::new(RulesFor::Speech, true);
+        rules.build_speech_patterns(&doc[0], Path::new("testing")).unwrap();
+
+        for rule in [
+            r#"{name: unknown, tag: mrow, match: ".", replace: [macro: unknown]}"#,
+            r#"{name: missing, tag: mrow, match: ".", replace: [{macro: fenced, open: "("}]}"#,
+            r#"{name: extra, tag: mrow, match: ".", replace: [{macro: fenced, open: "(", close: ")", middle: ","}]}"#,
+            r#"{name: recursive, tag: mrow, match: ".", replace: [macro: recursive]}"#,
+        ] {
+            let doc = YamlLoader::load_from_str(rule).unwrap();
+            assert!(SpeechPattern::build(&doc[0], Path::new("testing"), &mut rules).is_err(), "\nno error for {}", rule);
+        }
+    }
+
     #[test]
     fn test_read_statements_with_add() {
         let str = r#"---
@@ -64,11 +64,17 @@
 //! * All systems -- pauses are given in milliseconds
 //!
 //! Note: Pauses on output are scaled based on the ratio of the current rate to the default rate (180 wpm)
+//! This can be turned off by setting the `ScalePausesWithRate` preference to `false`.
 #![allow(clippy::needless_return)]
 
 use crate::{errors::*, prefs::PreferenceManager, speech::ReplacementArray};
+use crate::shim_filesystem::{read_to_string_shim, modified_time_shim};
 use sxd_document::dom::Element;
-use yaml_rust::Yaml;
+use yaml_rust::{Yaml, YamlLoader};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::SystemTime;
+use std::path::{Path, PathBuf};
 
 use std::{fmt};
 use crate::speech::{SpeechRulesWithContext, MyXPath, TreeOrString};
@@ -78,6 +84,7 @@ use strum_macros::{Display, EnumString};
 use regex::Regex;
 use sxd_xpath::Value;
 
+const DEFAULT_RATE:f64 = 180.0;     // words/min -- pauses are scaled relative to this rate
 const MIN_PAUSE:f64 = 50.0;         // ms -- avoids clutter of putting out pauses that probably can't be heard
 const PAUSE_SHORT:f64 = 150.0;  // ms
 const PAUSE_MEDIUM:f64 = 300.0; // ms
@@ -86,6 +93,15 @@ const PAUSE_AUTO:f64 = 987654321.5;   // ms -- hopefully unique
 pub const PAUSE_AUTO_STR: &str = "\u{F8FA}\u{F8FA}";
 const RATE_FROM_CONTEXT:f64 = 987654321.5;   // hopefully unique
 
+/// The (from, to) pairs in an `ATProfile` file
+type ATProfileReplacements = Rc<Vec<(String, String)>>;
+
+thread_local!{
+    /// The replacements in the last `ATProfile` file read along with its modification time
+    /// (the file is read again when the profile changes or the file is modified)
+    static AT_PROFILE_REPLACEMENTS: RefCell<(PathBuf, Option<SystemTime>, ATProfileReplacements)> = RefCell::new( (PathBuf::new(), None, Rc::new(Vec::new())) );
+}
+
 /// TTSCommand are the supported TTS commands
 /// When parsing the YAML rule files, they are converted to these enums
 #[derive(Debug, Clone, PartialEq, Eq, Display, EnumString)]
@@ -319,6 +335,12 @@ impl TTS {
             TTSCommand::Pronounce => {
                 TTSCommandValue::Pronounce( Box::new( Pronounce::build(values)? ) )
             },
+            TTSCommand::Voice | TTSCommand::Gender if tts_str_value.starts_with('$') => {
+                // a pref (e.g., '$Text_Voice')
+                TTSCommandValue::XPath(
+                    MyXPath::build(tts_value).chain_err(|| format!("while trying to evaluate value of '{}:'", tts_enum))?
+                )
+            },
             _ => {
                 TTSCommandValue::String(tts_str_value.to_string())
             },
@@ -345,7 +367,7 @@ impl TTS {
         // rather than pass a bunch of extra info into the generic handling routines, we just deal with them here
         if command.command == TTSCommand::Bookmark {
             // if we aren't suppose to generate bookmarks, short circuit and just return
-            if prefs.get_api_prefs().to_string("Bookmark") != "true"{
+            if !prefs.get_api_bool_pref("Bookmark") {
                 return Ok("".to_string());
             }
             return Ok( match self {
@@ -404,9 +426,16 @@ impl TTS {
             if let TTSCommandValue::Number(number_value) = command.value {
                 if number_value == RATE_FROM_CONTEXT {
                     // handle hack for $Rate -- need to look up in context
-                    let rate_from_context = crate::navigate::context_get_variable(rules_with_context.get_context(), "MathRate", mathml)?.1;
-                    assert!(rate_from_context.is_some());
-                    command.value = TTSCommandValue::Number(rate_from_context.unwrap());
+                    // a pref set at runtime (e.g., by 'set_preference') is a string, not a number
+                    let rate_from_context = match crate::navigate::context_get_variable(rules_with_context.get_context(), "MathRate", mathml)? {
+                        (_, Some(rate)) => rate,
+                        (Some(rate), None) => match rate.trim().parse::<f64>() {
+                            Ok(rate) => rate,
+                            Err(_) => bail!("MathRate ('{}') is not a number", rate),
+                        },
+                        (None, None) => bail!("MathRate is not set"),
+                    };
+                    command.value = TTSCommandValue::Number(rate_from_context);
                 }
             }
         }
@@ -414,17 +443,19 @@ impl TTS {
         // evaluate any xpath value now to simplify later code
         if let TTSCommandValue::XPath(xpath) = command.value {
             let eval_str = xpath.replace::<String>(rules_with_context, mathml)?;
+            let is_name = command.command == TTSCommand::Voice || command.command == TTSCommand::Gender;
             // can it be a number?
             command.value = match eval_str.parse::<f64>() {
-                Ok(num) => TTSCommandValue::Number(num),
-                Err(_) => TTSCommandValue::String(eval_str),
+                Ok(num) if !is_name => TTSCommandValue::Number(num),
+                _ => TTSCommandValue::String(eval_str.trim().to_string()),
             }
         };
 
 
         // small optimization to avoid generating tags that do nothing
         if ((command.command == TTSCommand::Pitch || command.command == TTSCommand::Volume || command.command == TTSCommand::Pause) && command.value.get_num() == 0.0) ||
-           (command.command == TTSCommand::Rate && command.value.get_num() == 100.0) {
+           (command.command == TTSCommand::Rate && command.value.get_num() == 100.0) ||
+           ((command.command == TTSCommand::Voice || command.command == TTSCommand::Gender) && command.value.get_string().is_empty()) {
             return command.replacements.replace::<String>(rules_with_context, mathml);
         }
 
@@ -459,7 +490,13 @@ impl TTS {
         fn compute_bookmark_element<'c, 's:'c, 'm, 'r>(value: &TTSCommandValue, tag_and_attr: &str, rules_with_context: &'r mut SpeechRulesWithContext<'c, 's, 'm>, mathml: Element<'c>) -> Result<String> {
             match value {
                 TTSCommandValue::XPath(xpath) => {
-                    let id = xpath.replace::<String>(rules_with_context, mathml)?;
+                    // evaluate directly rather than with 'replace' so that a single char id (e.g., "x") isn't spoken as a char
+                    let value = xpath.evaluate(rules_with_context.get_context(), mathml)
+                        .chain_err(|| format!("in 'bookmark': can't evaluate xpath \"{}\"", &xpath.to_string()) )?;
+                    let id = match value {
+                        Value::Nodeset(nodes) => nodes.document_order_first().map(|node| node.string_value()).unwrap_or_default(),
+                        _ => value.string(),
+                    };
                     return Ok( format!("<{}='{}'/>", tag_and_attr, id) );
                 },
                 _ => bail!("Implementation error: found bookmark value that did not evaluate to a string"),
@@ -494,7 +531,8 @@ impl TTS {
                 // debug!("spell rule: {}", command.value.get_string());
                 return command.value.get_string().to_string();
             } else if let TTSCommandValue::Pronounce(p) = &command.value {
-                return crate::speech::CONCAT_INDICATOR.to_string() + &p.text;
+                // a suffix (e.g., "-th") is attached to the previous word; otherwise (e.g., a Greek letter) it is a word
+                return if p.text.starts_with('-') {crate::speech::CONCAT_INDICATOR.to_string() + &p.text} else {p.text.clone()};
             }
         };
         return "".to_string();
@@ -509,7 +547,7 @@ impl TTS {
                 } else {
                     let amount = amount * TTS::get_pause_multiplier(prefs);
                     if amount > MIN_PAUSE {
-                        format!("<silence msec=='{}ms'/>", (amount * 180.0/prefs.get_rate()).round())
+                        format!("<silence msec=='{}ms'/>", amount.round())
                     } else {
                         "".to_string()
                     }
@@ -519,19 +557,24 @@ impl TTS {
             },
             // pitch must be in [-10, 10], logarithmic based on octaves
             // note MathPlayer uses 'absmiddle' (requires keeping a stack) -- could be 'middle' is not well supported
-            TTSCommand::Pitch => if is_start_tag {format!("<pitch middle=\"{}\">", (24.0*(1.0+command.value.get_num()/100.0).log2()).round())} else {String::from("</prosody>")},
+            TTSCommand::Pitch => if is_start_tag {format!("<pitch middle=\"{}\">", (24.0*(1.0+command.value.get_num()/100.0).log2()).round())} else {String::from("</pitch>")},
             // rate must be in [-10, 10], but we get relative %s. 300% => 10 (see comments at top of file)
             TTSCommand::Rate =>  if is_start_tag {format!("<rate speed='{:.1}'>", 10.0*(0.01*command.value.get_num()).log(3.0))} else {String::from("</rate>")},
             TTSCommand::Volume =>if is_start_tag {format!("<volume level='{}'>", command.value.get_num())} else {String::from("</volume>")},
             TTSCommand::Audio => "".to_string(),    // SAPI5 doesn't support audio
-            TTSCommand::Gender =>if is_start_tag {format!("<voice required=\"Gender={}\">", command.value.get_string())} else {String::from("</prosody>")},
-            TTSCommand::Voice =>if is_start_tag {format!("<voice required=\"Name={}\">", command.value.get_string())} else {String::from("</prosody>")},
+            TTSCommand::Gender =>if is_start_tag {format!("<voice required=\"Gender={}\">", command.value.get_string())} else {String::from("</voice>")},
+            TTSCommand::Voice =>if is_start_tag {format!("<voice required=\"Name={}\">", command.value.get_string())} else {String::from("</voice>")},
             TTSCommand::Spell =>if is_start_tag {format!("<spell>{}", command.value.get_string())} else {String::from("</spell>")},
-            TTSCommand::Pronounce =>if is_start_tag {
-                    format!("<pron sym='{}'>{}", &command.value.get_pronounce().sapi5, &command.value.get_pronounce().text)
-                } else {
-                    String::from("</pron>")
-                },
+            TTSCommand::Pronounce => {
+                // without a hint for SAPI5, just say the text
+                let pronounce = command.value.get_pronounce();
+                match (is_start_tag, pronounce.sapi5.is_empty()) {
+                    (true, true) => pronounce.text.clone(),
+                    (true, false) => format!("<pron sym='{}'>{}", &pronounce.sapi5, &pronounce.text),
+                    (false, true) => String::default(),
+                    (false, false) => String::from("</pron>"),
+                }
+            },
             TTSCommand::Bookmark => panic!("Internal error: bookmarks should have been handled earlier"),
         };
     }
@@ -546,7 +589,7 @@ impl TTS {
                     } else {
                         let amount = amount * TTS::get_pause_multiplier(prefs);
                         if amount > MIN_PAUSE {
-                            format!("<break time='{}ms'/>", (amount * 180.0/prefs.get_rate()).round())
+                            format!("<break time='{}ms'/>", amount.round())
                         } else {
                             "".to_string()
                         }
@@ -562,17 +605,28 @@ impl TTS {
             TTSCommand::Gender =>if is_start_tag {format!("<voice required='gender=\"{}\"'>", command.value.get_string())} else {String::from("</voice>")},
             TTSCommand::Voice =>if is_start_tag {format!("<voice required='{}'>", command.value.get_string())} else {String::from("</voice>")},
             TTSCommand::Spell =>if is_start_tag {format!("<say-as interpret-as='characters'>{}", command.value.get_string())} else {String::from("</say-as>")},
-            TTSCommand::Pronounce =>if is_start_tag {
-                format!("<phoneme alphabet='ipa' ph='{}'>{}", &command.value.get_pronounce().ipa, &command.value.get_pronounce().text)
-            } else {
-                String::from("</phoneme>")
+            TTSCommand::Pronounce => {
+                // without an IPA hint, just say the text
+                let pronounce = command.value.get_pronounce();
+                match (is_start_tag, pronounce.ipa.is_empty()) {
+                    (true, true) => pronounce.text.clone(),
+                    (true, false) => format!("<phoneme alphabet='ipa' ph='{}'>{}", &pronounce.ipa, &pronounce.text),
+                    (false, true) => String::default(),
+                    (false, false) => String::from("</phoneme>"),
+                }
             },
         TTSCommand::Bookmark => panic!("Internal error: bookmarks should have been handled earlier"),
         }
     }
 
+    /// The amount to scale pauses by: the user's `PauseFactor` and (unless turned off) the math speech rate relative to the default rate.
+    /// Faster speech means proportionally shorter pauses. The math rate is `MathRate` percent of the AT's `Rate`.
     fn get_pause_multiplier(prefs: &PreferenceManager) -> f64 {
-        return prefs.get_user_prefs().to_string("PauseFactor").parse::<f64>().unwrap_or(100.)/100.0;
+        let pause_factor = prefs.get_user_prefs().to_float("PauseFactor").unwrap_or(100.)/100.0;
+        if prefs.get_user_prefs().to_bool("ScalePausesWithRate") == Some(false) {
+            return pause_factor;
+        }
+        return pause_factor * DEFAULT_RATE/prefs.get_effective_rate(0.0);
     }
 
     /// Compute the length of the pause to use.
@@ -636,6 +690,37 @@ impl TTS {
         };        
     }
 
+    /// Apply the replacements in the file for the `ATProfile` preference (Rules/ATProfiles/<ATProfile>.yaml) to `speech`.
+    ///
+    /// Screen readers mangle some characters in the strings they are given (e.g., JAWS can speak ';' as "semicolon").
+    /// The replacements are only made in the text, not in the TTS markup.
+    pub fn apply_at_profile(&self, prefs: &PreferenceManager, speech: String) -> Result<String> {
+        lazy_static! {
+            static ref TTS_TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+        }
+        let file = match prefs.get_at_profile_file() {
+            None => return Ok(speech),
+            Some(file) => file,
+        };
+        let time = if prefs.is_auto_reload() {modified_time_shim(&file)} else {None};
+        let replacements = at_profile_replacements(&file, time)?;
+        let replace = |text: &str| replacements.iter()
+                .fold(text.to_string(), |text, (from, to)| text.replace(from, to));
+        if *self == TTS::None {
+            return Ok( replace(&speech) );
+        }
+        let mut result = String::with_capacity(speech.len());
+        let mut start = 0;
+        for tag in TTS_TAG.find_iter(&speech) {
+            result += &replace(&speech[start..tag.start()]);
+            result += tag.as_str();
+            start = tag.end();
+        }
+        result += &replace(&speech[start..]);
+        return Ok(result);
+    }
+
+
     fn merge_pauses_none(&self, str: &str) -> String {
         // punctuation used for pauses is ",", ";" 
         lazy_static! {
@@ -682,4 +767,141 @@ impl TTS {
         let replacement = |amount: usize| format!("<break time='{}ms'/>", amount);
         return TTS::merge_pauses_xml(str, &CONSECUTIVE_BREAKS, &PAUSE_AMOUNT, replacement);
     }
-}
\ No newline at end of file
+}
+
+/// The replacements in the ATProfile `file`.
+/// They are cached and only read again if `file` or its modification `time` differs from the last call.
+fn at_profile_replacements(file: &Path, time: Option<SystemTime>) -> Result<ATProfileReplacements> {
+    return AT_PROFILE_REPLACEMENTS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.0 != file || cache.1 != time {
+            *cache = (file.to_path_buf(), time, Rc::new(read_at_profile(file)?));
+        }
+        return Ok( cache.2.clone() );
+    });
+}
+
+/// The file is a list of `- "from": "to"` pairs
+fn read_at_profile(file: &Path) -> Result<Vec<(String, String)>> {
+    let contents = read_to_string_shim(file)
+            .chain_err(|| format!("Couldn't read the ATProfile file '{}'", file.display()))?;
+    let docs = YamlLoader::load_from_str(&contents)
+            .chain_err(|| format!("Couldn't parse the ATProfile file '{}'", file.display()))?;
+    let mut replacements = vec![];
+    let entries = match docs.first() {
+        None => return Ok(replacements),
+        Some(Yaml::Null) => return Ok(replacements),
+        Some(Yaml::Array(entries)) => entries,
+        Some(_) => bail!("The ATProfile file '{}' must be a list of replacements", file.display()),
+    };
+    for entry in entries {
+        match entry.as_hash() {
+            Some(hash) if hash.len() == 1 => {
+                let (from, to) = hash.front().unwrap();
+                match (from.as_str(), to.as_str()) {
+                    (Some(from), Some(to)) if !from.is_empty() => replacements.push( (from.to_string(), to.to_string()) ),
+                    _ => bail!("In the ATProfile file '{}', the replacement '{}' must be a non-empty string mapped to a string",
+                                file.display(), crate::pretty_print::yaml_to_string(entry, 0).trim()),
+                }
+            },
+            _ => bail!("In the ATProfile file '{}', each replacement must be of the form '- \"from\": \"to\"', not '{}'",
+                        file.display(), crate::pretty_print::yaml_to_string(entry, 0).trim()),
+        }
+    }
+    return Ok(replacements);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pause_command(amount: f64) -> TTSCommandRule {
+        return TTSCommandRule::new(TTSCommand::Pause, TTSCommandValue::Number(amount), ReplacementArray::build_empty());
+    }
+
+    #[test]
+    fn pause_scales_with_rate() {
+        let pref_manager = PreferenceManager::get();
+        let mut pref_manager = pref_manager.write().unwrap();
+        pref_manager.initialize(std::path::PathBuf::from(super::super::abs_rules_dir_path())).unwrap();
+        pref_manager.set_user_prefs("PauseFactor", "100");
+        pref_manager.set_user_prefs("ScalePausesWithRate", "true");
+        pref_manager.set_api_float_pref("Rate", 180.0);
+        assert_eq!(TTS::SSML.get_string_ssml(&pause_command(PAUSE_MEDIUM), &pref_manager, true), "<break time='300ms'/>");
+        pref_manager.set_api_float_pref("Rate", 360.0);
+        assert_eq!(TTS::SSML.get_string_ssml(&pause_command(PAUSE_MEDIUM), &pref_manager, true), "<break time='150ms'/>");
+        assert_eq!(TTS::SAPI5.get_string_sapi5(&pause_command(PAUSE_MEDIUM), &pref_manager, true), "<silence msec=='150ms'/>");
+        // a short pause becomes so short it is dropped
+        pref_manager.set_api_float_pref("Rate", 600.0);
+        assert_eq!(TTS::None.get_string_none(&pause_command(PAUSE_SHORT), &pref_manager, true), crate::speech::CONCAT_INDICATOR.to_string());
+    }
+
+    #[test]
+    fn pause_scaling_turned_off() {
+        let pref_manager = PreferenceManager::get();
+        let mut pref_manager = pref_manager.write().unwrap();
+        pref_manager.initialize(std::path::PathBuf::from(super::super::abs_rules_dir_path())).unwrap();
+        pref_manager.set_user_prefs("PauseFactor", "50");
+        pref_manager.set_user_prefs("ScalePausesWithRate", "false");
+        pref_manager.set_api_float_pref("Rate", 360.0);
+        assert_eq!(TTS::SSML.get_string_ssml(&pause_command(PAUSE_LONG), &pref_manager, true), "<break time='300ms'/>");
+    }
+
+    #[test]
+    fn math_rate_is_relative() {
+        let pref_manager = PreferenceManager::get();
+        let mut pref_manager = pref_manager.write().unwrap();
+        pref_manager.initialize(std::path::PathBuf::from(super::super::abs_rules_dir_path())).unwrap();
+        pref_manager.set_user_prefs("PauseFactor", "100");
+        pref_manager.set_user_prefs("ScalePausesWithRate", "true");
+        pref_manager.set_user_prefs("MathRate", "80");
+        pref_manager.set_api_float_pref("Rate", 180.0);
+        assert_eq!(pref_manager.get_effective_rate(0.0), 144.0);
+        assert_eq!(pref_manager.get_effective_rate(250.0), 200.0);
+        // math is spoken slower, so the pauses are longer
+        assert_eq!(TTS::SSML.get_string_ssml(&pause_command(PAUSE_MEDIUM), &pref_manager, true), "<break time='375ms'/>");
+
+        let rate = TTSCommandRule::new(TTSCommand::Rate, TTSCommandValue::Number(80.0), ReplacementArray::build_empty());
+        assert_eq!(TTS::SSML.get_string_ssml(&rate, &pref_manager, true), "<prosody rate='80%'>");
+        assert_eq!(TTS::SAPI5.get_string_sapi5(&rate, &pref_manager, true), "<rate speed='-2.0'>");
+    }
+
+    fn pronounce_command(yaml: &str) -> TTSCommandRule {
+        let pronounce = Pronounce::build(&YamlLoader::load_from_str(yaml).unwrap()[0]).unwrap();
+        return TTSCommandRule::new(TTSCommand::Pronounce, TTSCommandValue::Pronounce(Box::new(pronounce)), ReplacementArray::build_empty());
+    }
+
+    #[test]
+    fn pronounce_hints() {
+        let pref_manager = PreferenceManager::get();
+        let mut pref_manager = pref_manager.write().unwrap();
+        pref_manager.initialize(std::path::PathBuf::from(super::super::abs_rules_dir_path())).unwrap();
+        let chi = pronounce_command("[text: chi, ipa: kaɪ]");
+        assert_eq!(TTS::SSML.get_string_ssml(&chi, &pref_manager, true), "<phoneme alphabet='ipa' ph='kaɪ'>chi");
+        assert_eq!(TTS::SSML.get_string_ssml(&chi, &pref_manager, false), "</phoneme>");
+        // no hint for SAPI5, so just the text
+        assert_eq!(TTS::SAPI5.get_string_sapi5(&chi, &pref_manager, true), "chi");
+        assert_eq!(TTS::SAPI5.get_string_sapi5(&chi, &pref_manager, false), "");
+        assert_eq!(TTS::None.get_string_none(&chi, &pref_manager, true), "chi");
+        // a suffix is attached to the previous word
+        let th = pronounce_command("[text: -th, ipa: θ, sapi5: th]");
+        assert_eq!(TTS::SAPI5.get_string_sapi5(&th, &pref_manager, true), "<pron sym='th'>-th");
+        assert_eq!(TTS::None.get_string_none(&th, &pref_manager, true), crate::speech::CONCAT_INDICATOR.to_string() + "-th");
+    }
+
+    #[test]
+    fn at_profile_reread_when_modified() {
+        let dir = crate::TestDir::new("at-profile");
+        let file = dir.join("Test.yaml");
+        std::fs::write(&file, "- \";\": \",\"\n").unwrap();
+        let time = std::time::UNIX_EPOCH;
+        assert_eq!(at_profile_replacements(&file, Some(time)).unwrap()[0], (";".to_string(), ",".to_string()));
+
+        // same time -- the cached value is used
+        std::fs::write(&file, "- \";\": \" \"\n").unwrap();
+        assert_eq!(at_profile_replacements(&file, Some(time)).unwrap()[0].1, ",");
+        // the file changed -- it is read again
+        let time = time + std::time::Duration::from_secs(1);
+        assert_eq!(at_profile_replacements(&file, Some(time)).unwrap()[0].1, " ");
+    }
+}
@@ -4,14 +4,29 @@
 //! The function defined here are:
 //! * `IsNode(node, kind)`:  returns true if the node matches the "kind".
 //!    Valid values are "leaf", "2D", "simple", "common_fraction", "trig_name".
-//! * `ToOrdinal(number, fractional, plural)`: converts the number to an ordinal (e.g, third)
+//! * `ToOrdinal(number, fractional, plural, gender)`: converts the number to an ordinal (e.g, third)
 //!   * `number` -- the number to translate
 //!   * `fractional` -- true if this is a fractional ordinal (e.g, "half")
 //!   * `plural` -- true if answer should be plural
-//! * `ToCommonFraction(mfrac)` -- converts the fraction to an ordinal version (e.g, 2 thirds)
+//!   * `gender` -- optional grammatical gender and/or case (e.g., "Feminine" or "FeminineGenitive") -- the language's ordinals for it are used if defined
+//! * `ToCardinal(number)` -- converts an integer of any size to words (e.g., one hundred twenty three octillion ...);
+//!   numbers too big to have words are spoken in groups of three digits
+//! * `ToCommonFraction(mfrac, gender)` -- converts the fraction to an ordinal version (e.g, 2 thirds); `gender` is optional
+//! * `Gender(word)` -- returns the grammatical gender of the word (e.g., "Feminine" for "fraction" in French)
+//! * `Agree(forms_name, gender, count)` -- returns the form of a word (e.g., an article) that agrees with the gender and count
+//! * `Plural(word, count)` -- returns the plural of the word unless the (optional) count is 1
+//! * `PluralForm(count, form1, form2, ...)` -- returns the form that goes with the count in the current language (e.g., for Slavic languages)
 //! * `IsLargeOp(node)` -- returns true if the node is a large operator (e.g, integral or sum)
 //! * `IsBracketed(node, left, right, requires_comma)` -- returns true if the first/last element in the mrow match `left`/`right`.
 //!    If the optional `requires_comma` argument is given and is `true`, then there also must be a "," in the mrow (e.g., "f(x,y)")
+//! * `ConstantName(node, subject_area)` -- returns what the named constant is (e.g., "the imaginary unit") if `node` is a named constant
+//!   in the subject area (from `Constants_vec` in definitions.yaml), otherwise the empty string
+//! * `NodeSize(node)` -- returns the number of symbols (leaves other than invisible operators) in the node
+//! * `NestingDepth(node, name)` -- returns how many elements named `name` are nested inside each other below the node
+//!   (e.g., 2 for a fraction whose numerator has a fraction in its denominator)
+//! * `Repeat(string, count)` -- returns `string` repeated `count` times (e.g., "StartStart")
+//! * `IsFirstEncounter(name)` -- returns true only the first time it is called with `name` in a document session
+//!   (used by the Explanation style to explain a notation once; see [`reset_explanation_session`])
 //! * `DEBUG(xpath)` -- _Very_ useful function for debugging speech rules.
 //!    This can be used to surround a whole or part of an xpath expression in a match or output.
 //!    The result will be printed to standard output and the result returned so that `DEBUG` does not affect the computation.    
@@ -22,11 +37,14 @@ use sxd_xpath::{Value, Context, context, function::*, nodeset::*};
 use crate::definitions::DEFINITIONS;
 use regex::Regex;
 use crate::pretty_print::mml_to_string;
-use std::cell::Ref;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use phf::phf_set;
 
 
-use crate::canonicalize::{as_element, name};
+use crate::canonicalize::{as_element, as_text, name};
 
 // useful utility functions
 // note: child of an element is a ChildOfElement, so sometimes it is useful to have parallel functions,
@@ -311,7 +329,11 @@ impl IsNode {
                ALL_DIGITS.is_match(&denom) && is_small_enough(&denom, denom_limit);
 
         fn is_small_enough(val: &str, upper_bound: usize) -> bool {
-            return if let Ok(value) = val.parse::<usize>() { value <= upper_bound } else { false };
+            return match val.parse::<usize>() {
+                Ok(value) => value <= upper_bound,
+                // a number too big for a usize is only small enough if there is no limit
+                Err(e) => *e.kind() == std::num::IntErrorKind::PosOverflow && upper_bound == usize::MAX,
+            };
         }
     }
 
@@ -337,9 +359,8 @@ static MATHML_LEAF_NODES: phf::Set<&str> = phf_set! {
 };
 
 
-// Should mstack and mlongdiv be included here?
 static MATHML_2D_NODES: phf::Set<&str> = phf_set! {
-    "mfrac", "msqrt", "mroot", "menclose",
+    "mfrac", "msqrt", "mroot", "menclose", "mlongdiv", "mstack",
     "msub", "msup", "msubsup", "munder", "mover", "munderover", "mmultiscripts",
     "mtable", "mtr", "mlabeledtr", "mtd",
 };
@@ -399,15 +420,16 @@ struct ToOrdinal;
 impl ToOrdinal {
     // ordinals often have an irregular start (e.g., "half") before becoming regular.
     // if the number is irregular, return the ordinal form, otherwise return 'None'.
-    fn compute_irregular_fractional_speech(number: &str, plural: bool) -> Option<String> {
+    fn compute_irregular_fractional_speech(number: &str, plural: bool, gender: &str) -> Option<String> {
         DEFINITIONS.with(|definitions| {
             let definitions = definitions.borrow();
             let words = if plural {
-                definitions.get_vec("NumbersOrdinalFractionalPluralOnes").unwrap()
+                definitions.get_gendered_vec("NumbersOrdinalFractionalPluralOnes", gender).unwrap()
             } else {
-                definitions.get_vec("NumbersOrdinalFractionalOnes").unwrap()
+                definitions.get_gendered_vec("NumbersOrdinalFractionalOnes", gender).unwrap()
             };
-            let number_as_int: usize = number.parse().unwrap(); // already verified it is only digits
+            // already verified it is only digits, but it might be too big for a usize (which can't be irregular)
+            let number_as_int: usize = number.parse().ok()?;
             if number_as_int < words.len() {
                 // use the words associated with this irregular pattern.
                 return Some( words[number_as_int].clone() );
@@ -421,9 +443,11 @@ impl ToOrdinal {
      *   number -- the number to translate
      *   fractional -- true if this is a fractional ordinal (e.g, "half")
      *   plural -- true if answer should be plural
+     *   gender -- the grammatical gender (e.g., "Feminine") -- the ordinal words for the gender are used if the language
+     *             defines them (e.g., "NumbersOrdinalFeminineOnes" instead of "NumbersOrdinalOnes"). "" uses the ungendered words.
      * Returns the string representation of that number or an error message
      */
-    fn convert(number: &str, fractional: bool, plural: bool) -> String {
+    fn convert(number: &str, fractional: bool, plural: bool, gender: &str) -> String {
         lazy_static! {
             static ref NO_DIGIT: Regex = Regex::new(r"[^\d]").unwrap();    // match anything except a digit
         }
@@ -442,9 +466,15 @@ impl ToOrdinal {
 
             // first deal with the abnormalities of fractional ordinals (one half, etc). That simplifies what remains
             if fractional {
-                if let Some(string) = ToOrdinal::compute_irregular_fractional_speech(number, plural) {
+                if let Some(string) = ToOrdinal::compute_irregular_fractional_speech(number, plural, gender) {
                     return string;
                 }
+                // some languages form the denominator with a word before the number (e.g., "per" in Indonesian)
+                if let Some(prefix) = definitions.get_vec("NumbersOrdinalFractionalPrefix") {
+                    if let Some(prefix) = prefix.first() {
+                        return format!("{} {}", prefix, number);
+                    }
+                }
             }
 
             // at this point, we only need to worry about singular/plural distinction
@@ -500,9 +530,9 @@ impl ToOrdinal {
                 if num_thousands_at_end > 0 {
                     // add on "billionths", etc and we are done
                     let large_words = if plural {
-                        definitions.get_vec("NumbersOrdinalPluralLarge")
+                        definitions.get_gendered_vec("NumbersOrdinalPluralLarge", gender)
                     } else {
-                        definitions.get_vec("NumbersOrdinalLarge")
+                        definitions.get_gendered_vec("NumbersOrdinalLarge", gender)
                     };
                     return answer + &large_words.unwrap()[num_thousands_at_end];
                 }
@@ -516,22 +546,22 @@ impl ToOrdinal {
                     definitions.get_vec("NumbersOnes").unwrap(),
                 ],
                 (false, true) => [
-                    definitions.get_vec("NumbersOrdinalPluralHundreds").unwrap(),
-                    definitions.get_vec("NumbersOrdinalPluralTens").unwrap(),
-                    definitions.get_vec("NumbersOrdinalPluralOnes").unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalPluralHundreds", gender).unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalPluralTens", gender).unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalPluralOnes", gender).unwrap(),
                 ],
                 (false, false) => [
-                    definitions.get_vec("NumbersOrdinalHundreds").unwrap(),
-                    definitions.get_vec("NumbersOrdinalTens").unwrap(),
-                    definitions.get_vec("NumbersOrdinalOnes").unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalHundreds", gender).unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalTens", gender).unwrap(),
+                    definitions.get_gendered_vec("NumbersOrdinalOnes", gender).unwrap(),
                 ],
             };
             answer += &ToOrdinal::hundreds_to_words(&digits[digits.len()-3..], &words);
             if num_thousands_at_end > 0 {
                 let large_words = if plural {
-                    definitions.get_vec("NumbersOrdinalPluralLarge").unwrap()
+                    definitions.get_gendered_vec("NumbersOrdinalPluralLarge", gender).unwrap()
                 } else {
-                    definitions.get_vec("NumbersOrdinalLarge").unwrap()
+                    definitions.get_gendered_vec("NumbersOrdinalLarge", gender).unwrap()
                 };
                 answer = answer + " " + &large_words[num_thousands_at_end];
             }
@@ -574,17 +604,108 @@ impl Function for ToOrdinal {
                         -> Result<Value<'d>, Error>
     {
         let mut args = Args(args);
-        args.exactly(1)?;
+        args.at_least(1)?;
+        args.at_most(4)?;
+        if args.len() == 2 {
+            return Err( Error::Other("ToOrdinal -- 'fractional' and 'plural' must both be given".to_string()) );
+        }
+        let gender = if args.len() == 4 {args.pop_string()?} else {String::default()};
+        let (fractional, plural) = if args.len() == 3 {
+            let plural = args.pop_boolean()?;
+            (args.pop_boolean()?, plural)
+        } else {
+            (false, false)
+        };
         let node = validate_one_node(args.pop_nodeset()?, "ToOrdinal")?;
         return match node {
-            Node::Text(t) =>  Ok( Value::String( ToOrdinal::convert(t.text(), false, false) ) ),
-            Node::Element(e) => Ok( Value::String( ToOrdinal::convert(&get_text_from_element(&e), false, false) ) ),
+            Node::Text(t) =>  Ok( Value::String( ToOrdinal::convert(t.text(), fractional, plural, &gender) ) ),
+            Node::Element(e) => Ok( Value::String(
+                ToOrdinal::convert(&get_text_from_element(&e), fractional, plural, &gender)
+            ) ),
             _   =>  Err( Error::ArgumentNotANodeset{actual: ArgumentType::String} ),
         }
     }
 }
 
 
+struct ToCardinal;
+impl ToCardinal {
+    /**
+     * Translates an integer of any size into words (e.g., "one hundred twenty three octillion ...").
+     * The digits can include block separators (' ', ',', '`') -- they are ignored.
+     * Numbers with more digits than the language has words for (see `NumbersLarge`) are spoken in groups of three digits
+     * (e.g., "1, 234, 567") so that a speech engine doesn't try to read them as a quantity.
+     * Anything else (e.g., a decimal number) is returned unchanged.
+     */
+    fn convert(number: &str) -> String {
+        let digits: String = number.chars().filter(|ch| !matches!(ch, ' ' | ',' | '`')).collect();
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+            return String::from(number);
+        }
+        let digits = match digits.trim_start_matches('0') {
+            "" => "0",
+            trimmed => trimmed,
+        };
+        return DEFINITIONS.with(|definitions| {
+            let definitions = definitions.borrow();
+            let numbers_large = definitions.get_vec("NumbersLarge").unwrap();
+            let first_group_len = match digits.len() % 3 {
+                0 => 3,
+                n => n,
+            };
+            if digits.len() > 3*numbers_large.len() {
+                let (first, rest) = digits.split_at(first_group_len);
+                let mut groups = vec![first];
+                groups.extend( rest.as_bytes().chunks(3).map(|group| std::str::from_utf8(group).unwrap()) );
+                return groups.join(", ");
+            }
+            if digits == "0" {
+                return definitions.get_vec("NumbersOnes").unwrap()[0].clone();
+            }
+
+            // pad to a multiple of 3 digits -- we have already verified that there are only ASCII digits
+            const ASCII_0: usize = 48;
+            let digits = "00"[..3-first_group_len].bytes().chain(digits.bytes())
+                        .map(|byte| byte as usize - ASCII_0)
+                        .collect::<Vec<usize>>();
+            let words = [
+                definitions.get_vec("NumbersHundreds").unwrap(),
+                definitions.get_vec("NumbersTens").unwrap(),
+                definitions.get_vec("NumbersOnes").unwrap(),
+            ];
+            let n_groups = digits.len() / 3;
+            return digits.chunks(3)
+                    .enumerate()
+                    .filter(|(_, chunk)| chunk.iter().any(|&digit| digit != 0))
+                    .map(|(i, chunk)| {
+                        let large_word = &numbers_large[n_groups - 1 - i];
+                        let hundreds = ToOrdinal::hundreds_to_words(chunk, &words);
+                        if large_word.is_empty() {hundreds} else {hundreds + " " + large_word}
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ");
+        });
+    }
+}
+
+impl Function for ToCardinal {
+    // convert a node to a cardinal number
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(1)?;
+        let node = validate_one_node(args.pop_nodeset()?, "ToCardinal")?;
+        return match node {
+            Node::Text(t) =>  Ok( Value::String( ToCardinal::convert(t.text()) ) ),
+            Node::Element(e) => Ok( Value::String( ToCardinal::convert(&get_text_from_element(&e)) ) ),
+            _   =>  Err( Error::ArgumentNotANodeset{actual: ArgumentType::String} ),
+        }
+    }
+}
+
 struct ToCommonFraction;
 
 impl Function for ToCommonFraction {
@@ -595,7 +716,9 @@ impl Function for ToCommonFraction {
                         -> Result<Value<'d>, Error>
     {
         let mut args = Args(args);
-        args.exactly(1)?;
+        args.at_least(1)?;
+        args.at_most(2)?;
+        let gender = if args.len() == 2 {args.pop_string()?} else {String::default()};
 
         // FIX: should probably handle errors by logging them and then trying to evaluate any children
         let node = validate_one_node(args.pop_nodeset()?, "ToCommonFraction")?;
@@ -611,7 +734,7 @@ impl Function for ToCommonFraction {
             let denom = children[1].element().unwrap();
             let denom = get_text_from_element( &denom );
             let mut answer = num.clone() + " ";
-            answer += &ToOrdinal::convert(&denom, true, num!="1");
+            answer += &ToOrdinal::convert(&denom, true, num!="1", &gender);
             return Ok( Value::String( answer ) );    
         } else {
             return Err( Error::Other( "ToCommonFraction -- argument is not an element".to_string()) );
@@ -619,6 +742,226 @@ impl Function for ToCommonFraction {
     }
 }
 
+
+/// Support for grammatical agreement (gender and number) in languages such as French and Spanish.
+/// The words come from the language's definitions file:
+/// * `Genders_vec` -- the grammatical genders of the language (e.g., `["Masculine", "Feminine"]`); the first one is the default
+/// * `Nouns<gender>` (e.g., `NounsFeminine`) -- the nouns that have that gender
+/// * `<name>_vec` (e.g., `DefiniteArticle_vec`) -- the forms of a word that agrees with a noun:
+///   the singular form for each gender (in the order of `Genders_vec`) followed by the plural form for each gender
+/// * `PluralIrregular_vec` -- pairs of words and their plurals for words with irregular plurals
+/// * `PluralEndings_vec` -- pairs of word endings and what replaces them to form a plural; the first matching ending is used.
+///   An empty ending matches any word, so a final `"", "s"` pair adds an "s" to words that don't match an earlier ending.
+struct Agreement;
+impl Agreement {
+    /// Returns the gender of `word` (the default gender if it isn't listed) or "" if the language doesn't have genders
+    fn gender(word: &str) -> String {
+        return DEFINITIONS.with(|definitions| {
+            let definitions = definitions.borrow();
+            let genders = match definitions.get_vec("Genders_vec") {
+                None => return String::default(),
+                Some(genders) => genders,
+            };
+            let word = word.trim().to_lowercase();
+            for gender in genders.iter() {
+                if let Some(nouns) = definitions.get_hashset(&format!("Nouns{}", gender)) {
+                    if nouns.contains(&word) {
+                        return gender.clone();
+                    }
+                }
+            }
+            return genders.first().cloned().unwrap_or_default();
+        });
+    }
+
+    /// Returns the form in the `forms_name` definition that agrees with `gender` and `plural`
+    fn agree(forms_name: &str, gender: &str, plural: bool) -> Result<String, Error> {
+        return DEFINITIONS.with(|definitions| {
+            let definitions = definitions.borrow();
+            let forms = definitions.get_vec(forms_name)
+                    .ok_or_else(|| Error::Other(format!("Agree -- '{}' is not defined (or its name doesn't end in '_vec')", forms_name)))?;
+            let genders = definitions.get_vec("Genders_vec");
+            let n_genders = genders.as_ref().map_or(1, |genders| genders.len().max(1));
+            let i_gender = genders.as_ref()
+                    .and_then(|genders| genders.iter().position(|g| g == gender))
+                    .unwrap_or(0);
+            let i_form = if plural {n_genders + i_gender} else {i_gender};
+            return match forms.get(i_form) {
+                Some(form) => Ok( form.clone() ),
+                None => Err( Error::Other(format!("Agree -- '{}' should have {} entries (singular and plural for each gender) but has {}",
+                                                   forms_name, 2*n_genders, forms.len())) ),
+            };
+        });
+    }
+
+    /// Returns the plural of `word`
+    fn plural(word: &str) -> String {
+        return DEFINITIONS.with(|definitions| {
+            let definitions = definitions.borrow();
+            if let Some(irregular) = definitions.get_vec("PluralIrregular_vec") {
+                if let Some(pair) = irregular.chunks_exact(2).find(|pair| pair[0] == word) {
+                    return pair[1].clone();
+                }
+            }
+            if let Some(endings) = definitions.get_vec("PluralEndings_vec") {
+                if let Some(pair) = endings.chunks_exact(2).find(|pair| word.ends_with(pair[0].as_str())) {
+                    return word[..word.len()-pair[0].len()].to_string() + &pair[1];
+                }
+            }
+            return word.to_string();
+        });
+    }
+
+    /// Returns true if `value` says the agreeing word should be plural.
+    /// `value` is either a boolean or a count (a number, a string, or a node whose text is the count)
+    fn is_plural(value: &Value) -> bool {
+        return match value {
+            Value::Boolean(b) => *b,
+            Value::Number(n) => *n != 1.0,
+            Value::String(s) => s.trim() != "1",
+            Value::Nodeset(nodes) => nodes.size() != 1 || nodes.iter().next().unwrap().string_value().trim() != "1",
+        };
+    }
+}
+
+/// `Gender(word)` -- returns the grammatical gender of the word (see [`Agreement`])
+struct Gender;
+impl Function for Gender {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(1)?;
+        let word = args.pop_string()?;
+        return Ok( Value::String( Agreement::gender(&word) ) );
+    }
+}
+
+/// `Agree(forms_name, gender, count)` -- returns the form of a word that agrees with the gender and count (see [`Agreement`]).
+/// `count` can be a boolean (true if plural) or a number.
+struct Agree;
+impl Function for Agree {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(3)?;
+        let plural = Agreement::is_plural(&args.0.pop().unwrap());
+        let gender = args.pop_string()?;
+        let forms_name = args.pop_string()?;
+        return Ok( Value::String( Agreement::agree(&forms_name, &gender, plural)? ) );
+    }
+}
+
+/// `Plural(word, count)` -- returns the plural of the word unless the (optional) count is 1 (see [`Agreement`]).
+/// `count` can be a boolean (true if plural) or a number.
+struct Plural;
+impl Function for Plural {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.at_least(1)?;
+        args.at_most(2)?;
+        let plural = args.len() == 1 || Agreement::is_plural(&args.0.pop().unwrap());
+        let word = args.pop_string()?;
+        return Ok( Value::String( if plural {Agreement::plural(&word)} else {word} ) );
+    }
+}
+
+/// `PluralForm(count, form1, form2, ...)` -- returns the form of a word that goes with `count` in the current language.
+/// The forms are listed in the order of the language's plural categories:
+/// * East Slavic (ru, uk, be), Polish, and BCS (hr, sr, bs): one (1, 21, ...), few (2-4, 22-24, ...), many (0, 5-20, ...), fractional
+/// * Czech and Slovak (cs, sk): one (1), few (2-4), many (0, 5, ...), fractional
+/// * Slovenian (sl): one (1, 101, ...), two (2, 102, ...), few (3-4, ...), other
+/// * all other languages: one (1), other
+///
+/// For example, in Czech `PluralForm(., 'stupeň', 'stupně', 'stupňů')` gives "stupně" for 2 and "stupňů" for 5.
+/// If a fractional form isn't given, the "few" form (the genitive singular) is used for non-integer counts.
+/// If there are fewer forms than categories, the last form is used.
+struct PluralForm;
+impl PluralForm {
+    /// Returns the index of the form (see above) to use for `count` in `language` (e.g., "ru" or "cs-cz")
+    fn form_index(language: &str, count: &str) -> usize {
+        // the last '.' or ',' is the decimal separator unless it is repeated (e.g., "1,000,000")
+        let count = count.trim();
+        let (integer, fraction) = match count.rfind(['.', ',']) {
+            Some(i) if count.matches(&count[i..i+1]).count() == 1 => (&count[..i], count[i+1..].trim_end_matches('0')),
+            _ => (count, ""),
+        };
+        let integer: String = integer.chars().filter(|ch| ch.is_ascii_digit()).collect();
+        let i = match integer.parse::<u64>() {
+            Ok(i) => i,
+            // only the last few digits matter for a number too big for a u64 (keep them, but don't let it be 1-4)
+            Err(_) if integer.len() > 3 => 1000 + integer[integer.len()-3..].parse::<u64>().unwrap_or(0),
+            Err(_) => 0,
+        };
+        let is_integer = fraction.is_empty();
+        let language = language.split('-').next().unwrap_or_default();
+        let (i10, i100) = (i % 10, i % 100);
+        return match language {
+            "ru" | "uk" | "be" | "pl" | "hr" | "sr" | "bs" => {
+                if !is_integer {
+                    3
+                } else if (language == "pl" && i == 1) || (language != "pl" && i10 == 1 && i100 != 11) {
+                    0
+                } else if (2..=4).contains(&i10) && !(12..=14).contains(&i100) {
+                    1
+                } else {
+                    2
+                }
+            },
+            "cs" | "sk" => match i {
+                _ if !is_integer => 3,
+                1 => 0,
+                2..=4 => 1,
+                _ => 2,
+            },
+            "sl" => match i100 {
+                _ if !is_integer => 2,
+                1 => 0,
+                2 => 1,
+                3 | 4 => 2,
+                _ => 3,
+            },
+            _ => if is_integer && i == 1 {0} else {1},
+        };
+    }
+
+    fn choose<'a>(language: &str, count: &str, forms: &'a [String]) -> &'a str {
+        let mut i = PluralForm::form_index(language, count);
+        if i == 3 && forms.len() == 3 && !matches!(language.split('-').next(), Some("sl")) {
+            i = 1;      // no fractional form -- use "few"
+        }
+        return &forms[i.min(forms.len()-1)];
+    }
+}
+
+impl Function for PluralForm {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.at_least(2)?;
+        let mut forms = Vec::with_capacity(args.len()-1);
+        while args.len() > 1 {
+            forms.push(args.pop_string()?);
+        }
+        forms.reverse();
+        let count = args.0.pop().unwrap().string();
+        let language = crate::prefs::read_prefs_unchecked(&crate::prefs::PreferenceManager::get()).get_language();
+        return Ok( Value::String( PluralForm::choose(&language, &count, &forms).to_string() ) );
+    }
+}
+
 struct Min;
 /**
  * Returns true the smallest of the two args
@@ -934,6 +1277,45 @@ impl IsInDefinition {
     }
 }
 
+/// `ConstantName(node, subject_area)` -- returns what the named constant `node` is (e.g., "the base of the natural log" for "e")
+/// or the empty string if `node` isn't a named constant in `subject_area`.
+/// The constants are listed in `Constants_vec` in definitions.yaml as (symbol, subject areas, description) triples.
+/// The subject areas are separated by spaces; "All" means the symbol is a named constant in every subject area.
+struct ConstantName;
+impl ConstantName {
+    fn description(symbol: &str, subject_area: &str) -> String {
+        return DEFINITIONS.with(|definitions| {
+            let definitions = definitions.borrow();
+            if let Some(constants) = definitions.get_vec("Constants_vec") {
+                if let Some(triple) = constants.chunks_exact(3).find(|triple| triple[0] == symbol &&
+                            triple[1].split_whitespace().any(|area| area == "All" || area == subject_area)) {
+                    return triple[2].clone();
+                }
+            }
+            return String::default();
+        });
+    }
+}
+
+impl Function for ConstantName {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let subject_area = args.pop_string()?;
+        let node = validate_one_node(args.pop_nodeset()?, "ConstantName")?;
+        if let Node::Element(e) = node {
+            if is_leaf(e) {
+                return Ok( Value::String( ConstantName::description(as_text(e), &subject_area) ) );
+            }
+        }
+        return Ok( Value::String(String::default()) );
+    }
+}
+
 
 pub struct DistanceFromLeaf;
 impl DistanceFromLeaf {
@@ -985,6 +1367,93 @@ impl Function for DistanceFromLeaf {
 
 
 
+pub struct NodeSize;
+impl NodeSize {
+    /// The number of symbols (leaves other than invisible operators) in 'element' -- an estimate of how long it takes to read
+    pub fn size(element: Element) -> usize {
+        if is_leaf(element) {
+            let is_invisible = name(&element) == "mo" && as_text(element).chars().all(|ch| ('\u{2061}'..='\u{2064}').contains(&ch));
+            return if is_invisible {0} else {1};
+        }
+        return element.children().into_iter()
+                .filter_map(|child| child.element())
+                .map(NodeSize::size)
+                .sum();
+    }
+}
+
+/**
+ * Returns the number of symbols in the node (see `NodeSize::size`)
+ * node -- node to measure
+ */
+impl Function for NodeSize {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(1)?;
+        let node = validate_one_node(args.pop_nodeset()?, "NodeSize")?;
+        if let Node::Element(e) = node {
+            return Ok( Value::Number( NodeSize::size(e) as f64) );
+        }
+        return Err(Error::Other(format!("NodeSize: argument '{:?}' is not a node", node)));
+    }
+}
+
+pub struct NestingDepth;
+impl NestingDepth {
+    /// The largest number of 'element_name' elements nested inside of each other below 'element' (0 if there are none)
+    pub fn depth(element: Element, element_name: &str) -> usize {
+        return element.children().into_iter()
+                .filter_map(|child| child.element())
+                .map(|child| NestingDepth::depth(child, element_name) + if name(&child) == element_name {1} else {0})
+                .max()
+                .unwrap_or(0);
+    }
+}
+
+/**
+ * Returns how deeply elements with the given name are nested below the node (see `NestingDepth::depth`)
+ * node -- node to search
+ * name -- element name (e.g., "fraction")
+ */
+impl Function for NestingDepth {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let element_name = args.pop_string()?;
+        let node = validate_one_node(args.pop_nodeset()?, "NestingDepth")?;
+        if let Node::Element(e) = node {
+            return Ok( Value::Number( NestingDepth::depth(e, &element_name) as f64) );
+        }
+        return Err(Error::Other(format!("NestingDepth: first arg '{:?}' is not a node", node)));
+    }
+}
+
+struct Repeat;
+// Repeat(string, count)
+//      -- returns 'string' repeated 'count' times (the empty string if 'count' isn't positive)
+impl Function for Repeat {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let count = args.pop_number()?;
+        let string = args.pop_string()?;
+        let count = if count.is_finite() && count > 0.0 {count as usize} else {0};
+        return Ok( Value::String( string.repeat(count) ) );
+    }
+}
+
 pub struct EdgeNode;
 impl EdgeNode {
     // Return the root of the ancestor tree if we are at the left/right side of a path from that to 'element'
@@ -1059,6 +1528,341 @@ impl Function for EdgeNode {
     }
 }
 
+
+pub struct VariationIndex;
+
+thread_local!{
+    /// The `VariationIndex` of the elements in the trees being spoken (see [`VariationIndex::key`]).
+    /// A whole tree is indexed the first time one of its elements is looked up, so a tree with n elements costs O(n).
+    /// The keys are only unique while the trees exist, so this is cleared by [`reset_variation_indices`] before speaking a tree.
+    static VARIATION_INDICES: RefCell<HashMap<u64, usize>> = RefCell::new( HashMap::new() );
+}
+
+/// Forget the `VariationIndex` values found while speaking the previous tree
+pub fn reset_variation_indices() {
+    VARIATION_INDICES.with(|indices| indices.borrow_mut().clear());
+}
+
+impl VariationIndex {
+    /// Returns the number of elements with the same name as `element` that come before it in document order.
+    /// This is used to deterministically pick from alternative phrasings (e.g., "over" vs "divided by").
+    fn n_preceding_with_same_name(element: Element) -> usize {
+        let key = VariationIndex::key(element);
+        return VARIATION_INDICES.with(|indices| {
+            let mut indices = indices.borrow_mut();
+            if let Some(&index) = indices.get(&key) {
+                return index;
+            }
+            let mut root = element;
+            while let Some(parent) = root.parent().and_then(|parent| parent.element()) {
+                root = parent;
+            }
+            index_tree(root, &mut HashMap::new(), &mut indices);
+            return indices.get(&key).copied().unwrap_or(0);     // shouldn't happen, but don't panic if the walk from the root missed it
+        });
+
+        /// Record the index of `element` and its descendants (in document order) among the elements with the same name
+        fn index_tree<'a>(element: Element<'a>, counts: &mut HashMap<&'a str, usize>, indices: &mut HashMap<u64, usize>) {
+            let count = counts.entry(element.name().local_part()).or_insert(0);
+            indices.insert(VariationIndex::key(element), *count);
+            *count += 1;
+            for child in element.children() {
+                if let Some(child) = child.element() {
+                    index_tree(child, counts, indices);
+                }
+            }
+        }
+    }
+
+    /// Elements are compared by address, so hashing one gives a key that is unique while its tree exists
+    fn key(element: Element) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        element.hash(&mut hasher);
+        return hasher.finish();
+    }
+}
+
+// VariationIndex(node, n)
+//      -- returns a number in the range [0, n) that rotates for each node of the same name (in document order)
+//         This allows rules to vary the phrasing used for different occurrences of a notation
+impl Function for VariationIndex {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let n = args.pop_number()?;
+        let node = validate_one_node(args.pop_nodeset()?, "VariationIndex")?;
+        if n < 1.0 {
+            return Err(Error::Other(format!("VariationIndex: second arg '{}' must be at least 1", n)));
+        }
+        if let Node::Element(e) = node {
+            return Ok( Value::Number( (VariationIndex::n_preceding_with_same_name(e) % (n as usize)) as f64) );
+        }
+
+        return Err(Error::Other(format!("VariationIndex: first arg '{:?}' is not a node", node)));
+    }
+}
+
+
+pub struct FractionKind;
+impl FractionKind {
+    /// Classifies a fraction so that the 'FractionStyle' pref can pick "per", "divided by", or "over" in one place:
+    /// * "Rate" -- units (or quantities with units) in the numerator and denominator (miles per hour)
+    /// * "Common" -- a fraction ClearSpeak reads as an ordinal (three fourths) -- the denominator is at most `ordinal_max`
+    /// * "Numeric" -- other fractions of numbers (12 divided by 37)
+    /// * "Symbolic" -- ClearSpeak "simple" numerator and denominator (a over b)
+    /// * "Complex" -- anything else (these are left to the speech style)
+    pub fn kind(fraction: Element, ordinal_max: usize) -> &'static str {
+        let children = fraction.children();
+        if children.len() != 2 {
+            return "Complex";
+        }
+        let (numerator, denominator) = match (children[0].element(), children[1].element()) {
+            (Some(numerator), Some(denominator)) => (numerator, denominator),
+            _ => return "Complex",
+        };
+        if FractionKind::is_quantity(denominator) && FractionKind::is_quantity(numerator) &&
+           (FractionKind::is_unit(denominator) || FractionKind::is_unit(numerator)) {
+            return "Rate";
+        }
+        if let (Some(num), Some(den)) = (FractionKind::integer(numerator), FractionKind::integer(denominator)) {
+            if num < 20 && (2..=ordinal_max).contains(&den) {
+                return "Common";
+            }
+        }
+        if FractionKind::is_number(numerator) && FractionKind::is_number(denominator) {
+            return "Numeric";
+        }
+        if IsNode::is_simple(&numerator) && IsNode::is_simple(&denominator) {
+            return "Symbolic";
+        }
+        return "Complex";
+    }
+
+    /// A unit is marked with the ":unit" intent property or is a word in text (e.g., "miles")
+    fn is_unit(element: Element) -> bool {
+        if let Some(properties) = element.attribute_value("data-intent-property") {
+            if properties.contains(":unit:") {
+                return true;
+            }
+        }
+        if name(&element) == "mtext" && is_leaf(element) {
+            let text = as_text(element).trim();
+            return text.chars().count() > 1 && text.chars().all(|ch| ch.is_alphabetic() || ch == ' ');
+        }
+        return false;
+    }
+
+    /// A unit, a number, or a number times a unit (e.g., "60 miles")
+    fn is_quantity(element: Element) -> bool {
+        if FractionKind::is_unit(element) || FractionKind::is_number(element) {
+            return true;
+        }
+        let children = element.children();
+        if name(&element) != "mrow" || children.len() != 3 {
+            return false;
+        }
+        return match (children[0].element(), children[1].element(), children[2].element()) {
+            (Some(number), Some(times), Some(unit)) =>
+                FractionKind::is_number(number) && name(&times) == "mo" && as_text(times) == "\u{2062}" && FractionKind::is_unit(unit),
+            _ => false,
+        };
+    }
+
+    /// An mn or the negative of one
+    fn is_number(element: Element) -> bool {
+        if name(&element) == "negative" && element.children().len() == 1 {
+            if let Some(child) = element.children()[0].element() {
+                return name(&child) == "mn";
+            }
+        }
+        return name(&element) == "mn";
+    }
+
+    fn integer(element: Element) -> Option<usize> {
+        if name(&element) != "mn" {
+            return None;
+        }
+        return as_text(element).trim().parse::<usize>().ok();
+    }
+}
+
+// FractionKind(node, ordinal_max)
+//      -- returns "Rate", "Common", "Numeric", "Symbolic", or "Complex" (see FractionKind::kind)
+//         'ordinal_max' is the largest denominator read as an ordinal (optional -- defaults to 10)
+impl Function for FractionKind {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.at_least(1)?;
+        args.at_most(2)?;
+        let ordinal_max = if args.len() == 2 {args.pop_number()?} else {10.0};
+        let node = validate_one_node(args.pop_nodeset()?, "FractionKind")?;
+        if let Node::Element(e) = node {
+            return Ok( Value::String( FractionKind::kind(e, ordinal_max as usize).to_string() ) );
+        }
+
+        return Err(Error::Other(format!("FractionKind: arg '{:?}' is not a node", node)));
+    }
+}
+
+thread_local!{
+    /// The names passed to `IsFirstEncounter` in the current document session
+    static ENCOUNTERED: RefCell<HashSet<String>> = RefCell::new( HashSet::new() );
+}
+
+/// Start a new document session: `IsFirstEncounter` will return true again for every name
+pub fn reset_explanation_session() {
+    ENCOUNTERED.with(|encountered| encountered.borrow_mut().clear());
+}
+
+struct IsFirstEncounter;
+// IsFirstEncounter(name)
+//      -- returns true if this is the first time it was called with 'name' since the session was reset
+//         Note: this has a side effect, so it should be the last test in a 'match'
+impl Function for IsFirstEncounter {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(1)?;
+        let name = args.pop_string()?;
+        return Ok( Value::Boolean( ENCOUNTERED.with(|encountered| encountered.borrow_mut().insert(name)) ) );
+    }
+}
+
+struct SpellDigits;
+// SpellDigits(digits, group_size, max_digits)
+//      -- returns the first 'max_digits' (all of them if 0) of 'digits' separated by spaces so that each digit is spoken separately
+//         if 'group_size' > 0, the digits are in groups of that size separated by a ',' (pause)
+impl Function for SpellDigits {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(3)?;
+        let max_digits = args.pop_number()?;
+        let group_size = args.pop_number()?;
+        let digits = args.pop_string()?;
+        return Ok( Value::String( SpellDigits::spell(&digits, group_size as usize, max_digits as usize) ) );
+    }
+}
+
+impl SpellDigits {
+    fn spell(digits: &str, group_size: usize, max_digits: usize) -> String {
+        let mut digits: Vec<String> = digits.chars().filter(|ch| !ch.is_whitespace()).map(|ch| ch.to_string()).collect();
+        if max_digits > 0 {
+            digits.truncate(max_digits);
+        }
+        if group_size == 0 || digits.is_empty() {
+            return digits.join(" ");
+        }
+        return digits.chunks(group_size)
+                .map(|group| group.join(" "))
+                .collect::<Vec<String>>()
+                .join(", ");
+    }
+}
+
+struct SpellIdNumbers;
+// SpellIdNumbers(text, all)
+//      -- returns 'text' with the numbers that look like identifiers (phone numbers, ISBNs, citation years, ...) spelled out
+//         digit by digit (see 'SpellDigits') so they aren't read as quantities; if 'all' is true, every number is spelled out
+impl Function for SpellIdNumbers {
+    fn evaluate<'c, 'd>(&self,
+                        _context: &context::Evaluation<'c, 'd>,
+                        args: Vec<Value<'d>>)
+                        -> Result<Value<'d>, Error>
+    {
+        let mut args = Args(args);
+        args.exactly(2)?;
+        let all = args.pop_boolean()?;
+        let text = args.pop_string()?;
+        return Ok( Value::String( SpellIdNumbers::spell(&text, all) ) );
+    }
+}
+
+impl SpellIdNumbers {
+    /// Words that say the number that follows is an identifier
+    const ID_WORDS: &'static [&'static str] = &["isbn", "issn", "doi", "id", "no", "tel", "phone", "fax"];
+
+    fn spell(text: &str, all: bool) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::with_capacity(2 * text.len());
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_ascii_digit() {
+                result.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            // a run of digits with single separators between them (e.g., "555-123-4567" or "978 0 306")
+            let start = i;
+            let mut groups: Vec<String> = vec![String::new()];
+            let mut separators = String::new();
+            while i < chars.len() {
+                if chars[i].is_ascii_digit() {
+                    groups.last_mut().unwrap().push(chars[i]);
+                    i += 1;
+                } else if "-./ ".contains(chars[i]) && i+1 < chars.len() && chars[i+1].is_ascii_digit() {
+                    separators.push(chars[i]);
+                    groups.push(String::new());
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let before: String = chars[..start].iter().collect();
+            let after: String = chars[i..].iter().collect();
+            if all || SpellIdNumbers::is_id(&groups, &separators, &before, &after) {
+                result += &groups.iter()
+                        .map(|group| SpellDigits::spell(group, 0, 0))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+            } else {
+                result.extend(&chars[start..i]);
+            }
+        }
+        return result;
+    }
+
+    /// The heuristic for whether a number looks like an identifier rather than a quantity
+    fn is_id(groups: &[String], separators: &str, before: &str, after: &str) -> bool {
+        let n_digits: usize = groups.iter().map(|group| group.len()).sum();
+        // phone numbers, ISBNs, dates, ... (but not decimal numbers such as "3.14")
+        if groups.len() > 1 && n_digits >= 7 && separators != "." {
+            return true;
+        }
+        // a number after a word such as "ISBN" or "tel:"
+        let previous_word = before.trim_end_matches(|ch: char| ch.is_whitespace() || ch == ':' || ch == '.')
+                .rsplit(|ch: char| ch.is_whitespace())
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+        if SpellIdNumbers::ID_WORDS.contains(&previous_word.as_str()) || before.ends_with('#') {
+            return true;
+        }
+        // a year in a citation such as "(1998)" or "[2004]"
+        if groups.len() == 1 && n_digits == 4 && (groups[0].starts_with('1') || groups[0].starts_with('2')) {
+            let before = before.trim_end();
+            let after = after.trim_start();
+            return (before.ends_with('(') && (after.starts_with(')') || after.starts_with(','))) ||
+                   (before.ends_with('[') && after.starts_with(']'));
+        }
+        return false;
+    }
+}
+
 /// Add all the functions defined in this module to `context`.
 pub fn add_builtin_functions(context: &mut Context) {
     // FIX: should be a static cache that gets regenerated on update
@@ -1068,14 +1872,28 @@ pub fn add_builtin_functions(context: &mut Context) {
     context.set_function("BrailleChars", crate::braille::BrailleChars);
     context.set_function("IsNode", IsNode);
     context.set_function("ToOrdinal", ToOrdinal);
+    context.set_function("ToCardinal", ToCardinal);
     context.set_function("ToCommonFraction", ToCommonFraction);
+    context.set_function("Gender", Gender);
+    context.set_function("Agree", Agree);
+    context.set_function("Plural", Plural);
+    context.set_function("PluralForm", PluralForm);
     context.set_function("IsLargeOp", IsLargeOp);
     context.set_function("IsBracketed", IsBracketed);
     context.set_function("IsInDefinition", IsInDefinition);
+    context.set_function("ConstantName", ConstantName);
     context.set_function("BaseNode", BaseNode);
     context.set_function("IfThenElse", IfThenElse);
     context.set_function("DistanceFromLeaf", DistanceFromLeaf);
     context.set_function("EdgeNode", EdgeNode);
+    context.set_function("NodeSize", NodeSize);
+    context.set_function("NestingDepth", NestingDepth);
+    context.set_function("Repeat", Repeat);
+    context.set_function("VariationIndex", VariationIndex);
+    context.set_function("FractionKind", FractionKind);
+    context.set_function("IsFirstEncounter", IsFirstEncounter);
+    context.set_function("SpellDigits", SpellDigits);
+    context.set_function("SpellIdNumbers", SpellIdNumbers);
     context.set_function("DEBUG", Debug);
 }
 
@@ -1095,100 +1913,251 @@ mod tests {
             None
         ]);
         if let Err(e) = result {
-            panic!("unable to read 'Rules/Languages/en/definitions.yaml\n{}", e.to_string());
+            panic!("unable to read 'Rules/Languages/en/definitions.yaml\n{}", e);
         }
     }
 
-    #[test]
-    fn ordinal_one_digit() {
+    /// Add French-like agreement definitions to the English ones
+    fn init_agreement_word_list() {
+        use crate::definitions::Contains;
+        use std::{rc::Rc, cell::RefCell, collections::HashSet};
         init_word_list();
-        assert_eq!("zeroth", ToOrdinal::convert("0", false, false));
-        assert_eq!("second", ToOrdinal::convert("2", false, false));
-        assert_eq!("ninth", ToOrdinal::convert("9", false, false));
-
-        assert_eq!("zeroth", ToOrdinal::convert("0", false, true));
-        assert_eq!("seconds", ToOrdinal::convert("2", false, true));
-        assert_eq!("ninths", ToOrdinal::convert("9", false, true));
+        let vecs: [(&str, &[&str]); 7] = [
+            ("Genders_vec", &["Masculine", "Feminine"]),
+            ("DefiniteArticle_vec", &["le", "la", "les", "les"]),
+            ("Ordinal_vec", &["premier", "première", "premiers", "premières"]),
+            ("PluralIrregular_vec", &["œil", "yeux"]),
+            ("PluralEndings_vec", &["al", "aux", "s", "s", "", "s"]),
+            ("NumbersOrdinalFeminineOnes", &["zeroth", "firstess", "secondess"]),
+            ("NumbersOrdinalFemininePluralOnes", &["zeroths", "firstesses", "secondesses"]),
+        ];
+        DEFINITIONS.with(|definitions| {
+            let mapping = &mut definitions.borrow_mut().name_to_var_mapping;
+            for (name, values) in vecs {
+                let values = values.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+                mapping.insert(name.to_string(), Contains::Vec( Rc::new( RefCell::new(values) ) ));
+            }
+            let nouns = ["fraction", "racine"].iter().map(|value| value.to_string()).collect::<HashSet<String>>();
+            mapping.insert("NounsFeminine".to_string(), Contains::Set( Rc::new( RefCell::new(nouns) ) ));
+        });
+    }
 
-        assert_eq!("first", ToOrdinal::convert("1", true, false));
-        assert_eq!("half", ToOrdinal::convert("2", true, false));
-        assert_eq!("half", ToOrdinal::convert("02", true, false));
-        assert_eq!("ninth", ToOrdinal::convert("9", true, false));
+    #[test]
+    fn gendered_ordinals() {
+        init_agreement_word_list();
+        assert_eq!("secondess", ToOrdinal::convert("2", false, false, "Feminine"));
+        assert_eq!("secondesses", ToOrdinal::convert("2", false, true, "Feminine"));
+        // falls back to the ungendered words if there aren't gendered ones
+        assert_eq!("second", ToOrdinal::convert("2", false, false, "Masculine"));
+        assert_eq!("tenth", ToOrdinal::convert("10", false, false, "Feminine"));
+        assert_eq!("half", ToOrdinal::convert("2", true, false, "Feminine"));
+        assert_eq!("second", ToOrdinal::convert("2", false, false, ""));
+    }
 
-        assert_eq!("halves", ToOrdinal::convert("2", true, true));
-        assert_eq!("halves", ToOrdinal::convert("002", true, true));
-        assert_eq!("ninths", ToOrdinal::convert("9", true, true));
+    #[test]
+    fn gender_and_number_agreement() {
+        init_agreement_word_list();
+        assert_eq!(Agreement::gender("fraction"), "Feminine");
+        assert_eq!(Agreement::gender("Racine"), "Feminine");
+        assert_eq!(Agreement::gender("nombre"), "Masculine");
+
+        assert_eq!(Agreement::agree("DefiniteArticle_vec", "Feminine", false).unwrap(), "la");
+        assert_eq!(Agreement::agree("DefiniteArticle_vec", "Masculine", false).unwrap(), "le");
+        assert_eq!(Agreement::agree("Ordinal_vec", "Feminine", true).unwrap(), "premières");
+        assert_eq!(Agreement::agree("Ordinal_vec", "Unknown", false).unwrap(), "premier");
+        assert!(Agreement::agree("Undefined_vec", "Feminine", false).is_err());
+
+        assert_eq!(Agreement::plural("œil"), "yeux");
+        assert_eq!(Agreement::plural("total"), "totaux");
+        assert_eq!(Agreement::plural("fois"), "fois");
+        assert_eq!(Agreement::plural("racine"), "racines");
+
+        assert!(!Agreement::is_plural(&Value::Number(1.0)));
+        assert!(Agreement::is_plural(&Value::Number(2.0)));
+        assert!(Agreement::is_plural(&Value::String("3".to_string())));
+        assert!(Agreement::is_plural(&Value::Boolean(true)));
     }
 
     #[test]
-    fn ordinal_two_digit() {
-        init_word_list();
-        assert_eq!("tenth", ToOrdinal::convert("10", false, false));
-        assert_eq!("seventeenth", ToOrdinal::convert("17", false, false));
-        assert_eq!("thirty second", ToOrdinal::convert("32", false, false));
-        assert_eq!("fortieth", ToOrdinal::convert("40", false, false));
+    fn agreement_in_xpath() {
+        init_agreement_word_list();
+        let mathml = "<math><mfrac><mn>1</mn><mn>2</mn></mfrac></math>";
+        let package = parser::parse(mathml).expect("failed to parse XML");
+        let mathml = get_element(&package);
+        trim_element(&mathml);
+        let factory = sxd_xpath::Factory::new();
+        let mut context = sxd_xpath::Context::new();
+        add_builtin_functions(&mut context);
+        let evaluate = |xpath: &str| {
+            let xpath = factory.build(xpath).unwrap().unwrap();
+            return xpath.evaluate(&context, mathml).unwrap().string();
+        };
+        assert_eq!(evaluate("concat(Agree('DefiniteArticle_vec', Gender('fraction'), 1), ' fraction')"), "la fraction");
+        assert_eq!(evaluate("Plural('racine', ./*[1]/*[2])"), "racines");
+        assert_eq!(evaluate("Plural('racine', ./*[1]/*[1])"), "racine");
+        assert_eq!(evaluate("ToOrdinal(./*[1]/*[2], false(), true(), 'Feminine')"), "secondesses");
+        assert_eq!(evaluate("ToOrdinal(./*[1]/*[2])"), "second");
+    }
 
-        assert_eq!("tenths", ToOrdinal::convert("10", false, true));
-        assert_eq!("sixteenths", ToOrdinal::convert("16", false, true));
-        assert_eq!("eighty eights", ToOrdinal::convert("88", false, true));
-        assert_eq!("fiftieths", ToOrdinal::convert("50", false, true));
+    #[test]
+    fn plural_forms() {
+        let forms = ["stupeň", "stupně", "stupňů"].iter().map(|form| form.to_string()).collect::<Vec<String>>();
+        assert_eq!(PluralForm::choose("cs", "1", &forms), "stupeň");
+        assert_eq!(PluralForm::choose("cs", "2", &forms), "stupně");
+        assert_eq!(PluralForm::choose("cs", "5", &forms), "stupňů");
+        assert_eq!(PluralForm::choose("cs", "21", &forms), "stupňů");
+        assert_eq!(PluralForm::choose("cs", "2,5", &forms), "stupně");
+
+        let forms = ["градус", "градуса", "градусов"].iter().map(|form| form.to_string()).collect::<Vec<String>>();
+        assert_eq!(PluralForm::choose("ru", "1", &forms), "градус");
+        assert_eq!(PluralForm::choose("ru", "21", &forms), "градус");
+        assert_eq!(PluralForm::choose("ru", "11", &forms), "градусов");
+        assert_eq!(PluralForm::choose("ru", "23", &forms), "градуса");
+        assert_eq!(PluralForm::choose("ru", "13", &forms), "градусов");
+        assert_eq!(PluralForm::choose("ru", "1 000 000", &forms), "градусов");
+        assert_eq!(PluralForm::choose("ru", "1.5", &forms), "градуса");
+        assert_eq!(PluralForm::choose("ru", "2.0", &forms), "градуса");
+
+        assert_eq!(PluralForm::form_index("pl", "1"), 0);
+        assert_eq!(PluralForm::form_index("pl", "21"), 2);
+        assert_eq!(PluralForm::form_index("pl-pl", "22"), 1);
+        assert_eq!(PluralForm::form_index("sl", "102"), 1);
+        assert_eq!(PluralForm::form_index("sl", "5"), 3);
+
+        let forms = ["degree", "degrees"].iter().map(|form| form.to_string()).collect::<Vec<String>>();
+        assert_eq!(PluralForm::choose("en", "1", &forms), "degree");
+        assert_eq!(PluralForm::choose("en", "1,000,000", &forms), "degrees");
+        assert_eq!(PluralForm::choose("en", "1.5", &forms), "degrees");
+    }
 
-        assert_eq!("eleventh", ToOrdinal::convert("11", true, false));
-        assert_eq!("forty fourth", ToOrdinal::convert("44", true, false));
-        assert_eq!("ninth", ToOrdinal::convert("9", true, false));
-        assert_eq!("ninth", ToOrdinal::convert("00000009", true, false));
-        assert_eq!("sixtieth", ToOrdinal::convert("60", true, false));
+    #[test]
+    fn ordinal_one_digit() {
+        init_word_list();
+        assert_eq!("zeroth", ToOrdinal::convert("0", false, false, ""));
+        assert_eq!("second", ToOrdinal::convert("2", false, false, ""));
+        assert_eq!("ninth", ToOrdinal::convert("9", false, false, ""));
+
+        assert_eq!("zeroth", ToOrdinal::convert("0", false, true, ""));
+        assert_eq!("seconds", ToOrdinal::convert("2", false, true, ""));
+        assert_eq!("ninths", ToOrdinal::convert("9", false, true, ""));
+
+        assert_eq!("first", ToOrdinal::convert("1", true, false, ""));
+        assert_eq!("half", ToOrdinal::convert("2", true, false, ""));
+        assert_eq!("half", ToOrdinal::convert("02", true, false, ""));
+        assert_eq!("ninth", ToOrdinal::convert("9", true, false, ""));
+
+        assert_eq!("halves", ToOrdinal::convert("2", true, true, ""));
+        assert_eq!("halves", ToOrdinal::convert("002", true, true, ""));
+        assert_eq!("ninths", ToOrdinal::convert("9", true, true, ""));
+    }
 
-        assert_eq!("tenths", ToOrdinal::convert("10", true, true));
-        assert_eq!("tenths", ToOrdinal::convert("0010", true, true));
-        assert_eq!("elevenths", ToOrdinal::convert("11", true, true));
-        assert_eq!("nineteenths", ToOrdinal::convert("19", true, true));
-        assert_eq!("twentieths", ToOrdinal::convert("20", true, true));
+    #[test]
+    fn ordinal_two_digit() {
+        init_word_list();
+        assert_eq!("tenth", ToOrdinal::convert("10", false, false, ""));
+        assert_eq!("seventeenth", ToOrdinal::convert("17", false, false, ""));
+        assert_eq!("thirty second", ToOrdinal::convert("32", false, false, ""));
+        assert_eq!("fortieth", ToOrdinal::convert("40", false, false, ""));
+
+        assert_eq!("tenths", ToOrdinal::convert("10", false, true, ""));
+        assert_eq!("sixteenths", ToOrdinal::convert("16", false, true, ""));
+        assert_eq!("eighty eighths", ToOrdinal::convert("88", false, true, ""));
+        assert_eq!("fiftieths", ToOrdinal::convert("50", false, true, ""));
+
+        assert_eq!("eleventh", ToOrdinal::convert("11", true, false, ""));
+        assert_eq!("forty fourth", ToOrdinal::convert("44", true, false, ""));
+        assert_eq!("ninth", ToOrdinal::convert("9", true, false, ""));
+        assert_eq!("ninth", ToOrdinal::convert("00000009", true, false, ""));
+        assert_eq!("sixtieth", ToOrdinal::convert("60", true, false, ""));
+
+        assert_eq!("tenths", ToOrdinal::convert("10", true, true, ""));
+        assert_eq!("tenths", ToOrdinal::convert("0010", true, true, ""));
+        assert_eq!("elevenths", ToOrdinal::convert("11", true, true, ""));
+        assert_eq!("nineteenths", ToOrdinal::convert("19", true, true, ""));
+        assert_eq!("twentieths", ToOrdinal::convert("20", true, true, ""));
     }
 
     #[test]
     fn ordinal_three_digit() {
         init_word_list();
-        assert_eq!("one hundred first", ToOrdinal::convert("101", false, false));
-        assert_eq!("two hundred tenth", ToOrdinal::convert("210", false, false));
-        assert_eq!("four hundred thirty second", ToOrdinal::convert("432", false, false));
-        assert_eq!("four hundred second", ToOrdinal::convert("402", false, false));
-
-        assert_eq!("one hundred first", ToOrdinal::convert("101", true, false));
-        assert_eq!("two hundred second", ToOrdinal::convert("202", true, false));
-        assert_eq!("four hundred thirty second", ToOrdinal::convert("432", true, false));
-        assert_eq!("five hundred third", ToOrdinal::convert("503", true, false));
-
-        assert_eq!("three hundred elevenths", ToOrdinal::convert("311", false, true));
-        assert_eq!("four hundred ninety ninths", ToOrdinal::convert("499", false, true));
-        assert_eq!("nine hundred ninetieths", ToOrdinal::convert("990", false, true));
-        assert_eq!("six hundred seconds", ToOrdinal::convert("602", false, true));
+        assert_eq!("one hundred first", ToOrdinal::convert("101", false, false, ""));
+        assert_eq!("two hundred tenth", ToOrdinal::convert("210", false, false, ""));
+        assert_eq!("four hundred thirty second", ToOrdinal::convert("432", false, false, ""));
+        assert_eq!("four hundred second", ToOrdinal::convert("402", false, false, ""));
+
+        assert_eq!("one hundred first", ToOrdinal::convert("101", true, false, ""));
+        assert_eq!("two hundred second", ToOrdinal::convert("202", true, false, ""));
+        assert_eq!("four hundred thirty second", ToOrdinal::convert("432", true, false, ""));
+        assert_eq!("five hundred third", ToOrdinal::convert("503", true, false, ""));
+
+        assert_eq!("three hundred elevenths", ToOrdinal::convert("311", false, true, ""));
+        assert_eq!("four hundred ninety ninths", ToOrdinal::convert("499", false, true, ""));
+        assert_eq!("nine hundred ninetieths", ToOrdinal::convert("990", false, true, ""));
+        assert_eq!("six hundred seconds", ToOrdinal::convert("602", false, true, ""));
+
+        assert_eq!("seven hundredths", ToOrdinal::convert("700", true, true, ""));
+        assert_eq!("one hundredths", ToOrdinal::convert("100", true, true, ""));
+        assert_eq!("eight hundred seventeenths", ToOrdinal::convert("817", true, true, ""));
+        assert_eq!("one hundred twenty firsts", ToOrdinal::convert("121", true, true, ""));
+    }
 
-        assert_eq!("seven hundredths", ToOrdinal::convert("700", true, true));
-        assert_eq!("one hundredths", ToOrdinal::convert("100", true, true));
-        assert_eq!("eight hundred seventeenths", ToOrdinal::convert("817", true, true));
+    #[test]
+    fn fractional_ordinal_prefix() {
+        let result = crate::definitions::read_definitions_file(&[
+            Some(PathBuf::from("Rules/Languages/id/definitions.yaml")),
+            Some(PathBuf::from("Rules/definitions.yaml")),
+            None
+        ]);
+        if let Err(e) = result {
+            panic!("unable to read 'Rules/Languages/id/definitions.yaml\n{}", e);
+        }
+        assert_eq!("perenambelas", ToOrdinal::convert("16", true, true, ""));
+        assert_eq!("per 21", ToOrdinal::convert("21", true, true, ""));
+        // only fractions use the prefix
+        assert_eq!("dua puluh satu", ToOrdinal::convert("21", false, false, ""));
     }
+
     #[test]
     fn ordinal_large() {
         init_word_list();
-        assert_eq!("one thousandth", ToOrdinal::convert("1000", false, false));
-        assert_eq!("two thousand one hundredth", ToOrdinal::convert("2100", false, false));
-        assert_eq!("thirty thousandth", ToOrdinal::convert("30000", false, false));
-        assert_eq!("four hundred thousandth", ToOrdinal::convert("400000", false, false));
-
-        assert_eq!("four hundred thousandth", ToOrdinal::convert("400000", true, false));
-        assert_eq!("five hundred thousand second", ToOrdinal::convert("500002", true, false));
-        assert_eq!("six millionth", ToOrdinal::convert("6000000", true, false));
-        assert_eq!("sixty millionth", ToOrdinal::convert("60000000", true, false));
-
-        assert_eq!("seven billionths", ToOrdinal::convert("7000000000", false, true));
-        assert_eq!("eight trillionths", ToOrdinal::convert("8000000000000", false, true));
-        assert_eq!("nine quadrillionths", ToOrdinal::convert("9000000000000000", false, true));
-        assert_eq!("one quintillionth", ToOrdinal::convert("1000000000000000000", false, false));
+        assert_eq!("one thousandth", ToOrdinal::convert("1000", false, false, ""));
+        assert_eq!("two thousand one hundredth", ToOrdinal::convert("2100", false, false, ""));
+        assert_eq!("thirty thousandth", ToOrdinal::convert("30000", false, false, ""));
+        assert_eq!("four hundred thousandth", ToOrdinal::convert("400000", false, false, ""));
+
+        assert_eq!("four hundred thousandth", ToOrdinal::convert("400000", true, false, ""));
+        assert_eq!("five hundred thousand second", ToOrdinal::convert("500002", true, false, ""));
+        assert_eq!("six millionth", ToOrdinal::convert("6000000", true, false, ""));
+        assert_eq!("sixty millionth", ToOrdinal::convert("60000000", true, false, ""));
+
+        assert_eq!("seven billionths", ToOrdinal::convert("7000000000", false, true, ""));
+        assert_eq!("eight trillionths", ToOrdinal::convert("8000000000000", false, true, ""));
+        assert_eq!("nine quadrillionths", ToOrdinal::convert("9000000000000000", false, true, ""));
+        assert_eq!("one quintillionth", ToOrdinal::convert("1000000000000000000", false, false, ""));
+
+        assert_eq!("nine billion eight hundred seventy six million five hundred forty three thousand two hundred tenths", ToOrdinal::convert("9876543210", true, true, ""));
+        assert_eq!("nine billion five hundred forty three thousand two hundred tenths", ToOrdinal::convert("9000543210", true, true, ""));
+        assert_eq!("zeroth", ToOrdinal::convert("00000", false, false, ""));
+    }
 
-        assert_eq!("nine billion eight hundred seventy six million five hundred forty three thousand two hundred tenths", ToOrdinal::convert("9876543210", true, true));
-        assert_eq!("nine billion five hundred forty three thousand two hundred tenths", ToOrdinal::convert("9000543210", true, true));
-        assert_eq!("zeroth", ToOrdinal::convert("00000", false, false));
+    #[test]
+    fn big_numbers() {
+        init_word_list();
+        // too big for a usize -- there is no irregular fractional form
+        assert_eq!("one hundred twenty three sextillion four hundred fifty six quintillion seven hundred eighty nine quadrillion twelve trillion three hundred forty five billion six hundred seventy eight million nine hundred one thousand two hundred thirty fourths",
+                   ToOrdinal::convert("123456789012345678901234", true, true, ""));
+        assert_eq!("one quintillion two", ToCardinal::convert("1000000000000000002"));
+        assert_eq!("one hundred twenty three octillion four hundred fifty six septillion seven hundred eighty nine sextillion twelve quintillion three hundred forty five quadrillion six hundred seventy eight trillion nine hundred one billion two hundred thirty four million five hundred sixty seven thousand eight hundred ninety",
+                   ToCardinal::convert("123,456,789,012,345,678,901,234,567,890"));
+        assert_eq!("zero", ToCardinal::convert("000"));
+        assert_eq!("1.5", ToCardinal::convert("1.5"));
+        // more digits than there are words for
+        assert_eq!("12, 345, 678, 901, 234, 567, 890, 123, 456, 789, 012, 345",
+                   ToCardinal::convert("12345678901234567890123456789012345"));
+
+        let forms = ["stupeň", "stupně", "stupňů"].iter().map(|form| form.to_string()).collect::<Vec<String>>();
+        assert_eq!(PluralForm::choose("cs", "123456789012345678901234", &forms), "stupňů");
+        assert_eq!(PluralForm::form_index("ru", "123456789012345678901"), 0);
     }
 
 
@@ -1259,6 +2228,23 @@ mod tests {
         assert_eq!(EdgeNode::edge_node(mi, true, "2D"), None);
     }
 
+    #[test]
+    fn variation_index() {
+        let mathml = "<math><mrow><mfrac><mfrac><mi>a</mi><mi>b</mi></mfrac><mn>2</mn></mfrac><mo>+</mo><mfrac><mn>3</mn><mi>x</mi></mfrac></mrow></math>";
+        let package = parser::parse(mathml).expect("failed to parse XML");
+        let mathml = get_element(&package);
+        trim_element(&mathml);
+        let mrow = as_element(mathml.children()[0]);
+        let outer_frac = as_element(mrow.children()[0]);
+        let inner_frac = as_element(outer_frac.children()[0]);
+        let last_frac = as_element(mrow.children()[2]);
+        reset_variation_indices();
+        assert_eq!(VariationIndex::n_preceding_with_same_name(outer_frac), 0);
+        assert_eq!(VariationIndex::n_preceding_with_same_name(inner_frac), 1);
+        assert_eq!(VariationIndex::n_preceding_with_same_name(last_frac), 2);
+        assert_eq!(VariationIndex::n_preceding_with_same_name(as_element(last_frac.children()[1])), 2);
+    }
+
     #[test]
     fn at_right_edge() {
         let mathml = "<math><mrow><mfrac><mn>4</mn><mrow><mn>30</mn><mi>x</mi></mrow></mfrac><mo>.</mo></mrow></math>";
@@ -1274,4 +2260,47 @@ mod tests {
         let mn = as_element(as_element(fraction.children()[1]).children()[0]);
         assert_eq!(EdgeNode::edge_node(mn, true, "2D"), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn nesting_depth() {
+        let mathml = "<math><mrow><mfrac><mi>a</mi><mfrac><mn>1</mn><mrow><mn>1</mn><mo>+</mo><mfrac><mn>1</mn><mi>x</mi></mfrac></mrow></mfrac></mfrac>
+                      <mo>+</mo><mfrac><mn>1</mn><mi>y</mi></mfrac></mrow></math>";
+        let package = parser::parse(mathml).expect("failed to parse XML");
+        let mathml = get_element(&package);
+        trim_element(&mathml);
+        let mrow = as_element(mathml.children()[0]);
+        let outer_frac = as_element(mrow.children()[0]);
+        assert_eq!(NestingDepth::depth(mrow, "mfrac"), 3);
+        assert_eq!(NestingDepth::depth(outer_frac, "mfrac"), 2);
+        assert_eq!(NestingDepth::depth(as_element(mrow.children()[2]), "mfrac"), 0);
+        assert_eq!(NestingDepth::depth(outer_frac, "msqrt"), 0);
+    }
+
+    #[test]
+    fn repeat_in_xpath() {
+        let mathml = "<math><mi>x</mi></math>";
+        let package = parser::parse(mathml).expect("failed to parse XML");
+        let mathml = get_element(&package);
+        let factory = sxd_xpath::Factory::new();
+        let mut context = sxd_xpath::Context::new();
+        add_builtin_functions(&mut context);
+        let evaluate = |xpath: &str| {
+            let xpath = factory.build(xpath).unwrap().unwrap();
+            return xpath.evaluate(&context, mathml).unwrap().string();
+        };
+        assert_eq!(evaluate("Repeat('Start', 3)"), "StartStartStart");
+        assert_eq!(evaluate("Repeat('Start', 0)"), "");
+        assert_eq!(evaluate("Repeat('Start', -1)"), "");
+    }
+
+    #[test]
+    fn spell_id_numbers() {
+        assert_eq!(SpellIdNumbers::spell("call 555-123-4567 now", false), "call 5 5 5, 1 2 3, 4 5 6 7 now");
+        assert_eq!(SpellIdNumbers::spell("ISBN 9780306406157", false), "ISBN 9 7 8 0 3 0 6 4 0 6 1 5 7");
+        assert_eq!(SpellIdNumbers::spell("Smith (1998), p. 12", false), "Smith (1 9 9 8), p. 12");
+        assert_eq!(SpellIdNumbers::spell("order #4512", false), "order #4 5 1 2");
+        // quantities are left alone
+        assert_eq!(SpellIdNumbers::spell("costs 3.14 or 1998 dollars", false), "costs 3.14 or 1998 dollars");
+        assert_eq!(SpellIdNumbers::spell("costs 3.14 or 1998 dollars", true), "costs 3, 1 4 or 1 9 9 8 dollars");
+    }
+}
@@ -0,0 +1,45 @@
+#![allow(clippy::needless_return)]
+/// Runs a ClearSpeak example corpus (clearspeak_conformance/examples.csv) and reports pass/fail for each preference setting.
+///
+/// The corpus is a CSV export of the ClearSpeak example spreadsheet with the columns
+///   "Example", "Preference", "Setting", "MathML", and "Speech".
+///   "Preference" is a ClearSpeak preference name without the "ClearSpeak_" prefix (e.g., "Fraction");
+///   if it is empty, the example uses the default preferences.
+/// See `Corpus` in tests/common/mod.rs for known_failures.txt and the report
+///   (written to MATHCAT_CLEARSPEAK_REPORT or clearspeak-conformance.md in the target directory).
+use crate::common::*;
+
+const CORPUS: Corpus = Corpus {
+    name: "ClearSpeak",
+    dir: "tests/Languages/clearspeak_conformance",
+    group_heading: "Setting",
+    report_var: "MATHCAT_CLEARSPEAK_REPORT",
+    report_file: "clearspeak-conformance.md",
+};
+
+#[test]
+fn clearspeak_conformance() {
+    let results = CORPUS.read_examples(&["example", "preference", "setting", "mathml", "speech"]).into_iter()
+        .map(|example| {
+            let (name, preference, setting, mathml, expected) = (&example[0], &example[1], &example[2], &example[3], &example[4]);
+            let pref_name = if preference.is_empty() {String::default()} else {format!("ClearSpeak_{}", preference)};
+            let failure = match speak_ClearSpeak("en", &pref_name, setting, mathml) {
+                Ok(actual) if &actual == expected => None,
+                Ok(actual) => Some(format!("'{}'\n    expected: '{}'\n      actual: '{}'", name, expected, actual)),
+                Err(e) => Some(format!("'{}' failed: {}", name, e)),
+            };
+            let group = if preference.is_empty() {"Default".to_string()} else {format!("{}={}", preference, setting)};
+            ExampleResult { name: name.clone(), group, failure }
+        })
+        .collect::<Vec<ExampleResult>>();
+    CORPUS.check(&results);
+}
+
+#[test]
+fn csv_parsing() {
+    let records = parse_csv("a,\"b, \"\"c\"\"\"\r\n\"multi\nline\",\n\n");
+    assert_eq!(records, vec![
+        vec!["a".to_string(), "b, \"c\"".to_string()],
+        vec!["multi\nline".to_string(), "".to_string()],
+    ]);
+}
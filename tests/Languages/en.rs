@@ -11,6 +11,7 @@ mod ClearSpeak {
     mod sets;
     mod symbols_and_adornments;
     mod multiline;
+    mod parens;
 }
 
 mod SimpleSpeak {
@@ -26,8 +27,25 @@ mod SimpleSpeak {
     mod linear_algebra;
     mod multiline;
 }
+mod MathSpeak {
+    mod large_ops;
+    mod menclose;
+    mod mfrac;
+    mod mroot;
+    mod msup;
+    mod mtable;
+    mod symbols_and_adornments;
+}
 mod shared;
 mod chemistry;
 mod alphabets;
 mod intent;
+mod exam_mode;
+mod explanation;
 
+mod script_order;
+mod big_numbers;
+mod decimal_digits;
+mod id_numbers;
+mod number_theory;
+mod subject_area;
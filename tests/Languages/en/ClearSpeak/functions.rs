@@ -57,6 +57,38 @@ fn inverse_trig_arc() {
         "arc hyperbolic cosine of x");
 }
 
+#[test]
+fn inverse_trig_pref_overrides_clearspeak_trig() {
+    let expr = "<math><msup><mi>sin</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mi>x</mi></math>";
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_Trig", "TrigInverse"), ("TrigInverse", "ArcTrig")],
+        expr, "arc sine of x");
+}
+
+#[test]
+fn inverse_function() {
+    let expr = "<math><msup><mi>f</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mo>(</mo><mi>x</mi><mo>)</mo></math>";
+    test("en", "ClearSpeak", expr, "f inverse of x");
+}
+
+#[test]
+fn inverse_function_literal() {
+    let expr = "<math><msup><mi>f</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mo>(</mo><mi>x</mi><mo>)</mo></math>";
+    test_prefs("en", "ClearSpeak", vec![("FunctionInverse", "Literal")],
+        expr, "the negative 1 power of, f of x");
+}
+
+#[test]
+fn composition() {
+    let expr = "<math><mo>(</mo><mi>f</mi><mo>∘</mo><mi>g</mi><mo>)</mo><mo>(</mo><mi>x</mi><mo>)</mo></math>";
+    test("en", "ClearSpeak", expr, "f composed with g of x");
+}
+
+#[test]
+fn composition_with_inverse() {
+    let expr = "<math><msup><mi>f</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mo>∘</mo><mi>f</mi></math>";
+    test("en", "ClearSpeak", expr, "f inverse composed with f");
+}
+
 #[test]
 fn trig_squared() {
     let expr = "<math><msup><mi>sin</mi><mn>2</mn></msup><mi>x</mi></math>";
@@ -191,3 +191,43 @@ fn simple_speak_menclose_top_bottom() {
                 </math>";
     test("en", "SimpleSpeak", expr, "line on top, bottom, enclosing 3 halves end enclosure,");
 }
+
+#[test]
+fn unit_cancellation() {
+    let expr = "<math>
+        <mfrac><mrow><mn>60</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow><mi mathvariant='normal'>h</mi></mfrac>
+        <mo>×</mo>
+        <mfrac><mrow><mn>1000</mn><mi mathvariant='normal'>m</mi></mrow><mrow><mn>1</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow></mfrac>
+    </math>";
+    test_prefs("en", "ClearSpeak", vec![("SubjectArea", "Physics")], expr,
+        "the fraction with numerator; 60 cancelled km; and denominator h; times; \
+         the fraction with numerator 1000 m; and denominator 1 cancelled km; km cancels, leaving m per h");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Physics")], expr,
+        "fraction, 60 cancelled km, over h, end fraction; times; fraction, 1000 m, over, 1 cancelled km, end fraction; km cancels, leaving m per h");
+}
+
+#[test]
+fn unit_cancellation_chain() {
+    let expr = "<math>
+        <mfrac><mrow><mn>60</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow><menclose notation='updiagonalstrike'><mi mathvariant='normal'>h</mi></menclose></mfrac>
+        <mo>×</mo>
+        <mfrac><mrow><mn>1000</mn><mi mathvariant='normal'>m</mi></mrow><mrow><mn>1</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow></mfrac>
+        <mo>×</mo>
+        <mfrac><mrow><mn>1</mn><menclose notation='updiagonalstrike'><mi mathvariant='normal'>h</mi></menclose></mrow><mrow><mn>3600</mn><mi mathvariant='normal'>s</mi></mrow></mfrac>
+    </math>";
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Physics")], expr,
+        "fraction, 60 cancelled km, over, cancelled h, end fraction; times; fraction, 1000 m, over, 1 cancelled km, end fraction; \
+         times; fraction, 1 cancelled h, over, 3600 s, end fraction; km and h cancel, leaving m per s");
+}
+
+#[test]
+fn unit_cancellation_not_physics() {
+    let expr = "<math>
+        <mfrac><mrow><mn>60</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow><mi mathvariant='normal'>h</mi></mfrac>
+        <mo>×</mo>
+        <mfrac><mrow><mn>1000</mn><mi mathvariant='normal'>m</mi></mrow><mrow><mn>1</mn><menclose notation='updiagonalstrike'><mi>km</mi></menclose></mrow></mfrac>
+    </math>";
+    test("en", "SimpleSpeak", expr,
+        "fraction, 60, up diagonal, cross out, enclosing km; over h, end fraction; times; \
+         fraction, 1000 m, over, 1, up diagonal, cross out, enclosing km; end fraction;");
+}
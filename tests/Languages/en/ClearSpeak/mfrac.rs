@@ -38,6 +38,20 @@ fn not_ClearSpeak_common_fraction_tenths() {
     test_prefs("en", "ClearSpeak", vec![("Verbosity", "Medium"), ("ClearSpeak_Fractions", "Ordinal")], expr, "89 tenths");
 }
 
+#[test]
+fn ordinal_denominator_max() {
+    let expr = "<math>
+                    <mfrac> <mn>3</mn> <mn>16</mn> </mfrac>
+                </math>";
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_Fractions", "Auto")], expr, "3 over 16");
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_Fractions", "Auto"), ("OrdinalDenominatorMax", "20")], expr, "3 sixteenths");
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_Fractions", "Auto"), ("OrdinalDenominatorMax", "2")], expr, "3 over 16");
+    let expr = "<math>
+                    <mfrac> <mn>1</mn> <mn>3</mn> </mfrac>
+                </math>";
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_Fractions", "Auto"), ("OrdinalDenominatorMax", "2")], expr, "1 over 3");
+}
+
 #[test]
 fn non_simple_fraction() {
     let expr = "
@@ -235,6 +249,24 @@ fn function_over_function() {
     test_prefs("en", "ClearSpeak", vec![("Verbosity", "Verbose"), ("ClearSpeak_Fractions", "Auto")], expr, "f of x over g of x, end fraction,");
 }
 
+#[test]
+fn structure_verbosity() {
+    // the per-structure verbosities override Verbosity unless they are 'Auto'
+    let expr = "<math>
+            <mfrac><mrow><mi>f</mi><mo>(</mo><mi>x</mi><mo>)</mo></mrow><mn>2</mn></mfrac>
+            <mo>+</mo><msqrt><mi>x</mi></msqrt>
+            <mo>+</mo><msub><mi>x</mi><mn>1</mn></msub>
+        </math>";
+    test_prefs("en", "ClearSpeak", vec![("Verbosity", "Terse"), ("FractionVerbosity", "Verbose")], expr,
+             "f x over 2, end fraction; plus square root, x; plus x 1");
+    test_prefs("en", "ClearSpeak",
+             vec![("Verbosity", "Verbose"), ("FractionVerbosity", "Medium"), ("RootVerbosity", "Terse"), ("ScriptVerbosity", "Terse")],
+             expr, "f of x over 2 plus square root, x; plus x 1");
+    test_prefs("en", "ClearSpeak",
+             vec![("Verbosity", "Verbose"), ("FractionVerbosity", "Auto"), ("RootVerbosity", "Auto"), ("ScriptVerbosity", "Auto")],
+             expr, "f of x over 2, end fraction; plus the square root of x, end root; plus x sub 1");
+}
+
 #[test]
 fn non_simple_function_over_function() {
     let expr = "<math><mfrac>
@@ -247,6 +279,32 @@ fn non_simple_function_over_function() {
              "the fraction with numerator; f of, open paren x plus 1, close paren; and denominator g of x; end fraction,");
 }
 
+#[test]
+fn fraction_style_by_kind() {
+    let rate = "<math><mfrac><mrow><mn>60</mn><mtext>miles</mtext></mrow><mtext>hour</mtext></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], rate, "60 miles per hour,");
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "Auto")], rate, "60 miles over hour");
+    let units = "<math><mfrac><mi intent=':unit'>m</mi><mi intent=':unit'>s</mi></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], units, "m per s,");
+    let numeric = "<math><mfrac><mn>12</mn><mn>37</mn></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], numeric, "12 divided by 37,");
+    let common = "<math><mfrac><mn>3</mn><mn>4</mn></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], common, "3 fourths");
+    let symbolic = "<math><mfrac><mi>a</mi><mi>b</mi></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], symbolic, "eigh over b,");
+    let complex = "<math><mfrac><mrow><mi>a</mi><mo>+</mo><mn>1</mn></mrow><mi>b</mi></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "ByKind")], complex,
+             "the fraction with numerator; eigh plus 1; and denominator b;");
+}
+
+#[test]
+fn fraction_style_forced() {
+    let common = "<math><mfrac><mn>3</mn><mn>4</mn></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "DividedBy")], common, "3 divided by 4,");
+    let symbolic = "<math><mfrac><mi>a</mi><mi>b</mi></mfrac></math>";
+    test_prefs("en", "ClearSpeak", vec![("FractionStyle", "Per"), ("ClearSpeak_Fractions", "General")], symbolic, "eigh per b,");
+}
+
 #[test]
 fn binomial() {
     let expr = "<math>
@@ -960,3 +960,33 @@ fn matrix_binomial() {
     </math>";
   test_ClearSpeak("en", "ClearSpeak_Matrix", "Combinatorics", expr, "3 choose 2");
 }
+
+#[test]
+fn matrix_no_dimensions() {
+  let expr = "<math><mo>[</mo><mtable>
+      <mtr><mtd><mn>3</mn></mtd><mtd><mn>1</mn></mtd><mtd><mn>4</mn></mtd></mtr>
+      <mtr><mtd><mn>0</mn></mtd><mtd><mn>2</mn></mtd><mtd><mn>6</mn></mtd></mtr>
+    </mtable><mo>]</mo></math>";
+  test_prefs("en", "ClearSpeak", vec![("MatrixDimensions", "false")], expr, "the matrix; row 1; 3, 1, 4; row 2; 0, 2, 6;");
+  test_prefs("en", "SimpleSpeak", vec![("MatrixDimensions", "true")], expr,
+              "the 2 by 3 matrix; column 1; 3; column 2; 1; column 3; 4; column 1; 0; column 2; 2; column 3; 6;");
+  test_prefs("en", "SimpleSpeak", vec![("MatrixDimensions", "false")], expr,
+              "the matrix; column 1; 3; column 2; 1; column 3; 4; column 1; 0; column 2; 2; column 3; 6;");
+}
+
+#[test]
+fn vectors_no_dimensions() {
+  let column = "<math><mo>(</mo><mtable>
+      <mtr><mtd><mn>1</mn></mtd></mtr><mtr><mtd><mn>2</mn></mtd></mtr><mtr><mtd><mn>3</mn></mtd></mtr>
+    </mtable><mo>)</mo></math>";
+  test_prefs("en", "ClearSpeak", vec![("MatrixDimensions", "false"), ("ClearSpeak_Matrix", "Vector")], column,
+              "the column vector; 1; 2; 3;");
+  test_prefs("en", "ClearSpeak", vec![("MatrixDimensions", "false")], column, "the column matrix; 1; 2; 3;");
+  let row = "<math><mo>(</mo><mtable>
+      <mtr><mtd><mn>3</mn></mtd><mtd><mn>5</mn></mtd></mtr>
+    </mtable><mo>)</mo></math>";
+  test_prefs("en", "ClearSpeak", vec![("MatrixDimensions", "false"), ("ClearSpeak_Matrix", "Vector")], row,
+              "the row vector; 3, 5;");
+  let one_by_one = "<math><mo>|</mo><mtable><mtr><mtd><mn>3</mn></mtd></mtr></mtable><mo>|</mo></math>";
+  test_prefs("en", "ClearSpeak", vec![("MatrixDimensions", "false")], one_by_one, "the determinant with entry 3;");
+}
@@ -0,0 +1,53 @@
+/// Tests for the ClearSpeak_Paren preference
+use crate::common::*;
+
+#[test]
+fn paren_auto() {
+    let expr = "<math><mo>(</mo><mi>x</mi><mo>)</mo><mo>+</mo><mn>2</mn><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_Paren", "Auto", expr, "x plus, 2 times, open paren x plus 1, close paren");
+}
+
+#[test]
+fn paren_speak() {
+    let expr = "<math><mo>(</mo><mi>x</mi><mo>)</mo><mo>+</mo><mn>2</mn><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_Paren", "Speak", expr, "open paren x close paren, plus, 2 times, open paren x plus 1, close paren");
+}
+
+#[test]
+fn paren_silent() {
+    let expr = "<math><mo>(</mo><mi>x</mi><mo>)</mo><mo>+</mo><mn>2</mn><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_Paren", "Silent", expr, "x plus, 2 times x plus 1,");
+}
+
+#[test]
+fn paren_speak_nesting_level() {
+    let expr = "<math><mo>(</mo><mn>2</mn><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo><mo>)</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_Paren", "SpeakNestingLevel", expr,
+        "open paren 2 times, open second paren x plus 1, close second paren; close paren,");
+}
+
+#[test]
+fn paren_coord_point() {
+    let expr = "<math><mo>(</mo><mn>1</mn><mo>,</mo><mn>2</mn><mo>)</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_Paren", "Auto", expr, "open paren 1 comma 2, close paren");
+    test_ClearSpeak("en", "ClearSpeak_Paren", "CoordPoint", expr, "the point with coordinates 1 comma 2,");
+}
+
+#[test]
+fn absolute_value_determinant() {
+    let expr = "<math><mo>|</mo><mi>x</mi><mo>|</mo></math>";
+    test_ClearSpeak("en", "ClearSpeak_AbsoluteValue", "Determinant", expr, "the determinant of x,");
+}
+
+#[test]
+fn marked_fences() {
+    // converters mark fences with 'form' and 'fence' -- the middle bar is not a fence
+    let expr = "<math><mo fence='true' form='prefix'>|</mo><mi>x</mi><mo>|</mo><mi>y</mi><mo fence='true' form='postfix'>|</mo></math>";
+    test("en", "ClearSpeak", expr, "the absolute value of x divides y,");
+}
+
+#[test]
+fn invisible_fences() {
+    let expr = "<math><mo fence='true' form='prefix'>&#x2064;</mo><mi>x</mi><mo>+</mo><mi>y</mi><mo fence='true' form='postfix'>&#x2064;</mo></math>";
+    test("en", "ClearSpeak", expr, "x plus y");
+}
@@ -130,6 +130,40 @@ fn ellipses_and_so_on_both() {
             "dot dot dot comma negative 2 comma negative 1 comma 0 comma 1 comma 2 comma dot dot dot");
 }
 
+#[test]
+fn ellipses_and_so_on_sum() {
+    let expr = "<math>
+            <msub><mi>x</mi><mn>1</mn></msub><mo>+</mo><msub><mi>x</mi><mn>2</mn></msub>
+            <mo>+</mo><mo>⋯</mo><mo>+</mo><msub><mi>x</mi><mi>n</mi></msub>
+       </math>";
+    test_ClearSpeak("en", "ClearSpeak_Ellipses", "Auto", expr,
+            "x sub 1 plus x sub 2 plus dot dot dot plus x sub n");
+    test_ClearSpeak("en", "ClearSpeak_Ellipses", "AndSoOn", expr,
+            "x sub 1 plus x sub 2 plus and so on plus x sub n");
+}
+
+#[test]
+fn ellipses_and_so_on_midline() {
+    let expr = "<math>
+            <msub><mi>x</mi><mn>1</mn></msub><mo>,</mo><msub><mi>x</mi><mn>2</mn></msub>
+            <mo>,</mo><mo>⋯</mo><mo>,</mo><msub><mi>x</mi><mi>n</mi></msub>
+       </math>";
+    test_ClearSpeak("en", "ClearSpeak_Ellipses", "AndSoOn", expr,
+            "x sub 1 comma x sub 2 and so on up to x sub n");
+}
+
+#[test]
+fn ellipses_matrix() {
+    let expr = "<math><mo>(</mo><mtable>
+            <mtr><mtd><mn>1</mn></mtd><mtd><mo>⋯</mo></mtd><mtd><mn>0</mn></mtd></mtr>
+            <mtr><mtd><mo>⋮</mo></mtd><mtd><mo>⋱</mo></mtd><mtd><mo>⋮</mo></mtd></mtr>
+            <mtr><mtd><mn>0</mn></mtd><mtd><mo>⋯</mo></mtd><mtd><mn>1</mn></mtd></mtr>
+        </mtable><mo>)</mo></math>";
+    test("en", "ClearSpeak", expr,
+            "the 3 by 3 matrix; row 1; 1, dot dot dot, 0; \
+             row 2; vertical ellipsis, diagonal ellipsis, vertical ellipsis; row 3; 0, dot dot dot, 1;");
+}
+
 #[test]
 fn vertical_line_auto() {
     let expr = "<math>
@@ -328,4 +362,67 @@ fn vertical_line_evaluated_at_both_given() {
         </math>";
     test_ClearSpeak("en", "ClearSpeak_VerticalLine", "Given", expr,
         "x squared plus x, evaluated at 1 minus the same expression evaluated at 0");
-}
\ No newline at end of file
+}
+#[test]
+fn prime_auto() {
+    let expr = "<math><msup><mn>5</mn><mo>′</mo></msup><msup><mn>1</mn><mo>″</mo></msup></math>";
+    test_ClearSpeak("en", "ClearSpeak_Prime", "Auto", expr, "5 prime, 1 double prime,");
+}
+
+#[test]
+fn prime_angle() {
+    let expr = "<math><msup><mn>5</mn><mo>′</mo></msup><msup><mn>1</mn><mo>″</mo></msup></math>";
+    test_ClearSpeak("en", "ClearSpeak_Prime", "Angle", expr, "5 minutes, 1 second,");
+}
+
+#[test]
+fn prime_length() {
+    let expr = "<math><msup><mn>5</mn><mo>′</mo></msup><msup><mn>1</mn><mo>″</mo></msup></math>";
+    test_ClearSpeak("en", "ClearSpeak_Prime", "Length", expr, "5 feet, 1 inch,");
+}
+
+#[test]
+fn prime_length_not_a_number() {
+    let expr = "<math><msup><mi>f</mi><mo>′</mo></msup></math>";
+    test_ClearSpeak("en", "ClearSpeak_Prime", "Length", expr, "f prime,");
+}
+
+#[test]
+fn bar_auto() {
+    let expr = "<math><mover><mi>x</mi><mo>¯</mo></mover></math>";
+    test_ClearSpeak("en", "ClearSpeak_Bar", "Auto", expr, "x bar,");
+    test_ClearSpeak("en", "ClearSpeak_Bar", "Bar", expr, "x bar,");
+}
+
+#[test]
+fn bar_conjugate() {
+    let expr = "<math><mover><mi>z</mi><mo>¯</mo></mover></math>";
+    test_ClearSpeak("en", "ClearSpeak_Bar", "Conjugate", expr, "the complex conjugate of z");
+}
+
+#[test]
+fn bar_mean() {
+    let expr = "<math><mover><mi>x</mi><mo>¯</mo></mover></math>";
+    test_ClearSpeak("en", "ClearSpeak_Bar", "Mean", expr, "the mean of x");
+}
+
+#[test]
+fn triangle_symbol() {
+    let expr = "<math><mo>△</mo><mi>A</mi><mi>B</mi><mi>C</mi></math>";
+    test_ClearSpeak("en", "ClearSpeak_TriangleSymbol", "Auto", expr, "triangle, cap eigh cap b cap c");
+    test_ClearSpeak("en", "ClearSpeak_TriangleSymbol", "Delta", expr, "delta, cap eigh cap b cap c");
+}
+
+#[test]
+fn capital_letters_say_caps() {
+    let expr = "<math><mi>A</mi><mo>+</mo><mi>b</mi></math>";
+    test_prefs("en", "ClearSpeak", vec![("Impairment", "LowVision"), ("ClearSpeak_CapitalLetters", "Auto")], expr, "eigh plus b");
+    test_prefs("en", "ClearSpeak", vec![("Impairment", "LowVision"), ("ClearSpeak_CapitalLetters", "SayCaps")], expr, "cap eigh plus b");
+}
+
+#[test]
+fn permutation_choose_permute() {
+    let expr = "<math><mmultiscripts><mi>P</mi><mi>r</mi><none/><mprescripts/><mi>n</mi><none/></mmultiscripts></math>";
+    test_ClearSpeak("en", "ClearSpeak_CombinationPermutation", "Auto", expr, "r permutations of n");
+    test_ClearSpeak("en", "ClearSpeak_CombinationPermutation", "ChoosePermute", expr, "n permute r");
+}
@@ -0,0 +1,21 @@
+/// Tests for large operators
+///   these are read with the shared rules; only the scripts in the operand use the MathSpeak words
+use crate::common::*;
+
+#[test]
+fn sum_both() {
+    let expr = "<math>
+                    <munderover> <mo>∑</mo> <mrow> <mi>i</mi> <mo>=</mo> <mn>1</mn> </mrow> <mi>n</mi> </munderover>
+                    <msub> <mi>a</mi> <mi>i</mi> </msub>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "the sum from i is equal to 1 to n of; eigh Subscript i");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "the sum from i is equal to 1 to n of; eigh Sub i");
+}
+
+#[test]
+fn integral_both() {
+    let expr = "<math>
+                    <msubsup> <mo>∫</mo> <mn>0</mn> <mn>1</mn> </msubsup> <mi>x</mi>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "the integral from 0 to 1 of x");
+}
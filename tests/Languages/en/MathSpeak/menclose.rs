@@ -0,0 +1,38 @@
+/// Tests for menclose
+///   the notation is named; strikes are read as a cross out
+use crate::common::*;
+
+#[test]
+fn menclose_box() {
+    let expr = "<math>
+                    <menclose notation='box'> <mi>x</mi> <mo>+</mo> <mn>1</mn> </menclose>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartEnclose box x plus 1 EndEnclose");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "StartEnclose box x plus 1 EndEnclose");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Enclose box x plus 1 EndEnclose");
+}
+
+#[test]
+fn menclose_multiple_notations() {
+    let expr = "<math>
+                    <menclose notation='circle  left'> <mn>3</mn> </menclose>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartEnclose circle left 3 EndEnclose");
+}
+
+#[test]
+fn menclose_default_notation() {
+    let expr = "<math>
+                    <menclose> <mn>12</mn> </menclose>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartEnclose longdiv 12 EndEnclose");
+}
+
+#[test]
+fn menclose_cross_out() {
+    let expr = "<math>
+                    <menclose notation='updiagonalstrike downdiagonalstrike'> <mi>x</mi> </menclose>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "CrossOut x EndCrossOut");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "CrossOut x EndCrossOut");
+}
@@ -0,0 +1,77 @@
+/// Tests for fractions
+///   includes nested fractions and each of the MathSpeak verbosities
+use crate::common::*;
+
+#[test]
+fn simple_fraction() {
+    let expr = "<math>
+                    <mfrac> <mi>a</mi> <mi>b</mi> </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartFraction eigh Over b EndFraction");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "StartFrac eigh Over b EndFrac");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Frac eigh Over b EndFrac");
+}
+
+#[test]
+fn numeric_fraction() {
+    // MathSpeak doesn't read fractions as ordinals
+    let expr = "<math>
+                    <mfrac> <mn>1</mn> <mn>2</mn> </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartFraction 1 Over 2 EndFraction");
+}
+
+#[test]
+fn nested_fraction() {
+    let expr = "<math>
+                    <mfrac>
+                        <mfrac> <mn>1</mn> <mi>x</mi> </mfrac>
+                        <mi>y</mi>
+                    </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr,
+               "StartStartFraction StartFraction 1 Over x EndFraction OverOver y EndEndFraction");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr,
+               "StartStartFrac StartFrac 1 Over x EndFrac OverOver y EndEndFrac");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr,
+               "NestFrac Frac 1 Over x EndFrac NestOver y NestEndFrac");
+}
+
+#[test]
+fn doubly_nested_fraction() {
+    let expr = "<math>
+                    <mfrac>
+                        <mn>1</mn>
+                        <mfrac> <mn>1</mn> <mfrac> <mn>1</mn> <mi>x</mi> </mfrac> </mfrac>
+                    </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr,
+               "StartStartStartFraction 1 OverOverOver StartStartFraction 1 OverOver StartFraction 1 Over x EndFraction EndEndFraction EndEndEndFraction");
+}
+
+#[test]
+fn triply_nested_fraction() {
+    // the words are repeated once for each level of nesting, however deep it is
+    let expr = "<math>
+                    <mfrac>
+                        <mfrac> <mn>1</mn> <mfrac> <mn>1</mn> <mfrac> <mn>1</mn> <mi>x</mi> </mfrac> </mfrac> </mfrac>
+                        <mi>y</mi>
+                    </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr,
+               "StartStartStartStartFrac StartStartStartFrac 1 OverOverOver StartStartFrac 1 OverOver StartFrac 1 Over x EndFrac EndEndFrac EndEndEndFrac OverOverOverOver y EndEndEndEndFrac");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr,
+               "NestNestNestFrac NestNestFrac 1 NestNestOver NestFrac 1 NestOver Frac 1 Over x EndFrac NestEndFrac NestNestEndFrac NestNestNestOver y NestNestNestEndFrac");
+}
+
+#[test]
+fn verbosity_auto() {
+    // 'Auto' maps Verbosity onto the MathSpeak verbosities
+    let expr = "<math>
+                    <mfrac> <mi>a</mi> <mi>b</mi> </mfrac>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Auto"), ("Verbosity", "Verbose")], expr, "StartFraction eigh Over b EndFraction");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Auto"), ("Verbosity", "Medium")], expr, "StartFrac eigh Over b EndFrac");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Auto"), ("Verbosity", "Terse")], expr, "Frac eigh Over b EndFrac");
+    test_prefs("en", "MathSpeak", vec![("Verbosity", "Medium")], expr, "StartFrac eigh Over b EndFrac");
+}
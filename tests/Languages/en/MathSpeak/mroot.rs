@@ -0,0 +1,32 @@
+/// Tests for roots
+///   includes nested roots and roots with an index
+use crate::common::*;
+
+#[test]
+fn square_root() {
+    let expr = "<math>
+                    <msqrt> <mi>x</mi> </msqrt>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartRoot x EndRoot");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Root x EndRoot");
+}
+
+#[test]
+fn cube_root() {
+    let expr = "<math>
+                    <mroot> <mi>x</mi> <mn>3</mn> </mroot>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "RootIndex 3 StartRoot x EndRoot");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "RootIndex 3 StartRoot x EndRoot");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Index 3 Root x EndRoot");
+}
+
+#[test]
+fn nested_root() {
+    let expr = "<math>
+                    <msqrt> <mi>x</mi> <mo>+</mo> <msqrt> <mi>y</mi> </msqrt> </msqrt>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartRoot x plus StartNestedRoot y EndNestedRoot EndRoot");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "StartRoot x plus StartNestRoot y EndNestRoot EndRoot");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Root x plus NestRoot y EndNestRoot EndRoot");
+}
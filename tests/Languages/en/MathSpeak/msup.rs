@@ -0,0 +1,109 @@
+/// Tests for superscripts and subscripts
+///   includes nested scripts and the return to the baseline
+use crate::common::*;
+
+#[test]
+fn squared() {
+    let expr = "<math>
+                    <msup> <mi>x</mi> <mn>2</mn> </msup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x squared");
+}
+
+#[test]
+fn superscript_at_end() {
+    let expr = "<math>
+                    <msup> <mi>x</mi> <mi>n</mi> </msup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Superscript n");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "x Sup n");
+}
+
+#[test]
+fn superscript_baseline() {
+    let expr = "<math>
+                    <msup> <mi>x</mi> <mi>n</mi> </msup> <mo>+</mo> <mn>1</mn>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Superscript n Baseline; plus 1");
+    // the pause after returning to the baseline doesn't depend upon the verbosity
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "x Sup n Base; plus 1");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "x Sup n Base; plus 1");
+}
+
+#[test]
+fn nested_superscript() {
+    let expr = "<math>
+                    <msup> <mi>e</mi> <msup> <mi>x</mi> <mi>n</mi> </msup> </msup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "e Superscript x SuperSuperscript n");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "e Sup x SupSup n");
+}
+
+#[test]
+fn triply_nested_superscript() {
+    let expr = "<math>
+                    <msup> <mi>x</mi> <msup> <mi>y</mi> <msup> <mi>z</mi> <mi>w</mi> </msup> </msup> </msup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Superscript y SuperSuperscript z SuperSuperSuperscript w");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "x Sup y SupSup z SupSupSup w");
+}
+
+#[test]
+fn nested_superscript_return() {
+    let expr = "<math>
+                    <msup> <mi>x</mi> <mrow> <msup> <mi>y</mi> <mi>z</mi> </msup> <mo>+</mo> <mn>1</mn> </mrow> </msup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Superscript y SuperSuperscript z Superscript; plus 1");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "x Sup y SupSup z Sup; plus 1");
+}
+
+#[test]
+fn numeric_subscript() {
+    let expr = "<math>
+                    <msub> <mi>x</mi> <mn>1</mn> </msub>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x 1");
+}
+
+#[test]
+fn subscript() {
+    let expr = "<math>
+                    <msub> <mi>x</mi> <mi>i</mi> </msub> <mo>+</mo> <mn>1</mn>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Subscript i Baseline; plus 1");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "x Sub i Base; plus 1");
+}
+
+#[test]
+fn subscript_superscript() {
+    let expr = "<math>
+                    <msubsup> <mi>x</mi> <mi>i</mi> <mi>n</mi> </msubsup>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "x Subscript i Superscript n");
+}
+
+#[test]
+fn prescripts() {
+    let expr = "<math>
+                    <mmultiscripts> <mi>C</mi> <mprescripts/> <mn>6</mn> <mn>14</mn> </mmultiscripts>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "Presubscript 6 Presuperscript 14 Baseline cap c");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "PreSub 6 PreSup 14 Base cap c");
+}
+
+#[test]
+fn postscripts() {
+    let expr = "<math>
+                    <mmultiscripts> <mi>R</mi> <mi>i</mi> <none/> <none/> <mi>k</mi> </mmultiscripts> <mo>+</mo> <mn>1</mn>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "cap r Subscript i Superscript k Baseline; plus 1");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "cap r Sub i Sup k Base; plus 1");
+}
+
+#[test]
+fn pre_and_postscripts() {
+    let expr = "<math>
+                    <mmultiscripts> <mi>T</mi> <mi>j</mi> <none/> <mprescripts/> <none/> <mi>k</mi> </mmultiscripts>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "Presuperscript k Baseline cap t Subscript j");
+}
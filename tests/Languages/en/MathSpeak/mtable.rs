@@ -0,0 +1,104 @@
+/// Tests for matrices, determinants, binomials, and other tables
+///   "Row n"/"Column n" are left out when there is only one row/column
+use crate::common::*;
+
+#[test]
+fn matrix_2x2() {
+    let expr = "<math>
+                    <mrow><mo>[</mo>
+                        <mtable>
+                            <mtr> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mtr>
+                            <mtr> <mtd><mi>c</mi></mtd> <mtd><mi>d</mi></mtd> </mtr>
+                        </mtable>
+                    <mo>]</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr,
+               "Start 2 By 2 Matrix; Row 1 Column 1 eigh, Column 2 b; Row 2 Column 1 c, Column 2 d; EndMatrix");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr,
+               "2 By 2 Matrix; Row 1 Column 1 eigh, Column 2 b; Row 2 Column 1 c, Column 2 d; EndMatrix");
+}
+
+#[test]
+fn matrix_no_dimensions() {
+    let expr = "<math>
+                    <mrow><mo>[</mo>
+                        <mtable>
+                            <mtr> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mtr>
+                            <mtr> <mtd><mi>c</mi></mtd> <mtd><mi>d</mi></mtd> </mtr>
+                        </mtable>
+                    <mo>]</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose"), ("MatrixDimensions", "false")], expr,
+               "StartMatrix; Row 1 Column 1 eigh, Column 2 b; Row 2 Column 1 c, Column 2 d; EndMatrix");
+}
+
+#[test]
+fn determinant() {
+    let expr = "<math>
+                    <mrow><mo>|</mo>
+                        <mtable>
+                            <mtr> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mtr>
+                            <mtr> <mtd><mi>c</mi></mtd> <mtd><mi>d</mi></mtd> </mtr>
+                        </mtable>
+                    <mo>|</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr,
+               "Start 2 By 2 Determinant; Row 1 Column 1 eigh, Column 2 b; Row 2 Column 1 c, Column 2 d; EndDeterminant");
+}
+
+#[test]
+fn column_matrix() {
+    let expr = "<math>
+                    <mrow><mo>(</mo>
+                        <mtable>
+                            <mtr> <mtd><mi>a</mi></mtd> </mtr>
+                            <mtr> <mtd><mi>c</mi></mtd> </mtr>
+                        </mtable>
+                    <mo>)</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "Start 2 By 1 Matrix; Row 1 eigh; Row 2 c; EndMatrix");
+}
+
+#[test]
+fn row_matrix() {
+    let expr = "<math>
+                    <mrow><mo>[</mo>
+                        <mtable>
+                            <mtr> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mtr>
+                        </mtable>
+                    <mo>]</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "Start 1 By 2 Matrix; Column 1 eigh, Column 2 b; EndMatrix");
+}
+
+#[test]
+fn layout() {
+    let expr = "<math>
+                    <mtable>
+                        <mtr> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mtr>
+                    </mtable>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartLayout; Column 1 eigh, Column 2 b; EndLayout");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Layout; Column 1 eigh, Column 2 b; EndLayout");
+}
+
+#[test]
+fn labeled_layout() {
+    let expr = "<math>
+                    <mtable>
+                        <mlabeledtr> <mtd><mtext>(1)</mtext></mtd> <mtd><mi>a</mi></mtd> <mtd><mi>b</mi></mtd> </mlabeledtr>
+                        <mtr> <mtd><mi>c</mi></mtd> <mtd><mi>d</mi></mtd> </mtr>
+                    </mtable>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr,
+               "StartLayout; Row 1 Label left paren 1 right paren Column 1 eigh, Column 2 b; Row 2 Column 1 c, Column 2 d; EndLayout");
+}
+
+#[test]
+fn binomial() {
+    let expr = "<math>
+                    <mrow><mo>(</mo> <mfrac linethickness='0'> <mi>n</mi> <mi>k</mi> </mfrac> <mo>)</mo></mrow>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "StartBinomial n Choose k EndBinomial");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "Binomial n Choose k EndBinomial");
+}
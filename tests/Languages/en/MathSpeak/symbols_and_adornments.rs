@@ -0,0 +1,46 @@
+/// Tests for accents and other under/overscripts
+///   a single character above/below modifies the base; other scripts are read as "Underscript"/"Overscript"
+use crate::common::*;
+
+#[test]
+fn modifying_above() {
+    let expr = "<math>
+                    <mover> <mi>x</mi> <mo>¯</mo> </mover>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "ModifyingAbove x With bar");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "ModAbove x With bar");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "SuperBrief")], expr, "ModAbove x With bar");
+}
+
+#[test]
+fn modifying_above_expr() {
+    let expr = "<math>
+                    <mover> <mrow> <mi>x</mi> <mo>+</mo> <mi>y</mi> </mrow> <mo>⏞</mo> </mover>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "ModifyingAbove x plus y With top brace");
+}
+
+#[test]
+fn modifying_below() {
+    let expr = "<math>
+                    <munder> <mi>y</mi> <mo>_</mo> </munder>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "ModifyingBelow y With line");
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Brief")], expr, "ModBelow y With line");
+}
+
+#[test]
+fn underscript() {
+    let expr = "<math>
+                    <munder> <mi>X</mi> <mrow> <mi>a</mi> <mo>=</mo> <mn>1</mn> </mrow> </munder>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "cap x Underscript eigh is equal to 1 Endscripts");
+}
+
+#[test]
+fn underscript_overscript() {
+    let expr = "<math>
+                    <munderover> <mi>X</mi> <mi>a</mi> <mi>b</mi> </munderover>
+                </math>";
+    test_prefs("en", "MathSpeak", vec![("MathSpeak", "Verbose")], expr, "cap x Underscript eigh Overscript b Endscripts");
+}
@@ -43,6 +43,24 @@ fn inverse_trig() {
     test("en", "SimpleSpeak", expr, "inverse sine of x");
 }
 
+#[test]
+fn inverse_trig_arc() {
+    let expr = "<math><msup><mi>sin</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mi>x</mi></math>";
+    test_prefs("en", "SimpleSpeak", vec![("TrigInverse", "ArcTrig")], expr, "arc sine of x");
+}
+
+#[test]
+fn inverse_function() {
+    let expr = "<math><msup><mi>f</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mo>(</mo><mi>x</mi><mo>)</mo></math>";
+    test("en", "SimpleSpeak", expr, "f inverse of x");
+}
+
+#[test]
+fn composition() {
+    let expr = "<math><mo>(</mo><mi>f</mi><mo>∘</mo><mi>g</mi><mo>)</mo><mo>(</mo><mi>x</mi><mo>)</mo></math>";
+    test("en", "SimpleSpeak", expr, "f composed with g of x");
+}
+
 #[test]
 fn trig_squared() {
     let expr = "<math><msup><mi>sin</mi><mn>2</mn></msup><mi>x</mi></math>";
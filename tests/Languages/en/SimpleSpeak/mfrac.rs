@@ -214,3 +214,40 @@ fn binomial() {
                 </math>";
     test("en", "SimpleSpeak", expr, "2 times 7 choose 3");
 }
+
+#[test]
+fn varied_speech() {
+    let expr = "<math>
+                    <mfrac> <mi>a</mi> <mi>b</mi> </mfrac> <mo>=</mo>
+                    <mfrac> <mi>c</mi> <mi>d</mi> </mfrac> <mo>=</mo>
+                    <mfrac> <mi>e</mi> <mi>f</mi> </mfrac>
+                </math>";
+    test_prefs("en", "SimpleSpeak", vec![("VariedSpeech", "false")], expr,
+        "eigh over b, is equal to c over d, is equal to e over f,");
+    test_prefs("en", "SimpleSpeak", vec![("VariedSpeech", "true")], expr,
+        "eigh over b, is equal to c divided by d, equals e over f,");
+}
+
+#[test]
+fn fraction_style() {
+    let expr = "<math>
+                    <mfrac> <mtext>miles</mtext> <mtext>hour</mtext> </mfrac> <mo>,</mo>
+                    <mfrac> <mn>12</mn> <mn>37</mn> </mfrac> <mo>,</mo>
+                    <mfrac> <mi>a</mi> <mi>b</mi> </mfrac>
+                </math>";
+    test_prefs("en", "SimpleSpeak", vec![("FractionStyle", "Auto")], expr,
+        "miles over hour, comma 12 over 37, comma eigh over b,");
+    test_prefs("en", "SimpleSpeak", vec![("FractionStyle", "ByKind")], expr,
+        "miles per hour, comma 12 divided by 37, comma eigh over b,");
+    test_prefs("en", "SimpleSpeak", vec![("FractionStyle", "Over")], expr,
+        "miles over hour, comma 12 over 37, comma eigh over b,");
+}
+
+#[test]
+fn ordinal_denominator_max() {
+    let expr = "<math>
+                    <mfrac> <mn>5</mn> <mn>21</mn> </mfrac>
+                </math>";
+    test_prefs("en", "SimpleSpeak", vec![("OrdinalDenominatorMax", "10")], expr, "5 over 21,");
+    test_prefs("en", "SimpleSpeak", vec![("OrdinalDenominatorMax", "100")], expr, "5 twenty firsts");
+}
@@ -0,0 +1,38 @@
+/// Tests for integers that are too big for speech engines (and for u64s) to read
+use crate::common::*;
+
+#[test]
+fn big_integer() {
+    let expr = "<math><mn>1000000000000000000</mn></math>";
+    test("en", "ClearSpeak", expr, "one quintillion");
+    test("en", "SimpleSpeak", expr, "one quintillion");
+}
+
+#[test]
+fn big_integer_with_separators() {
+    let expr = "<math><mn>123,456,789,012,345,678,901</mn></math>";
+    test("en", "ClearSpeak", expr,
+        "one hundred twenty three quintillion four hundred fifty six quadrillion seven hundred eighty nine trillion \
+         twelve billion three hundred forty five million six hundred seventy eight thousand nine hundred one");
+}
+
+#[test]
+fn small_integer_unchanged() {
+    let expr = "<math><mn>123456789012345</mn></math>";
+    test("en", "ClearSpeak", expr, "123456789012345");
+}
+
+#[test]
+fn huge_integer_grouped() {
+    let expr = "<math><mn>1234567890123456789012345678901234567890</mn><mo>!</mo></math>";
+    test("en", "ClearSpeak", expr,
+        "1, 234, 567, 890, 123, 456, 789, 012, 345, 678, 901, 234, 567, 890; factorial");
+}
+
+#[test]
+fn big_ordinal_fraction() {
+    let expr = "<math><mfrac><mn>1</mn><mn>123456789012345678901</mn></mfrac></math>";
+    test_ClearSpeak("en", "ClearSpeak_Fractions", "Ordinal", expr,
+        "1 one hundred twenty three quintillion four hundred fifty six quadrillion seven hundred eighty nine trillion \
+         twelve billion three hundred forty five million six hundred seventy eight thousand nine hundred first");
+}
@@ -0,0 +1,35 @@
+/// Tests for the DecimalDigits and DecimalDigitsMax preferences -- how the digits after a decimal point are read
+use crate::common::*;
+
+#[test]
+fn decimal_digits_auto() {
+    let expr = "<math><mn>3.14159</mn></math>";
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Auto"), ("DecimalDigitsMax", "0")], expr, "3.14159");
+}
+
+#[test]
+fn decimal_digits_one_at_a_time() {
+    let expr = "<math><mn>3.14159</mn></math>";
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "0")], expr, "3 point 1 4 1 5 9");
+    test_prefs("en", "SimpleSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "0")], expr, "3 point 1 4 1 5 9");
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Grouped"), ("DecimalDigitsMax", "0")], expr, "3 point 1 4 1, 5 9");
+}
+
+#[test]
+fn decimal_digits_max() {
+    let expr = "<math><mn>3.14159265358979323846</mn></math>";
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "6")], expr,
+        "3 point 1 4 1 5 9 2 and 14 more digits");
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Grouped"), ("DecimalDigitsMax", "6")], expr,
+        "3 point 1 4 1, 5 9 2 and 14 more digits");
+    // no summary if all the digits are read
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "6")], "<math><mn>0.125</mn></math>",
+        "0 point 1 2 5");
+}
+
+#[test]
+fn decimal_digits_not_decimal() {
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "0")], "<math><mn>1,234</mn></math>", "1234");
+    test_prefs("en", "ClearSpeak", vec![("DecimalDigits", "Digits"), ("DecimalDigitsMax", "0")],
+        "<math><mn>2.5</mn><mo>+</mo><mi>x</mi></math>", "2 point 5 plus x");
+}
@@ -0,0 +1,28 @@
+/// Tests for ExamMode -- no inferences that might give away an answer
+use crate::common::*;
+
+#[test]
+fn interval_not_inferred() {
+    let expr = "<math><mi>x</mi><mo>∈</mo><mrow><mo>(</mo><mrow><mn>0</mn><mo>,</mo><mn>1</mn></mrow><mo>]</mo></mrow></math>";
+    test_prefs("en", "ClearSpeak", vec![("ExamMode", "false")], expr,
+        "x is a member of, the interval from 0 to 1, not including 0 but including 1");
+    test_prefs("en", "ClearSpeak", vec![("ExamMode", "true")], expr,
+        "x is a member of, open paren 0 comma 1, close bracket");
+}
+
+#[test]
+fn inverse_trig_read_as_notation() {
+    let expr = "<math><msup><mi>sin</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup><mi>x</mi></math>";
+    test_prefs("en", "ClearSpeak", vec![("ExamMode", "false")], expr, "inverse sine of x");
+    test_prefs("en", "ClearSpeak", vec![("ExamMode", "true")], expr, "sine inverse of x");
+    test_prefs("en", "ClearSpeak", vec![("ExamMode", "true"), ("ClearSpeak_Trig", "ArcTrig")], expr, "arc sine of x");
+    test_prefs("en", "SimpleSpeak", vec![("ExamMode", "true")], expr, "sine inverse of x");
+    test_prefs("en", "SimpleSpeak", vec![("ExamMode", "true"), ("TrigInverse", "ArcTrig")], expr, "arc sine of x");
+}
+
+#[test]
+fn constants_not_named() {
+    let expr = "<math><mi>E</mi><mo>=</mo><mi>ℎ</mi><mi>ν</mi></math>";
+    test_prefs("en", "SimpleSpeak", vec![("ExamMode", "false")], expr, "cap e is equal to, planck constant nu");
+    test_prefs("en", "SimpleSpeak", vec![("ExamMode", "true")], expr, "cap e is equal to h nu");
+}
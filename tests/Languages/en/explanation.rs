@@ -0,0 +1,39 @@
+/// Tests for the (experimental) Explanation style -- notations are explained the first time they are spoken in a session
+use crate::common::*;
+use libmathcat::interface::reset_explanation_session;
+
+#[test]
+fn explained_once() {
+    reset_explanation_session();
+    let expr = "<math><mfrac><mi>x</mi><mi>y</mi></mfrac><mo>+</mo><mfrac><mi>a</mi><mi>b</mi></mfrac></math>";
+    test("en", "Explanation", expr,
+        "this is a fraction; a fraction means division: the numerator is divided by the denominator; x over y; plus eigh over b,");
+    // same session -- no more explanations
+    test("en", "Explanation", expr, "x over y, plus eigh over b,");
+    reset_explanation_session();
+    test("en", "Explanation", expr,
+        "this is a fraction; a fraction means division: the numerator is divided by the denominator; x over y; plus eigh over b,");
+}
+
+#[test]
+fn explain_each_notation() {
+    reset_explanation_session();
+    let expr = "<math><msqrt><mi>x</mi></msqrt><mo>+</mo><msup><mi>x</mi><mn>2</mn></msup></math>";
+    test("en", "Explanation", expr,
+        "this is a square root; the square root of a number is the value that, multiplied by itself, gives the number; \
+         the square root of x; plus; \
+         this is a power; the exponent says how many times the base is multiplied by itself; x squared");
+}
+
+#[test]
+fn same_as_simple_speak_after_explanation() {
+    reset_explanation_session();
+    let expr = "<math><mo>|</mo><mi>x</mi><mo>|</mo><mo>+</mo><msqrt><mi>y</mi></msqrt></math>";
+    test("en", "Explanation", "<math><mo>|</mo><mi>z</mi><mo>|</mo><mo>+</mo><msqrt><mi>z</mi></msqrt></math>",
+        "this is an absolute value; the absolute value of a number is its distance from zero, so it is never negative; \
+         the absolute value of z; plus; \
+         this is a square root; the square root of a number is the value that, multiplied by itself, gives the number; \
+         the square root of z,");
+    test("en", "SimpleSpeak", expr, "the absolute value of x; plus the square root of y,");
+    test("en", "Explanation", expr, "the absolute value of x; plus the square root of y,");
+}
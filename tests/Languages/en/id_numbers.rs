@@ -0,0 +1,22 @@
+/// Tests for the IdNumbers preference -- numbers in text that look like identifiers are read digit by digit
+use crate::common::*;
+
+#[test]
+fn phone_number() {
+    let expr = "<math><mtext>call 555-123-4567</mtext></math>";
+    test_prefs("en", "ClearSpeak", vec![("IdNumbers", "Auto")], expr, "call 5 5 5, 1 2 3, 4 5 6 7");
+    test_prefs("en", "ClearSpeak", vec![("IdNumbers", "Never")], expr, "call 555-123-4567");
+}
+
+#[test]
+fn isbn() {
+    let expr = "<math><mtext>ISBN 9780306406157</mtext></math>";
+    test_prefs("en", "SimpleSpeak", vec![("IdNumbers", "Auto")], expr, "ISBN 9 7 8 0 3 0 6 4 0 6 1 5 7");
+}
+
+#[test]
+fn quantity_in_text() {
+    let expr = "<math><mn>2</mn><mtext>for 1998 dollars</mtext></math>";
+    test_prefs("en", "ClearSpeak", vec![("IdNumbers", "Auto")], expr, "2 for 1998 dollars");
+    test_prefs("en", "ClearSpeak", vec![("IdNumbers", "Always")], expr, "2 for 1 9 9 8 dollars");
+}
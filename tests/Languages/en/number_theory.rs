@@ -0,0 +1,46 @@
+/// Tests for number theory notation (congruences)
+use crate::common::*;
+
+#[test]
+fn congruence_mod_parens() {
+    let expr = "<math><mi>x</mi><mo>≡</mo><mi>y</mi><mo>(</mo><mi>mod</mi><mi>n</mi><mo>)</mo></math>";
+    test("en", "ClearSpeak", expr, "x is congruent to y, modulo n");
+    test("en", "SimpleSpeak", expr, "x is congruent to y, modulo n");
+}
+
+#[test]
+fn congruence_mod_no_parens() {
+    let expr = "<math><mi>x</mi><mo>≡</mo><mi>y</mi><mo lspace='1em'>mod</mo><mi>n</mi></math>";
+    test("en", "ClearSpeak", expr, "x is congruent to y, modulo n");
+}
+
+#[test]
+fn congruence_mod_expr() {
+    let expr = "<math><mn>2</mn><mi>x</mi><mo>+</mo><mn>1</mn><mo>≡</mo><mn>3</mn>
+                    <mo stretchy='false'>(</mo><mo>mod</mo><mn>4</mn><mo stretchy='false'>)</mo></math>";
+    test("en", "SimpleSpeak", expr, "2 x plus 1 is congruent to 3, modulo 4");
+}
+
+#[test]
+fn congruence_mod_terse() {
+    let expr = "<math><mn>17</mn><mo>≡</mo><mn>2</mn><mo>(</mo><mi>mod</mi><mn>5</mn><mo>)</mo></math>";
+    test_prefs("en", "SimpleSpeak", vec![("Verbosity", "Terse")], expr, "17 congruent to 2, modulo 5");
+}
+
+#[test]
+fn identical_to() {
+    let expr = "<math><mi>x</mi><mo>≡</mo><mi>y</mi></math>";
+    test("en", "ClearSpeak", expr, "x is identical to y");
+}
+
+#[test]
+fn congruent_to_number_theory() {
+    let expr = "<math><mi>x</mi><mo>≡</mo><mi>y</mi></math>";
+    test_prefs("en", "ClearSpeak", vec![("SubjectArea", "NumberTheory")], expr, "x is congruent to y");
+}
+
+#[test]
+fn congruence_mod_number_theory() {
+    let expr = "<math><mi>x</mi><mo>≡</mo><mi>y</mi><mo>(</mo><mi>mod</mi><mi>n</mi><mo>)</mo></math>";
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "NumberTheory")], expr, "x is congruent to y, modulo n");
+}
@@ -0,0 +1,31 @@
+/// Tests for the ScriptOrder preferences -- the order a subscript/superscript pair is spoken
+use crate::common::*;
+
+#[test]
+fn msubsup_order() {
+    let expr = "<math><msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup></math>";
+    test_prefs("en", "ClearSpeak", vec![("ScriptOrder_Postscripts", "SubSuper")], expr, "x sub i squared");
+    test_prefs("en", "ClearSpeak", vec![("ScriptOrder_Postscripts", "SuperSub")], expr, "x squared sub i");
+    test_prefs("en", "SimpleSpeak", vec![("ScriptOrder_Postscripts", "SuperSub")], expr, "x squared sub i");
+}
+
+#[test]
+fn mmultiscripts_order() {
+    let expr = "<math><mmultiscripts><mi>x</mi><mi>i</mi><mi>n</mi><mprescripts/><mi>a</mi><mi>b</mi></mmultiscripts></math>";
+    test_prefs("en", "ClearSpeak", vec![("ScriptOrder_Postscripts", "SubSuper"), ("ScriptOrder_Prescripts", "SubSuper")], expr,
+        "x pre-sub eigh and pre-super b, sub i and super n");
+    test_prefs("en", "ClearSpeak", vec![("ScriptOrder_Postscripts", "SuperSub"), ("ScriptOrder_Prescripts", "SubSuper")], expr,
+        "x pre-sub eigh and pre-super b, super n and sub i");
+    test_prefs("en", "SimpleSpeak", vec![("ScriptOrder_Postscripts", "SubSuper"), ("ScriptOrder_Prescripts", "SuperSub")], expr,
+        "x pre-super b and pre-sub eigh, sub i and super n");
+}
+
+#[test]
+fn nuclide_order() {
+    let expr = "<math><mmultiscripts><mi>C</mi><mprescripts/><mn>6</mn><mn>14</mn></mmultiscripts><mo>→</mo>
+                      <mmultiscripts><mi>N</mi><mprescripts/><mn>7</mn><mn>14</mn></mmultiscripts></math>";
+    test_prefs("en", "SimpleSpeak", vec![("ScriptOrder_Chemistry", "SuperSub")], expr,
+        "super 14, sub 6, cap c; decays to, super 14, sub 7, cap n,");
+    test_prefs("en", "SimpleSpeak", vec![("ScriptOrder_Chemistry", "SubSuper")], expr,
+        "sub 6, super 14, cap c; decays to, sub 7, super 14, cap n,");
+}
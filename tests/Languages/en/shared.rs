@@ -1,5 +1,7 @@
 /// Tests for rules shared between various speech styles:
 /// *  modified var
+/// *  long division
+/// *  answer blanks in interactive widgets
 use crate::common::*;
 
 #[test]
@@ -23,6 +25,14 @@ fn modified_vars() {
             x dot, y dot, z double dot, u triple dot, v quadruple dot; plus x hat, plus vector t");
 }
 
+#[test]
+fn combining_accent_vars() {
+    let expr = "<math> <mrow>
+        <mi>x&#x0302;</mi> <mo>+</mo> <mi>t&#x20D7;</mi> <mo>+</mo> <mi>a&#x0300;</mi>
+        </mrow> </math>";
+    test("en", "SimpleSpeak", expr, "x hat, plus vector t plus eigh grave,");
+}
+
 #[test]
 fn limit() {
     let expr = "<math>
@@ -286,3 +296,81 @@ fn mn_with_space() {
     let expr = "<math><mn>1 234 567</mn></math>";
     test("en", "SimpleSpeak", expr, "1234567");
 }
+
+#[test]
+fn long_division_mlongdiv() {
+    let expr = "<math><mlongdiv>
+            <mn>5</mn><mn>25</mn><mn>125</mn>
+            <msrow><mo>-</mo><mn>10</mn></msrow>
+            <msline/>
+            <msrow><mn>25</mn></msrow>
+            <msrow><mo>-</mo><mn>25</mn></msrow>
+            <msline/>
+            <msrow><mn>0</mn></msrow>
+        </mlongdiv></math>";
+    test("en", "ClearSpeak", expr, "125 divided by 5; quotient 25; with steps 5 goes into 12, 2 times; 2 times 5 is 10; subtract to get 2; bring down 5 to make 25; \
+                5 goes into 25, 5 times; 5 times 5 is 25; subtract to get 0");
+}
+
+#[test]
+fn long_division_mlongdiv_unexplained_step() {
+    // 5 isn't a multiple of the divisor, so the step is just read
+    let expr = "<math><mlongdiv>
+            <mn>7</mn><mn>1</mn><mn>10</mn>
+            <msrow><mo>-</mo><mn>5</mn></msrow>
+            <msline/>
+            <mn>5</mn>
+        </mlongdiv></math>";
+    test("en", "SimpleSpeak", expr, "10 divided by 7; quotient 1; with steps subtract 5 to get 5");
+}
+
+#[test]
+fn elementary_math_addition_with_carries() {
+    let expr = "<math><mstack>
+            <mscarries><mn>1</mn><none/></mscarries>
+            <mn>48</mn>
+            <msrow><mo>+</mo><mn>34</mn></msrow>
+            <msline/>
+            <mn>82</mn>
+        </mstack></math>";
+    test("en", "SimpleSpeak", expr, "carry 1 to the tens column; 48 plus 34; equals 82");
+}
+
+#[test]
+fn elementary_math_multiplication() {
+    let expr = "<math><mstack>
+            <mn>123</mn>
+            <msrow><mo>×</mo><mn>45</mn></msrow>
+            <msline/>
+            <mn>615</mn>
+            <msrow><mn>492</mn><none/></msrow>
+            <msline/>
+            <mn>5535</mn>
+        </mstack></math>";
+    test("en", "ClearSpeak", expr, "123 times 45; partial products 615, 492; equals 5535");
+}
+
+#[test]
+fn long_division_menclose() {
+    let expr = "<math><mn>5</mn><menclose notation='longdiv'><mn>125</mn></menclose></math>";
+    test("en", "SimpleSpeak", expr, "125 divided by 5");
+}
+
+#[test]
+fn long_division_menclose_remainder() {
+    let expr = "<math>
+            <mn>5</mn>
+            <mover><menclose notation='longdiv'><mn>127</mn></menclose><mrow><mn>25</mn><mi>R</mi><mn>2</mn></mrow></mover>
+        </math>";
+    test("en", "ClearSpeak", expr, "127 divided by 5; quotient 25, remainder 2");
+}
+
+#[test]
+fn answer_blanks() {
+    let expr = "<math>
+            <mn>2</mn><mo>+</mo><mi data-role='answer'></mi><mo>=</mo>
+            <mfrac><mrow role='textbox'><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mtext aria-role='blank'>_</mtext></mfrac>
+        </math>";
+    test("en", "SimpleSpeak", expr,
+        "2 plus answer blank 1, is equal to; fraction, answer blank 2 containing x plus 1; over, answer blank 3, end fraction;");
+}
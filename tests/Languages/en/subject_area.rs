@@ -0,0 +1,70 @@
+/// Tests for notation whose reading depends on 'SubjectArea'
+use crate::common::*;
+
+#[test]
+fn vertical_bars() {
+    let expr = "<math><mo>|</mo><mi>A</mi><mo>|</mo></math>";
+    test("en", "SimpleSpeak", expr, "the absolute value of cap eigh,");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "SetTheory")], expr, "the cardinality of cap eigh,");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Statistics")], expr, "the absolute value of cap eigh,");
+}
+
+#[test]
+fn vertical_bars_statistics_clearspeak() {
+    let expr = "<math><mo>|</mo><mi>x</mi><mo>|</mo></math>";
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_AbsoluteValue", "Cardinality")], expr, "the cardinality of x,");
+    test_prefs("en", "ClearSpeak", vec![("ClearSpeak_AbsoluteValue", "Cardinality"), ("SubjectArea", "Statistics")], expr,
+                "the absolute value of x,");
+}
+
+#[test]
+fn segment_length() {
+    let expr = "<math><mo>|</mo><mi>A</mi><mi>B</mi><mo>|</mo></math>";
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Geometry")], expr, "the length of cap eigh cap b,");
+}
+
+#[test]
+fn tilde_operator() {
+    let expr = "<math><mi>X</mi><mo>∼</mo><mi>Y</mi></math>";
+    test("en", "SimpleSpeak", expr, "cap x varies with cap y");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Geometry")], expr, "cap x is similar to cap y");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Statistics")], expr, "cap x is distributed as cap y");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Calculus")], expr, "cap x is asymptotic to cap y");
+}
+
+#[test]
+fn calculus_interval_and_arrow() {
+    let expr = "<math><mi>x</mi><mo>∈</mo><mo>(</mo><mn>0</mn><mo>,</mo><mn>1</mn><mo>)</mo></math>";
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Calculus")], expr,
+                "x an element of, the open interval from 0 to 1");
+    let expr = "<math><mi>x</mi><mo>→</mo><mn>0</mn></math>";
+    test("en", "SimpleSpeak", expr, "x right arrow 0");
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Calculus")], expr, "x approaches 0");
+}
+
+#[test]
+fn calculus_function_args_are_not_intervals() {
+    let expr = "<math><mi>f</mi><mo>&#x2061;</mo><mo>(</mo><mi>a</mi><mo>,</mo><mi>b</mi><mo>)</mo></math>";
+    test_prefs("en", "SimpleSpeak", vec![("SubjectArea", "Calculus")], expr, "f of, open paren eigh comma b, close paren");
+}
+
+#[test]
+fn named_constants() {
+    let expr = "<math><msup><mi>e</mi><mi>x</mi></msup></math>";
+    test_prefs("en", "SimpleSpeak", vec![("Verbosity", "Verbose"), ("SubjectArea", "Calculus")], expr,
+                "e, the base of the natural log, to the x-th");
+    test_prefs("en", "SimpleSpeak", vec![("Verbosity", "Verbose")], expr, "e to the x-th");
+    test_prefs("en", "SimpleSpeak", vec![("Verbosity", "Medium"), ("SubjectArea", "Calculus")], expr, "e to the x-th");
+    let expr = "<math><mn>3</mn><mo>+</mo><mn>4</mn><mi>i</mi></math>";
+    test_prefs("en", "SimpleSpeak", vec![("Verbosity", "Verbose"), ("SubjectArea", "Calculus")], expr,
+                "3 plus, 4 i, the imaginary unit,");
+}
+
+#[test]
+fn named_constants_suppressed() {
+    let expr = "<math><mn>2</mn><mi>π</mi><mi>r</mi></math>";
+    test_prefs("en", "ClearSpeak", vec![("Verbosity", "Verbose")], expr,
+                "2, pi, the ratio of the circumference of a circle to its diameter; r");
+    test_prefs("en", "ClearSpeak", vec![("Verbosity", "Verbose"), ("ExplainConstants", "false")], expr, "2 pi r");
+    test_prefs("en", "ClearSpeak", vec![("Verbosity", "Verbose"), ("ExamMode", "true")], expr, "2 pi r");
+}
@@ -0,0 +1,29 @@
+/// Tests for British English (en-gb) -- the region overrides in Rules/Languages/en/gb layered over the en rules
+use crate::common::*;
+
+#[test]
+fn parens_are_brackets() {
+    let expr = "<math><mn>2</mn><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></math>";
+    test("en-gb", "ClearSpeak", expr, "2 times, open bracket x plus 1, close bracket");
+    test("en", "ClearSpeak", expr, "2 times, open paren x plus 1, close paren");
+}
+
+#[test]
+fn square_brackets() {
+    let expr = "<math><mo>[</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>]</mo></math>";
+    test("en-gb", "SimpleSpeak", expr, "open square bracket, x plus 1, close square bracket");
+}
+
+#[test]
+fn zed() {
+    let expr = "<math><mi>z</mi><mo>+</mo><mi>Z</mi></math>";
+    test("en-gb", "SimpleSpeak", expr, "zed plus cap zed");
+    test("en", "SimpleSpeak", expr, "z plus cap z");
+}
+
+#[test]
+fn unchanged_from_en() {
+    // rules that aren't overridden by the region come from the language
+    let expr = "<math><mfrac><mn>1</mn><mn>2</mn></mfrac><mo>⊂</mo><mi>x</mi></math>";
+    test("en-gb", "ClearSpeak", expr, "1 half is a subset of x");
+}
@@ -0,0 +1,156 @@
+#![allow(clippy::needless_return)]
+/// Runs the shared test corpus (feature_matrix/corpus.yaml) for each language and builds a support matrix.
+///
+/// Each language has a feature_matrix/<language>.yaml file that gives the speech style to use,
+///   the expected speech for the cases it supports, and the cases it skips along with the reason they are skipped.
+/// A case that is neither expected nor skipped is an error so that unsupported features are always explicitly noted.
+/// The matrix is written as a markdown table to the file named by the environment variable MATHCAT_FEATURE_MATRIX
+///   or, if that isn't set, to feature-matrix.md in the target directory.
+/// To add a language, add it to LANGUAGES and add its yaml file.
+use crate::common::*;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+const LANGUAGES: &[&str] = &["en", "id", "vi"];
+
+struct Case {
+    name: String,
+    feature: String,
+    mathml: String,
+}
+
+#[derive(Default)]
+struct FeatureResult {
+    passed: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+fn feature_matrix_dir() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/Languages/feature_matrix");
+}
+
+fn read_yaml(file: &Path) -> Yaml {
+    let contents = std::fs::read_to_string(file)
+        .unwrap_or_else(|e| panic!("Couldn't read {}: {}", file.display(), e));
+    let mut docs = YamlLoader::load_from_str(&contents)
+        .unwrap_or_else(|e| panic!("Couldn't parse {}: {}", file.display(), e));
+    assert_eq!(docs.len(), 1, "{} should contain exactly one yaml document", file.display());
+    return docs.remove(0);
+}
+
+fn read_corpus() -> Vec<Case> {
+    let file = feature_matrix_dir().join("corpus.yaml");
+    let corpus = read_yaml(&file);
+    let cases = corpus.as_vec().unwrap_or_else(|| panic!("{} should be an array of cases", file.display()));
+    return cases.iter()
+        .map(|case| {
+            let field = |key: &str| case[key].as_str()
+                    .unwrap_or_else(|| panic!("'{}' is missing from a case in {}: {:?}", key, file.display(), case))
+                    .to_string();
+            Case { name: field("case"), feature: field("feature"), mathml: field("mathml") }
+        })
+        .collect();
+}
+
+/// Returns the features in the order they first appear in the corpus
+fn features(corpus: &[Case]) -> Vec<&str> {
+    let mut features: Vec<&str> = vec![];
+    for case in corpus {
+        if !features.contains(&case.feature.as_str()) {
+            features.push(&case.feature);
+        }
+    }
+    return features;
+}
+
+/// Run the corpus for 'language', returning the result for each feature (in the order of 'features') and the errors
+fn run_language(language: &str, corpus: &[Case], features: &[&str]) -> (Vec<FeatureResult>, Vec<String>) {
+    let file = feature_matrix_dir().join(format!("{}.yaml", language));
+    let expected = read_yaml(&file);
+    let style = expected["style"].as_str().unwrap_or("ClearSpeak");
+    let mut results: Vec<FeatureResult> = features.iter().map(|_| FeatureResult::default()).collect();
+    let mut errors = vec![];
+    for case in corpus {
+        let result = &mut results[features.iter().position(|&feature| feature == case.feature).unwrap()];
+        let name = Yaml::String(case.name.clone());
+        let skip = expected["skip"].as_hash().and_then(|skips| skips.get(&name));
+        let speech = expected["speech"].as_hash().and_then(|speech| speech.get(&name));
+        match (skip, speech) {
+            (Some(_), Some(_)) => {
+                result.failed += 1;
+                errors.push(format!("{}: '{}' is both expected and skipped", language, case.name));
+            },
+            (Some(_), None) => result.skipped += 1,
+            (None, None) => {
+                result.failed += 1;
+                errors.push(format!("{}: '{}' needs to be added to either 'speech' or 'skip' in {}",
+                                    language, case.name, file.display()));
+            },
+            (None, Some(speech)) => {
+                let speech = speech.as_str().unwrap_or_default();
+                match speak(language, style, &case.mathml) {
+                    Ok(actual) if actual == speech => result.passed += 1,
+                    Ok(actual) => {
+                        result.failed += 1;
+                        errors.push(format!("{}: '{}'\n    expected: '{}'\n      actual: '{}'", language, case.name, speech, actual));
+                    },
+                    Err(e) => {
+                        result.failed += 1;
+                        errors.push(format!("{}: '{}' failed: {}", language, case.name, e));
+                    },
+                }
+            }
+        }
+    }
+    return (results, errors);
+}
+
+fn cell(result: &FeatureResult) -> String {
+    let total = result.passed + result.skipped + result.failed;
+    let mut cell = if result.passed == total {
+        "yes".to_string()
+    } else if result.passed == 0 && result.failed == 0 {
+        "no".to_string()
+    } else {
+        format!("{}/{}", result.passed, total)
+    };
+    if result.skipped > 0 && result.passed > 0 {
+        cell += &format!(" ({} skipped)", result.skipped);
+    }
+    if result.failed > 0 {
+        cell += &format!(" ({} FAILED)", result.failed);
+    }
+    return cell;
+}
+
+fn matrix_file() -> PathBuf {
+    if let Ok(file) = std::env::var("MATHCAT_FEATURE_MATRIX") {
+        return PathBuf::from(file);
+    }
+    return std::env::current_exe().unwrap().parent().unwrap().join("../../feature-matrix.md");
+}
+
+#[test]
+fn feature_matrix() {
+    let corpus = read_corpus();
+    let features = features(&corpus);
+    let mut table = format!("| Feature | {} |\n|---|{}\n", LANGUAGES.join(" | "), "---|".repeat(LANGUAGES.len()));
+    let mut all_results = vec![];
+    let mut all_errors = vec![];
+    for language in LANGUAGES {
+        let (results, mut errors) = run_language(language, &corpus, &features);
+        all_results.push(results);
+        all_errors.append(&mut errors);
+    }
+    for (i, feature) in features.iter().enumerate() {
+        let cells: Vec<String> = all_results.iter().map(|results| cell(&results[i])).collect();
+        table += &format!("| {} | {} |\n", feature, cells.join(" | "));
+    }
+
+    let file = matrix_file();
+    if let Err(e) = std::fs::write(&file, &table) {
+        eprintln!("Couldn't write the feature matrix to {}: {}", file.display(), e);
+    }
+    assert!(all_errors.is_empty(), "Feature matrix failures:\n{}\n\n{}", all_errors.join("\n"), table);
+}
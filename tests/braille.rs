@@ -8,6 +8,8 @@ mod braille {
         mod SRE_Nemeth72;
         mod SRE_NemethBase;
         mod AataNemeth;
+        mod chemistry;
+        mod conformance;
     }
     mod UEB {
         mod iceb;
@@ -0,0 +1,88 @@
+// Nemeth tests for chemistry (states, charges, reaction arrows, hydrates)
+// Isotope notation (prescripts such as ¹⁴₆C) has no chemistry-specific handling yet -- that is left for a follow-up
+use crate::common::*;
+
+#[test]
+fn dissociation() {
+    let expr = "<math><mrow><mi>Na</mi><mi>Cl</mi><mo>(</mo><mi>s</mi><mo>)</mo><mo>→</mo>
+                    <msup><mi>Na</mi><mo>+</mo></msup><mo>(</mo><mi>aq</mi><mo>)</mo><mo>+</mo>
+                    <msup><mi>Cl</mi><mo>−</mo></msup><mo>(</mo><mi>aq</mi><mo>)</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠝⠁⠠⠉⠇⠷⠎⠾⠀⠫⠒⠒⠕⠀⠠⠝⠁⠘⠬⠐⠷⠁⠟⠾⠬⠠⠉⠇⠘⠤⠐⠷⠁⠟⠾");
+}
+
+#[test]
+fn water_formation() {
+    let expr = "<math><mrow><mn>2</mn><msub><mi>H</mi><mn>2</mn></msub><mo>(</mo><mi>g</mi><mo>)</mo><mo>+</mo>
+                    <msub><mi>O</mi><mn>2</mn></msub><mo>(</mo><mi>g</mi><mo>)</mo><mo>→</mo>
+                    <mn>2</mn><msub><mi>H</mi><mn>2</mn></msub><mi>O</mi><mo>(</mo><mi>l</mi><mo>)</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠼⠆⠠⠓⠆⠷⠛⠾⠬⠠⠕⠆⠷⠛⠾⠀⠫⠒⠒⠕⠀⠼⠆⠠⠓⠆⠠⠕⠷⠇⠾");
+}
+
+#[test]
+fn ion_charge() {
+    let expr = "<math><mrow><msup><mi>Ca</mi><mrow><mn>2</mn><mo>+</mo></mrow></msup><mo>(</mo><mi>aq</mi><mo>)</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠉⠁⠘⠆⠬⠐⠷⠁⠟⠾");
+}
+
+#[test]
+fn two_letter_element_subscript() {
+    // numeric subscripts on element symbols don't use a subscript indicator
+    let expr = "<math><mrow><mi>Ca</mi><msub><mi>Cl</mi><mn>2</mn></msub><mo>(</mo><mi>s</mi><mo>)</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠉⠁⠠⠉⠇⠆⠷⠎⠾");
+}
+
+#[test]
+fn parenthesized_group_subscript() {
+    let expr = "<math><mrow><msub><mi>Al</mi><mn>2</mn></msub>
+                    <msub><mrow><mo>(</mo><mi>S</mi><msub><mi>O</mi><mn>4</mn></msub><mo>)</mo></mrow><mn>3</mn></msub></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠁⠇⠆⠷⠠⠎⠠⠕⠲⠾⠰⠒");
+}
+
+#[test]
+fn non_chemistry_arrow() {
+    // only reaction arrows use the uncontracted form
+    let expr = "<math><mrow><msub><mi>x</mi><mn>2</mn></msub><mo>→</mo><msub><mi>y</mi><mn>2</mn></msub></mrow></math>";
+    test_braille("Nemeth", expr, "⠭⠆⠀⠫⠕⠀⠽⠆");
+}
+
+#[test]
+fn polyatomic_ion_charge() {
+    // the charge is a superscript simultaneous with the numeric subscript
+    let expr = "<math><mrow><mi>S</mi><msubsup><mi>O</mi><mn>4</mn><mrow><mn>2</mn><mo>−</mo></mrow></msubsup><mo>(</mo><mi>aq</mi><mo>)</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠎⠠⠕⠲⠘⠆⠤⠐⠷⠁⠟⠾");
+}
+
+#[test]
+fn electron_transfer() {
+    let expr = "<math><mrow><msup><mi>Fe</mi><mrow><mn>3</mn><mo>+</mo></mrow></msup><mo>+</mo><msup><mi>e</mi><mo>−</mo></msup>
+                    <mo>→</mo><msup><mi>Fe</mi><mrow><mn>2</mn><mo>+</mo></mrow></msup></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠋⠑⠘⠒⠬⠐⠬⠑⠘⠤⠀⠫⠒⠒⠕⠀⠠⠋⠑⠘⠆⠬");
+}
+
+#[test]
+fn equilibrium_arrow() {
+    let expr = "<math><mrow><msub><mi>N</mi><mn>2</mn></msub><mo>+</mo><mn>3</mn><msub><mi>H</mi><mn>2</mn></msub>
+                    <mo>⇌</mo><mn>2</mn><mi>N</mi><msub><mi>H</mi><mn>3</mn></msub></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠝⠆⠬⠒⠠⠓⠆⠀⠫⠒⠒⠈⠕⠫⠈⠪⠒⠒⠀⠼⠆⠠⠝⠠⠓⠒");
+}
+
+#[test]
+fn reaction_conditions_and_gas() {
+    // the condition over the arrow is a modifier; the gas evolved arrow is spaced like the reaction arrow
+    let expr = "<math><mrow><mi>Ca</mi><mi>C</mi><msub><mi>O</mi><mn>3</mn></msub><mover><mo>→</mo><mi>Δ</mi></mover>
+                    <mi>Ca</mi><mi>O</mi><mo>+</mo><mi>C</mi><msub><mi>O</mi><mn>2</mn></msub><mo>↑</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠉⠁⠠⠉⠠⠕⠒⠀⠐⠫⠒⠒⠕⠣⠨⠠⠙⠻⠀⠠⠉⠁⠠⠕⠬⠠⠉⠠⠕⠆⠀⠫⠣⠒⠒⠕");
+}
+
+#[test]
+fn precipitate() {
+    let expr = "<math><mrow><msup><mi>Ag</mi><mo>+</mo></msup><mo>+</mo><msup><mi>Cl</mi><mo>−</mo></msup><mo>→</mo>
+                    <mi>Ag</mi><mi>Cl</mi><mo>↓</mo></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠁⠛⠘⠬⠐⠬⠠⠉⠇⠘⠤⠀⠫⠒⠒⠕⠀⠠⠁⠛⠠⠉⠇⠀⠫⠩⠒⠒⠕");
+}
+
+#[test]
+fn hydrate() {
+    let expr = "<math><mrow><mi>Cu</mi><mi>S</mi><msub><mi>O</mi><mn>4</mn></msub><mo>·</mo><mn>5</mn><msub><mi>H</mi><mn>2</mn></msub><mi>O</mi></mrow></math>";
+    test_braille("Nemeth", expr, "⠠⠉⠥⠠⠎⠠⠕⠲⠡⠢⠠⠓⠆⠠⠕");
+}
@@ -0,0 +1,40 @@
+#![allow(clippy::needless_return)]
+/// Runs a Nemeth sample corpus (conformance/examples.csv) and reports pass/fail for each rule of the Nemeth Code.
+///
+/// The corpus is a CSV file with the columns "Example", "Rule", "MathML", and "Braille" (Unicode braille).
+///   "Rule" is the number of the rule in the BANA Nemeth Code book that the example illustrates;
+///   examples without a rule are reported as "Other".
+/// See `Corpus` in tests/common/mod.rs for known_failures.txt and the report
+///   (written to MATHCAT_NEMETH_REPORT or nemeth-conformance.md in the target directory).
+use crate::common::*;
+
+const CORPUS: Corpus = Corpus {
+    name: "Nemeth",
+    dir: "tests/braille/Nemeth/conformance",
+    group_heading: "Rule",
+    report_var: "MATHCAT_NEMETH_REPORT",
+    report_file: "nemeth-conformance.md",
+};
+
+/// Sort key for rules: numerically by rule number with "Other" at the end
+fn rule_order(rule: &str) -> usize {
+    return rule.parse().unwrap_or(usize::MAX);
+}
+
+#[test]
+fn nemeth_conformance() {
+    let mut results = CORPUS.read_examples(&["example", "rule", "mathml", "braille"]).into_iter()
+        .map(|example| {
+            let (name, rule, mathml, expected) = (&example[0], &example[1], &example[2], &example[3]);
+            let failure = match braille("Nemeth", mathml) {
+                Ok(actual) if &actual == expected => None,
+                Ok(actual) => Some(format!("'{}'\n    expected: {}\n      actual: {}", name, expected, actual)),
+                Err(e) => Some(format!("'{}' failed: {}", name, e)),
+            };
+            let group = if rule.is_empty() {"Other".to_string()} else {rule.clone()};
+            ExampleResult { name: name.clone(), group, failure }
+        })
+        .collect::<Vec<ExampleResult>>();
+    results.sort_by_key(|result| rule_order(&result.group));
+    CORPUS.check(&results);
+}
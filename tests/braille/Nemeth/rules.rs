@@ -98,6 +98,19 @@ fn boldface_32_b_6() {
     test_braille("Nemeth", expr, "⠸⠼⠲⠼⠒⠢");
 }
 
+#[test]
+fn boldface_multi_letter() {
+    // typeform indicators apply only to the next letter, so each letter gets its own
+    let expr = "<math><mi mathvariant='bold'>AB</mi><mo>+</mo><mi>𝐚𝐛</mi></math>";
+    test_braille("Nemeth", expr, "⠸⠰⠠⠁⠸⠰⠠⠃⠬⠸⠰⠁⠸⠰⠃");
+}
+
+#[test]
+fn sans_serif_letter() {
+    let expr = "<math><mi>𝖠</mi><mo>=</mo><mi>ℝ</mi></math>";
+    test_braille("Nemeth", expr, "⠠⠨⠰⠠⠁⠀⠨⠅⠀⠈⠰⠠⠗");
+}
+
 #[test]
 fn punct_37_1_2() {
     let expr = "<math>
@@ -1273,3 +1286,11 @@ fn tensor_from_mathml_spec() {
     // Note: the braille answer was verified to be correct (see https://github.com/NSoiffer/MathCAT/issues/55) 
     test_braille("Nemeth", expr, "⠠⠗⠰⠊⠐⠘⠚⠐⠰⠅⠐⠰⠇");
 }
+
+#[test]
+fn midline_ellipsis_spacing() {
+    // the midline ellipsis is spaced like "…" (rule 43b)
+    let expr = "<math><msub><mi>a</mi><mn>1</mn></msub><mo>+</mo><msub><mi>a</mi><mn>2</mn></msub>
+                    <mo>+</mo><mo>⋯</mo><mo>+</mo><msub><mi>a</mi><mi>n</mi></msub></math>";
+    test_braille("Nemeth", expr, "⠁⠂⠬⠁⠆⠬⠀⠄⠄⠄⠀⠬⠁⠰⠝");
+}
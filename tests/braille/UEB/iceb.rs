@@ -232,6 +232,12 @@ fn bold_2_7_3() {
     test_braille("UEB", expr, "⠼⠋⠛⠘⠂⠼⠓⠙⠘⠄⠼⠑");
 }
 
+#[test]
+fn script_letters() {
+    let expr = "<math><mi>𝒶</mi><mo>+</mo><mi>𝓐</mi></math>";
+    test_braille("UEB", expr, "⠈⠆⠁⠐⠖⠘⠆⠈⠆⠠⠁");
+}
+
 #[test]
 fn signs_2_10_2() {
     let expr = "<math><mo>$</mo><mn>0.30</mn><mo>,</mo><mo>&#xA0;</mo>
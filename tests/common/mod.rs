@@ -1,6 +1,6 @@
 //!  Useful functionality for testing
+#![allow(clippy::needless_return)]
 #[cfg(test)]
-
 use regex::Regex;
 extern crate lazy_static;
 use lazy_static::lazy_static;
@@ -52,21 +52,75 @@ pub fn test(language: &str, style: &str, mathml: &str, speech: &str) {
     set_rules_dir(abs_rules_dir_path()).unwrap();
     libmathcat::speech::SPEECH_RULES.with(|rules| {
         let mut rules = rules.borrow_mut();
-        let changes;
+        let mut changes;
         {
-            let mut prefs = rules.pref_manager.borrow_mut();
+            let mut prefs = rules.pref_manager.write().unwrap();
             prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
             prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
-            prefs.set_user_prefs("Language", language);
-            changes = prefs.set_user_prefs("SpeechStyle", style);
-        }
-        if let Some(changes) = changes {
-            rules.invalidate(changes);
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", style).unwrap_or_default());
         }
+        rules.invalidate(changes);
     });
     check_answer(mathml, speech);
 }
 
+// Return the result of speaking the mathml input (with spaces collapsed) or the error message if it can't be spoken
+// This uses default preferences
+#[allow(dead_code)]     // used in testing
+pub fn speak(language: &str, style: &str, mathml: &str) -> Result<String, String> {
+    set_rules_dir(abs_rules_dir_path()).unwrap();
+    libmathcat::speech::SPEECH_RULES.with(|rules| {
+        let mut rules = rules.borrow_mut();
+        let mut changes;
+        {
+            let mut prefs = rules.pref_manager.write().unwrap();
+            prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
+            prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", style).unwrap_or_default());
+        }
+        rules.invalidate(changes);
+    });
+    if let Err(e) = set_mathml(mathml.to_string()) {
+        return Err( errors_to_string(&e) );
+    };
+    return match get_spoken_text() {
+        Ok(speech) => Ok( strip_spaces(speech) ),
+        Err(e) => Err( errors_to_string(&e) ),
+    };
+}
+
+// Return the ClearSpeak speech for the mathml input with 'pref_name' set to 'pref_value' (or Err with the error message)
+// An empty 'pref_name' uses the default ClearSpeak preferences
+#[allow(dead_code)]     // used in testing
+#[allow(non_snake_case)]
+pub fn speak_ClearSpeak(language: &str, pref_name: &str, pref_value: &str, mathml: &str) -> Result<String, String> {
+    set_rules_dir(abs_rules_dir_path()).unwrap();
+    libmathcat::speech::SPEECH_RULES.with(|rules| {
+        let mut rules = rules.borrow_mut();
+        let mut changes;
+        {
+            let mut prefs = rules.pref_manager.write().unwrap();
+            prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
+            prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", "ClearSpeak").unwrap_or_default());
+            if !pref_name.is_empty() {
+                changes.add_changes(prefs.set_user_prefs(pref_name, pref_value).unwrap_or_default());
+            }
+        }
+        rules.invalidate(changes);
+    });
+    if let Err(e) = set_mathml(mathml.to_string()) {
+        return Err( errors_to_string(&e) );
+    };
+    return match get_spoken_text() {
+        Ok(speech) => Ok( strip_spaces(speech) ),
+        Err(e) => Err( errors_to_string(&e) ),
+    };
+}
+
 // Compare the result of speaking the mathml input to the output 'speech'
 // This takes the speech style along with a vector of (pref_name, pref_value)
 #[allow(dead_code)]     // used in testing
@@ -77,11 +131,11 @@ pub fn test_prefs(language: &str, speech_style: &str, test_prefs: Vec<(&str, &st
         let mut rules = rules.borrow_mut();
         let mut changes;
         {
-            let mut prefs = rules.pref_manager.borrow_mut();
+            let mut prefs = rules.pref_manager.write().unwrap();
             prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
             prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
-            prefs.set_user_prefs("Language", language);
-            changes = prefs.set_user_prefs("SpeechStyle", speech_style).unwrap_or_default();
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", speech_style).unwrap_or_default());
             for (pref_name, pref_value) in test_prefs {
                 if let Some(more_changes) = prefs.set_user_prefs(pref_name, pref_value) {
                     changes.add_changes(more_changes);
@@ -103,11 +157,11 @@ pub fn test_ClearSpeak(language: &str, pref_name: &str, pref_value: &str, mathml
         let mut rules = rules.borrow_mut();
         let mut changes;
         {   // needs to be scoped due to problems with rules potentially being used with prefs' destructor runs in an outer scope
-            let mut prefs = rules.pref_manager.borrow_mut();
+            let mut prefs = rules.pref_manager.write().unwrap();
             prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
             prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
-            prefs.set_user_prefs("Language", language);
-            changes = prefs.set_user_prefs("SpeechStyle", "ClearSpeak").unwrap_or_default();
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", "ClearSpeak").unwrap_or_default());
             let more_changes = prefs.set_user_prefs(pref_name, pref_value).unwrap_or_default();
             changes.add_changes(more_changes);
         }
@@ -126,11 +180,11 @@ pub fn test_ClearSpeak_prefs(language: &str, test_prefs: Vec<(&str, &str)>, math
         let mut rules = rules.borrow_mut();
         let mut changes;
         {
-            let mut prefs = rules.pref_manager.borrow_mut();
+            let mut prefs = rules.pref_manager.write().unwrap();
             prefs.set_user_prefs("SpeechOverrides_CapitalLetters", "");         // makes testing simpler
             prefs.set_user_prefs("PauseFactor", "100");                         // makes testing simpler
-            prefs.set_user_prefs("Language", language);
-            changes = prefs.set_user_prefs("SpeechStyle", "ClearSpeak").unwrap_or_default();
+            changes = prefs.set_user_prefs("Language", language).unwrap_or_default();
+            changes.add_changes(prefs.set_user_prefs("SpeechStyle", "ClearSpeak").unwrap_or_default());
             for (pref_name, pref_value) in test_prefs {
                 if let Some(more_changes) = prefs.set_user_prefs(pref_name, pref_value) {
                     changes.add_changes(more_changes);
@@ -142,6 +196,23 @@ pub fn test_ClearSpeak_prefs(language: &str, test_prefs: Vec<(&str, &str)>, math
     check_answer(mathml, speech);
 }
 
+// Return the braille for the mathml input using the braille 'code' (or Err with the error message)
+#[allow(dead_code)]     // used in testing
+pub fn braille(code: &str, mathml: &str) -> Result<String, String> {
+    set_rules_dir(abs_rules_dir_path()).unwrap();
+    libmathcat::speech::BRAILLE_RULES.with(|rules| {
+        let mut rules = rules.borrow_mut();
+        let changes = rules.pref_manager.write().unwrap().set_user_prefs("BrailleCode", code);
+        if let Some(changes) = changes {
+            rules.invalidate(changes);
+        }
+    });
+    if let Err(e) = set_mathml(mathml.to_string()) {
+        return Err( errors_to_string(&e) );
+    };
+    return get_braille("".to_string()).map_err(|e| errors_to_string(&e));
+}
+
 // Compare the result of brailling the mathml input to the output (Unicode) 'braille'
 #[allow(dead_code)]     // used in testing
 #[allow(non_snake_case)]
@@ -149,7 +220,7 @@ pub fn test_braille(code: &str, mathml: &str, braille: &str) {
     set_rules_dir(abs_rules_dir_path()).unwrap();
     libmathcat::speech::BRAILLE_RULES.with(|rules| {
         let mut rules = rules.borrow_mut();
-        let changes = rules.pref_manager.borrow_mut().set_user_prefs("BrailleCode", code);
+        let changes = rules.pref_manager.write().unwrap().set_user_prefs("BrailleCode", code);
         if let Some(changes) = changes {
             rules.invalidate(changes);
         }
@@ -162,3 +233,171 @@ pub fn test_braille(code: &str, mathml: &str, braille: &str) {
         Err(e) => panic!("{}", errors_to_string(&e)),
     };    
 }
+
+// Split CSV text (e.g., a spreadsheet export of a test corpus) into records of fields.
+// Quoted fields can contain commas and newlines; a doubled quote inside a quoted field is a quote.
+#[allow(dead_code)]     // used in testing
+pub fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            match ch {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => in_quotes = false,
+                _ => field.push(ch),
+            }
+        } else {
+            match ch {
+                '"' => in_quotes = true,
+                ',' => record.push(std::mem::take(&mut field)),
+                '\r' => (),
+                '\n' => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                },
+                _ => field.push(ch),
+            }
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // blank lines (e.g., at the end of a spreadsheet export) aren't records
+    return records.into_iter()
+            .filter(|record| record.iter().any(|field| !field.trim().is_empty()))
+            .collect();
+}
+
+// A conformance test corpus: 'dir' (relative to the crate dir) has the examples in examples.csv and the names of the examples
+//   that are known not to match in known_failures.txt (one name per line; '#' starts a comment).
+// An example that fails and isn't listed is a regression and a listed example that now passes needs to be removed from that file,
+//   so the list always reflects the current state of conformance. Only add an example to the list along with an issue explaining the difference.
+// The report is written as a markdown table to the file named by the environment variable 'report_var'
+//   or, if that isn't set, to 'report_file' in the target directory.
+#[allow(dead_code)]     // used in testing
+pub struct Corpus {
+    pub name: &'static str,             // e.g., "Nemeth" -- used in the report title and messages
+    pub dir: &'static str,
+    pub group_heading: &'static str,    // the report's heading for the groups the examples are counted in (e.g., "Rule")
+    pub report_var: &'static str,
+    pub report_file: &'static str,
+}
+
+// The result of running an example in a corpus: 'failure' is None if it passed
+#[allow(dead_code)]     // used in testing
+pub struct ExampleResult {
+    pub name: String,
+    pub group: String,
+    pub failure: Option<String>,
+}
+
+#[allow(dead_code)]     // used in testing
+impl Corpus {
+    fn path(&self, file_name: &str) -> std::path::PathBuf {
+        return std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(self.dir).join(file_name);
+    }
+
+    // Read the examples in examples.csv -- each one has the values of 'columns' (found by their header name, case insensitive).
+    // Other columns are ignored.
+    pub fn read_examples(&self, columns: &[&str]) -> Vec<Vec<String>> {
+        let file = self.path("examples.csv");
+        let contents = std::fs::read_to_string(&file)
+            .unwrap_or_else(|e| panic!("Couldn't read {}: {}", file.display(), e));
+        let mut records = parse_csv(&contents).into_iter();
+        let header = records.next().unwrap_or_else(|| panic!("{} is empty", file.display()));
+        let indices: Vec<usize> = columns.iter()
+            .map(|&name| header.iter().position(|column| column.trim().eq_ignore_ascii_case(name))
+                    .unwrap_or_else(|| panic!("{} is missing the '{}' column", file.display(), name)))
+            .collect();
+        return records
+            .enumerate()
+            .map(|(i, record)| indices.iter().zip(columns)
+                .map(|(&index, name)| record.get(index)
+                    .unwrap_or_else(|| panic!("Record {} in {} is missing the '{}' field", i+1, file.display(), name))
+                    .trim()
+                    .to_string())
+                .collect())
+            .collect();
+    }
+
+    fn read_known_failures(&self) -> Vec<String> {
+        let file = self.path("known_failures.txt");
+        let contents = std::fs::read_to_string(&file)
+            .unwrap_or_else(|e| panic!("Couldn't read {}: {}", file.display(), e));
+        return contents.lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect();
+    }
+
+    fn report_file(&self) -> std::path::PathBuf {
+        if let Ok(file) = std::env::var(self.report_var) {
+            return std::path::PathBuf::from(file);
+        }
+        return std::env::current_exe().unwrap().parent().unwrap().join("../..").join(self.report_file);
+    }
+
+    // Write the report for 'results' (counted by their group in the order the groups first appear)
+    // and panic if they don't match the known failures.
+    pub fn check(&self, results: &[ExampleResult]) {
+        let known_failures = self.read_known_failures();
+        let mut groups: Vec<(&str, usize, usize)> = vec![];     // (group, passed, failed)
+        let mut failures = vec![];
+        let mut errors = vec![];
+        for result in results {
+            let i = match groups.iter().position(|(group, _, _)| *group == result.group) {
+                Some(i) => i,
+                None => {
+                    groups.push((&result.group, 0, 0));
+                    groups.len() - 1
+                },
+            };
+            let is_known_failure = known_failures.contains(&result.name);
+            match &result.failure {
+                None => {
+                    groups[i].1 += 1;
+                    if is_known_failure {
+                        errors.push(format!("'{}' passes -- remove it from known_failures.txt", result.name));
+                    }
+                },
+                Some(failure) => {
+                    groups[i].2 += 1;
+                    if !is_known_failure {
+                        errors.push(failure.clone());
+                    }
+                    failures.push(failure.as_str());
+                },
+            }
+        }
+        for name in &known_failures {
+            if !results.iter().any(|result| &result.name == name) {
+                errors.push(format!("'{}' is in known_failures.txt but isn't in the corpus", name));
+            }
+        }
+
+        let passed: usize = groups.iter().map(|(_, passed, _)| passed).sum();
+        let mut report = format!("# {} conformance: {}/{} examples pass ({:.1}%)\n\n| {} | Passed | Failed |\n|---|---|---|\n",
+                                 self.name, passed, results.len(), 100.0 * passed as f64 / results.len().max(1) as f64, self.group_heading);
+        for (group, passed, failed) in &groups {
+            report += &format!("| {} | {} | {} |\n", group, passed, failed);
+        }
+        if !failures.is_empty() {
+            report += &format!("\n## Failures\n\n```\n{}\n```\n", failures.join("\n"));
+        }
+
+        let file = self.report_file();
+        if let Err(e) = std::fs::write(&file, &report) {
+            eprintln!("Couldn't write the {} conformance report to {}: {}", self.name, file.display(), e);
+        }
+        assert!(errors.is_empty(), "{} conformance errors:\n{}\n\n{}", self.name, errors.join("\n"), report);
+    }
+}
@@ -4,4 +4,7 @@ mod common;
 
 mod Languages {
     mod en;
+    mod en_gb;
+    mod feature_matrix;
+    mod clearspeak_conformance;
 }